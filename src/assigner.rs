@@ -1,7 +1,7 @@
+#[derive(Clone)]
 pub struct Assigner {
     block_pos: Vec<i32>,
-    while_block_pos: Vec<usize>,
-    pre_var: i32,
+    while_block_pos: Vec<(usize, Option<String>)>, // 循环或switch所在的层次及continue跳转到的块，switch为None
     var: i32,
 }
 
@@ -10,7 +10,6 @@ impl Assigner {
         Assigner {
             block_pos: Vec::new(),
             while_block_pos: Vec::new(),
-            pre_var: 0,
             var: 0,
         }
     }
@@ -18,22 +17,35 @@ impl Assigner {
     pub fn reset(&mut self) {
         self.block_pos.clear();
         self.while_block_pos.clear();
-        self.pre_var = 0;
         self.var = 0;
     }
 
-    pub fn new_pre_var(&mut self) -> String {
-        self.pre_var = self.pre_var + 1;
-        format!("%{}", self.pre_var)
+    pub fn new_var(&mut self) -> String {
+        self.var += 1;
+        format!("x{}", self.var)
     }
 
-    pub fn new_var(&mut self) -> String {
-        self.var = self.var + 1;
-        format!("%x{}", self.var)
+    // 可以break，即在循环或switch中
+    pub fn is_in_while(&self) -> bool {
+        !self.while_block_pos.is_empty()
+    }
+
+    // 可以continue，即在循环中
+    pub fn is_in_loop(&self) -> bool {
+        self.while_block_pos
+            .iter()
+            .any(|(_, block)| block.is_some())
+    }
+
+    // 循环的continue跳转到cond_block，break跳转到同级的下一块
+    pub fn new_while_block(&mut self, cond_block: &str) {
+        self.while_block_pos
+            .push((self.block_pos.len(), Some(cond_block.to_string())));
     }
 
-    pub fn new_while_block(&mut self) {
-        self.while_block_pos.push(self.block_pos.len());
+    // switch中只有break
+    pub fn new_switch_block(&mut self) {
+        self.while_block_pos.push((self.block_pos.len(), None));
     }
 
     pub fn get_current_block(&self) -> String {
@@ -55,21 +67,23 @@ impl Assigner {
         self.get_current_block() + "_1"
     }
 
+    // 条件中的短路求值也会占用同级的块，需要记录条件块本身
     pub fn get_continue_block(&mut self) -> String {
-        let mut block = String::from("b");
-        for item in 0..*self.while_block_pos.last().unwrap() {
-            block += format!("_{}", self.block_pos[item]).as_str();
-        }
-        block
+        self.while_block_pos
+            .iter()
+            .rev()
+            .find_map(|(_, block)| block.clone())
+            .unwrap()
     }
 
     pub fn get_break_block(&mut self) -> String {
-        self.block_pos[*self.while_block_pos.last().unwrap() - 1] += 1;
+        let level = self.while_block_pos.last().unwrap().0;
+        self.block_pos[level - 1] += 1;
         let mut block = String::from("b");
-        for item in 0..*self.while_block_pos.last().unwrap() {
+        for item in 0..level {
             block += format!("_{}", self.block_pos[item]).as_str();
         }
-        self.block_pos[*self.while_block_pos.last().unwrap() - 1] -= 1;
+        self.block_pos[level - 1] -= 1;
         block
     }
 
@@ -81,7 +95,7 @@ impl Assigner {
         self.block_pos.pop();
         self.while_block_pos.sort();
         while !self.while_block_pos.is_empty()
-            && self.while_block_pos.last().unwrap() >= &self.block_pos.len()
+            && self.while_block_pos.last().unwrap().0 >= self.block_pos.len()
         {
             self.while_block_pos.pop();
         }
@@ -0,0 +1,435 @@
+use std::fmt;
+
+use super::span::Span;
+
+// 语法树由Parser构建，语义检查与代码生成都在Codegen中完成
+pub struct CompUnit {
+    pub items: Vec<Item>,
+    pub end: Span, // 文件末尾的位置
+}
+
+pub enum Item {
+    Decl(Decl),
+    FuncDef(FuncDef),
+}
+
+// 基本类型
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BType {
+    #[default]
+    Int,
+    Float,
+}
+
+impl fmt::Display for BType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BType::Int => write!(f, "int"),
+            BType::Float => write!(f, "float"),
+        }
+    }
+}
+
+pub struct Decl {
+    pub is_const: bool,
+    pub ty: BType,
+    pub defs: Vec<VarDef>,
+}
+
+pub struct VarDef {
+    pub name: String,
+    pub name_span: Span,
+    pub dims: Vec<Expr>,
+    pub init: Option<InitVal>,
+}
+
+pub enum InitVal {
+    Expr(Expr),
+    List(Vec<InitVal>, Span),
+}
+
+impl InitVal {
+    pub fn span(&self) -> Span {
+        match self {
+            InitVal::Expr(expr) => expr.span,
+            InitVal::List(_, span) => *span,
+        }
+    }
+}
+
+pub struct FuncDef {
+    pub has_return: bool,
+    pub ty: BType, // 返回值类型，void函数为Int
+    pub name: String,
+    pub name_span: Span,
+    pub params: Vec<FuncParam>,
+    pub body: Block,
+}
+
+pub struct FuncParam {
+    pub ty: BType,
+    pub name: String,
+    pub span: Span,
+    pub dims: Vec<Option<Expr>>, // 数组参数的第一维省略，记为None
+}
+
+pub struct Block {
+    pub items: Vec<BlockItem>,
+    pub end: Span, // 右大括号的位置
+}
+
+pub enum BlockItem {
+    Decl(Decl),
+    Stmt(Box<Stmt>),
+}
+
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+pub enum StmtKind {
+    Expr(Option<Expr>),
+    Block(Block),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    While(Expr, Box<Stmt>),
+    // 初始化可以是声明，省略条件时为真
+    For(
+        Option<Box<BlockItem>>,
+        Option<Expr>,
+        Option<Expr>,
+        Box<Stmt>,
+    ),
+    DoWhile(Box<Stmt>, Expr),
+    Switch(Expr, Vec<Case>),
+    Break,
+    Continue,
+    Return(Option<Expr>),
+}
+
+// switch中的一个标号及其后直到下一个标号的语句，default的值为None
+pub struct Case {
+    pub value: Option<Expr>,
+    pub span: Span, // 标号的位置
+    pub items: Vec<BlockItem>,
+}
+
+pub struct LVal {
+    pub name: String,
+    pub name_span: Span,
+    pub indices: Vec<Expr>,
+    pub span: Span,
+}
+
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+pub enum ExprKind {
+    Number(i32),
+    Float(f32),
+    LVal(LVal),
+    Call {
+        name: String,
+        name_span: Span,
+        args: Vec<Expr>,
+    },
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    // 条件表达式，只求值选中的一个分支
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    // 复合赋值带有对应的二元运算，值为赋值之后左值的值
+    Assign(Option<BinaryOp>, LVal, Box<Expr>),
+    IncDec(IncDecOp, LVal),
+    Comma(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UnaryOp {
+    Plus,
+    Minus,
+    Not,
+    BitNot,
+}
+
+// 前缀形式的值是修改之后的值，后缀形式是修改之前的值
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IncDecOp {
+    PreInc,
+    PreDec,
+    PostInc,
+    PostDec,
+}
+
+impl IncDecOp {
+    pub fn is_prefix(self) -> bool {
+        matches!(self, IncDecOp::PreInc | IncDecOp::PreDec)
+    }
+
+    // 对左值做的加减运算
+    pub fn binary_op(self) -> BinaryOp {
+        match self {
+            IncDecOp::PreInc | IncDecOp::PostInc => BinaryOp::Add,
+            IncDecOp::PreDec | IncDecOp::PostDec => BinaryOp::Sub,
+        }
+    }
+
+    pub fn text(self) -> &'static str {
+        match self.binary_op() {
+            BinaryOp::Add => "++",
+            _ => "--",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BinaryOp {
+    Mul,
+    Div,
+    Mod,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::Lt => "<",
+            BinaryOp::Gt => ">",
+            BinaryOp::Le => "<=",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitXor => "^",
+            BinaryOp::BitOr => "|",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+        };
+        write!(f, "`{}`", text)
+    }
+}
+
+// --emit ast输出的缩进树，每行一个声明或语句，表达式写成完全加括号的形式
+impl CompUnit {
+    pub fn dump(&self) -> String {
+        let mut lines = vec![];
+        for item in &self.items {
+            match item {
+                Item::Decl(decl) => dump_decl(&mut lines, 0, decl),
+                Item::FuncDef(func) => {
+                    let params: Vec<String> = func
+                        .params
+                        .iter()
+                        .map(|param| {
+                            let dims: String = param
+                                .dims
+                                .iter()
+                                .map(|dim| match dim {
+                                    Some(dim) => format!("[{}]", dump_expr(dim)),
+                                    None => "[]".to_string(),
+                                })
+                                .collect();
+                            format!("{} {}{}", param.ty, param.name, dims)
+                        })
+                        .collect();
+                    let ret = if func.has_return {
+                        func.ty.to_string()
+                    } else {
+                        "void".to_string()
+                    };
+                    lines.push(format!("func {} {}({})", ret, func.name, params.join(", ")));
+                    dump_block(&mut lines, 1, &func.body);
+                }
+            }
+        }
+        let mut res = lines.join("\n");
+        res.push('\n');
+        res
+    }
+}
+
+fn dump_init(init: &InitVal) -> String {
+    match init {
+        InitVal::Expr(exp) => dump_expr(exp),
+        InitVal::List(vals, _) => {
+            let vals: Vec<String> = vals.iter().map(dump_init).collect();
+            format!("{{{}}}", vals.join(", "))
+        }
+    }
+}
+
+fn dump_decl(lines: &mut Vec<String>, depth: usize, decl: &Decl) {
+    for def in &decl.defs {
+        let dims: String = def
+            .dims
+            .iter()
+            .map(|dim| format!("[{}]", dump_expr(dim)))
+            .collect();
+        let init = match &def.init {
+            Some(init) => format!(" = {}", dump_init(init)),
+            None => String::new(),
+        };
+        let kind = if decl.is_const {
+            format!("const {}", decl.ty)
+        } else {
+            decl.ty.to_string()
+        };
+        lines.push(format!(
+            "{}{} {}{}{}",
+            "  ".repeat(depth),
+            kind,
+            def.name,
+            dims,
+            init
+        ));
+    }
+}
+
+fn dump_block(lines: &mut Vec<String>, depth: usize, block: &Block) {
+    lines.push(format!("{}block", "  ".repeat(depth)));
+    for item in &block.items {
+        match item {
+            BlockItem::Decl(decl) => dump_decl(lines, depth + 1, decl),
+            BlockItem::Stmt(stmt) => dump_stmt(lines, depth + 1, stmt),
+        }
+    }
+}
+
+fn dump_stmt(lines: &mut Vec<String>, depth: usize, stmt: &Stmt) {
+    let indent = "  ".repeat(depth);
+    match &stmt.kind {
+        StmtKind::Expr(Some(exp)) => lines.push(format!("{}expr {}", indent, dump_expr(exp))),
+        StmtKind::Expr(None) => lines.push(format!("{}empty", indent)),
+        StmtKind::Block(block) => dump_block(lines, depth, block),
+        StmtKind::If(cond, then, els) => {
+            lines.push(format!("{}if {}", indent, dump_expr(cond)));
+            dump_stmt(lines, depth + 1, then);
+            if let Some(els) = els {
+                lines.push(format!("{}else", indent));
+                dump_stmt(lines, depth + 1, els);
+            }
+        }
+        StmtKind::While(cond, body) => {
+            lines.push(format!("{}while {}", indent, dump_expr(cond)));
+            dump_stmt(lines, depth + 1, body);
+        }
+        StmtKind::For(init, cond, step, body) => {
+            lines.push(format!("{}for", indent));
+            match init.as_deref() {
+                Some(BlockItem::Decl(decl)) => dump_decl(lines, depth + 1, decl),
+                Some(BlockItem::Stmt(stmt)) => dump_stmt(lines, depth + 1, stmt),
+                None => lines.push(format!("{}  empty", indent)),
+            }
+            match cond {
+                Some(cond) => lines.push(format!("{}  cond {}", indent, dump_expr(cond))),
+                None => lines.push(format!("{}  empty", indent)),
+            }
+            match step {
+                Some(step) => lines.push(format!("{}  step {}", indent, dump_expr(step))),
+                None => lines.push(format!("{}  empty", indent)),
+            }
+            dump_stmt(lines, depth + 1, body);
+        }
+        StmtKind::DoWhile(body, cond) => {
+            lines.push(format!("{}do", indent));
+            dump_stmt(lines, depth + 1, body);
+            lines.push(format!("{}while {}", indent, dump_expr(cond)));
+        }
+        StmtKind::Switch(exp, cases) => {
+            lines.push(format!("{}switch {}", indent, dump_expr(exp)));
+            for case in cases {
+                match &case.value {
+                    Some(value) => lines.push(format!("{}  case {}", indent, dump_expr(value))),
+                    None => lines.push(format!("{}  default", indent)),
+                }
+                for item in &case.items {
+                    match item {
+                        BlockItem::Decl(decl) => dump_decl(lines, depth + 2, decl),
+                        BlockItem::Stmt(stmt) => dump_stmt(lines, depth + 2, stmt),
+                    }
+                }
+            }
+        }
+        StmtKind::Break => lines.push(format!("{}break", indent)),
+        StmtKind::Continue => lines.push(format!("{}continue", indent)),
+        StmtKind::Return(Some(exp)) => {
+            lines.push(format!("{}return {}", indent, dump_expr(exp)));
+        }
+        StmtKind::Return(None) => lines.push(format!("{}return", indent)),
+    }
+}
+
+fn dump_lval(lval: &LVal) -> String {
+    let indices: String = lval
+        .indices
+        .iter()
+        .map(|index| format!("[{}]", dump_expr(index)))
+        .collect();
+    format!("{}{}", lval.name, indices)
+}
+
+fn dump_expr(exp: &Expr) -> String {
+    match &exp.kind {
+        ExprKind::Number(val) => val.to_string(),
+        ExprKind::Float(val) => format!("{:?}", val),
+        ExprKind::LVal(lval) => dump_lval(lval),
+        ExprKind::Call { name, args, .. } => {
+            let args: Vec<String> = args.iter().map(dump_expr).collect();
+            format!("{}({})", name, args.join(", "))
+        }
+        ExprKind::Unary(op, exp) => {
+            let op = match op {
+                UnaryOp::Plus => "+",
+                UnaryOp::Minus => "-",
+                UnaryOp::Not => "!",
+                UnaryOp::BitNot => "~",
+            };
+            format!("({}{})", op, dump_expr(exp))
+        }
+        ExprKind::Binary(op, lhs, rhs) => format!(
+            "({} {} {})",
+            dump_expr(lhs),
+            op.to_string().trim_matches('`'),
+            dump_expr(rhs)
+        ),
+        ExprKind::Assign(op, lval, exp) => {
+            let op = op.map_or(String::new(), |op| {
+                op.to_string().trim_matches('`').to_string()
+            });
+            format!("({} {}= {})", dump_lval(lval), op, dump_expr(exp))
+        }
+        ExprKind::IncDec(op, lval) if op.is_prefix() => {
+            format!("({}{})", op.text(), dump_lval(lval))
+        }
+        ExprKind::IncDec(op, lval) => format!("({}{})", dump_lval(lval), op.text()),
+        ExprKind::Conditional(cond, then_exp, else_exp) => format!(
+            "({} ? {} : {})",
+            dump_expr(cond),
+            dump_expr(then_exp),
+            dump_expr(else_exp)
+        ),
+        ExprKind::Comma(lhs, rhs) => format!("({}, {})", dump_expr(lhs), dump_expr(rhs)),
+    }
+}
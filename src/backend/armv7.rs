@@ -0,0 +1,510 @@
+use super::super::ir::{BinOp, BlockId, CmpOp, Function, InstId, InstKind, Module, Type, Value};
+use super::regalloc::RegClass;
+use super::{size_of, write_data, Frame, Location};
+
+// ARMv7-A的GNU汇编(ARM状态，统一语法)，遵循AAPCS的硬浮点调用约定
+// SSA值分配到r4~r10或栈上的4字节栈槽，r0、r1、r2为临时寄存器，ip用于超出范围的偏移
+// 无法编码为立即数的常量与全局变量的地址放在文字池中，除法调用libgcc的__aeabi_idiv与__aeabi_idivmod
+// float的位模式与整数一样存放，运算时移入s0与s1
+const PTR_SIZE: usize = 4;
+const ARG_REGS: [&str; 4] = ["r0", "r1", "r2", "r3"];
+const FLOAT_ARG_REGS: usize = 16;
+const REGS: [&str; 7] = ["r4", "r5", "r6", "r7", "r8", "r9", "r10"];
+
+// 文字池中的常量只能在ldr前后4KiB内访问，每隔一段距离插入一次文字池
+const POOL_DISTANCE: usize = 256;
+
+pub fn emit(module: &Module) -> String {
+    let mut lines = vec![
+        "    .arch armv7-a".to_string(),
+        "    .syntax unified".to_string(),
+        "    .arm".to_string(),
+        "    .fpu vfpv3-d16".to_string(),
+        "    .text".to_string(),
+    ];
+    for (index, func) in module.functions.iter().enumerate() {
+        if !func.is_declaration() {
+            FuncEmitter::new(module, func, index).emit(&mut lines);
+        }
+    }
+    write_data(&mut lines, module);
+    lines.push("    .section .note.GNU-stack,\"\",%progbits".to_string());
+    let mut res = lines.join("\n");
+    res.push('\n');
+    res
+}
+
+// 参数的位置，整数与指针依次使用ARG_REGS，float依次使用s0~s15，其余按顺序放在栈上
+#[derive(Clone, Copy)]
+enum ArgLoc {
+    Reg(&'static str),
+    FloatReg(usize),
+    Stack(usize),
+}
+
+fn arg_locations(params: &[Type]) -> Vec<ArgLoc> {
+    let (mut ints, mut floats, mut stack) = (0, 0, 0);
+    let next = |count: &mut usize| {
+        *count += 1;
+        *count - 1
+    };
+    params
+        .iter()
+        .map(|ty| match ty {
+            Type::Float if floats < FLOAT_ARG_REGS => ArgLoc::FloatReg(next(&mut floats)),
+            Type::Float => ArgLoc::Stack(next(&mut stack)),
+            _ if ints < ARG_REGS.len() => ArgLoc::Reg(ARG_REGS[next(&mut ints)]),
+            _ => ArgLoc::Stack(next(&mut stack)),
+        })
+        .collect()
+}
+
+// 能否编码为ARM数据处理指令的立即数，即8位数循环右移偶数位
+fn is_imm(val: i32) -> bool {
+    (0..16).any(|rot| (val as u32).rotate_left(rot * 2) <= 0xff)
+}
+
+struct FuncEmitter<'a> {
+    module: &'a Module,
+    func: &'a Function,
+    index: usize,
+    frame: Frame,
+    lines: Vec<String>,
+    since_pool: usize, // 距上一个文字池的指令数
+}
+
+impl<'a> FuncEmitter<'a> {
+    fn new(module: &'a Module, func: &'a Function, index: usize) -> FuncEmitter<'a> {
+        FuncEmitter {
+            module,
+            func,
+            index,
+            frame: Frame::new(
+                func,
+                PTR_SIZE,
+                &RegClass {
+                    count: REGS.len(),
+                    clobbered: vec![],
+                },
+                0,
+            ),
+            lines: vec![],
+            since_pool: 0,
+        }
+    }
+
+    fn asm(&mut self, text: String) {
+        self.lines.push(format!("    {}", text));
+        self.since_pool += 1;
+        if self.since_pool >= POOL_DISTANCE {
+            self.asm_pool(true);
+        }
+    }
+
+    fn asm_pool(&mut self, skip: bool) {
+        if skip {
+            self.lines.push("    b 1f".to_string());
+        }
+        self.lines.push("    .ltorg".to_string());
+        if skip {
+            self.lines.push("1:".to_string());
+        }
+        self.since_pool = 0;
+    }
+
+    fn label(&self, block: BlockId) -> String {
+        format!(".LBB{}_{}", self.index, block.0)
+    }
+
+    // 以fp为基址访问栈帧，如ldr r0, [fp, #-12]
+    fn access(&mut self, op: &str, reg: &str, offset: i32) {
+        if (-4095..4096).contains(&offset) {
+            self.asm(format!("{} {}, [fp, #{}]", op, reg, offset));
+        } else {
+            self.asm(format!("ldr ip, ={}", offset));
+            self.asm(format!("{} {}, [fp, ip]", op, reg));
+        }
+    }
+
+    fn mov_imm(&mut self, reg: &str, val: i32) {
+        if is_imm(val) {
+            self.asm(format!("mov {}, #{}", reg, val));
+        } else if is_imm(!val) {
+            self.asm(format!("mvn {}, #{}", reg, !val));
+        } else {
+            self.asm(format!("ldr {}, ={}", reg, val));
+        }
+    }
+
+    fn add_imm(&mut self, dst: &str, src: &str, val: i32) {
+        if is_imm(val) {
+            self.asm(format!("add {}, {}, #{}", dst, src, val));
+        } else if is_imm(val.wrapping_neg()) {
+            self.asm(format!("sub {}, {}, #{}", dst, src, val.wrapping_neg()));
+        } else {
+            self.asm(format!("ldr ip, ={}", val));
+            self.asm(format!("add {}, {}, ip", dst, src));
+        }
+    }
+
+    fn load(&mut self, value: Value, reg: &str) {
+        match value {
+            Value::Undef => self.mov_imm(reg, 0),
+            Value::Int(val) => self.mov_imm(reg, val),
+            Value::Float(bits) => self.mov_imm(reg, bits as i32),
+            Value::Bool(val) => self.mov_imm(reg, val as i32),
+            Value::Global(global) => {
+                let name = &self.module.globals[global.0].name;
+                self.asm(format!("ldr {}, ={}", reg, name));
+            }
+            Value::Inst(inst) if self.frame.allocas.contains_key(&inst) => {
+                let offset = self.frame.allocas[&inst];
+                self.add_imm(reg, "fp", offset);
+            }
+            Value::Inst(inst) => self.load_from(self.frame.values[&inst], reg),
+            Value::Param(index) => self.load_from(self.frame.params[index], reg),
+        }
+    }
+
+    fn load_from(&mut self, location: Location, reg: &str) {
+        match location {
+            Location::Reg(src) if REGS[src] == reg => {}
+            Location::Reg(src) => self.asm(format!("mov {}, {}", reg, REGS[src])),
+            Location::Stack(offset) => self.access("ldr", reg, offset),
+        }
+    }
+
+    // 值所在的寄存器，不在寄存器中时先加载到临时寄存器
+    fn operand(&mut self, value: Value, scratch: &'static str) -> &'static str {
+        let location = match value {
+            Value::Inst(inst) => self.frame.values.get(&inst).copied(),
+            Value::Param(index) => Some(self.frame.params[index]),
+            _ => None,
+        };
+        match location {
+            Some(Location::Reg(reg)) => REGS[reg],
+            _ => {
+                self.load(value, scratch);
+                scratch
+            }
+        }
+    }
+
+    // 经过临时寄存器把float值移入浮点寄存器
+    fn float_operand(&mut self, value: Value, scratch: &'static str, freg: &str) {
+        let reg = self.operand(value, scratch);
+        self.asm(format!("vmov {}, {}", freg, reg));
+    }
+
+    // 分配到寄存器的指令结果直接在该寄存器中计算
+    fn dest(&self, inst: InstId, scratch: &'static str) -> &'static str {
+        self.reg_of(self.frame.values[&inst], scratch)
+    }
+
+    fn reg_of(&self, location: Location, scratch: &'static str) -> &'static str {
+        match location {
+            Location::Reg(reg) => REGS[reg],
+            Location::Stack(_) => scratch,
+        }
+    }
+
+    fn store(&mut self, inst: InstId, reg: &str) {
+        self.store_to(self.frame.values[&inst], reg);
+    }
+
+    fn store_to(&mut self, location: Location, reg: &str) {
+        match location {
+            Location::Reg(dst) if REGS[dst] == reg => {}
+            Location::Reg(dst) => self.asm(format!("mov {}, {}", REGS[dst], reg)),
+            Location::Stack(offset) => self.access("str", reg, offset),
+        }
+    }
+
+    fn emit(mut self, lines: &mut Vec<String>) {
+        let name = &self.func.name;
+        lines.push(format!("    .global {}", name));
+        lines.push("    .p2align 2".to_string());
+        lines.push(format!("    .type {}, %function", name));
+        lines.push(format!("{}:", name));
+        self.asm("push {fp, lr}".to_string());
+        self.asm("mov fp, sp".to_string());
+        if self.frame.size != 0 {
+            let size = self.frame.size;
+            self.add_imm("sp", "sp", -size);
+        }
+        for (reg, offset) in self.frame.saved.clone() {
+            self.access("str", REGS[reg], offset);
+        }
+        // 寄存器放不下的参数由调用者放在fp+8之上
+        // 先移走整数参数寄存器中的参数，其余的参数再经过r0
+        let types: Vec<Type> = self.func.params.iter().map(|(_, ty)| ty.clone()).collect();
+        let args = arg_locations(&types);
+        for (index, arg) in args.iter().enumerate() {
+            if let ArgLoc::Reg(reg) = arg {
+                self.store_to(self.frame.params[index], reg);
+            }
+        }
+        for (index, arg) in args.iter().enumerate() {
+            let location = self.frame.params[index];
+            let reg = self.reg_of(location, "r0");
+            match arg {
+                ArgLoc::Reg(_) => continue,
+                ArgLoc::FloatReg(freg) => self.asm(format!("vmov {}, s{}", reg, freg)),
+                ArgLoc::Stack(pos) => self.access("ldr", reg, 8 + 4 * *pos as i32),
+            }
+            self.store_to(location, reg);
+        }
+        let layout = &self.func.layout;
+        for (pos, block) in layout.iter().enumerate() {
+            let next = layout.get(pos + 1).copied();
+            let label = self.label(*block);
+            self.lines.push(format!("{}:", label));
+            for inst in &self.func.block(*block).insts {
+                self.emit_inst(*block, *inst, next);
+            }
+        }
+        self.asm_pool(false);
+        lines.append(&mut self.lines);
+        lines.push(format!("    .size {}, .-{}", name, name));
+    }
+
+    fn emit_inst(&mut self, block: BlockId, inst: InstId, next: Option<BlockId>) {
+        match &self.func.inst(inst).kind {
+            InstKind::Alloca(_) => {}
+            InstKind::Load(ptr) => {
+                let ptr = self.operand(*ptr, "r0");
+                let dst = self.dest(inst, "r0");
+                match self.func.inst(inst).ty {
+                    Type::I1 => self.asm(format!("ldrb {}, [{}]", dst, ptr)),
+                    _ => self.asm(format!("ldr {}, [{}]", dst, ptr)),
+                }
+                self.store(inst, dst);
+            }
+            InstKind::Store(val, ptr) => {
+                let ptr = self.operand(*ptr, "r0");
+                let val_reg = self.operand(*val, "r1");
+                match self.module.value_type(self.func, *val) {
+                    Type::I1 => self.asm(format!("strb {}, [{}]", val_reg, ptr)),
+                    _ => self.asm(format!("str {}, [{}]", val_reg, ptr)),
+                }
+            }
+            InstKind::Binary(op @ (BinOp::SDiv | BinOp::SRem), lhs, rhs) => {
+                self.load(*lhs, "r0");
+                self.load(*rhs, "r1");
+                if *op == BinOp::SDiv {
+                    self.asm("bl __aeabi_idiv".to_string());
+                    self.store(inst, "r0");
+                } else {
+                    self.asm("bl __aeabi_idivmod".to_string());
+                    self.store(inst, "r1");
+                }
+            }
+            InstKind::Binary(op, lhs, rhs) if op.is_float() => {
+                self.float_operand(*lhs, "r0", "s0");
+                self.float_operand(*rhs, "r1", "s1");
+                let dst = self.dest(inst, "r0");
+                let op = match op {
+                    BinOp::FAdd => "vadd.f32",
+                    BinOp::FSub => "vsub.f32",
+                    BinOp::FMul => "vmul.f32",
+                    _ => "vdiv.f32",
+                };
+                self.asm(format!("{} s0, s0, s1", op));
+                self.asm(format!("vmov {}, s0", dst));
+                self.store(inst, dst);
+            }
+            InstKind::Binary(op, lhs, rhs) => {
+                let lhs = self.operand(*lhs, "r0");
+                let rhs = self.operand(*rhs, "r1");
+                let dst = self.dest(inst, "r0");
+                let op = match op {
+                    BinOp::Add => "add",
+                    BinOp::Sub => "sub",
+                    BinOp::Mul => "mul",
+                    BinOp::And => "and",
+                    BinOp::Or => "orr",
+                    BinOp::Xor => "eor",
+                    BinOp::Shl => "lsl",
+                    BinOp::AShr => "asr",
+                    _ => unreachable!(),
+                };
+                self.asm(format!("{} {}, {}, {}", op, dst, lhs, rhs));
+                self.store(inst, dst);
+            }
+            InstKind::Icmp(op, lhs, rhs) => {
+                let lhs = self.operand(*lhs, "r0");
+                let rhs = self.operand(*rhs, "r1");
+                let dst = self.dest(inst, "r0");
+                let cond = match op {
+                    CmpOp::Eq => "eq",
+                    CmpOp::Ne => "ne",
+                    CmpOp::Slt => "lt",
+                    CmpOp::Sgt => "gt",
+                    CmpOp::Sle => "le",
+                    CmpOp::Sge => "ge",
+                };
+                self.asm(format!("cmp {}, {}", lhs, rhs));
+                self.asm(format!("mov {}, #0", dst));
+                self.asm(format!("mov{} {}, #1", cond, dst));
+                self.store(inst, dst);
+            }
+            InstKind::Fcmp(op, lhs, rhs) => {
+                self.float_operand(*lhs, "r0", "s0");
+                self.float_operand(*rhs, "r1", "s1");
+                let dst = self.dest(inst, "r0");
+                // 无序时vcmp置C与V，选用在无序时不成立的条件，只有不等成立
+                let cond = match op {
+                    CmpOp::Eq => "eq",
+                    CmpOp::Ne => "ne",
+                    CmpOp::Slt => "mi",
+                    CmpOp::Sgt => "gt",
+                    CmpOp::Sle => "ls",
+                    CmpOp::Sge => "ge",
+                };
+                self.asm("vcmp.f32 s0, s1".to_string());
+                self.asm("vmrs APSR_nzcv, fpscr".to_string());
+                self.asm(format!("mov {}, #0", dst));
+                self.asm(format!("mov{} {}, #1", cond, dst));
+                self.store(inst, dst);
+            }
+            InstKind::Zext(val) => {
+                let dst = self.dest(inst, "r0");
+                self.load(*val, dst);
+                self.store(inst, dst);
+            }
+            InstKind::Sitofp(val) | InstKind::Fptosi(val) => {
+                self.float_operand(*val, "r0", "s0");
+                let dst = self.dest(inst, "r0");
+                match self.func.inst(inst).kind {
+                    InstKind::Sitofp(_) => self.asm("vcvt.f32.s32 s0, s0".to_string()),
+                    _ => self.asm("vcvt.s32.f32 s0, s0".to_string()),
+                }
+                self.asm(format!("vmov {}, s0", dst));
+                self.store(inst, dst);
+            }
+            InstKind::Gep(base, indices) => {
+                self.load(*base, "r0");
+                let mut ty = self.module.value_type(self.func, *base).pointee().clone();
+                for (pos, index) in indices.iter().enumerate() {
+                    if pos != 0 {
+                        ty = match ty {
+                            Type::Array(_, elem) => *elem,
+                            _ => panic!("getelementptr into {}", ty),
+                        };
+                    }
+                    let size = size_of(&ty, PTR_SIZE);
+                    match index {
+                        Value::Int(0) => {}
+                        Value::Int(val) => self.add_imm("r0", "r0", val.wrapping_mul(size as i32)),
+                        _ => {
+                            self.load(*index, "r1");
+                            if size.is_power_of_two() {
+                                let shift = size.trailing_zeros();
+                                self.asm(format!("add r0, r0, r1, lsl #{}", shift));
+                            } else {
+                                self.mov_imm("r2", size as i32);
+                                self.asm("mla r0, r1, r2, r0".to_string());
+                            }
+                        }
+                    }
+                }
+                self.store(inst, "r0");
+            }
+            InstKind::Call(callee, args) => {
+                let callee = self.module.function(*callee);
+                let types: Vec<Type> = callee.params.iter().map(|(_, ty)| ty.clone()).collect();
+                let locations = arg_locations(&types);
+                let stack_args = locations
+                    .iter()
+                    .filter(|arg| matches!(arg, ArgLoc::Stack(_)))
+                    .count();
+                // 调用前sp需要8字节对齐
+                let space = (stack_args * 4).div_ceil(8) * 8;
+                if space != 0 {
+                    self.add_imm("sp", "sp", -(space as i32));
+                }
+                // 经过r0的参数先于整数参数寄存器中的参数
+                for (arg, location) in args.iter().zip(locations.iter()) {
+                    match location {
+                        ArgLoc::Reg(_) => {}
+                        ArgLoc::FloatReg(freg) => {
+                            self.float_operand(*arg, "r0", &format!("s{}", freg))
+                        }
+                        ArgLoc::Stack(pos) => {
+                            self.load(*arg, "r0");
+                            self.asm(format!("str r0, [sp, #{}]", pos * 4));
+                        }
+                    }
+                }
+                for (arg, location) in args.iter().zip(locations.iter()) {
+                    if let ArgLoc::Reg(reg) = location {
+                        self.load(*arg, reg);
+                    }
+                }
+                self.asm(format!("bl {}", callee.name));
+                if space != 0 {
+                    self.add_imm("sp", "sp", space as i32);
+                }
+                if callee.ret_ty == Type::Float {
+                    self.asm("vmov r0, s0".to_string());
+                }
+                if callee.ret_ty != Type::Void {
+                    self.store(inst, "r0");
+                }
+            }
+            InstKind::Phi(_) => {
+                let (from, to) = (self.frame.phi_values[&inst], self.frame.values[&inst]);
+                let reg = self.reg_of(from, self.reg_of(to, "r0"));
+                self.load_from(from, reg);
+                self.store_to(to, reg);
+            }
+            InstKind::Br(target) => {
+                self.emit_phi_copies(block, *target);
+                if Some(*target) != next {
+                    self.asm(format!("b {}", self.label(*target)));
+                }
+            }
+            InstKind::CondBr(cond, then_block, else_block) => {
+                self.emit_phi_copies(block, *then_block);
+                if else_block != then_block {
+                    self.emit_phi_copies(block, *else_block);
+                }
+                let cond = self.operand(*cond, "r0");
+                self.asm(format!("cmp {}, #0", cond));
+                self.asm(format!("bne {}", self.label(*then_block)));
+                if Some(*else_block) != next {
+                    self.asm(format!("b {}", self.label(*else_block)));
+                }
+            }
+            InstKind::Ret(val) => {
+                if let Some(val) = val {
+                    self.load(*val, "r0");
+                    if self.func.ret_ty == Type::Float {
+                        self.asm("vmov s0, r0".to_string());
+                    }
+                }
+                for (reg, offset) in self.frame.saved.clone() {
+                    self.access("ldr", REGS[reg], offset);
+                }
+                self.asm("mov sp, fp".to_string());
+                self.asm("pop {fp, pc}".to_string());
+            }
+        }
+    }
+
+    // 前驱跳转前把值写入phi的暂存值，块开头再复制给phi，避免多个phi的复制互相覆盖
+    fn emit_phi_copies(&mut self, block: BlockId, succ: BlockId) {
+        for inst in &self.func.block(succ).insts {
+            let incoming = match &self.func.inst(*inst).kind {
+                InstKind::Phi(incoming) => incoming,
+                _ => break,
+            };
+            if let Some((val, _)) = incoming.iter().find(|(_, pred)| *pred == block) {
+                let location = self.frame.phi_values[inst];
+                let reg = self.reg_of(location, "r0");
+                self.load(*val, reg);
+                self.store_to(location, reg);
+            }
+        }
+    }
+}
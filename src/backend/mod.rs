@@ -0,0 +1,296 @@
+// 目标代码生成，将优化后的IR翻译为各平台的汇编
+mod armv7;
+mod regalloc;
+mod riscv64;
+mod wasm;
+mod x86_64;
+
+use std::collections::{HashMap, HashSet};
+
+use super::ir::{BlockId, Constant, Function, InstId, InstKind, Module, Type, Value};
+use super::pass::dominator::DomTree;
+use regalloc::RegClass;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Target {
+    Llvm,
+    X86_64,
+    Riscv64,
+    Armv7,
+    Wasm,
+}
+
+impl Target {
+    pub fn from_name(name: &str) -> Option<Target> {
+        match name {
+            "llvm" => Some(Target::Llvm),
+            "x86_64" => Some(Target::X86_64),
+            "riscv64" => Some(Target::Riscv64),
+            "armv7" => Some(Target::Armv7),
+            "wasm" => Some(Target::Wasm),
+            _ => None,
+        }
+    }
+}
+
+pub fn emit(module: &Module, target: Target) -> String {
+    match target {
+        Target::Llvm => module.to_string(),
+        Target::X86_64 => x86_64::emit(module),
+        Target::Riscv64 => riscv64::emit(module),
+        Target::Armv7 => armv7::emit(module),
+        Target::Wasm => wasm::emit(module),
+    }
+}
+
+// 类型占用的字节数
+fn size_of(ty: &Type, ptr_size: usize) -> usize {
+    match ty {
+        Type::Void => 0,
+        Type::I1 => 1,
+        Type::I32 | Type::Float => 4,
+        Type::Pointer(_) => ptr_size,
+        Type::Array(len, elem) => len * size_of(elem, ptr_size),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Location {
+    Reg(usize), // 寄存器类中的下标
+    Stack(i32),
+}
+
+// 栈帧布局与寄存器分配的结果，偏移量相对于帧指针
+// 帧指针之下依次为保留区、被调用者保存的寄存器、alloca、溢出的值
+struct Frame {
+    allocas: HashMap<InstId, i32>,         // alloca的栈上空间
+    values: HashMap<InstId, Location>,     // 指令结果的位置
+    phi_values: HashMap<InstId, Location>, // 前驱在跳转前将phi的值写入这里
+    params: Vec<Location>,                 // 参数在函数开头被移到这里
+    saved: Vec<(usize, i32)>,              // 用到的被调用者保存的寄存器与保存位置
+    size: i32,                             // 包括保留区，按16字节对齐
+}
+
+impl Frame {
+    fn new(func: &Function, ptr_size: usize, class: &RegClass, reserved: i32) -> Frame {
+        let (vregs, phi_vregs, colors) = allocate(func, class);
+        let slot_size = ptr_size as i32;
+        let mut size = reserved;
+        let mut alloc = |bytes: usize| {
+            size += (bytes as i32 + slot_size - 1) / slot_size * slot_size;
+            -size
+        };
+        let mut used: Vec<usize> = colors.iter().flatten().copied().collect();
+        used.sort_unstable();
+        used.dedup();
+        let saved = used
+            .into_iter()
+            .filter(|reg| !class.clobbered.contains(reg))
+            .map(|reg| (reg, alloc(ptr_size)))
+            .collect();
+        let mut allocas = HashMap::new();
+        for block in &func.layout {
+            for inst in &func.block(*block).insts {
+                if let InstKind::Alloca(ty) = &func.inst(*inst).kind {
+                    allocas.insert(*inst, alloc(size_of(ty, ptr_size)));
+                }
+            }
+        }
+        let mut locate = |vreg: usize| match colors[vreg] {
+            Some(reg) => Location::Reg(reg),
+            None => Location::Stack(alloc(ptr_size)),
+        };
+        let params = (0..func.params.len()).map(&mut locate).collect();
+        let mut values = HashMap::new();
+        let mut phi_values = HashMap::new();
+        for block in &func.layout {
+            for inst in &func.block(*block).insts {
+                if let Some(vreg) = vregs.get(inst) {
+                    values.insert(*inst, locate(*vreg));
+                }
+                if let Some(vreg) = phi_vregs.get(inst) {
+                    phi_values.insert(*inst, locate(*vreg));
+                }
+            }
+        }
+        Frame {
+            allocas,
+            values,
+            phi_values,
+            params,
+            saved,
+            size: (size + 15) / 16 * 16,
+        }
+    }
+}
+
+type VRegs = HashMap<InstId, usize>;
+
+// 把函数的SSA值作为虚拟寄存器交给寄存器分配器，编号依次为参数、指令结果，每个phi额外有一个暂存值
+// 暂存值在前驱的跳转前写入，在phi所在块的开头复制给phi，与各后端的代码生成顺序一致
+// 返回指令结果与phi暂存值的虚拟寄存器编号，以及每个虚拟寄存器分配到的寄存器
+fn allocate(func: &Function, class: &RegClass) -> (VRegs, VRegs, Vec<Option<usize>>) {
+    let mut vregs = HashMap::new();
+    let mut phi_vregs = HashMap::new();
+    let mut count = func.params.len();
+    for block in &func.layout {
+        for inst in &func.block(*block).insts {
+            let inst_ref = func.inst(*inst);
+            match &inst_ref.kind {
+                InstKind::Alloca(_) => {}
+                InstKind::Phi(_) => {
+                    vregs.insert(*inst, count);
+                    phi_vregs.insert(*inst, count + 1);
+                    count += 2;
+                }
+                _ if inst_ref.ty != Type::Void => {
+                    vregs.insert(*inst, count);
+                    count += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+    let vreg_of = |value: Value| match value {
+        Value::Inst(inst) => vregs.get(&inst).copied(),
+        Value::Param(index) => Some(index),
+        _ => None,
+    };
+    let depths = loop_depths(func);
+    let positions: HashMap<BlockId, usize> = func
+        .layout
+        .iter()
+        .enumerate()
+        .map(|(pos, block)| (*block, pos))
+        .collect();
+    let mut blocks = vec![];
+    for block in &func.layout {
+        let mut insts = vec![];
+        if *block == func.entry() {
+            insts.push(regalloc::Inst {
+                defs: (0..func.params.len()).collect(),
+                uses: vec![],
+                is_move: false,
+                is_call: false,
+            });
+        }
+        let mut succs = func.successors(*block);
+        succs.dedup();
+        for inst in &func.block(*block).insts {
+            let kind = &func.inst(*inst).kind;
+            if kind.is_terminator() {
+                for succ in &succs {
+                    for phi in &func.block(*succ).insts {
+                        let incoming = match &func.inst(*phi).kind {
+                            InstKind::Phi(incoming) => incoming,
+                            _ => break,
+                        };
+                        if let Some((val, _)) = incoming.iter().find(|(_, pred)| pred == block) {
+                            let uses: Vec<usize> = vreg_of(*val).into_iter().collect();
+                            insts.push(regalloc::Inst {
+                                defs: vec![phi_vregs[phi]],
+                                is_move: !uses.is_empty(),
+                                uses,
+                                is_call: false,
+                            });
+                        }
+                    }
+                }
+            }
+            let uses = match kind {
+                InstKind::Phi(_) => vec![phi_vregs[inst]],
+                _ => kind.operands().into_iter().filter_map(vreg_of).collect(),
+            };
+            insts.push(regalloc::Inst {
+                defs: vregs.get(inst).copied().into_iter().collect(),
+                uses,
+                is_move: matches!(kind, InstKind::Phi(_) | InstKind::Zext(_)),
+                is_call: matches!(kind, InstKind::Call(_, _)),
+            });
+        }
+        blocks.push(regalloc::Block {
+            insts,
+            succs: succs.iter().map(|succ| positions[succ]).collect(),
+            depth: depths[block.0],
+        });
+    }
+    let colors = regalloc::allocate(&blocks, &vec![0; count], std::slice::from_ref(class));
+    (vregs, phi_vregs, colors)
+}
+
+// 各基本块的循环嵌套深度，以BlockId为下标，回边的目标支配其源
+fn loop_depths(func: &Function) -> Vec<u32> {
+    let dom = DomTree::new(func);
+    let preds = func.predecessors();
+    let mut depths = vec![0; func.blocks.len()];
+    for header in &dom.rpo {
+        // 自然循环为从回边的源逆向可达且不经过循环头的基本块
+        let mut body = HashSet::new();
+        body.insert(*header);
+        let mut stack: Vec<BlockId> = preds[header.0]
+            .iter()
+            .copied()
+            .filter(|pred| dom.dominates(*header, *pred))
+            .collect();
+        if stack.is_empty() {
+            continue;
+        }
+        while let Some(block) = stack.pop() {
+            if body.insert(block) {
+                stack.extend(preds[block.0].iter().copied());
+            }
+        }
+        for block in body {
+            depths[block.0] += 1;
+        }
+    }
+    depths
+}
+
+// GNU as格式的全局变量，常量放在只读段
+fn write_data(lines: &mut Vec<String>, module: &Module) {
+    for global in &module.globals {
+        if global.is_const {
+            lines.push("    .section .rodata".to_string());
+        } else {
+            lines.push("    .data".to_string());
+        }
+        lines.push(format!("    .globl {}", global.name));
+        lines.push("    .p2align 2".to_string());
+        lines.push(format!("{}:", global.name));
+        let mut zeros = 0;
+        write_constant(lines, &global.ty, &global.init, &mut zeros);
+        if zeros != 0 {
+            lines.push(format!("    .zero {}", zeros));
+        }
+    }
+}
+
+// 连续的0合并为一条.zero，zeros为尚未输出的0的字节数
+fn write_constant(lines: &mut Vec<String>, ty: &Type, constant: &Constant, zeros: &mut usize) {
+    match (constant, ty) {
+        (Constant::Int(0), _) => *zeros += 4,
+        (Constant::Int(val), _) => {
+            if *zeros != 0 {
+                lines.push(format!("    .zero {}", zeros));
+                *zeros = 0;
+            }
+            lines.push(format!("    .long {}", val));
+        }
+        (Constant::Float(val), _) if val.to_bits() == 0 => *zeros += 4,
+        (Constant::Float(val), _) => {
+            if *zeros != 0 {
+                lines.push(format!("    .zero {}", zeros));
+                *zeros = 0;
+            }
+            lines.push(format!("    .long {}", val.to_bits() as i32));
+        }
+        (Constant::Zero, _) => *zeros += size_of(ty, 0),
+        (Constant::Array(elems), Type::Array(_, elem_ty)) => {
+            for elem in elems {
+                write_constant(lines, elem_ty, elem, zeros);
+            }
+        }
+        (Constant::Array(_), _) => panic!("array constant of type {}", ty),
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::HashSet;
+
+// 与目标无关的图着色寄存器分配(Chaitin-Briggs)，包括保守合并、乐观着色与溢出
+// 虚拟寄存器用下标表示，分配结果为寄存器类中的寄存器下标，溢出的虚拟寄存器由后端放在栈上，通过保留的临时寄存器访问
+
+// 一类可以互相替换的寄存器，如整数寄存器
+pub struct RegClass {
+    pub count: usize,          // 可分配的寄存器数
+    pub clobbered: Vec<usize>, // 调用时不保存的寄存器
+}
+
+pub struct Inst {
+    pub defs: Vec<usize>,
+    pub uses: Vec<usize>,
+    pub is_move: bool, // 两端分配到同一个寄存器时可以省去的复制
+    pub is_call: bool,
+}
+
+pub struct Block {
+    pub insts: Vec<Inst>,
+    pub succs: Vec<usize>,
+    pub depth: u32, // 循环嵌套深度，用于估计溢出代价
+}
+
+struct Graph {
+    adj: Vec<HashSet<usize>>,
+    forbidden: Vec<HashSet<usize>>, // 跨越调用而不能使用的寄存器
+    cost: Vec<f64>,
+    moves: Vec<(usize, usize)>,
+}
+
+// vreg_classes为每个虚拟寄存器所属的寄存器类，返回分配到的寄存器，None表示溢出
+pub fn allocate(
+    blocks: &[Block],
+    vreg_classes: &[usize],
+    classes: &[RegClass],
+) -> Vec<Option<usize>> {
+    let count = vreg_classes.len();
+    let live_out = liveness(blocks, count);
+    let mut graph = build(blocks, &live_out, vreg_classes, classes);
+    let mut alias: Vec<usize> = (0..count).collect();
+    coalesce(&mut graph, &mut alias, vreg_classes, classes);
+    let colors = color(&graph, &alias, vreg_classes, classes);
+    (0..count).map(|vreg| colors[find(&alias, vreg)]).collect()
+}
+
+// 各基本块出口处活跃的虚拟寄存器
+fn liveness(blocks: &[Block], count: usize) -> Vec<HashSet<usize>> {
+    let mut uses = vec![];
+    let mut defs = vec![];
+    for block in blocks {
+        let mut block_uses = HashSet::new();
+        let mut block_defs = HashSet::new();
+        for inst in &block.insts {
+            for vreg in &inst.uses {
+                if !block_defs.contains(vreg) {
+                    block_uses.insert(*vreg);
+                }
+            }
+            block_defs.extend(inst.defs.iter().copied());
+        }
+        uses.push(block_uses);
+        defs.push(block_defs);
+    }
+    let mut live_in: Vec<HashSet<usize>> = vec![HashSet::new(); blocks.len()];
+    let mut live_out: Vec<HashSet<usize>> = vec![HashSet::new(); blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in (0..blocks.len()).rev() {
+            let mut out = HashSet::new();
+            for succ in &blocks[index].succs {
+                out.extend(live_in[*succ].iter().copied());
+            }
+            let mut new_in = uses[index].clone();
+            new_in.extend(out.difference(&defs[index]).copied());
+            if new_in.len() != live_in[index].len() {
+                live_in[index] = new_in;
+                changed = true;
+            }
+            live_out[index] = out;
+        }
+    }
+    debug_assert!(live_in.iter().flatten().all(|vreg| *vreg < count));
+    live_out
+}
+
+fn build(
+    blocks: &[Block],
+    live_out: &[HashSet<usize>],
+    vreg_classes: &[usize],
+    classes: &[RegClass],
+) -> Graph {
+    let count = vreg_classes.len();
+    let mut graph = Graph {
+        adj: vec![HashSet::new(); count],
+        forbidden: vec![HashSet::new(); count],
+        cost: vec![0.0; count],
+        moves: vec![],
+    };
+    for (block, out) in blocks.iter().zip(live_out) {
+        let weight = 10f64.powi(block.depth.min(8) as i32);
+        let mut live = out.clone();
+        for inst in block.insts.iter().rev() {
+            if inst.is_call {
+                for vreg in live.difference(&inst.defs.iter().copied().collect()) {
+                    let clobbered = &classes[vreg_classes[*vreg]].clobbered;
+                    graph.forbidden[*vreg].extend(clobbered.iter().copied());
+                }
+            }
+            for def in &inst.defs {
+                for vreg in &live {
+                    // 复制指令的源与目的不冲突
+                    let is_source = inst.is_move && inst.uses.contains(vreg);
+                    if *vreg != *def && !is_source && vreg_classes[*vreg] == vreg_classes[*def] {
+                        graph.adj[*def].insert(*vreg);
+                        graph.adj[*vreg].insert(*def);
+                    }
+                }
+            }
+            for def in &inst.defs {
+                live.remove(def);
+                graph.cost[*def] += weight;
+            }
+            for vreg in &inst.uses {
+                live.insert(*vreg);
+                graph.cost[*vreg] += weight;
+            }
+            if inst.is_move && inst.defs.len() == 1 && inst.uses.len() == 1 {
+                graph.moves.push((inst.defs[0], inst.uses[0]));
+            }
+        }
+    }
+    graph
+}
+
+fn find(alias: &[usize], mut vreg: usize) -> usize {
+    while alias[vreg] != vreg {
+        vreg = alias[vreg];
+    }
+    vreg
+}
+
+// 可用的寄存器数
+fn available(graph: &Graph, vreg: usize, vreg_classes: &[usize], classes: &[RegClass]) -> usize {
+    classes[vreg_classes[vreg]]
+        .count
+        .saturating_sub(graph.forbidden[vreg].len())
+}
+
+// Briggs保守合并：合并后度数不小于可用寄存器数的邻居少于可用寄存器数时才合并，不会使图变得更难着色
+fn coalesce(graph: &mut Graph, alias: &mut [usize], vreg_classes: &[usize], classes: &[RegClass]) {
+    let moves = std::mem::take(&mut graph.moves);
+    for (dst, src) in moves {
+        let (dst, src) = (find(alias, dst), find(alias, src));
+        if dst == src || vreg_classes[dst] != vreg_classes[src] || graph.adj[dst].contains(&src) {
+            continue;
+        }
+        let mut forbidden = graph.forbidden[dst].clone();
+        forbidden.extend(graph.forbidden[src].iter().copied());
+        let limit = classes[vreg_classes[dst]]
+            .count
+            .saturating_sub(forbidden.len());
+        let neighbors: HashSet<usize> = graph.adj[dst].union(&graph.adj[src]).copied().collect();
+        let significant = neighbors
+            .iter()
+            .filter(|vreg| {
+                graph.adj[**vreg].len() >= available(graph, **vreg, vreg_classes, classes)
+            })
+            .count();
+        if significant >= limit {
+            continue;
+        }
+        alias[src] = dst;
+        for vreg in std::mem::take(&mut graph.adj[src]) {
+            graph.adj[vreg].remove(&src);
+            graph.adj[vreg].insert(dst);
+            graph.adj[dst].insert(vreg);
+        }
+        graph.forbidden[dst] = forbidden;
+        graph.cost[dst] += graph.cost[src];
+    }
+}
+
+fn color(
+    graph: &Graph,
+    alias: &[usize],
+    vreg_classes: &[usize],
+    classes: &[RegClass],
+) -> Vec<Option<usize>> {
+    let count = vreg_classes.len();
+    let nodes: Vec<usize> = (0..count)
+        .filter(|vreg| find(alias, *vreg) == *vreg)
+        .collect();
+    let mut degree: Vec<usize> = (0..count).map(|vreg| graph.adj[vreg].len()).collect();
+    let mut removed = vec![false; count];
+    let mut stack = vec![];
+    let mut low: Vec<usize> = vec![];
+    let mut high: HashSet<usize> = HashSet::new();
+    for vreg in &nodes {
+        if degree[*vreg] < available(graph, *vreg, vreg_classes, classes) {
+            low.push(*vreg);
+        } else {
+            high.insert(*vreg);
+        }
+    }
+    while stack.len() < nodes.len() {
+        // 没有度数小的节点时，选择代价与度数之比最小的节点乐观地入栈
+        let vreg = match low.pop() {
+            Some(vreg) => vreg,
+            None => {
+                let vreg = *high
+                    .iter()
+                    .min_by(|lhs, rhs| {
+                        let lhs_cost = graph.cost[**lhs] / (degree[**lhs] + 1) as f64;
+                        let rhs_cost = graph.cost[**rhs] / (degree[**rhs] + 1) as f64;
+                        lhs_cost.total_cmp(&rhs_cost).then(lhs.cmp(rhs))
+                    })
+                    .unwrap();
+                high.remove(&vreg);
+                vreg
+            }
+        };
+        removed[vreg] = true;
+        stack.push(vreg);
+        for neighbor in &graph.adj[vreg] {
+            if removed[*neighbor] {
+                continue;
+            }
+            degree[*neighbor] -= 1;
+            if degree[*neighbor] < available(graph, *neighbor, vreg_classes, classes)
+                && high.remove(neighbor)
+            {
+                low.push(*neighbor);
+            }
+        }
+    }
+    let mut colors: Vec<Option<usize>> = vec![None; count];
+    while let Some(vreg) = stack.pop() {
+        let used: HashSet<usize> = graph.adj[vreg]
+            .iter()
+            .filter_map(|neighbor| colors[*neighbor])
+            .collect();
+        colors[vreg] = (0..classes[vreg_classes[vreg]].count)
+            .find(|reg| !used.contains(reg) && !graph.forbidden[vreg].contains(reg));
+    }
+    colors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{allocate, Block, Inst, RegClass};
+
+    fn inst(defs: &[usize], uses: &[usize]) -> Inst {
+        Inst {
+            defs: defs.to_vec(),
+            uses: uses.to_vec(),
+            is_move: false,
+            is_call: false,
+        }
+    }
+
+    fn class(count: usize) -> RegClass {
+        RegClass {
+            count,
+            clobbered: vec![],
+        }
+    }
+
+    // 三个互相冲突的虚拟寄存器只有两个寄存器，溢出代价最小的一个
+    #[test]
+    fn spills_cheapest_vreg() {
+        let blocks = [
+            Block {
+                insts: vec![
+                    inst(&[0], &[]),
+                    inst(&[1], &[]),
+                    inst(&[2], &[]),
+                    inst(&[], &[0, 1]),
+                ],
+                succs: vec![1],
+                depth: 0,
+            },
+            // v2在循环中使用，代价最高
+            Block {
+                insts: vec![inst(&[], &[2])],
+                succs: vec![1],
+                depth: 2,
+            },
+        ];
+        let colors = allocate(&blocks, &[0; 3], &[class(2)]);
+        assert!(colors[2].is_some(), "{:?}", colors);
+        assert_eq!(colors[..2].iter().filter(|c| c.is_none()).count(), 1);
+        assert_ne!(colors[0].or(colors[1]), colors[2]);
+    }
+
+    // v0复制到v4，v3只与v0冲突，v1、v2只与v4冲突；不合并时v0与v4会着上不同的颜色
+    // redefine时复制之后重新定义v0，两端冲突
+    fn copy_block(redefine: bool) -> Block {
+        let mut insts = vec![
+            inst(&[0], &[]),
+            inst(&[3], &[]),
+            inst(&[], &[3]),
+            Inst {
+                is_move: true,
+                ..inst(&[4], &[0])
+            },
+            inst(&[1], &[]),
+            inst(&[2], &[]),
+            inst(&[], &[4, 1, 2]),
+        ];
+        if redefine {
+            insts.insert(4, inst(&[0], &[]));
+            insts.push(inst(&[], &[0]));
+        }
+        Block {
+            insts,
+            succs: vec![],
+            depth: 0,
+        }
+    }
+
+    #[test]
+    fn coalesces_move() {
+        let colors = allocate(&[copy_block(false)], &[0; 5], &[class(3)]);
+        assert!(colors.iter().all(|c| c.is_some()), "{:?}", colors);
+        assert_eq!(colors[0], colors[4], "{:?}", colors);
+        let colors = allocate(&[copy_block(true)], &[0; 5], &[class(4)]);
+        assert!(colors.iter().all(|c| c.is_some()), "{:?}", colors);
+        assert_ne!(colors[0], colors[4], "{:?}", colors);
+    }
+}
@@ -0,0 +1,486 @@
+use super::super::ir::{BinOp, BlockId, CmpOp, Function, InstId, InstKind, Module, Type, Value};
+use super::regalloc::RegClass;
+use super::{size_of, write_data, Frame, Location};
+
+// RV64GC的GNU汇编，遵循LP64D调用约定
+// SSA值分配到寄存器或栈上的8字节栈槽，t0、t1、t2为临时寄存器，t3用于超出12位立即数的偏移
+// float的位模式与整数一样存放，运算时移入ft0与ft1
+const PTR_SIZE: usize = 8;
+const ARG_REGS: [&str; 8] = ["a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"];
+const FLOAT_ARG_REGS: [&str; 8] = ["fa0", "fa1", "fa2", "fa3", "fa4", "fa5", "fa6", "fa7"];
+
+// 可分配的寄存器，t4、t5、t6在调用时不保存
+const REGS: [&str; 14] = [
+    "s1", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t4", "t5", "t6",
+];
+
+pub fn emit(module: &Module) -> String {
+    let mut lines = vec!["    .text".to_string()];
+    for (index, func) in module.functions.iter().enumerate() {
+        if !func.is_declaration() {
+            FuncEmitter::new(module, func, index).emit(&mut lines);
+        }
+    }
+    write_data(&mut lines, module);
+    let mut res = lines.join("\n");
+    res.push('\n');
+    res
+}
+
+// 参数的位置，float先使用浮点参数寄存器，用完后与整数一样使用剩余的整数参数寄存器，其余按顺序放在栈上
+#[derive(Clone, Copy)]
+enum ArgLoc {
+    Reg(&'static str),
+    FloatReg(&'static str),
+    Stack(usize),
+}
+
+fn arg_locations(params: &[Type]) -> Vec<ArgLoc> {
+    let (mut ints, mut floats, mut stack) = (0, 0, 0);
+    let next = |count: &mut usize| {
+        *count += 1;
+        *count - 1
+    };
+    params
+        .iter()
+        .map(|ty| match ty {
+            Type::Float if floats < FLOAT_ARG_REGS.len() => {
+                ArgLoc::FloatReg(FLOAT_ARG_REGS[next(&mut floats)])
+            }
+            _ if ints < ARG_REGS.len() => ArgLoc::Reg(ARG_REGS[next(&mut ints)]),
+            _ => ArgLoc::Stack(next(&mut stack)),
+        })
+        .collect()
+}
+
+fn is_imm12(val: i64) -> bool {
+    (-2048..2048).contains(&val)
+}
+
+struct FuncEmitter<'a> {
+    module: &'a Module,
+    func: &'a Function,
+    index: usize,
+    frame: Frame,
+    lines: Vec<String>,
+}
+
+impl<'a> FuncEmitter<'a> {
+    // s0之下的16字节保存ra与旧的s0
+    fn new(module: &'a Module, func: &'a Function, index: usize) -> FuncEmitter<'a> {
+        FuncEmitter {
+            module,
+            func,
+            index,
+            frame: Frame::new(
+                func,
+                PTR_SIZE,
+                &RegClass {
+                    count: REGS.len(),
+                    clobbered: vec![11, 12, 13],
+                },
+                16,
+            ),
+            lines: vec![],
+        }
+    }
+
+    fn asm(&mut self, text: String) {
+        self.lines.push(format!("    {}", text));
+    }
+
+    fn label(&self, block: BlockId) -> String {
+        format!(".LBB{}_{}", self.index, block.0)
+    }
+
+    fn is_pointer(&self, value: Value) -> bool {
+        matches!(self.module.value_type(self.func, value), Type::Pointer(_))
+    }
+
+    // 以s0为基址访问栈帧，如lw t0, -24(s0)
+    fn access(&mut self, op: &str, reg: &str, offset: i32) {
+        if is_imm12(offset as i64) {
+            self.asm(format!("{} {}, {}(s0)", op, reg, offset));
+        } else {
+            self.asm(format!("li t3, {}", offset));
+            self.asm("add t3, s0, t3".to_string());
+            self.asm(format!("{} {}, 0(t3)", op, reg));
+        }
+    }
+
+    fn add_imm(&mut self, dst: &str, src: &str, val: i64) {
+        if is_imm12(val) {
+            self.asm(format!("addi {}, {}, {}", dst, src, val));
+        } else {
+            self.asm(format!("li t3, {}", val));
+            self.asm(format!("add {}, {}, t3", dst, src));
+        }
+    }
+
+    fn load(&mut self, value: Value, reg: &str) {
+        match value {
+            Value::Undef => self.asm(format!("li {}, 0", reg)),
+            Value::Int(val) => self.asm(format!("li {}, {}", reg, val)),
+            Value::Float(bits) => self.asm(format!("li {}, {}", reg, bits as i32)),
+            Value::Bool(val) => self.asm(format!("li {}, {}", reg, val as i32)),
+            Value::Global(global) => {
+                let name = &self.module.globals[global.0].name;
+                self.asm(format!("la {}, {}", reg, name));
+            }
+            Value::Inst(inst) if self.frame.allocas.contains_key(&inst) => {
+                let offset = self.frame.allocas[&inst];
+                self.add_imm(reg, "s0", offset as i64);
+            }
+            Value::Inst(inst) => self.load_from(self.frame.values[&inst], value, reg),
+            Value::Param(index) => self.load_from(self.frame.params[index], value, reg),
+        }
+    }
+
+    fn load_from(&mut self, location: Location, value: Value, reg: &str) {
+        match location {
+            Location::Reg(src) if REGS[src] == reg => {}
+            Location::Reg(src) => self.asm(format!("mv {}, {}", reg, REGS[src])),
+            Location::Stack(offset) => {
+                let op = if self.is_pointer(value) { "ld" } else { "lw" };
+                self.access(op, reg, offset);
+            }
+        }
+    }
+
+    // 值所在的寄存器，不在寄存器中时先加载到临时寄存器
+    fn operand(&mut self, value: Value, scratch: &'static str) -> &'static str {
+        let location = match value {
+            Value::Inst(inst) => self.frame.values.get(&inst).copied(),
+            Value::Param(index) => Some(self.frame.params[index]),
+            _ => None,
+        };
+        match location {
+            Some(Location::Reg(reg)) => REGS[reg],
+            _ => {
+                self.load(value, scratch);
+                scratch
+            }
+        }
+    }
+
+    // 经过临时寄存器把float值移入浮点寄存器
+    fn float_operand(&mut self, value: Value, scratch: &'static str, freg: &str) {
+        let reg = self.operand(value, scratch);
+        self.asm(format!("fmv.w.x {}, {}", freg, reg));
+    }
+
+    // 分配到寄存器的指令结果直接在该寄存器中计算
+    fn dest(&self, inst: InstId, scratch: &'static str) -> &'static str {
+        self.reg_of(self.frame.values[&inst], scratch)
+    }
+
+    fn reg_of(&self, location: Location, scratch: &'static str) -> &'static str {
+        match location {
+            Location::Reg(reg) => REGS[reg],
+            Location::Stack(_) => scratch,
+        }
+    }
+
+    fn store(&mut self, inst: InstId, reg: &str) {
+        let pointer = self.is_pointer(Value::Inst(inst));
+        self.store_to(self.frame.values[&inst], reg, pointer);
+    }
+
+    fn store_to(&mut self, location: Location, reg: &str, pointer: bool) {
+        match location {
+            Location::Reg(dst) if REGS[dst] == reg => {}
+            Location::Reg(dst) => self.asm(format!("mv {}, {}", REGS[dst], reg)),
+            Location::Stack(offset) => self.access(if pointer { "sd" } else { "sw" }, reg, offset),
+        }
+    }
+
+    fn emit(mut self, lines: &mut Vec<String>) {
+        let name = &self.func.name;
+        lines.push(format!("    .globl {}", name));
+        lines.push(format!("    .type {}, @function", name));
+        lines.push(format!("{}:", name));
+        self.asm("addi sp, sp, -16".to_string());
+        self.asm("sd ra, 8(sp)".to_string());
+        self.asm("sd s0, 0(sp)".to_string());
+        self.asm("addi s0, sp, 16".to_string());
+        let size = self.frame.size as i64 - 16;
+        if size != 0 {
+            self.add_imm("sp", "sp", -size);
+        }
+        for (reg, offset) in self.frame.saved.clone() {
+            self.access("sd", REGS[reg], offset);
+        }
+        // 寄存器放不下的参数由调用者放在s0之上
+        let types: Vec<Type> = self.func.params.iter().map(|(_, ty)| ty.clone()).collect();
+        for (index, arg) in arg_locations(&types).into_iter().enumerate() {
+            let location = self.frame.params[index];
+            let pointer = self.is_pointer(Value::Param(index));
+            let reg = match arg {
+                ArgLoc::Reg(reg) => reg,
+                ArgLoc::FloatReg(freg) => {
+                    let reg = self.reg_of(location, "t0");
+                    self.asm(format!("fmv.x.w {}, {}", reg, freg));
+                    reg
+                }
+                ArgLoc::Stack(pos) => {
+                    let reg = self.reg_of(location, "t0");
+                    self.access("ld", reg, 8 * pos as i32);
+                    reg
+                }
+            };
+            self.store_to(location, reg, pointer);
+        }
+        let layout = &self.func.layout;
+        for (pos, block) in layout.iter().enumerate() {
+            let next = layout.get(pos + 1).copied();
+            let label = self.label(*block);
+            self.lines.push(format!("{}:", label));
+            for inst in &self.func.block(*block).insts {
+                self.emit_inst(*block, *inst, next);
+            }
+        }
+        lines.append(&mut self.lines);
+        lines.push(format!("    .size {}, .-{}", name, name));
+    }
+
+    fn emit_inst(&mut self, block: BlockId, inst: InstId, next: Option<BlockId>) {
+        match &self.func.inst(inst).kind {
+            InstKind::Alloca(_) => {}
+            InstKind::Load(ptr) => {
+                let ptr = self.operand(*ptr, "t0");
+                let dst = self.dest(inst, "t0");
+                match self.func.inst(inst).ty {
+                    Type::I1 => self.asm(format!("lbu {}, 0({})", dst, ptr)),
+                    _ => self.asm(format!("lw {}, 0({})", dst, ptr)),
+                }
+                self.store(inst, dst);
+            }
+            InstKind::Store(val, ptr) => {
+                let ptr = self.operand(*ptr, "t0");
+                let val_reg = self.operand(*val, "t1");
+                match self.module.value_type(self.func, *val) {
+                    Type::I1 => self.asm(format!("sb {}, 0({})", val_reg, ptr)),
+                    _ => self.asm(format!("sw {}, 0({})", val_reg, ptr)),
+                }
+            }
+            InstKind::Binary(op, lhs, rhs) if op.is_float() => {
+                self.float_operand(*lhs, "t0", "ft0");
+                self.float_operand(*rhs, "t1", "ft1");
+                let dst = self.dest(inst, "t0");
+                let op = match op {
+                    BinOp::FAdd => "fadd.s",
+                    BinOp::FSub => "fsub.s",
+                    BinOp::FMul => "fmul.s",
+                    _ => "fdiv.s",
+                };
+                self.asm(format!("{} ft0, ft0, ft1", op));
+                self.asm(format!("fmv.x.w {}, ft0", dst));
+                self.store(inst, dst);
+            }
+            InstKind::Binary(op, lhs, rhs) => {
+                let lhs = self.operand(*lhs, "t0");
+                let rhs = self.operand(*rhs, "t1");
+                let dst = self.dest(inst, "t0");
+                let op = match op {
+                    BinOp::Add => "addw",
+                    BinOp::Sub => "subw",
+                    BinOp::Mul => "mulw",
+                    BinOp::SDiv => "divw",
+                    BinOp::SRem => "remw",
+                    BinOp::And => "and",
+                    BinOp::Or => "or",
+                    BinOp::Xor => "xor",
+                    BinOp::Shl => "sllw",
+                    BinOp::AShr => "sraw",
+                    _ => unreachable!(),
+                };
+                self.asm(format!("{} {}, {}, {}", op, dst, lhs, rhs));
+                self.store(inst, dst);
+            }
+            InstKind::Icmp(op, lhs, rhs) => {
+                let lhs = self.operand(*lhs, "t0");
+                let rhs = self.operand(*rhs, "t1");
+                let dst = self.dest(inst, "t0");
+                // 操作数都已符号扩展到64位，可以直接比较
+                match op {
+                    CmpOp::Eq | CmpOp::Ne => {
+                        self.asm(format!("sub {}, {}, {}", dst, lhs, rhs));
+                        let op = if *op == CmpOp::Eq { "seqz" } else { "snez" };
+                        self.asm(format!("{} {}, {}", op, dst, dst));
+                    }
+                    CmpOp::Slt | CmpOp::Sge => {
+                        self.asm(format!("slt {}, {}, {}", dst, lhs, rhs));
+                        if *op == CmpOp::Sge {
+                            self.asm(format!("xori {}, {}, 1", dst, dst));
+                        }
+                    }
+                    CmpOp::Sgt | CmpOp::Sle => {
+                        self.asm(format!("slt {}, {}, {}", dst, rhs, lhs));
+                        if *op == CmpOp::Sle {
+                            self.asm(format!("xori {}, {}, 1", dst, dst));
+                        }
+                    }
+                }
+                self.store(inst, dst);
+            }
+            InstKind::Fcmp(op, lhs, rhs) => {
+                self.float_operand(*lhs, "t0", "ft0");
+                self.float_operand(*rhs, "t1", "ft1");
+                let dst = self.dest(inst, "t0");
+                // 比较的操作数含NaN时结果为0，不等取相等的反
+                match op {
+                    CmpOp::Eq | CmpOp::Ne => {
+                        self.asm(format!("feq.s {}, ft0, ft1", dst));
+                        if *op == CmpOp::Ne {
+                            self.asm(format!("xori {}, {}, 1", dst, dst));
+                        }
+                    }
+                    CmpOp::Slt => self.asm(format!("flt.s {}, ft0, ft1", dst)),
+                    CmpOp::Sgt => self.asm(format!("flt.s {}, ft1, ft0", dst)),
+                    CmpOp::Sle => self.asm(format!("fle.s {}, ft0, ft1", dst)),
+                    CmpOp::Sge => self.asm(format!("fle.s {}, ft1, ft0", dst)),
+                }
+                self.store(inst, dst);
+            }
+            InstKind::Zext(val) => {
+                let dst = self.dest(inst, "t0");
+                self.load(*val, dst);
+                self.store(inst, dst);
+            }
+            InstKind::Sitofp(val) => {
+                let val = self.operand(*val, "t0");
+                let dst = self.dest(inst, "t0");
+                self.asm(format!("fcvt.s.w ft0, {}", val));
+                self.asm(format!("fmv.x.w {}, ft0", dst));
+                self.store(inst, dst);
+            }
+            InstKind::Fptosi(val) => {
+                self.float_operand(*val, "t0", "ft0");
+                let dst = self.dest(inst, "t0");
+                self.asm(format!("fcvt.w.s {}, ft0, rtz", dst));
+                self.store(inst, dst);
+            }
+            InstKind::Gep(base, indices) => {
+                self.load(*base, "t0");
+                let mut ty = self.module.value_type(self.func, *base).pointee().clone();
+                for (pos, index) in indices.iter().enumerate() {
+                    if pos != 0 {
+                        ty = match ty {
+                            Type::Array(_, elem) => *elem,
+                            _ => panic!("getelementptr into {}", ty),
+                        };
+                    }
+                    let size = size_of(&ty, PTR_SIZE);
+                    match index {
+                        Value::Int(0) => {}
+                        Value::Int(val) => self.add_imm("t0", "t0", *val as i64 * size as i64),
+                        _ => {
+                            self.load(*index, "t1");
+                            if size.is_power_of_two() {
+                                self.asm(format!("slli t1, t1, {}", size.trailing_zeros()));
+                            } else {
+                                self.asm(format!("li t2, {}", size));
+                                self.asm("mul t1, t1, t2".to_string());
+                            }
+                            self.asm("add t0, t0, t1".to_string());
+                        }
+                    }
+                }
+                self.store(inst, "t0");
+            }
+            InstKind::Call(callee, args) => {
+                let callee = self.module.function(*callee);
+                let types: Vec<Type> = callee.params.iter().map(|(_, ty)| ty.clone()).collect();
+                let locations = arg_locations(&types);
+                let stack_args = locations
+                    .iter()
+                    .filter(|arg| matches!(arg, ArgLoc::Stack(_)))
+                    .count();
+                // 调用前sp需要16字节对齐
+                let space = (stack_args * 8).div_ceil(16) * 16;
+                if space != 0 {
+                    self.add_imm("sp", "sp", -(space as i64));
+                }
+                for (arg, location) in args.iter().zip(locations.iter()) {
+                    match location {
+                        ArgLoc::Reg(reg) => self.load(*arg, reg),
+                        ArgLoc::FloatReg(freg) => self.float_operand(*arg, "t0", freg),
+                        ArgLoc::Stack(pos) => {
+                            self.load(*arg, "t0");
+                            self.asm(format!("sd t0, {}(sp)", pos * 8));
+                        }
+                    }
+                }
+                self.asm(format!("call {}", callee.name));
+                if space != 0 {
+                    self.add_imm("sp", "sp", space as i64);
+                }
+                if callee.ret_ty == Type::Float {
+                    self.asm("fmv.x.w a0, fa0".to_string());
+                }
+                if callee.ret_ty != Type::Void {
+                    self.store(inst, "a0");
+                }
+            }
+            InstKind::Phi(_) => {
+                let (from, to) = (self.frame.phi_values[&inst], self.frame.values[&inst]);
+                let reg = self.reg_of(from, self.reg_of(to, "t0"));
+                if let Location::Stack(offset) = from {
+                    self.access("ld", reg, offset);
+                }
+                self.store_to(to, reg, true);
+            }
+            InstKind::Br(target) => {
+                self.emit_phi_copies(block, *target);
+                if Some(*target) != next {
+                    self.asm(format!("j {}", self.label(*target)));
+                }
+            }
+            InstKind::CondBr(cond, then_block, else_block) => {
+                self.emit_phi_copies(block, *then_block);
+                if else_block != then_block {
+                    self.emit_phi_copies(block, *else_block);
+                }
+                let cond = self.operand(*cond, "t0");
+                // 条件分支只能跳转±4KiB，跳转到目标块统一使用j
+                self.asm(format!("beqz {}, 1f", cond));
+                self.asm(format!("j {}", self.label(*then_block)));
+                self.lines.push("1:".to_string());
+                if Some(*else_block) != next {
+                    self.asm(format!("j {}", self.label(*else_block)));
+                }
+            }
+            InstKind::Ret(val) => {
+                if let Some(val) = val {
+                    self.load(*val, "a0");
+                    if self.func.ret_ty == Type::Float {
+                        self.asm("fmv.w.x fa0, a0".to_string());
+                    }
+                }
+                for (reg, offset) in self.frame.saved.clone() {
+                    self.access("ld", REGS[reg], offset);
+                }
+                self.asm("mv sp, s0".to_string());
+                self.asm("ld ra, -8(sp)".to_string());
+                self.asm("ld s0, -16(sp)".to_string());
+                self.asm("ret".to_string());
+            }
+        }
+    }
+
+    // 前驱跳转前把值写入phi的暂存值，块开头再复制给phi，避免多个phi的复制互相覆盖
+    fn emit_phi_copies(&mut self, block: BlockId, succ: BlockId) {
+        for inst in &self.func.block(succ).insts {
+            let incoming = match &self.func.inst(*inst).kind {
+                InstKind::Phi(incoming) => incoming,
+                _ => break,
+            };
+            if let Some((val, _)) = incoming.iter().find(|(_, pred)| *pred == block) {
+                let location = self.frame.phi_values[inst];
+                let reg = self.reg_of(location, "t0");
+                self.load(*val, reg);
+                self.store_to(location, reg, true);
+            }
+        }
+    }
+}
@@ -0,0 +1,473 @@
+use std::collections::HashMap;
+
+use super::super::ir::{
+    BinOp, BlockId, CmpOp, Constant, Function, InstId, InstKind, Module, Type, Value,
+};
+use super::super::pass::dominator::DomTree;
+use super::size_of;
+
+// WebAssembly文本格式(WAT)，地址为32位
+// SSA值放在局部变量中，float为f32，其余为i32，全局变量与alloca放在线性内存里，alloca使用从内存顶端向下增长的栈，栈指针为全局变量$sp
+// 运行时库函数从env模块导入，线性内存导出为memory
+const PTR_SIZE: usize = 4;
+const DATA_BASE: usize = 1024;
+const STACK_SIZE: usize = 8 << 20;
+const PAGE_SIZE: usize = 1 << 16;
+
+pub fn emit(module: &Module) -> String {
+    let mut lines = vec!["(module".to_string()];
+    for func in module.functions.iter().filter(|func| func.is_declaration()) {
+        lines.push(format!(
+            "  (import \"env\" \"{}\" (func ${}{}))",
+            func.name,
+            func.name,
+            signature(func)
+        ));
+    }
+    let mut addrs = vec![];
+    let mut data_end = DATA_BASE;
+    for global in &module.globals {
+        addrs.push(data_end as i32);
+        data_end += size_of(&global.ty, PTR_SIZE).div_ceil(4) * 4;
+    }
+    let pages = (data_end + STACK_SIZE).div_ceil(PAGE_SIZE);
+    lines.push(format!("  (memory (export \"memory\") {})", pages));
+    lines.push(format!(
+        "  (global $sp (mut i32) (i32.const {}))",
+        pages * PAGE_SIZE
+    ));
+    for (global, addr) in module.globals.iter().zip(&addrs) {
+        let mut words = vec![];
+        flatten(&global.ty, &global.init, &mut words);
+        while words.last() == Some(&0) {
+            words.pop();
+        }
+        if words.is_empty() {
+            continue;
+        }
+        let bytes: String = words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .map(|byte| format!("\\{:02x}", byte))
+            .collect();
+        lines.push(format!(
+            "  (data (i32.const {}) \"{}\") ;; {}",
+            addr, bytes, global.name
+        ));
+    }
+    for func in module
+        .functions
+        .iter()
+        .filter(|func| !func.is_declaration())
+    {
+        FuncEmitter::new(module, func, &addrs).emit(&mut lines);
+        if func.name == "main" {
+            lines.push("  (export \"main\" (func $main))".to_string());
+        }
+    }
+    lines.push(")".to_string());
+    let mut res = lines.join("\n");
+    res.push('\n');
+    res
+}
+
+// SSA值对应的wasm类型
+fn value_type(ty: &Type) -> &'static str {
+    match ty {
+        Type::Float => "f32",
+        _ => "i32",
+    }
+}
+
+fn signature(func: &Function) -> String {
+    let mut res = String::new();
+    for (index, (_, ty)) in func.params.iter().enumerate() {
+        res.push_str(&format!(" (param $p{} {})", index, value_type(ty)));
+    }
+    if func.ret_ty != Type::Void {
+        res.push_str(&format!(" (result {})", value_type(&func.ret_ty)));
+    }
+    res
+}
+
+// f32常量的文本，十进制为能还原出原值的最短表示
+fn float_text(val: f32) -> String {
+    if val.is_nan() {
+        "nan".to_string()
+    } else if val.is_infinite() {
+        if val < 0.0 { "-inf" } else { "inf" }.to_string()
+    } else {
+        format!("{:?}", val)
+    }
+}
+
+// 按4字节展开全局变量的初始值
+fn flatten(ty: &Type, constant: &Constant, words: &mut Vec<i32>) {
+    match (constant, ty) {
+        (Constant::Int(val), _) => words.push(*val),
+        (Constant::Float(val), _) => words.push(val.to_bits() as i32),
+        (Constant::Zero, _) => words.extend(std::iter::repeat_n(0, size_of(ty, PTR_SIZE) / 4)),
+        (Constant::Array(elems), Type::Array(_, elem_ty)) => {
+            for elem in elems {
+                flatten(elem_ty, elem, words);
+            }
+        }
+        (Constant::Array(_), _) => panic!("array constant of type {}", ty),
+    }
+}
+
+// 使用Ramsey的算法("Beyond Relooper")从可归约的控制流图恢复结构化控制流
+// 支配树中每个节点的代码由它的合并节点(有多个前向前驱的子节点)包围：依次嵌套的block之后是合并节点的代码
+// 循环头外面加上loop，回边跳转到loop的开头，到合并节点的前向边跳出对应的block，其余的前向边直接展开目标子树
+struct FuncEmitter<'a> {
+    module: &'a Module,
+    func: &'a Function,
+    addrs: &'a [i32],
+    dom: DomTree,
+    order: Vec<usize>,        // 基本块在逆后序中的位置，不可达为usize::MAX
+    preds: Vec<Vec<BlockId>>, // 只包括可达的前驱
+    allocas: HashMap<InstId, i32>,
+    frame_size: i32,
+    lines: Vec<String>,
+    depth: usize,
+}
+
+impl<'a> FuncEmitter<'a> {
+    fn new(module: &'a Module, func: &'a Function, addrs: &'a [i32]) -> FuncEmitter<'a> {
+        let dom = DomTree::new(func);
+        let mut order = vec![usize::MAX; func.blocks.len()];
+        for (index, block) in dom.rpo.iter().enumerate() {
+            order[block.0] = index;
+        }
+        let preds = func
+            .predecessors()
+            .into_iter()
+            .map(|preds| {
+                preds
+                    .into_iter()
+                    .filter(|pred| dom.is_reachable(*pred))
+                    .collect()
+            })
+            .collect();
+        let mut allocas = HashMap::new();
+        let mut frame_size = 0;
+        for block in &func.layout {
+            for inst in &func.block(*block).insts {
+                if let InstKind::Alloca(ty) = &func.inst(*inst).kind {
+                    allocas.insert(*inst, frame_size);
+                    frame_size += (size_of(ty, PTR_SIZE) as i32 + 3) / 4 * 4;
+                }
+            }
+        }
+        FuncEmitter {
+            module,
+            func,
+            addrs,
+            dom,
+            order,
+            preds,
+            allocas,
+            frame_size: (frame_size + 15) / 16 * 16,
+            lines: vec![],
+            depth: 2,
+        }
+    }
+
+    fn asm(&mut self, text: String) {
+        self.lines
+            .push(format!("{}{}", "  ".repeat(self.depth), text));
+    }
+
+    fn open(&mut self, text: String) {
+        self.asm(text);
+        self.depth += 1;
+    }
+
+    fn close(&mut self) {
+        self.depth -= 1;
+        self.asm("end".to_string());
+    }
+
+    fn is_backward(&self, from: BlockId, to: BlockId) -> bool {
+        self.order[to.0] <= self.order[from.0]
+    }
+
+    fn is_merge(&self, block: BlockId) -> bool {
+        self.preds[block.0]
+            .iter()
+            .filter(|pred| !self.is_backward(**pred, block))
+            .count()
+            >= 2
+    }
+
+    fn is_loop_header(&self, block: BlockId) -> bool {
+        self.preds[block.0]
+            .iter()
+            .any(|pred| self.is_backward(*pred, block))
+    }
+
+    fn emit(mut self, lines: &mut Vec<String>) {
+        lines.push(format!(
+            "  (func ${}{}",
+            self.func.name,
+            signature(self.func)
+        ));
+        if self.frame_size != 0 {
+            lines.push("    (local $fp i32)".to_string());
+        }
+        for block in &self.func.layout {
+            for inst in &self.func.block(*block).insts {
+                let inst_ref = self.func.inst(*inst);
+                match inst_ref.kind {
+                    InstKind::Alloca(_) => {}
+                    InstKind::Phi(_) => {
+                        let ty = value_type(&inst_ref.ty);
+                        lines.push(format!(
+                            "    (local $v{} {}) (local $t{} {})",
+                            inst.0, ty, inst.0, ty
+                        ));
+                    }
+                    _ if inst_ref.ty != Type::Void => {
+                        let ty = value_type(&inst_ref.ty);
+                        lines.push(format!("    (local $v{} {})", inst.0, ty));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if self.frame_size != 0 {
+            self.asm("global.get $sp".to_string());
+            self.asm(format!("i32.const {}", self.frame_size));
+            self.asm("i32.sub".to_string());
+            self.asm("local.tee $fp".to_string());
+            self.asm("global.set $sp".to_string());
+        }
+        self.emit_tree(self.func.entry());
+        // 所有路径都以return结束，函数末尾不可达
+        if self.func.ret_ty != Type::Void {
+            self.asm("unreachable".to_string());
+        }
+        lines.append(&mut self.lines);
+        lines.push("  )".to_string());
+    }
+
+    fn emit_tree(&mut self, block: BlockId) {
+        let mut merges: Vec<BlockId> = self.dom.children[block.0]
+            .iter()
+            .copied()
+            .filter(|child| self.is_merge(*child))
+            .collect();
+        // 逆后序靠后的合并节点在外层
+        merges.sort_by_key(|merge| std::cmp::Reverse(self.order[merge.0]));
+        let is_loop = self.is_loop_header(block);
+        if is_loop {
+            self.open(format!("loop $L{}", block.0));
+        }
+        self.emit_within(block, &merges);
+        if is_loop {
+            self.close();
+        }
+    }
+
+    fn emit_within(&mut self, block: BlockId, merges: &[BlockId]) {
+        match merges.split_first() {
+            Some((merge, rest)) => {
+                self.open(format!("block $B{}", merge.0));
+                self.emit_within(block, rest);
+                self.close();
+                self.emit_tree(*merge);
+            }
+            None => {
+                for inst in &self.func.block(block).insts {
+                    self.emit_inst(block, *inst);
+                }
+            }
+        }
+    }
+
+    fn emit_branch(&mut self, from: BlockId, to: BlockId) {
+        self.emit_phi_copies(from, to);
+        if self.is_backward(from, to) {
+            self.asm(format!("br $L{}", to.0));
+        } else if self.is_merge(to) {
+            self.asm(format!("br $B{}", to.0));
+        } else {
+            self.emit_tree(to);
+        }
+    }
+
+    fn push(&mut self, value: Value) {
+        let text = match value {
+            Value::Undef => "i32.const 0".to_string(),
+            Value::Int(val) => format!("i32.const {}", val),
+            Value::Float(bits) => format!("f32.const {}", float_text(f32::from_bits(bits))),
+            Value::Bool(val) => format!("i32.const {}", val as i32),
+            Value::Global(global) => format!("i32.const {}", self.addrs[global.0]),
+            Value::Inst(inst) if self.allocas.contains_key(&inst) => {
+                let offset = self.allocas[&inst];
+                self.asm("local.get $fp".to_string());
+                if offset == 0 {
+                    return;
+                }
+                self.asm(format!("i32.const {}", offset));
+                "i32.add".to_string()
+            }
+            Value::Inst(inst) => format!("local.get $v{}", inst.0),
+            Value::Param(index) => format!("local.get $p{}", index),
+        };
+        self.asm(text);
+    }
+
+    fn emit_inst(&mut self, block: BlockId, inst: InstId) {
+        match &self.func.inst(inst).kind {
+            InstKind::Alloca(_) => return,
+            InstKind::Load(ptr) => {
+                self.push(*ptr);
+                match self.func.inst(inst).ty {
+                    Type::I1 => self.asm("i32.load8_u".to_string()),
+                    Type::Float => self.asm("f32.load".to_string()),
+                    _ => self.asm("i32.load".to_string()),
+                }
+            }
+            InstKind::Store(val, ptr) => {
+                self.push(*ptr);
+                self.push(*val);
+                match self.module.value_type(self.func, *val) {
+                    Type::I1 => self.asm("i32.store8".to_string()),
+                    Type::Float => self.asm("f32.store".to_string()),
+                    _ => self.asm("i32.store".to_string()),
+                }
+            }
+            InstKind::Binary(op, lhs, rhs) => {
+                self.push(*lhs);
+                self.push(*rhs);
+                let op = match op {
+                    BinOp::Add => "i32.add",
+                    BinOp::Sub => "i32.sub",
+                    BinOp::Mul => "i32.mul",
+                    BinOp::SDiv => "i32.div_s",
+                    BinOp::SRem => "i32.rem_s",
+                    BinOp::And => "i32.and",
+                    BinOp::Or => "i32.or",
+                    BinOp::Xor => "i32.xor",
+                    BinOp::Shl => "i32.shl",
+                    BinOp::AShr => "i32.shr_s",
+                    BinOp::FAdd => "f32.add",
+                    BinOp::FSub => "f32.sub",
+                    BinOp::FMul => "f32.mul",
+                    BinOp::FDiv => "f32.div",
+                };
+                self.asm(op.to_string());
+            }
+            InstKind::Icmp(op, lhs, rhs) => {
+                self.push(*lhs);
+                self.push(*rhs);
+                let op = match op {
+                    CmpOp::Eq => "i32.eq",
+                    CmpOp::Ne => "i32.ne",
+                    CmpOp::Slt => "i32.lt_s",
+                    CmpOp::Sgt => "i32.gt_s",
+                    CmpOp::Sle => "i32.le_s",
+                    CmpOp::Sge => "i32.ge_s",
+                };
+                self.asm(op.to_string());
+            }
+            InstKind::Fcmp(op, lhs, rhs) => {
+                self.push(*lhs);
+                self.push(*rhs);
+                let op = match op {
+                    CmpOp::Eq => "f32.eq",
+                    CmpOp::Ne => "f32.ne",
+                    CmpOp::Slt => "f32.lt",
+                    CmpOp::Sgt => "f32.gt",
+                    CmpOp::Sle => "f32.le",
+                    CmpOp::Sge => "f32.ge",
+                };
+                self.asm(op.to_string());
+            }
+            InstKind::Zext(val) => self.push(*val),
+            InstKind::Sitofp(val) => {
+                self.push(*val);
+                self.asm("f32.convert_i32_s".to_string());
+            }
+            // 超出范围时取最接近的值而不是陷入
+            InstKind::Fptosi(val) => {
+                self.push(*val);
+                self.asm("i32.trunc_sat_f32_s".to_string());
+            }
+            InstKind::Gep(base, indices) => {
+                self.push(*base);
+                let mut ty = self.module.value_type(self.func, *base).pointee().clone();
+                for (pos, index) in indices.iter().enumerate() {
+                    if pos != 0 {
+                        ty = match ty {
+                            Type::Array(_, elem) => *elem,
+                            _ => panic!("getelementptr into {}", ty),
+                        };
+                    }
+                    let size = size_of(&ty, PTR_SIZE) as i32;
+                    match index {
+                        Value::Int(0) => continue,
+                        Value::Int(val) => {
+                            self.asm(format!("i32.const {}", val.wrapping_mul(size)))
+                        }
+                        _ => {
+                            self.push(*index);
+                            self.asm(format!("i32.const {}", size));
+                            self.asm("i32.mul".to_string());
+                        }
+                    }
+                    self.asm("i32.add".to_string());
+                }
+            }
+            InstKind::Call(callee, args) => {
+                for arg in args {
+                    self.push(*arg);
+                }
+                let callee = self.module.function(*callee);
+                self.asm(format!("call ${}", callee.name));
+            }
+            InstKind::Phi(_) => self.asm(format!("local.get $t{}", inst.0)),
+            InstKind::Br(target) => self.emit_branch(block, *target),
+            InstKind::CondBr(cond, then_block, else_block) => {
+                self.push(*cond);
+                self.open("if".to_string());
+                self.emit_branch(block, *then_block);
+                self.depth -= 1;
+                self.asm("else".to_string());
+                self.depth += 1;
+                self.emit_branch(block, *else_block);
+                self.close();
+            }
+            InstKind::Ret(val) => {
+                if self.frame_size != 0 {
+                    self.asm("local.get $fp".to_string());
+                    self.asm(format!("i32.const {}", self.frame_size));
+                    self.asm("i32.add".to_string());
+                    self.asm("global.set $sp".to_string());
+                }
+                if let Some(val) = val {
+                    self.push(*val);
+                }
+                self.asm("return".to_string());
+            }
+        }
+        if self.func.inst(inst).ty != Type::Void {
+            self.asm(format!("local.set $v{}", inst.0));
+        }
+    }
+
+    // 跳转前把值写入phi的暂存变量，目标块开头再复制给phi，避免多个phi的复制互相覆盖
+    fn emit_phi_copies(&mut self, block: BlockId, succ: BlockId) {
+        for inst in &self.func.block(succ).insts {
+            let incoming = match &self.func.inst(*inst).kind {
+                InstKind::Phi(incoming) => incoming,
+                _ => break,
+            };
+            if let Some((val, _)) = incoming.iter().find(|(_, pred)| *pred == block) {
+                self.push(*val);
+                self.asm(format!("local.set $t{}", inst.0));
+            }
+        }
+    }
+}
@@ -0,0 +1,482 @@
+use super::super::ir::{BinOp, BlockId, CmpOp, Function, InstId, InstKind, Module, Type, Value};
+use super::regalloc::RegClass;
+use super::{size_of, write_data, Frame, Location};
+
+// x86-64 System V GNU汇编(AT&T语法)
+// SSA值分配到寄存器或栈上的8字节栈槽，指令通过rax、rcx、rdx计算，传参寄存器不参与分配
+// float的位模式与整数一样存放，运算时移入xmm0与xmm1
+const PTR_SIZE: usize = 8;
+const FLOAT_ARG_REGS: usize = 8;
+
+// 寄存器的64位与32位名字
+#[derive(Clone, Copy)]
+struct Reg(&'static str, &'static str);
+
+impl Reg {
+    fn q(self) -> &'static str {
+        self.0
+    }
+
+    fn l(self) -> &'static str {
+        self.1
+    }
+}
+
+const RAX: Reg = Reg("%rax", "%eax");
+const RCX: Reg = Reg("%rcx", "%ecx");
+const ARG_REGS: [Reg; 6] = [
+    Reg("%rdi", "%edi"),
+    Reg("%rsi", "%esi"),
+    Reg("%rdx", "%edx"),
+    RCX,
+    Reg("%r8", "%r8d"),
+    Reg("%r9", "%r9d"),
+];
+
+// 可分配的寄存器，r10与r11在调用时不保存
+const REGS: [Reg; 7] = [
+    Reg("%rbx", "%ebx"),
+    Reg("%r12", "%r12d"),
+    Reg("%r13", "%r13d"),
+    Reg("%r14", "%r14d"),
+    Reg("%r15", "%r15d"),
+    Reg("%r10", "%r10d"),
+    Reg("%r11", "%r11d"),
+];
+
+// 参数的位置，整数与指针依次使用ARG_REGS，float依次使用xmm0~xmm7，其余按顺序放在栈上
+#[derive(Clone, Copy)]
+enum ArgLoc {
+    Reg(Reg),
+    Xmm(usize),
+    Stack(usize),
+}
+
+fn arg_locations(params: &[Type]) -> Vec<ArgLoc> {
+    let (mut ints, mut floats, mut stack) = (0, 0, 0);
+    let next = |count: &mut usize| {
+        *count += 1;
+        *count - 1
+    };
+    params
+        .iter()
+        .map(|ty| match ty {
+            Type::Float if floats < FLOAT_ARG_REGS => ArgLoc::Xmm(next(&mut floats)),
+            Type::Float => ArgLoc::Stack(next(&mut stack)),
+            _ if ints < ARG_REGS.len() => ArgLoc::Reg(ARG_REGS[next(&mut ints)]),
+            _ => ArgLoc::Stack(next(&mut stack)),
+        })
+        .collect()
+}
+
+pub fn emit(module: &Module) -> String {
+    let mut lines = vec!["    .text".to_string()];
+    for (index, func) in module.functions.iter().enumerate() {
+        if !func.is_declaration() {
+            FuncEmitter::new(module, func, index).emit(&mut lines);
+        }
+    }
+    write_data(&mut lines, module);
+    lines.push("    .section .note.GNU-stack,\"\",@progbits".to_string());
+    let mut res = lines.join("\n");
+    res.push('\n');
+    res
+}
+
+struct FuncEmitter<'a> {
+    module: &'a Module,
+    func: &'a Function,
+    index: usize,
+    frame: Frame,
+    lines: Vec<String>,
+}
+
+impl<'a> FuncEmitter<'a> {
+    fn new(module: &'a Module, func: &'a Function, index: usize) -> FuncEmitter<'a> {
+        FuncEmitter {
+            module,
+            func,
+            index,
+            frame: Frame::new(
+                func,
+                PTR_SIZE,
+                &RegClass {
+                    count: REGS.len(),
+                    clobbered: vec![5, 6],
+                },
+                0,
+            ),
+            lines: vec![],
+        }
+    }
+
+    fn asm(&mut self, text: String) {
+        self.lines.push(format!("    {}", text));
+    }
+
+    fn label(&self, block: BlockId) -> String {
+        format!(".LBB{}_{}", self.index, block.0)
+    }
+
+    fn is_pointer(&self, value: Value) -> bool {
+        matches!(self.module.value_type(self.func, value), Type::Pointer(_))
+    }
+
+    fn load(&mut self, value: Value, reg: Reg) {
+        let text = match value {
+            Value::Undef => format!("movl $0, {}", reg.l()),
+            Value::Int(val) => format!("movl ${}, {}", val, reg.l()),
+            Value::Float(bits) => format!("movl ${}, {}", bits as i32, reg.l()),
+            Value::Bool(val) => format!("movl ${}, {}", val as i32, reg.l()),
+            Value::Global(global) => {
+                format!(
+                    "leaq {}(%rip), {}",
+                    self.module.globals[global.0].name,
+                    reg.q()
+                )
+            }
+            Value::Inst(inst) if self.frame.allocas.contains_key(&inst) => {
+                format!("leaq {}(%rbp), {}", self.frame.allocas[&inst], reg.q())
+            }
+            Value::Inst(inst) => return self.load_from(self.frame.values[&inst], value, reg),
+            Value::Param(index) => return self.load_from(self.frame.params[index], value, reg),
+        };
+        self.asm(text);
+    }
+
+    fn load_from(&mut self, location: Location, value: Value, reg: Reg) {
+        let pointer = self.is_pointer(value);
+        match location {
+            Location::Reg(src) if REGS[src].q() == reg.q() => {}
+            Location::Reg(src) if pointer => {
+                self.asm(format!("movq {}, {}", REGS[src].q(), reg.q()))
+            }
+            Location::Reg(src) => self.asm(format!("movl {}, {}", REGS[src].l(), reg.l())),
+            Location::Stack(offset) if pointer => {
+                self.asm(format!("movq {}(%rbp), {}", offset, reg.q()))
+            }
+            Location::Stack(offset) => self.asm(format!("movl {}(%rbp), {}", offset, reg.l())),
+        }
+    }
+
+    fn store(&mut self, inst: InstId, reg: Reg) {
+        let pointer = self.is_pointer(Value::Inst(inst));
+        self.store_to(self.frame.values[&inst], reg, pointer);
+    }
+
+    fn store_to(&mut self, location: Location, reg: Reg, pointer: bool) {
+        match location {
+            Location::Reg(dst) if REGS[dst].q() == reg.q() => {}
+            Location::Reg(dst) if pointer => {
+                self.asm(format!("movq {}, {}", reg.q(), REGS[dst].q()))
+            }
+            Location::Reg(dst) => self.asm(format!("movl {}, {}", reg.l(), REGS[dst].l())),
+            Location::Stack(offset) if pointer => {
+                self.asm(format!("movq {}, {}(%rbp)", reg.q(), offset))
+            }
+            Location::Stack(offset) => self.asm(format!("movl {}, {}(%rbp)", reg.l(), offset)),
+        }
+    }
+
+    // 经过临时寄存器把float值移入xmm寄存器
+    fn load_xmm(&mut self, value: Value, scratch: Reg, xmm: usize) {
+        self.load(value, scratch);
+        self.asm(format!("movd {}, %xmm{}", scratch.l(), xmm));
+    }
+
+    // 分配到寄存器的值直接在该寄存器中计算，否则使用临时寄存器
+    fn reg_of(&self, location: Location, scratch: Reg) -> Reg {
+        match location {
+            Location::Reg(reg) => REGS[reg],
+            Location::Stack(_) => scratch,
+        }
+    }
+
+    fn restore_regs(&mut self) {
+        for (reg, offset) in self.frame.saved.clone() {
+            self.asm(format!("movq {}(%rbp), {}", offset, REGS[reg].q()));
+        }
+    }
+
+    fn emit(mut self, lines: &mut Vec<String>) {
+        let name = &self.func.name;
+        lines.push(format!("    .globl {}", name));
+        lines.push(format!("    .type {}, @function", name));
+        lines.push(format!("{}:", name));
+        self.asm("pushq %rbp".to_string());
+        self.asm("movq %rsp, %rbp".to_string());
+        if self.frame.size != 0 {
+            self.asm(format!("subq ${}, %rsp", self.frame.size));
+        }
+        for (reg, offset) in self.frame.saved.clone() {
+            self.asm(format!("movq {}, {}(%rbp)", REGS[reg].q(), offset));
+        }
+        // 寄存器放不下的参数由调用者放在rbp+16之上
+        let types: Vec<Type> = self.func.params.iter().map(|(_, ty)| ty.clone()).collect();
+        for (index, arg) in arg_locations(&types).into_iter().enumerate() {
+            let location = self.frame.params[index];
+            let pointer = self.is_pointer(Value::Param(index));
+            let reg = match arg {
+                ArgLoc::Reg(reg) => reg,
+                ArgLoc::Xmm(xmm) => {
+                    self.asm(format!("movd %xmm{}, %eax", xmm));
+                    RAX
+                }
+                ArgLoc::Stack(pos) => {
+                    let reg = self.reg_of(location, RAX);
+                    self.asm(format!("movq {}(%rbp), {}", 16 + 8 * pos, reg.q()));
+                    reg
+                }
+            };
+            self.store_to(location, reg, pointer);
+        }
+        let layout = &self.func.layout;
+        for (pos, block) in layout.iter().enumerate() {
+            let next = layout.get(pos + 1).copied();
+            let label = self.label(*block);
+            self.lines.push(format!("{}:", label));
+            for inst in &self.func.block(*block).insts {
+                self.emit_inst(*block, *inst, next);
+            }
+        }
+        lines.append(&mut self.lines);
+        lines.push(format!("    .size {}, .-{}", name, name));
+    }
+
+    fn emit_inst(&mut self, block: BlockId, inst: InstId, next: Option<BlockId>) {
+        match &self.func.inst(inst).kind {
+            InstKind::Alloca(_) => {}
+            InstKind::Load(ptr) => {
+                self.load(*ptr, RAX);
+                match self.func.inst(inst).ty {
+                    Type::I1 => self.asm("movzbl (%rax), %eax".to_string()),
+                    _ => self.asm("movl (%rax), %eax".to_string()),
+                }
+                self.store(inst, RAX);
+            }
+            InstKind::Store(val, ptr) => {
+                self.load(*ptr, RAX);
+                self.load(*val, RCX);
+                match self.module.value_type(self.func, *val) {
+                    Type::I1 => self.asm("movb %cl, (%rax)".to_string()),
+                    _ => self.asm("movl %ecx, (%rax)".to_string()),
+                }
+            }
+            InstKind::Binary(op, lhs, rhs) if op.is_float() => {
+                self.load_xmm(*lhs, RAX, 0);
+                self.load_xmm(*rhs, RCX, 1);
+                let op = match op {
+                    BinOp::FAdd => "addss",
+                    BinOp::FSub => "subss",
+                    BinOp::FMul => "mulss",
+                    _ => "divss",
+                };
+                self.asm(format!("{} %xmm1, %xmm0", op));
+                self.asm("movd %xmm0, %eax".to_string());
+                self.store(inst, RAX);
+            }
+            InstKind::Binary(op, lhs, rhs) => {
+                self.load(*lhs, RAX);
+                self.load(*rhs, RCX);
+                match op {
+                    BinOp::Add => self.asm("addl %ecx, %eax".to_string()),
+                    BinOp::Sub => self.asm("subl %ecx, %eax".to_string()),
+                    BinOp::Mul => self.asm("imull %ecx, %eax".to_string()),
+                    BinOp::And => self.asm("andl %ecx, %eax".to_string()),
+                    BinOp::Or => self.asm("orl %ecx, %eax".to_string()),
+                    BinOp::Xor => self.asm("xorl %ecx, %eax".to_string()),
+                    BinOp::Shl => self.asm("shll %cl, %eax".to_string()),
+                    BinOp::AShr => self.asm("sarl %cl, %eax".to_string()),
+                    BinOp::SDiv | BinOp::SRem => {
+                        self.asm("cltd".to_string());
+                        self.asm("idivl %ecx".to_string());
+                        if *op == BinOp::SRem {
+                            self.asm("movl %edx, %eax".to_string());
+                        }
+                    }
+                    _ => unreachable!(),
+                }
+                self.store(inst, RAX);
+            }
+            InstKind::Icmp(op, lhs, rhs) => {
+                self.load(*lhs, RAX);
+                self.load(*rhs, RCX);
+                self.asm("cmpl %ecx, %eax".to_string());
+                let cond = match op {
+                    CmpOp::Eq => "e",
+                    CmpOp::Ne => "ne",
+                    CmpOp::Slt => "l",
+                    CmpOp::Sgt => "g",
+                    CmpOp::Sle => "le",
+                    CmpOp::Sge => "ge",
+                };
+                self.asm(format!("set{} %al", cond));
+                self.asm("movzbl %al, %eax".to_string());
+                self.store(inst, RAX);
+            }
+            InstKind::Fcmp(op, lhs, rhs) => {
+                // 无序时ucomiss置ZF、PF与CF，小于与小于等于交换操作数后判断大于与大于等于
+                let (lhs, rhs) = match op {
+                    CmpOp::Slt | CmpOp::Sle => (rhs, lhs),
+                    _ => (lhs, rhs),
+                };
+                self.load_xmm(*lhs, RAX, 0);
+                self.load_xmm(*rhs, RCX, 1);
+                self.asm("ucomiss %xmm1, %xmm0".to_string());
+                match op {
+                    CmpOp::Eq => {
+                        self.asm("sete %al".to_string());
+                        self.asm("setnp %cl".to_string());
+                        self.asm("andb %cl, %al".to_string());
+                    }
+                    CmpOp::Ne => {
+                        self.asm("setne %al".to_string());
+                        self.asm("setp %cl".to_string());
+                        self.asm("orb %cl, %al".to_string());
+                    }
+                    CmpOp::Slt | CmpOp::Sgt => self.asm("seta %al".to_string()),
+                    CmpOp::Sle | CmpOp::Sge => self.asm("setae %al".to_string()),
+                }
+                self.asm("movzbl %al, %eax".to_string());
+                self.store(inst, RAX);
+            }
+            InstKind::Zext(val) => {
+                self.load(*val, RAX);
+                self.store(inst, RAX);
+            }
+            InstKind::Sitofp(val) => {
+                self.load(*val, RAX);
+                self.asm("cvtsi2ssl %eax, %xmm0".to_string());
+                self.asm("movd %xmm0, %eax".to_string());
+                self.store(inst, RAX);
+            }
+            InstKind::Fptosi(val) => {
+                self.load_xmm(*val, RAX, 0);
+                self.asm("cvttss2si %xmm0, %eax".to_string());
+                self.store(inst, RAX);
+            }
+            InstKind::Gep(base, indices) => {
+                self.load(*base, RAX);
+                let mut ty = self.module.value_type(self.func, *base).pointee().clone();
+                for (pos, index) in indices.iter().enumerate() {
+                    if pos != 0 {
+                        ty = match ty {
+                            Type::Array(_, elem) => *elem,
+                            _ => panic!("getelementptr into {}", ty),
+                        };
+                    }
+                    let size = size_of(&ty, PTR_SIZE) as i64;
+                    match index {
+                        Value::Int(0) => {}
+                        Value::Int(val) => self.asm(format!("addq ${}, %rax", *val as i64 * size)),
+                        _ => {
+                            self.load(*index, RCX);
+                            self.asm("movslq %ecx, %rcx".to_string());
+                            self.asm(format!("imulq ${}, %rcx", size));
+                            self.asm("addq %rcx, %rax".to_string());
+                        }
+                    }
+                }
+                self.store(inst, RAX);
+            }
+            InstKind::Call(callee, args) => {
+                let callee = self.module.function(*callee);
+                let types: Vec<Type> = callee.params.iter().map(|(_, ty)| ty.clone()).collect();
+                let locations = arg_locations(&types);
+                let stack_args = locations
+                    .iter()
+                    .filter(|arg| matches!(arg, ArgLoc::Stack(_)))
+                    .count();
+                // 调用前rsp需要16字节对齐
+                let padding = stack_args % 2 * 8;
+                if padding != 0 {
+                    self.asm(format!("subq ${}, %rsp", padding));
+                }
+                for (arg, location) in args.iter().zip(locations.iter()).rev() {
+                    if let ArgLoc::Stack(_) = location {
+                        self.load(*arg, RAX);
+                        self.asm("pushq %rax".to_string());
+                    }
+                }
+                for (arg, location) in args.iter().zip(locations.iter()) {
+                    if let ArgLoc::Xmm(xmm) = location {
+                        self.load_xmm(*arg, RAX, *xmm);
+                    }
+                }
+                for (arg, location) in args.iter().zip(locations.iter()) {
+                    if let ArgLoc::Reg(reg) = location {
+                        self.load(*arg, *reg);
+                    }
+                }
+                if callee.is_declaration() {
+                    self.asm(format!("call {}@PLT", callee.name));
+                } else {
+                    self.asm(format!("call {}", callee.name));
+                }
+                let pop = stack_args * 8 + padding;
+                if pop != 0 {
+                    self.asm(format!("addq ${}, %rsp", pop));
+                }
+                if callee.ret_ty == Type::Float {
+                    self.asm("movd %xmm0, %eax".to_string());
+                }
+                if callee.ret_ty != Type::Void {
+                    self.store(inst, RAX);
+                }
+            }
+            InstKind::Phi(_) => {
+                let (from, to) = (self.frame.phi_values[&inst], self.frame.values[&inst]);
+                let reg = self.reg_of(from, self.reg_of(to, RAX));
+                if let Location::Stack(offset) = from {
+                    self.asm(format!("movq {}(%rbp), {}", offset, reg.q()));
+                }
+                self.store_to(to, reg, true);
+            }
+            InstKind::Br(target) => {
+                self.emit_phi_copies(block, *target);
+                if Some(*target) != next {
+                    self.asm(format!("jmp {}", self.label(*target)));
+                }
+            }
+            InstKind::CondBr(cond, then_block, else_block) => {
+                self.emit_phi_copies(block, *then_block);
+                if else_block != then_block {
+                    self.emit_phi_copies(block, *else_block);
+                }
+                self.load(*cond, RAX);
+                self.asm("testl %eax, %eax".to_string());
+                self.asm(format!("jne {}", self.label(*then_block)));
+                if Some(*else_block) != next {
+                    self.asm(format!("jmp {}", self.label(*else_block)));
+                }
+            }
+            InstKind::Ret(val) => {
+                if let Some(val) = val {
+                    self.load(*val, RAX);
+                    if self.func.ret_ty == Type::Float {
+                        self.asm("movd %eax, %xmm0".to_string());
+                    }
+                }
+                self.restore_regs();
+                self.asm("leave".to_string());
+                self.asm("ret".to_string());
+            }
+        }
+    }
+
+    // 前驱跳转前把值写入phi的暂存值，块开头再复制给phi，避免多个phi的复制互相覆盖
+    fn emit_phi_copies(&mut self, block: BlockId, succ: BlockId) {
+        for inst in &self.func.block(succ).insts {
+            let incoming = match &self.func.inst(*inst).kind {
+                InstKind::Phi(incoming) => incoming,
+                _ => break,
+            };
+            if let Some((val, _)) = incoming.iter().find(|(_, pred)| *pred == block) {
+                let location = self.frame.phi_values[inst];
+                let reg = self.reg_of(location, RAX);
+                self.load(*val, reg);
+                self.store_to(location, reg, true);
+            }
+        }
+    }
+}
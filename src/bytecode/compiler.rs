@@ -0,0 +1,857 @@
+use std::collections::HashMap;
+
+use super::super::ast::{
+    BType, BinaryOp, Block, BlockItem, Case, CompUnit, Decl, Expr, ExprKind, FuncDef, InitVal,
+    Item, LVal, Stmt, StmtKind, UnaryOp,
+};
+use super::super::ir::{GlobalId, Value};
+use super::super::symbol::{SymbolTable, Variable};
+use super::{Function, Op, Program, BUILTINS};
+
+// 把通过语义检查的语法树编译为字节码，作用域与数组的形状沿用SymbolTable
+// 变量的位置记在Variable::reg中：全局变量为Value::Global(地址)，局部变量为Value::Int(栈帧中的偏移)
+// 标量常量在使用处直接折叠，不占用内存；形状第一维为0的是数组参数，栈帧中存放的是数组的地址
+// 表达式的类型由操作数推出，int与float混合运算时先转换为float
+
+// 循环与switch中待回填的跳转，switch没有continue
+struct Loop {
+    continues: Option<Vec<usize>>,
+    breaks: Vec<usize>,
+}
+
+// 赋值的目标，数组元素的地址先存入栈帧中的临时变量，只计算一次
+enum Place {
+    Global(u32),
+    Local(u32),
+    Elem(u32),
+}
+
+struct Compiler {
+    symbol: SymbolTable,
+    funcs: HashMap<String, u32>,
+    globals: Vec<i32>,
+    functions: Vec<Function>,
+    code: Vec<Op>,
+    slots: u32, // 当前作用域已经分配的栈帧大小
+    frame_size: u32,
+    loops: Vec<Loop>,
+}
+
+pub fn compile(comp_unit: &CompUnit) -> Program {
+    let mut compiler = Compiler {
+        symbol: SymbolTable::new(),
+        funcs: HashMap::new(),
+        globals: vec![],
+        functions: vec![],
+        code: vec![],
+        slots: 0,
+        frame_size: 0,
+        loops: vec![],
+    };
+    for item in &comp_unit.items {
+        match item {
+            Item::Decl(decl) => compiler.decl(decl),
+            Item::FuncDef(func) => compiler.func_def(func),
+        }
+    }
+    let data = compiler
+        .globals
+        .iter()
+        .enumerate()
+        .filter(|(_, val)| **val != 0)
+        .map(|(addr, val)| (addr as u32, *val))
+        .collect();
+    Program {
+        global_size: compiler.globals.len() as u32,
+        data,
+        main: compiler.funcs["main"],
+        functions: compiler.functions,
+    }
+}
+
+fn stride(shape: &[i32]) -> u32 {
+    shape.iter().product::<i32>() as u32
+}
+
+// 常量在内存中的位模式
+fn bits(val: Value) -> i32 {
+    match val {
+        Value::Int(val) => val,
+        Value::Float(bits) => bits as i32,
+        _ => unreachable!(),
+    }
+}
+
+fn to_float(val: Value) -> f32 {
+    match val {
+        Value::Int(val) => val as f32,
+        val => val.as_float().unwrap(),
+    }
+}
+
+// 二元运算的操作数类型
+fn promote(lhs: BType, rhs: BType) -> BType {
+    if lhs == BType::Float || rhs == BType::Float {
+        BType::Float
+    } else {
+        BType::Int
+    }
+}
+
+// 操作数已经转换为ty的二元运算
+fn binary_op(op: BinaryOp, ty: BType) -> Op {
+    match (op, ty) {
+        (BinaryOp::Mul, BType::Float) => Op::FMul,
+        (BinaryOp::Div, BType::Float) => Op::FDiv,
+        (BinaryOp::Add, BType::Float) => Op::FAdd,
+        (BinaryOp::Sub, BType::Float) => Op::FSub,
+        (BinaryOp::Lt, BType::Float) => Op::FLt,
+        (BinaryOp::Gt, BType::Float) => Op::FGt,
+        (BinaryOp::Le, BType::Float) => Op::FLe,
+        (BinaryOp::Ge, BType::Float) => Op::FGe,
+        (BinaryOp::Eq, BType::Float) => Op::FEq,
+        (BinaryOp::Ne, BType::Float) => Op::FNe,
+        (op, _) => match op {
+            BinaryOp::Mul => Op::Mul,
+            BinaryOp::Div => Op::Div,
+            BinaryOp::Mod => Op::Mod,
+            BinaryOp::Add => Op::Add,
+            BinaryOp::Sub => Op::Sub,
+            BinaryOp::Lt => Op::Lt,
+            BinaryOp::Gt => Op::Gt,
+            BinaryOp::Le => Op::Le,
+            BinaryOp::Ge => Op::Ge,
+            BinaryOp::Eq => Op::Eq,
+            BinaryOp::Ne => Op::Ne,
+            BinaryOp::Shl => Op::Shl,
+            BinaryOp::Shr => Op::Shr,
+            BinaryOp::BitAnd => Op::And,
+            BinaryOp::BitXor => Op::Xor,
+            BinaryOp::BitOr => Op::Or,
+            _ => unreachable!(),
+        },
+    }
+}
+
+fn cast(val: Value, ty: BType) -> Value {
+    match (val, ty) {
+        (Value::Float(_), BType::Int) => Value::Int(to_float(val) as i32),
+        (Value::Int(val), BType::Float) => Value::float(val as f32),
+        _ => val,
+    }
+}
+
+impl Compiler {
+    fn var(&self, name: &str) -> Variable {
+        self.symbol
+            .get_var(name, Default::default())
+            .unwrap()
+            .clone()
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.code.push(op);
+        self.code.len() - 1
+    }
+
+    fn here(&self) -> u32 {
+        self.code.len() as u32
+    }
+
+    // 把跳转的目标回填为当前位置
+    fn patch(&mut self, index: usize) {
+        let target = self.here();
+        self.patch_to(index, target);
+    }
+
+    fn patch_to(&mut self, index: usize, target: u32) {
+        self.code[index] = match self.code[index] {
+            Op::Jump(_) => Op::Jump(target),
+            Op::JumpIfZero(_) => Op::JumpIfZero(target),
+            Op::JumpIfNonZero(_) => Op::JumpIfNonZero(target),
+            op => unreachable!("{}", op),
+        };
+    }
+
+    fn alloc(&mut self, size: u32) -> u32 {
+        let offset = self.slots;
+        self.slots += size;
+        self.frame_size = self.frame_size.max(self.slots);
+        offset
+    }
+
+    // 常量表达式，语义检查保证其中只有数字与标量常量
+    fn eval(&self, exp: &Expr) -> Value {
+        match &exp.kind {
+            ExprKind::Number(val) => Value::Int(*val),
+            ExprKind::Float(val) => Value::float(*val),
+            ExprKind::LVal(lval) => self.var(&lval.name).value,
+            ExprKind::Unary(op, exp) => match (op, self.eval(exp)) {
+                (UnaryOp::Plus, val) => val,
+                (UnaryOp::Minus, Value::Int(val)) => Value::Int(val.wrapping_neg()),
+                (UnaryOp::Minus, val) => Value::float(-to_float(val)),
+                (UnaryOp::Not, val) => Value::Int((to_float(val) == 0.0) as i32),
+                (UnaryOp::BitNot, val) => Value::Int(!val.as_int().unwrap()),
+            },
+            ExprKind::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = match (self.eval(lhs), self.eval(rhs)) {
+                    (Value::Int(lhs), Value::Int(rhs)) => (lhs, rhs),
+                    (lhs, rhs) => {
+                        let (lhs, rhs) = (to_float(lhs), to_float(rhs));
+                        return match op {
+                            BinaryOp::Mul => Value::float(lhs * rhs),
+                            BinaryOp::Div => Value::float(lhs / rhs),
+                            BinaryOp::Add => Value::float(lhs + rhs),
+                            BinaryOp::Sub => Value::float(lhs - rhs),
+                            BinaryOp::Lt => Value::Int((lhs < rhs) as i32),
+                            BinaryOp::Gt => Value::Int((lhs > rhs) as i32),
+                            BinaryOp::Le => Value::Int((lhs <= rhs) as i32),
+                            BinaryOp::Ge => Value::Int((lhs >= rhs) as i32),
+                            BinaryOp::Eq => Value::Int((lhs == rhs) as i32),
+                            BinaryOp::Ne => Value::Int((lhs != rhs) as i32),
+                            BinaryOp::And => Value::Int((lhs != 0.0 && rhs != 0.0) as i32),
+                            BinaryOp::Or => Value::Int((lhs != 0.0 || rhs != 0.0) as i32),
+                            _ => unreachable!(),
+                        };
+                    }
+                };
+                Value::Int(match op {
+                    BinaryOp::Mul => lhs.wrapping_mul(rhs),
+                    BinaryOp::Div => lhs.checked_div(rhs).unwrap_or(0),
+                    BinaryOp::Mod => lhs.checked_rem(rhs).unwrap_or(0),
+                    BinaryOp::Add => lhs.wrapping_add(rhs),
+                    BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                    BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+                    BinaryOp::Shr => lhs.wrapping_shr(rhs as u32),
+                    BinaryOp::BitAnd => lhs & rhs,
+                    BinaryOp::BitXor => lhs ^ rhs,
+                    BinaryOp::BitOr => lhs | rhs,
+                    BinaryOp::Lt => (lhs < rhs) as i32,
+                    BinaryOp::Gt => (lhs > rhs) as i32,
+                    BinaryOp::Le => (lhs <= rhs) as i32,
+                    BinaryOp::Ge => (lhs >= rhs) as i32,
+                    BinaryOp::Eq => (lhs == rhs) as i32,
+                    BinaryOp::Ne => (lhs != rhs) as i32,
+                    BinaryOp::And => (lhs != 0 && rhs != 0) as i32,
+                    BinaryOp::Or => (lhs != 0 || rhs != 0) as i32,
+                })
+            }
+            ExprKind::Conditional(cond, then_exp, else_exp) => {
+                let ty = promote(self.ty(then_exp), self.ty(else_exp));
+                if to_float(self.eval(cond)) != 0.0 {
+                    cast(self.eval(then_exp), ty)
+                } else {
+                    cast(self.eval(else_exp), ty)
+                }
+            }
+            ExprKind::Call { .. }
+            | ExprKind::Assign(..)
+            | ExprKind::IncDec(..)
+            | ExprKind::Comma(..) => unreachable!(),
+        }
+    }
+
+    fn eval_int(&self, exp: &Expr) -> i32 {
+        self.eval(exp).as_int().unwrap()
+    }
+
+    // 表达式的类型
+    fn ty(&self, exp: &Expr) -> BType {
+        match &exp.kind {
+            ExprKind::Number(_) => BType::Int,
+            ExprKind::Float(_) => BType::Float,
+            ExprKind::LVal(lval) => self.var(&lval.name).ty,
+            ExprKind::Call { name, .. } => self.symbol.get_func(name, exp.span).unwrap().ty,
+            ExprKind::Unary(UnaryOp::Not, _) => BType::Int,
+            ExprKind::Unary(_, exp) => self.ty(exp),
+            ExprKind::Binary(
+                BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod | BinaryOp::Add | BinaryOp::Sub,
+                lhs,
+                rhs,
+            ) => promote(self.ty(lhs), self.ty(rhs)),
+            ExprKind::Binary(..) => BType::Int,
+            ExprKind::Conditional(_, then_exp, else_exp) => {
+                promote(self.ty(then_exp), self.ty(else_exp))
+            }
+            ExprKind::Assign(_, lval, _) | ExprKind::IncDec(_, lval) => self.var(&lval.name).ty,
+            ExprKind::Comma(_, rhs) => self.ty(rhs),
+        }
+    }
+
+    // 全局数组的初始值直接写入全局内存
+    fn global_init(&mut self, addr: u32, ty: BType, shape: &[i32], init: &InitVal) {
+        match init {
+            InitVal::Expr(exp) => self.globals[addr as usize] = bits(cast(self.eval(exp), ty)),
+            InitVal::List(vals, _) => {
+                for (index, val) in vals.iter().enumerate() {
+                    let addr = addr + index as u32 * stride(&shape[1..]);
+                    self.global_init(addr, ty, &shape[1..], val);
+                }
+            }
+        }
+    }
+
+    fn local_init(&mut self, offset: u32, ty: BType, shape: &[i32], init: &InitVal) {
+        match init {
+            InitVal::Expr(exp) => {
+                self.expr_as(exp, ty);
+                self.emit(Op::StoreLocal(offset));
+            }
+            InitVal::List(vals, _) => {
+                for (index, val) in vals.iter().enumerate() {
+                    let offset = offset + index as u32 * stride(&shape[1..]);
+                    self.local_init(offset, ty, &shape[1..], val);
+                }
+            }
+        }
+    }
+
+    fn decl(&mut self, decl: &Decl) {
+        for def in &decl.defs {
+            let shape: Vec<i32> = def.dims.iter().map(|dim| self.eval_int(dim)).collect();
+            let size = stride(&shape);
+            let span = def.name_span;
+            if decl.is_const && shape.is_empty() {
+                let exp = match def.init.as_ref().unwrap() {
+                    InitVal::Expr(exp) => exp,
+                    InitVal::List(_, _) => unreachable!(),
+                };
+                let value = cast(self.eval(exp), decl.ty);
+                self.symbol
+                    .insert_var(
+                        Variable {
+                            name: def.name.clone(),
+                            reg: Value::Undef,
+                            is_const: true,
+                            ty: decl.ty,
+                            shape,
+                            value,
+                        },
+                        span,
+                    )
+                    .unwrap();
+            } else if self.symbol.is_global() {
+                let addr = self.globals.len() as u32;
+                self.globals.resize((addr + size) as usize, 0);
+                if let Some(init) = &def.init {
+                    self.global_init(addr, decl.ty, &shape, init);
+                }
+                let reg = Value::Global(GlobalId(addr as usize));
+                self.symbol
+                    .insert_var(
+                        Variable {
+                            name: def.name.clone(),
+                            reg,
+                            is_const: decl.is_const,
+                            ty: decl.ty,
+                            shape: shape.clone(),
+                            value: Value::Undef,
+                        },
+                        span,
+                    )
+                    .unwrap();
+            } else {
+                // 与生成IR时一致，局部变量在求初始值之前就已经声明
+                let offset = self.alloc(size);
+                self.symbol
+                    .insert_var(
+                        Variable {
+                            name: def.name.clone(),
+                            reg: Value::Int(offset as i32),
+                            is_const: decl.is_const,
+                            ty: decl.ty,
+                            shape: shape.clone(),
+                            value: Value::Undef,
+                        },
+                        span,
+                    )
+                    .unwrap();
+                if let Some(init) = &def.init {
+                    if !shape.is_empty() {
+                        self.emit(Op::ZeroLocal(offset, size));
+                    }
+                    self.local_init(offset, decl.ty, &shape, init);
+                }
+            }
+        }
+    }
+
+    fn func_def(&mut self, func: &FuncDef) {
+        let mut shapes = vec![];
+        for param in &func.params {
+            let shape: Vec<i32> = param
+                .dims
+                .iter()
+                .map(|dim| dim.as_ref().map_or(0, |dim| self.eval_int(dim)))
+                .collect();
+            shapes.push((param.ty, shape));
+        }
+        self.code.clear();
+        self.slots = 0;
+        self.frame_size = 0;
+        self.symbol.go_down();
+        for (param, (ty, shape)) in func.params.iter().zip(shapes.iter()) {
+            let offset = self.alloc(1);
+            self.symbol
+                .insert_var(
+                    Variable {
+                        name: param.name.clone(),
+                        reg: Value::Int(offset as i32),
+                        is_const: false,
+                        ty: *ty,
+                        shape: shape.clone(),
+                        value: Value::Undef,
+                    },
+                    param.span,
+                )
+                .unwrap();
+        }
+        self.symbol
+            .insert_func(
+                &func.name,
+                func.has_return,
+                func.ty,
+                &shapes,
+                func.name_span,
+            )
+            .unwrap();
+        self.funcs
+            .insert(func.name.clone(), self.functions.len() as u32);
+        // 形参与函数体共用一个作用域
+        self.block_items(&func.body.items);
+        self.symbol.go_up();
+        // 没有返回语句时返回0
+        if func.has_return {
+            self.emit(Op::Push(0));
+            self.emit(Op::Ret);
+        } else {
+            self.emit(Op::RetVoid);
+        }
+        self.functions.push(Function {
+            name: func.name.clone(),
+            params: func.params.len() as u32,
+            frame_size: self.frame_size,
+            has_return: func.has_return,
+            code: std::mem::take(&mut self.code),
+        });
+    }
+
+    fn block_items(&mut self, items: &[BlockItem]) {
+        for item in items {
+            match item {
+                BlockItem::Decl(decl) => self.decl(decl),
+                BlockItem::Stmt(stmt) => self.stmt(stmt),
+            }
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        let slots = self.slots;
+        self.symbol.go_down();
+        self.block_items(&block.items);
+        self.symbol.go_up();
+        self.slots = slots;
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Expr(Some(exp)) => self.effect(exp),
+            StmtKind::Expr(None) => {}
+            StmtKind::Block(block) => self.block(block),
+            StmtKind::If(cond, then, els) => {
+                self.cond(cond);
+                let to_else = self.emit(Op::JumpIfZero(0));
+                self.stmt(then);
+                match els {
+                    Some(els) => {
+                        let to_end = self.emit(Op::Jump(0));
+                        self.patch(to_else);
+                        self.stmt(els);
+                        self.patch(to_end);
+                    }
+                    None => self.patch(to_else),
+                }
+            }
+            StmtKind::While(cond, body) => {
+                let start = self.here();
+                self.cond(cond);
+                let to_end = self.emit(Op::JumpIfZero(0));
+                self.loops.push(Loop {
+                    continues: Some(vec![]),
+                    breaks: vec![to_end],
+                });
+                self.stmt(body);
+                self.emit(Op::Jump(start));
+                self.end_loop(start);
+            }
+            StmtKind::For(init, cond, step, body) => {
+                // 初始化中的声明只在for中可见
+                let slots = self.slots;
+                self.symbol.go_down();
+                match init.as_deref() {
+                    Some(BlockItem::Decl(decl)) => self.decl(decl),
+                    Some(BlockItem::Stmt(stmt)) => self.stmt(stmt),
+                    None => {}
+                }
+                let start = self.here();
+                let mut breaks = vec![];
+                if let Some(cond) = cond {
+                    self.cond(cond);
+                    breaks.push(self.emit(Op::JumpIfZero(0)));
+                }
+                self.loops.push(Loop {
+                    continues: Some(vec![]),
+                    breaks,
+                });
+                self.stmt(body);
+                let step_start = self.here();
+                if let Some(step) = step {
+                    self.effect(step);
+                }
+                self.emit(Op::Jump(start));
+                self.end_loop(step_start);
+                self.symbol.go_up();
+                self.slots = slots;
+            }
+            StmtKind::DoWhile(body, cond) => {
+                let start = self.here();
+                self.loops.push(Loop {
+                    continues: Some(vec![]),
+                    breaks: vec![],
+                });
+                self.stmt(body);
+                let cond_start = self.here();
+                self.cond(cond);
+                self.emit(Op::JumpIfNonZero(start));
+                self.end_loop(cond_start);
+            }
+            StmtKind::Switch(exp, cases) => self.switch(exp, cases),
+            StmtKind::Break => {
+                let index = self.emit(Op::Jump(0));
+                self.loops.last_mut().unwrap().breaks.push(index);
+            }
+            StmtKind::Continue => {
+                let index = self.emit(Op::Jump(0));
+                let continues = self
+                    .loops
+                    .iter_mut()
+                    .rev()
+                    .find_map(|item| item.continues.as_mut());
+                continues.unwrap().push(index);
+            }
+            StmtKind::Return(exp) => match exp {
+                Some(exp) => {
+                    let ty = self.symbol.get_current_func().ty;
+                    self.expr_as(exp, ty);
+                    self.emit(Op::Ret);
+                }
+                None => {
+                    self.emit(Op::RetVoid);
+                }
+            },
+        }
+    }
+
+    // continue跳转到continue_target，break跳转到当前位置
+    fn end_loop(&mut self, continue_target: u32) {
+        let Loop { continues, breaks } = self.loops.pop().unwrap();
+        for index in continues.unwrap_or_default() {
+            self.patch_to(index, continue_target);
+        }
+        for index in breaks {
+            self.patch(index);
+        }
+    }
+
+    // 把条件的值存入临时变量，依次与各标号比较，都不相等时跳转到default或switch之后
+    fn switch(&mut self, exp: &Expr, cases: &[Case]) {
+        let slots = self.slots;
+        let temp = self.alloc(1);
+        self.expr(exp);
+        self.emit(Op::StoreLocal(temp));
+        let mut jumps = vec![];
+        for case in cases {
+            if let Some(value) = &case.value {
+                self.emit(Op::LoadLocal(temp));
+                self.emit(Op::Push(bits(self.eval(value))));
+                self.emit(Op::Eq);
+                jumps.push(Some(self.emit(Op::JumpIfNonZero(0))));
+            } else {
+                jumps.push(None);
+            }
+        }
+        let to_default = self.emit(Op::Jump(0));
+        self.loops.push(Loop {
+            continues: None,
+            breaks: vec![],
+        });
+        self.symbol.go_down();
+        for (case, jump) in cases.iter().zip(jumps) {
+            match jump {
+                Some(index) => self.patch(index),
+                None => self.patch(to_default),
+            }
+            self.block_items(&case.items);
+        }
+        self.symbol.go_up();
+        if !cases.iter().any(|case| case.value.is_none()) {
+            self.patch(to_default);
+        }
+        self.end_loop(0);
+        self.slots = slots;
+    }
+
+    // 只有调用void函数的表达式不产生值
+    fn has_value(&self, exp: &Expr) -> bool {
+        match &exp.kind {
+            ExprKind::Call { name, .. } => self.symbol.get_func(name, exp.span).unwrap().has_return,
+            ExprKind::Comma(_, rhs) => self.has_value(rhs),
+            _ => true,
+        }
+    }
+
+    // 只求表达式的副作用，不在栈上留下值
+    fn effect(&mut self, exp: &Expr) {
+        match &exp.kind {
+            ExprKind::Assign(..) | ExprKind::IncDec(..) => self.assign(exp, false),
+            ExprKind::Comma(lhs, rhs) => {
+                self.effect(lhs);
+                self.effect(rhs);
+            }
+            _ => {
+                self.expr(exp);
+                if self.has_value(exp) {
+                    self.emit(Op::Pop);
+                }
+            }
+        }
+    }
+
+    fn place(&mut self, lval: &LVal) -> (Place, BType) {
+        let var = self.var(&lval.name);
+        let place = match var.reg {
+            Value::Global(GlobalId(addr)) if var.shape.is_empty() => Place::Global(addr as u32),
+            reg if var.shape.is_empty() => Place::Local(reg.as_int().unwrap() as u32),
+            _ => {
+                let temp = self.alloc(1);
+                self.lval_addr(lval);
+                self.emit(Op::StoreLocal(temp));
+                Place::Elem(temp)
+            }
+        };
+        (place, var.ty)
+    }
+
+    fn load_place(&mut self, place: &Place) {
+        match *place {
+            Place::Global(addr) => {
+                self.emit(Op::LoadGlobal(addr));
+            }
+            Place::Local(offset) => {
+                self.emit(Op::LoadLocal(offset));
+            }
+            Place::Elem(temp) => {
+                self.emit(Op::LoadLocal(temp));
+                self.emit(Op::Load);
+            }
+        }
+    }
+
+    // Store依次弹出值与地址，数组元素的地址要在value求值之前压栈
+    fn store_place(&mut self, place: &Place, value: impl FnOnce(&mut Self)) {
+        if let Place::Elem(temp) = *place {
+            self.emit(Op::LoadLocal(temp));
+        }
+        value(self);
+        self.emit(match *place {
+            Place::Global(addr) => Op::StoreGlobal(addr),
+            Place::Local(offset) => Op::StoreLocal(offset),
+            Place::Elem(_) => Op::Store,
+        });
+    }
+
+    // 赋值与自增自减，keep为真时在栈上留下表达式的值
+    fn assign(&mut self, exp: &Expr, keep: bool) {
+        let slots = self.slots;
+        match &exp.kind {
+            ExprKind::Assign(op, lval, rhs) => {
+                let (place, ty) = self.place(lval);
+                self.store_place(&place, |this| match op {
+                    None => this.expr_as(rhs, ty),
+                    // 复合赋值先读出原来的值
+                    Some(op) => {
+                        let op_ty = promote(ty, this.ty(rhs));
+                        this.load_place(&place);
+                        this.convert(ty, op_ty);
+                        this.expr_as(rhs, op_ty);
+                        this.emit(binary_op(*op, op_ty));
+                        this.convert(op_ty, ty);
+                    }
+                });
+                if keep {
+                    self.load_place(&place);
+                }
+            }
+            ExprKind::IncDec(op, lval) => {
+                let (place, ty) = self.place(lval);
+                if keep && !op.is_prefix() {
+                    self.load_place(&place);
+                }
+                self.store_place(&place, |this| {
+                    this.load_place(&place);
+                    this.emit(Op::Push(match ty {
+                        BType::Int => 1,
+                        BType::Float => 1f32.to_bits() as i32,
+                    }));
+                    this.emit(binary_op(op.binary_op(), ty));
+                });
+                if keep && op.is_prefix() {
+                    self.load_place(&place);
+                }
+            }
+            _ => unreachable!(),
+        }
+        self.slots = slots;
+    }
+
+    // 计算左值的地址，下标不全时得到子数组的地址
+    fn lval_addr(&mut self, lval: &LVal) {
+        let var = self.var(&lval.name);
+        self.emit(match var.reg {
+            Value::Global(GlobalId(addr)) => Op::Push(addr as i32),
+            reg if var.shape[0] == 0 => Op::LoadLocal(reg.as_int().unwrap() as u32),
+            reg => Op::LocalAddr(reg.as_int().unwrap() as u32),
+        });
+        for (index, exp) in lval.indices.iter().enumerate() {
+            self.expr(exp);
+            let bound = var.shape[index] as u32;
+            self.emit(Op::Index(stride(&var.shape[index + 1..]), bound));
+        }
+    }
+
+    // 求值并转换为指定的类型
+    fn expr_as(&mut self, exp: &Expr, ty: BType) {
+        self.expr(exp);
+        self.convert(self.ty(exp), ty);
+    }
+
+    // 转换栈顶的值
+    fn convert(&mut self, from: BType, to: BType) {
+        match (from, to) {
+            (BType::Int, BType::Float) => {
+                self.emit(Op::IntToFloat);
+            }
+            (BType::Float, BType::Int) => {
+                self.emit(Op::FloatToInt);
+            }
+            _ => {}
+        }
+    }
+
+    // 求条件的值，结果为0或1的int
+    fn cond(&mut self, exp: &Expr) {
+        self.expr(exp);
+        if self.ty(exp) == BType::Float {
+            self.emit(Op::Push(0));
+            self.emit(Op::FNe);
+        }
+    }
+
+    fn expr(&mut self, exp: &Expr) {
+        match &exp.kind {
+            ExprKind::Number(val) => {
+                self.emit(Op::Push(*val));
+            }
+            ExprKind::Float(val) => {
+                self.emit(Op::Push(val.to_bits() as i32));
+            }
+            ExprKind::LVal(lval) => {
+                let var = self.var(&lval.name);
+                if var.shape.is_empty() {
+                    self.emit(match var.reg {
+                        _ if var.is_const => Op::Push(bits(var.value)),
+                        Value::Global(GlobalId(addr)) => Op::LoadGlobal(addr as u32),
+                        reg => Op::LoadLocal(reg.as_int().unwrap() as u32),
+                    });
+                } else {
+                    self.lval_addr(lval);
+                    if lval.indices.len() == var.shape.len() {
+                        self.emit(Op::Load);
+                    }
+                }
+            }
+            ExprKind::Call { name, args, .. } => {
+                let params = self.symbol.get_func(name, exp.span).unwrap().params.clone();
+                for (arg, (ty, shape)) in args.iter().zip(params.iter()) {
+                    if shape.is_empty() {
+                        self.expr_as(arg, *ty);
+                    } else {
+                        self.expr(arg);
+                    }
+                }
+                self.emit(match BUILTINS.iter().position(|builtin| builtin == name) {
+                    Some(index) => Op::Builtin(index as u32),
+                    None => Op::Call(self.funcs[name]),
+                });
+            }
+            ExprKind::Unary(UnaryOp::Not, exp) => {
+                self.cond(exp);
+                self.emit(Op::Not);
+            }
+            ExprKind::Unary(op, exp) => {
+                self.expr(exp);
+                match op {
+                    UnaryOp::Minus => {
+                        self.emit(match self.ty(exp) {
+                            BType::Int => Op::Neg,
+                            BType::Float => Op::FNeg,
+                        });
+                    }
+                    UnaryOp::BitNot => {
+                        self.emit(Op::BitNot);
+                    }
+                    _ => {}
+                }
+            }
+            ExprKind::Binary(op @ (BinaryOp::And | BinaryOp::Or), lhs, rhs) => {
+                // 短路求值，结果为0或1
+                let (jump, short): (fn(u32) -> Op, i32) = if *op == BinaryOp::And {
+                    (Op::JumpIfZero, 0)
+                } else {
+                    (Op::JumpIfNonZero, 1)
+                };
+                self.cond(lhs);
+                let first = self.emit(jump(0));
+                self.cond(rhs);
+                let second = self.emit(jump(0));
+                self.emit(Op::Push(1 - short));
+                let to_end = self.emit(Op::Jump(0));
+                self.patch(first);
+                self.patch(second);
+                self.emit(Op::Push(short));
+                self.patch(to_end);
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                let ty = promote(self.ty(lhs), self.ty(rhs));
+                self.expr_as(lhs, ty);
+                self.expr_as(rhs, ty);
+                self.emit(binary_op(*op, ty));
+            }
+            ExprKind::Conditional(cond, then_exp, else_exp) => {
+                let ty = self.ty(exp);
+                self.cond(cond);
+                let to_else = self.emit(Op::JumpIfZero(0));
+                self.expr_as(then_exp, ty);
+                let to_end = self.emit(Op::Jump(0));
+                self.patch(to_else);
+                self.expr_as(else_exp, ty);
+                self.patch(to_end);
+            }
+            ExprKind::Assign(..) | ExprKind::IncDec(..) => self.assign(exp, true),
+            ExprKind::Comma(lhs, rhs) => {
+                self.effect(lhs);
+                self.expr(rhs);
+            }
+        }
+    }
+}
@@ -0,0 +1,532 @@
+// 栈式虚拟机的字节码，compiler由语法树生成Program，vm解释执行
+// 浮点数在操作数栈与内存中按位存放为i32，由带F前缀的指令按浮点数运算
+// Program可以序列化为.cbc文件，所有整数按小端序存放：
+//   魔数与版本号，全局内存的大小，非零的全局内存(地址, 值)，各函数，main函数的编号
+//   函数依次为名字、参数个数、栈帧大小、是否有返回值与指令，指令为1字节的操作码加上每个4字节的操作数
+// 读入时校验操作数的范围与各处的操作数栈深度，保证虚拟机执行时不会越界
+
+pub mod compiler;
+pub mod vm;
+
+use std::fmt;
+
+pub const MAGIC: &[u8; 4] = b"\0cbc";
+const VERSION: u32 = 1;
+
+// 全局内存与栈帧的总大小上限，以i32为单位
+pub const MEM_LIMIT: usize = 1 << 28;
+
+pub const BUILTINS: [&str; 10] = [
+    "getint",
+    "getch",
+    "getarray",
+    "putint",
+    "putch",
+    "putarray",
+    "getfloat",
+    "getfarray",
+    "putfloat",
+    "putfarray",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Op {
+    Push(i32),
+    LoadLocal(u32), // 栈帧中的偏移
+    StoreLocal(u32),
+    LocalAddr(u32),
+    LoadGlobal(u32), // 全局内存中的地址
+    StoreGlobal(u32),
+    Load,                // 弹出地址
+    Store,               // 依次弹出值与地址
+    Index(u32, u32),     // 步长与上界，上界为0时只检查下标非负
+    ZeroLocal(u32, u32), // 清零栈帧中从偏移开始的若干个元素
+    Neg,
+    Not,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    Jump(u32),
+    JumpIfZero(u32),
+    JumpIfNonZero(u32),
+    Call(u32),
+    Builtin(u32), // BUILTINS中的下标
+    Pop,
+    Ret,
+    RetVoid,
+    FNeg,
+    FAdd,
+    FSub,
+    FMul,
+    FDiv,
+    FLt,
+    FGt,
+    FLe,
+    FGe,
+    FEq,
+    FNe,
+    IntToFloat,
+    FloatToInt,
+    BitNot,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr, // 算术右移，移位量超出范围时结果未定义，实际只取低5位
+}
+
+impl Op {
+    fn opcode(&self) -> u8 {
+        match self {
+            Op::Push(_) => 0,
+            Op::LoadLocal(_) => 1,
+            Op::StoreLocal(_) => 2,
+            Op::LocalAddr(_) => 3,
+            Op::LoadGlobal(_) => 4,
+            Op::StoreGlobal(_) => 5,
+            Op::Load => 6,
+            Op::Store => 7,
+            Op::Index(_, _) => 8,
+            Op::ZeroLocal(_, _) => 9,
+            Op::Neg => 10,
+            Op::Not => 11,
+            Op::Add => 12,
+            Op::Sub => 13,
+            Op::Mul => 14,
+            Op::Div => 15,
+            Op::Mod => 16,
+            Op::Lt => 17,
+            Op::Gt => 18,
+            Op::Le => 19,
+            Op::Ge => 20,
+            Op::Eq => 21,
+            Op::Ne => 22,
+            Op::Jump(_) => 23,
+            Op::JumpIfZero(_) => 24,
+            Op::JumpIfNonZero(_) => 25,
+            Op::Call(_) => 26,
+            Op::Builtin(_) => 27,
+            Op::Pop => 28,
+            Op::Ret => 29,
+            Op::RetVoid => 30,
+            Op::FNeg => 31,
+            Op::FAdd => 32,
+            Op::FSub => 33,
+            Op::FMul => 34,
+            Op::FDiv => 35,
+            Op::FLt => 36,
+            Op::FGt => 37,
+            Op::FLe => 38,
+            Op::FGe => 39,
+            Op::FEq => 40,
+            Op::FNe => 41,
+            Op::IntToFloat => 42,
+            Op::FloatToInt => 43,
+            Op::BitNot => 44,
+            Op::And => 45,
+            Op::Or => 46,
+            Op::Xor => 47,
+            Op::Shl => 48,
+            Op::Shr => 49,
+        }
+    }
+
+    fn operands(&self) -> Vec<u32> {
+        match *self {
+            Op::Push(val) => vec![val as u32],
+            Op::LoadLocal(arg)
+            | Op::StoreLocal(arg)
+            | Op::LocalAddr(arg)
+            | Op::LoadGlobal(arg)
+            | Op::StoreGlobal(arg)
+            | Op::Jump(arg)
+            | Op::JumpIfZero(arg)
+            | Op::JumpIfNonZero(arg)
+            | Op::Call(arg)
+            | Op::Builtin(arg) => vec![arg],
+            Op::Index(lhs, rhs) | Op::ZeroLocal(lhs, rhs) => vec![lhs, rhs],
+            _ => vec![],
+        }
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Op, String> {
+        let opcode = reader.u8()?;
+        let op = match opcode {
+            0 => Op::Push(reader.u32()? as i32),
+            1 => Op::LoadLocal(reader.u32()?),
+            2 => Op::StoreLocal(reader.u32()?),
+            3 => Op::LocalAddr(reader.u32()?),
+            4 => Op::LoadGlobal(reader.u32()?),
+            5 => Op::StoreGlobal(reader.u32()?),
+            6 => Op::Load,
+            7 => Op::Store,
+            8 => Op::Index(reader.u32()?, reader.u32()?),
+            9 => Op::ZeroLocal(reader.u32()?, reader.u32()?),
+            10 => Op::Neg,
+            11 => Op::Not,
+            12 => Op::Add,
+            13 => Op::Sub,
+            14 => Op::Mul,
+            15 => Op::Div,
+            16 => Op::Mod,
+            17 => Op::Lt,
+            18 => Op::Gt,
+            19 => Op::Le,
+            20 => Op::Ge,
+            21 => Op::Eq,
+            22 => Op::Ne,
+            23 => Op::Jump(reader.u32()?),
+            24 => Op::JumpIfZero(reader.u32()?),
+            25 => Op::JumpIfNonZero(reader.u32()?),
+            26 => Op::Call(reader.u32()?),
+            27 => Op::Builtin(reader.u32()?),
+            28 => Op::Pop,
+            29 => Op::Ret,
+            30 => Op::RetVoid,
+            31 => Op::FNeg,
+            32 => Op::FAdd,
+            33 => Op::FSub,
+            34 => Op::FMul,
+            35 => Op::FDiv,
+            36 => Op::FLt,
+            37 => Op::FGt,
+            38 => Op::FLe,
+            39 => Op::FGe,
+            40 => Op::FEq,
+            41 => Op::FNe,
+            42 => Op::IntToFloat,
+            43 => Op::FloatToInt,
+            44 => Op::BitNot,
+            45 => Op::And,
+            46 => Op::Or,
+            47 => Op::Xor,
+            48 => Op::Shl,
+            49 => Op::Shr,
+            _ => return Err(format!("unknown opcode {}", opcode)),
+        };
+        Ok(op)
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = format!("{:?}", self);
+        let name = name.split('(').next().unwrap().to_lowercase();
+        let operands: Vec<String> = match *self {
+            Op::Push(val) => vec![val.to_string()],
+            Op::Builtin(index) => vec![BUILTINS
+                .get(index as usize)
+                .map_or(index.to_string(), |name| name.to_string())],
+            _ => self.operands().iter().map(|arg| arg.to_string()).collect(),
+        };
+        if operands.is_empty() {
+            write!(f, "{}", name)
+        } else {
+            write!(f, "{} {}", name, operands.join(", "))
+        }
+    }
+}
+
+pub struct Function {
+    pub name: String,
+    pub params: u32, // 参数依次放在栈帧的开头
+    pub frame_size: u32,
+    pub has_return: bool,
+    pub code: Vec<Op>,
+}
+
+pub struct Program {
+    pub global_size: u32,
+    pub data: Vec<(u32, i32)>, // 非零的全局内存
+    pub functions: Vec<Function>,
+    pub main: u32,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() - self.pos < len {
+            return Err("unexpected end of file".to_string());
+        }
+        self.pos += len;
+        Ok(&self.bytes[self.pos - len..self.pos])
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+pub fn is_bytecode(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+impl Program {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut res = MAGIC.to_vec();
+        let put = |res: &mut Vec<u8>, val: u32| res.extend_from_slice(&val.to_le_bytes());
+        put(&mut res, VERSION);
+        put(&mut res, self.global_size);
+        put(&mut res, self.data.len() as u32);
+        for (addr, val) in &self.data {
+            put(&mut res, *addr);
+            put(&mut res, *val as u32);
+        }
+        put(&mut res, self.functions.len() as u32);
+        for func in &self.functions {
+            put(&mut res, func.name.len() as u32);
+            res.extend_from_slice(func.name.as_bytes());
+            put(&mut res, func.params);
+            put(&mut res, func.frame_size);
+            res.push(func.has_return as u8);
+            put(&mut res, func.code.len() as u32);
+            for op in &func.code {
+                res.push(op.opcode());
+                for arg in op.operands() {
+                    put(&mut res, arg);
+                }
+            }
+        }
+        put(&mut res, self.main);
+        res
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Program, String> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err("not a calcium bytecode file".to_string());
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(format!("unsupported bytecode version {}", version));
+        }
+        let global_size = reader.u32()?;
+        let mut data = vec![];
+        for _ in 0..reader.u32()? {
+            data.push((reader.u32()?, reader.u32()? as i32));
+        }
+        let mut functions = vec![];
+        for _ in 0..reader.u32()? {
+            let len = reader.u32()? as usize;
+            let name = String::from_utf8(reader.take(len)?.to_vec())
+                .map_err(|_| "function name is not valid UTF-8".to_string())?;
+            let params = reader.u32()?;
+            let frame_size = reader.u32()?;
+            let has_return = reader.u8()? != 0;
+            let mut code = vec![];
+            for _ in 0..reader.u32()? {
+                code.push(Op::decode(&mut reader)?);
+            }
+            functions.push(Function {
+                name,
+                params,
+                frame_size,
+                has_return,
+                code,
+            });
+        }
+        let program = Program {
+            global_size,
+            data,
+            functions,
+            main: reader.u32()?,
+        };
+        if reader.pos != bytes.len() {
+            return Err("trailing data after the program".to_string());
+        }
+        program.verify()?;
+        Ok(program)
+    }
+
+    fn verify(&self) -> Result<(), String> {
+        if self.global_size as usize > MEM_LIMIT {
+            return Err("global memory is too large".to_string());
+        }
+        if self.data.iter().any(|(addr, _)| *addr >= self.global_size) {
+            return Err("global data is out of range".to_string());
+        }
+        match self.functions.get(self.main as usize) {
+            Some(main) if main.params == 0 && main.has_return => {}
+            _ => return Err("invalid main function".to_string()),
+        }
+        for func in &self.functions {
+            self.verify_function(func)
+                .map_err(|err| format!("in function `{}`: {}", func.name, err))?;
+        }
+        Ok(())
+    }
+
+    // 检查操作数的范围，并沿控制流求出每条指令处的操作数栈深度，要求各路径一致且不会下溢
+    fn verify_function(&self, func: &Function) -> Result<(), String> {
+        if func.params > func.frame_size || func.frame_size as usize > MEM_LIMIT {
+            return Err("invalid frame size".to_string());
+        }
+        let len = func.code.len() as u32;
+        let mut depths: Vec<Option<usize>> = vec![None; func.code.len()];
+        let mut worklist = vec![(0, 0)];
+        while let Some((pc, depth)) = worklist.pop() {
+            let op = match func.code.get(pc) {
+                Some(op) => *op,
+                None => return Err("control flows past the end of the code".to_string()),
+            };
+            match depths[pc] {
+                Some(old) if old == depth => continue,
+                Some(_) => return Err(format!("inconsistent stack depth at {}", pc)),
+                None => depths[pc] = Some(depth),
+            }
+            let (pops, pushes) = match op {
+                Op::LoadLocal(offset) | Op::LocalAddr(offset) if offset < func.frame_size => (0, 1),
+                Op::StoreLocal(offset) if offset < func.frame_size => (1, 0),
+                Op::ZeroLocal(offset, size)
+                    if offset as u64 + size as u64 <= func.frame_size as u64 =>
+                {
+                    (0, 0)
+                }
+                Op::LoadGlobal(addr) if addr < self.global_size => (0, 1),
+                Op::StoreGlobal(addr) if addr < self.global_size => (1, 0),
+                Op::Push(_) => (0, 1),
+                Op::Load
+                | Op::Neg
+                | Op::Not
+                | Op::BitNot
+                | Op::FNeg
+                | Op::IntToFloat
+                | Op::FloatToInt => (1, 1),
+                Op::Store => (2, 0),
+                Op::Index(_, _)
+                | Op::Add
+                | Op::Sub
+                | Op::Mul
+                | Op::Div
+                | Op::Mod
+                | Op::Lt
+                | Op::Gt
+                | Op::Le
+                | Op::Ge
+                | Op::Eq
+                | Op::Ne
+                | Op::And
+                | Op::Or
+                | Op::Xor
+                | Op::Shl
+                | Op::Shr
+                | Op::FAdd
+                | Op::FSub
+                | Op::FMul
+                | Op::FDiv
+                | Op::FLt
+                | Op::FGt
+                | Op::FLe
+                | Op::FGe
+                | Op::FEq
+                | Op::FNe => (2, 1),
+                Op::Jump(target) | Op::JumpIfZero(target) | Op::JumpIfNonZero(target)
+                    if target < len =>
+                {
+                    ((op != Op::Jump(target)) as usize, 0)
+                }
+                Op::Call(callee) if (callee as usize) < self.functions.len() => {
+                    let callee = &self.functions[callee as usize];
+                    (callee.params as usize, callee.has_return as usize)
+                }
+                Op::Builtin(index) if (index as usize) < BUILTINS.len() => {
+                    match BUILTINS[index as usize] {
+                        "getint" | "getch" | "getfloat" => (0, 1),
+                        "getarray" | "getfarray" => (1, 1),
+                        "putint" | "putch" | "putfloat" => (1, 0),
+                        _ => (2, 0),
+                    }
+                }
+                Op::Pop => (1, 0),
+                Op::Ret if func.has_return => (1, 0),
+                Op::RetVoid if !func.has_return => (0, 0),
+                _ => return Err(format!("invalid instruction `{}` at {}", op, pc)),
+            };
+            if depth < pops {
+                return Err(format!("stack underflow at {}", pc));
+            }
+            let depth = depth - pops + pushes;
+            match op {
+                Op::Ret | Op::RetVoid if depth != 0 => {
+                    return Err(format!("values left on the stack at {}", pc))
+                }
+                Op::Ret | Op::RetVoid => {}
+                Op::Jump(target) => worklist.push((target as usize, depth)),
+                Op::JumpIfZero(target) | Op::JumpIfNonZero(target) => {
+                    worklist.push((target as usize, depth));
+                    worklist.push((pc + 1, depth));
+                }
+                _ => worklist.push((pc + 1, depth)),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{vm, Program};
+    use crate::testing::compile_source;
+    use crate::{Emit, Options};
+
+    fn encode(source: &str) -> Vec<u8> {
+        let options = Options {
+            emit: Emit::Bytecode,
+            ..Options::default()
+        };
+        compile_source(source, &options).code
+    }
+
+    const SOURCE: &str = "int g[3] = {1, 2, 3};
+        float scale = 1.5;
+        int sum(int a[], int n) { int s = 0, i = 0; while (i < n) { s = s + a[i]; i = i + 1; } return s; }
+        int main() { return sum(g, 3) * scale; }";
+
+    // 载入后再编码得到相同的字节，执行结果不变
+    #[test]
+    fn round_trips_program() {
+        let bytes = encode(SOURCE);
+        let program = Program::decode(&bytes).unwrap();
+        assert_eq!(program.encode(), bytes);
+        assert_eq!(vm::run(&program).ok(), Some(9));
+    }
+
+    // 截断或有多余数据的文件在载入时被拒绝
+    #[test]
+    fn rejects_truncated_file() {
+        let bytes = encode(SOURCE);
+        for len in 0..bytes.len() {
+            assert!(
+                Program::decode(&bytes[..len]).is_err(),
+                "accepted a file truncated to {} bytes",
+                len
+            );
+        }
+        let res = Program::decode(&bytes[..bytes.len() - 1]);
+        assert_eq!(res.err().as_deref(), Some("unexpected end of file"));
+        let mut extended = bytes;
+        extended.push(0);
+        let res = Program::decode(&extended);
+        assert_eq!(
+            res.err().as_deref(),
+            Some("trailing data after the program")
+        );
+    }
+}
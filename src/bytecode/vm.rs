@@ -0,0 +1,371 @@
+use std::fmt;
+
+use super::super::runtime::Runtime;
+use super::{Op, Program, BUILTINS};
+
+// 执行校验过的字节码，运算按i32补码回绕，与生成的IR一致；浮点数转换为整数时超出范围的结果取最接近的值，NaN取0
+// 内存以i32为单位，全局变量在最前面，之后是各函数的栈帧；数组下标越界、访问内存之外的地址与除以零时停止并报告
+// 栈帧与调用深度的总和超过STACK_CELLS时报告栈溢出
+
+const STACK_CELLS: usize = 1 << 24;
+
+pub struct Trap {
+    pub func: String,
+    pub pc: usize,
+    pub op: String,
+    pub message: String,
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}\n  in {} at {}: {}",
+            self.message, self.func, self.pc, self.op
+        )
+    }
+}
+
+struct Frame {
+    func: usize,
+    pc: usize, // 返回后继续执行的位置
+    fp: usize,
+}
+
+struct Vm<'a> {
+    program: &'a Program,
+    mem: Vec<i32>,
+    stack: Vec<i32>,
+    frames: Vec<Frame>,
+    runtime: Runtime,
+}
+
+pub fn run(program: &Program) -> Result<i32, Trap> {
+    let mut mem = vec![0; program.global_size as usize];
+    for (addr, val) in &program.data {
+        mem[*addr as usize] = *val;
+    }
+    let mut vm = Vm {
+        program,
+        mem,
+        stack: vec![],
+        frames: vec![],
+        runtime: Runtime::new(),
+    };
+    let res = vm.run();
+    vm.runtime.flush();
+    res
+}
+
+impl<'a> Vm<'a> {
+    fn pop(&mut self) -> i32 {
+        // 校验保证操作数栈不会下溢
+        self.stack.pop().unwrap()
+    }
+
+    fn pop_float(&mut self) -> f32 {
+        f32::from_bits(self.pop() as u32)
+    }
+
+    fn push_float(&mut self, val: f32) {
+        self.stack.push(val.to_bits() as i32);
+    }
+
+    // 内存中从addr开始的连续count个i32的起点
+    fn addr(&self, addr: i32, count: i32) -> Result<usize, String> {
+        if addr < 0 || addr as usize + count.max(0) as usize > self.mem.len() {
+            return Err(format!("out-of-bounds memory access at address {}", addr));
+        }
+        Ok(addr as usize)
+    }
+
+    // 分配栈帧并把实参移入其中，栈空间不足时返回false
+    fn enter(&mut self, callee: usize, fp: &mut usize) -> bool {
+        let func = &self.program.functions[callee];
+        let used = self.mem.len() - self.program.global_size as usize + self.frames.len();
+        if used + func.frame_size as usize + 1 > STACK_CELLS {
+            return false;
+        }
+        *fp = self.mem.len();
+        self.mem.resize(*fp + func.frame_size as usize, 0);
+        let args = self.stack.len() - func.params as usize;
+        self.mem[*fp..*fp + func.params as usize].copy_from_slice(&self.stack[args..]);
+        self.stack.truncate(args);
+        true
+    }
+
+    fn run(&mut self) -> Result<i32, Trap> {
+        let program = self.program;
+        let (mut func, mut pc, mut fp) = (program.main as usize, 0, 0);
+        if !self.enter(func, &mut fp) {
+            return self.trap(func, pc, Err("stack overflow".to_string()));
+        }
+        loop {
+            let code = &program.functions[func].code;
+            let op = code[pc];
+            pc += 1;
+            let res = match op {
+                Op::Push(val) => {
+                    self.stack.push(val);
+                    Ok(())
+                }
+                Op::LoadLocal(offset) => {
+                    self.stack.push(self.mem[fp + offset as usize]);
+                    Ok(())
+                }
+                Op::StoreLocal(offset) => {
+                    self.mem[fp + offset as usize] = self.pop();
+                    Ok(())
+                }
+                Op::LocalAddr(offset) => {
+                    self.stack.push((fp + offset as usize) as i32);
+                    Ok(())
+                }
+                Op::LoadGlobal(addr) => {
+                    self.stack.push(self.mem[addr as usize]);
+                    Ok(())
+                }
+                Op::StoreGlobal(addr) => {
+                    self.mem[addr as usize] = self.pop();
+                    Ok(())
+                }
+                Op::Load => {
+                    let addr = self.pop();
+                    self.addr(addr, 1)
+                        .map(|addr| self.stack.push(self.mem[addr]))
+                }
+                Op::Store => {
+                    let val = self.pop();
+                    let addr = self.pop();
+                    self.addr(addr, 1).map(|addr| self.mem[addr] = val)
+                }
+                Op::Index(stride, bound) => {
+                    let index = self.pop();
+                    let base = self.pop();
+                    if index < 0 || bound != 0 && index as u32 >= bound {
+                        Err(format!("index {} is out of bounds", index))
+                    } else {
+                        let offset = (index as u32).wrapping_mul(stride);
+                        self.stack.push(base.wrapping_add(offset as i32));
+                        Ok(())
+                    }
+                }
+                Op::ZeroLocal(offset, size) => {
+                    let start = fp + offset as usize;
+                    self.mem[start..start + size as usize].fill(0);
+                    Ok(())
+                }
+                Op::Neg => {
+                    let val = self.pop();
+                    self.stack.push(val.wrapping_neg());
+                    Ok(())
+                }
+                Op::Not => {
+                    let val = self.pop();
+                    self.stack.push((val == 0) as i32);
+                    Ok(())
+                }
+                Op::BitNot => {
+                    let val = self.pop();
+                    self.stack.push(!val);
+                    Ok(())
+                }
+                Op::Add
+                | Op::Sub
+                | Op::Mul
+                | Op::Div
+                | Op::Mod
+                | Op::Lt
+                | Op::Gt
+                | Op::Le
+                | Op::Ge
+                | Op::Eq
+                | Op::Ne
+                | Op::And
+                | Op::Or
+                | Op::Xor
+                | Op::Shl
+                | Op::Shr => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    let res = match op {
+                        Op::Div | Op::Mod if rhs == 0 => Err("division by zero".to_string()),
+                        Op::Add => Ok(lhs.wrapping_add(rhs)),
+                        Op::Sub => Ok(lhs.wrapping_sub(rhs)),
+                        Op::Mul => Ok(lhs.wrapping_mul(rhs)),
+                        Op::Div => Ok(lhs.wrapping_div(rhs)),
+                        Op::Mod => Ok(lhs.wrapping_rem(rhs)),
+                        Op::Lt => Ok((lhs < rhs) as i32),
+                        Op::Gt => Ok((lhs > rhs) as i32),
+                        Op::Le => Ok((lhs <= rhs) as i32),
+                        Op::Ge => Ok((lhs >= rhs) as i32),
+                        Op::Eq => Ok((lhs == rhs) as i32),
+                        Op::Ne => Ok((lhs != rhs) as i32),
+                        Op::And => Ok(lhs & rhs),
+                        Op::Or => Ok(lhs | rhs),
+                        Op::Xor => Ok(lhs ^ rhs),
+                        Op::Shl => Ok(lhs.wrapping_shl(rhs as u32)),
+                        _ => Ok(lhs.wrapping_shr(rhs as u32)),
+                    };
+                    res.map(|res| self.stack.push(res))
+                }
+                Op::FNeg => {
+                    let val = self.pop_float();
+                    self.push_float(-val);
+                    Ok(())
+                }
+                Op::FAdd
+                | Op::FSub
+                | Op::FMul
+                | Op::FDiv
+                | Op::FLt
+                | Op::FGt
+                | Op::FLe
+                | Op::FGe
+                | Op::FEq
+                | Op::FNe => {
+                    let rhs = self.pop_float();
+                    let lhs = self.pop_float();
+                    match op {
+                        Op::FAdd => self.push_float(lhs + rhs),
+                        Op::FSub => self.push_float(lhs - rhs),
+                        Op::FMul => self.push_float(lhs * rhs),
+                        Op::FDiv => self.push_float(lhs / rhs),
+                        Op::FLt => self.stack.push((lhs < rhs) as i32),
+                        Op::FGt => self.stack.push((lhs > rhs) as i32),
+                        Op::FLe => self.stack.push((lhs <= rhs) as i32),
+                        Op::FGe => self.stack.push((lhs >= rhs) as i32),
+                        Op::FEq => self.stack.push((lhs == rhs) as i32),
+                        _ => self.stack.push((lhs != rhs) as i32),
+                    }
+                    Ok(())
+                }
+                Op::IntToFloat => {
+                    let val = self.pop();
+                    self.push_float(val as f32);
+                    Ok(())
+                }
+                Op::FloatToInt => {
+                    let val = self.pop_float();
+                    self.stack.push(val as i32);
+                    Ok(())
+                }
+                Op::Jump(target) => {
+                    pc = target as usize;
+                    Ok(())
+                }
+                Op::JumpIfZero(target) => {
+                    if self.pop() == 0 {
+                        pc = target as usize;
+                    }
+                    Ok(())
+                }
+                Op::JumpIfNonZero(target) => {
+                    if self.pop() != 0 {
+                        pc = target as usize;
+                    }
+                    Ok(())
+                }
+                Op::Call(callee) => {
+                    let caller = Frame { func, pc, fp };
+                    if self.enter(callee as usize, &mut fp) {
+                        self.frames.push(caller);
+                        func = callee as usize;
+                        pc = 0;
+                        Ok(())
+                    } else {
+                        Err("stack overflow".to_string())
+                    }
+                }
+                Op::Builtin(index) => self.builtin(BUILTINS[index as usize]),
+                Op::Pop => {
+                    self.pop();
+                    Ok(())
+                }
+                Op::Ret | Op::RetVoid => {
+                    self.mem.truncate(fp);
+                    match self.frames.pop() {
+                        // 校验保证main有返回值
+                        None => return Ok(self.pop()),
+                        Some(frame) => {
+                            func = frame.func;
+                            pc = frame.pc;
+                            fp = frame.fp;
+                        }
+                    }
+                    Ok(())
+                }
+            };
+            if let Err(message) = res {
+                return self.trap(func, pc - 1, Err(message));
+            }
+        }
+    }
+
+    fn trap<T>(&self, func: usize, pc: usize, res: Result<T, String>) -> Result<T, Trap> {
+        let func = &self.program.functions[func];
+        res.map_err(|message| Trap {
+            func: func.name.clone(),
+            pc,
+            op: func.code[pc].to_string(),
+            message,
+        })
+    }
+
+    // 运行时库函数
+    fn builtin(&mut self, name: &str) -> Result<(), String> {
+        match name {
+            "getint" => {
+                let val = self.runtime.getint();
+                self.stack.push(val);
+            }
+            "getch" => {
+                let val = self.runtime.getch();
+                self.stack.push(val);
+            }
+            "getfloat" => {
+                let val = self.runtime.getfloat();
+                self.push_float(val);
+            }
+            "getarray" | "getfarray" => {
+                let addr = self.pop();
+                let count = self.runtime.getint();
+                let addr = self.addr(addr, count)?;
+                for index in 0..count.max(0) as usize {
+                    self.mem[addr + index] = if name == "getarray" {
+                        self.runtime.getint()
+                    } else {
+                        self.runtime.getfloat().to_bits() as i32
+                    };
+                }
+                self.stack.push(count);
+            }
+            "putint" => {
+                let val = self.pop();
+                self.runtime.putint(val);
+            }
+            "putch" => {
+                let val = self.pop();
+                self.runtime.putch(val);
+            }
+            "putfloat" => {
+                let val = self.pop_float();
+                self.runtime.putfloat(val);
+            }
+            _ => {
+                let addr = self.pop();
+                let count = self.pop();
+                let addr = self.addr(addr, count)?;
+                let vals = &self.mem[addr..addr + count.max(0) as usize];
+                if name == "putarray" {
+                    self.runtime.putarray(count, vals);
+                } else {
+                    let vals: Vec<f32> =
+                        vals.iter().map(|val| f32::from_bits(*val as u32)).collect();
+                    self.runtime.putfarray(count, &vals);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1787 @@
+use std::collections::HashMap;
+
+use super::assigner::Assigner;
+use super::ast::{
+    BType, BinaryOp, Block, BlockItem, Case, CompUnit, Decl, Expr, ExprKind, FuncDef, InitVal,
+    Item, LVal, Stmt, StmtKind, UnaryOp, VarDef,
+};
+use super::diagnostic::{Diagnostic, ErrorCode};
+use super::ir::{
+    self, BinOp, BlockId, CmpOp, Constant, FuncId, GlobalVariable, InstKind, Module, Type, Value,
+};
+use super::span::Span;
+use super::symbol::{SymbolTable, Variable};
+
+pub struct Codegen {
+    symbol: SymbolTable,
+    assigner: Assigner,
+    module: Module,
+    func: FuncId,                     // 正在生成的函数
+    entry: BlockId,                   // 入口块，只存放alloca
+    current: BlockId,                 // 正在生成的基本块
+    blocks: HashMap<String, BlockId>, // 标号对应的基本块
+    is_folding_const: bool,           // 是否正在计算局部常量及数组维度
+    errors: Vec<Diagnostic>,
+    error_limit: usize, // 为0时不限制错误数量
+    warnings: Vec<Diagnostic>,
+}
+
+impl Codegen {
+    fn error<T>(&self, code: ErrorCode, span: Span, message: &str) -> Result<T, Diagnostic> {
+        Err(Diagnostic::error(code, message.to_string(), span))
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic) {
+        if self.error_limit == 0 || self.errors.len() < self.error_limit {
+            self.errors.push(diagnostic);
+        }
+    }
+
+    // 表达式必须有值，不能是void函数调用
+    fn expect_value(&self, var: Option<Variable>, span: Span) -> Result<Variable, Diagnostic> {
+        match var {
+            Some(var) => Ok(var),
+            None => self.error(
+                ErrorCode::InvalidOperand,
+                span,
+                "void value not ignored as it ought to be",
+            ),
+        }
+    }
+
+    // 常量表达式的值，为整数或浮点常量
+    fn const_value(&self, var: Option<Variable>, span: Span) -> Result<Value, Diagnostic> {
+        match self.expect_value(var, span)?.reg {
+            val @ (Value::Int(_) | Value::Float(_)) => Ok(val),
+            _ => self.error(
+                ErrorCode::NotConstant,
+                span,
+                "expression is not a compile-time constant",
+            ),
+        }
+    }
+
+    // 数组的维度，必须是整数常量
+    fn gen_const_exp(&mut self, exp: &Expr, is_const: bool) -> Result<i32, Diagnostic> {
+        let var = self.gen_add_exp(exp, is_const)?;
+        match self.const_value(var, exp.span)? {
+            Value::Int(val) => Ok(val),
+            _ => self.error(
+                ErrorCode::InvalidArray,
+                exp.span,
+                "size of array has non-integer type `float`",
+            ),
+        }
+    }
+
+    fn gen_value_exp(&mut self, exp: &Expr, is_const: bool) -> Result<Variable, Diagnostic> {
+        let var = self.gen_add_exp(exp, is_const)?;
+        self.expect_value(var, exp.span)
+    }
+
+    // 赋值、初始化、传参与返回时的值，先转换为目标类型
+    fn gen_typed_exp(
+        &mut self,
+        exp: &Expr,
+        is_const: bool,
+        ty: BType,
+    ) -> Result<Variable, Diagnostic> {
+        let var = self.gen_value_exp(exp, is_const)?;
+        if !var.shape.is_empty() {
+            return self.error(
+                ErrorCode::InvalidOperand,
+                exp.span,
+                format!("array cannot be converted to `{}`", ty).as_str(),
+            );
+        }
+        Ok(self.convert(var, ty))
+    }
+
+    // 标量的隐式类型转换，常量直接折叠
+    fn convert(&mut self, mut var: Variable, ty: BType) -> Variable {
+        if var.ty == ty || !var.shape.is_empty() {
+            return var;
+        }
+        var.reg = match (var.reg, ty) {
+            (Value::Int(val), _) => Value::float(val as f32),
+            (Value::Float(_), _) => Value::Int(var.reg.as_float().unwrap() as i32),
+            (reg, BType::Float) => {
+                let name = self.assigner.new_var();
+                self.add_inst(InstKind::Sitofp(reg), Type::Float, Some(name))
+            }
+            (reg, BType::Int) => {
+                let name = self.assigner.new_var();
+                self.add_inst(InstKind::Fptosi(reg), Type::I32, Some(name))
+            }
+        };
+        var.ty = ty;
+        var
+    }
+
+    fn ir_type(ty: BType) -> Type {
+        match ty {
+            BType::Int => Type::I32,
+            BType::Float => Type::Float,
+        }
+    }
+
+    fn zero(ty: BType) -> Value {
+        match ty {
+            BType::Int => Value::Int(0),
+            BType::Float => Value::float(0.0),
+        }
+    }
+
+    // 与var同类型的常量0
+    fn zero_of(var: &Variable) -> Variable {
+        let mut res = Variable::new();
+        res.reg = Self::zero(var.ty);
+        res.ty = var.ty;
+        res
+    }
+
+    // 标量的初始值不能是花括号列表
+    fn init_exp<'b>(&self, init: &'b InitVal) -> Result<&'b Expr, Diagnostic> {
+        match init {
+            InitVal::Expr(exp) => Ok(exp),
+            InitVal::List(_, span) => self.error(
+                ErrorCode::InvalidArray,
+                *span,
+                "scalar cannot be initialized with an initializer list",
+            ),
+        }
+    }
+
+    fn init_list<'b>(&self, init: &'b InitVal) -> Result<&'b [InitVal], Diagnostic> {
+        match init {
+            InitVal::List(vals, _) => Ok(vals),
+            InitVal::Expr(exp) => self.error(
+                ErrorCode::InvalidArray,
+                exp.span,
+                "array must be initialized with an initializer list",
+            ),
+        }
+    }
+
+    fn func(&mut self) -> &mut ir::Function {
+        self.module.function_mut(self.func)
+    }
+
+    // 当前块已经结束时，之后的指令放入新的无名块
+    fn add_inst(&mut self, kind: InstKind, ty: Type, name: Option<String>) -> Value {
+        let current = self.current;
+        if self.func().terminator(current).is_some() {
+            let block = self.func().new_block(None);
+            self.func().layout.push(block);
+            self.current = block;
+        }
+        let current = self.current;
+        Value::Inst(self.func().push_inst(current, kind, ty, name))
+    }
+
+    fn add_store(&mut self, val: Value, ptr: Value) {
+        self.add_inst(InstKind::Store(val, ptr), Type::Void, None);
+    }
+
+    fn add_alloca(&mut self, ty: Type) -> Value {
+        let entry = self.entry;
+        let inst = self
+            .func()
+            .push_inst(entry, InstKind::Alloca(ty.clone()), ty.ptr(), None);
+        Value::Inst(inst)
+    }
+
+    fn add_global(
+        &mut self,
+        name: &str,
+        is_const: bool,
+        ty: BType,
+        shape: &[i32],
+        init: Constant,
+    ) -> Value {
+        Value::Global(self.module.add_global(GlobalVariable {
+            name: name.to_string(),
+            ty: Type::from_shape(shape, Self::ir_type(ty)),
+            is_const,
+            init,
+        }))
+    }
+
+    fn get_block(&mut self, name: &str) -> BlockId {
+        if let Some(block) = self.blocks.get(name) {
+            return *block;
+        }
+        let block = self.func().new_block(Some(name.to_string()));
+        self.blocks.insert(name.to_string(), block);
+        block
+    }
+
+    // 标号在生成条件跳转之后才能确定的块，先创建再命名
+    fn new_block(&mut self) -> BlockId {
+        self.func().new_block(None)
+    }
+
+    fn name_block(&mut self, block: BlockId, name: &str) {
+        self.func().blocks[block.0].name = Some(name.to_string());
+        self.blocks.insert(name.to_string(), block);
+    }
+
+    // 之后的指令放入标号为name的块，每个块只能开始一次
+    fn start_block(&mut self, name: &str) {
+        let block = self.get_block(name);
+        debug_assert!(
+            !self.func().layout.contains(&block),
+            "block {} is already laid out",
+            name
+        );
+        self.func().layout.push(block);
+        self.current = block;
+    }
+
+    fn add_br(&mut self, target: &str) {
+        let target = self.get_block(target);
+        self.add_inst(InstKind::Br(target), Type::Void, None);
+    }
+
+    fn add_cond_br(&mut self, cond: Value, then_block: BlockId, else_block: BlockId) {
+        self.add_inst(
+            InstKind::CondBr(cond, then_block, else_block),
+            Type::Void,
+            None,
+        );
+    }
+
+    fn get_elem_pos(
+        &mut self,
+        var_name: &str,
+        pos: Vec<Value>,
+        span: Span,
+    ) -> Result<Variable, Diagnostic> {
+        let mut var = self.symbol.get_var(var_name, span)?.clone();
+        if var.shape.len() < pos.len() {
+            return self.error(
+                ErrorCode::InvalidOperand,
+                span,
+                "subscripted value is not an array",
+            );
+        }
+        for (index, item) in pos.into_iter().enumerate() {
+            let new_reg = self.assigner.new_var();
+            // 数组参数是指针，第一个下标直接作用于指针
+            let indices = if index == 0 && var.shape[0] == 0 {
+                vec![item]
+            } else {
+                vec![Value::Int(0), item]
+            };
+            var.shape.remove(0);
+            let ty = Type::from_shape(&var.shape, Self::ir_type(var.ty)).ptr();
+            var.reg = self.add_inst(InstKind::Gep(var.reg, indices), ty, Some(new_reg));
+        }
+        Ok(var)
+    }
+}
+
+impl Codegen {
+    // errors为语法分析阶段的错误，语义错误追加在其后并按位置排序
+    pub fn generate(
+        comp_unit: &CompUnit,
+        errors: Vec<Diagnostic>,
+        error_limit: usize,
+        warnings: &mut Vec<Diagnostic>,
+    ) -> Result<Module, Vec<Diagnostic>> {
+        let mut codegen = Codegen {
+            symbol: SymbolTable::new(),
+            assigner: Assigner::new(),
+            module: Module::new(),
+            func: FuncId(0),
+            entry: BlockId(0),
+            current: BlockId(0),
+            blocks: HashMap::new(),
+            is_folding_const: false,
+            errors,
+            error_limit,
+            warnings: vec![],
+        };
+        codegen.gen_comp_unit(comp_unit);
+        warnings.append(&mut codegen.warnings);
+        if codegen.errors.is_empty() {
+            Ok(codegen.module)
+        } else {
+            codegen
+                .errors
+                .sort_by_key(|err| (err.span.file, err.span.start));
+            Err(codegen.errors)
+        }
+    }
+
+    fn gen_comp_unit(&mut self, comp_unit: &CompUnit) {
+        // 运行时库函数的声明
+        let builtins = [
+            ("getint", Type::I32, vec![]),
+            ("getch", Type::I32, vec![]),
+            ("getfloat", Type::Float, vec![]),
+            ("getarray", Type::I32, vec![Type::I32.ptr()]),
+            ("getfarray", Type::I32, vec![Type::Float.ptr()]),
+            ("putch", Type::Void, vec![Type::I32]),
+            ("putint", Type::Void, vec![Type::I32]),
+            ("putfloat", Type::Void, vec![Type::Float]),
+            ("putarray", Type::Void, vec![Type::I32, Type::I32.ptr()]),
+            ("putfarray", Type::Void, vec![Type::I32, Type::Float.ptr()]),
+        ];
+        for (name, ret_ty, params) in builtins {
+            let params = params
+                .into_iter()
+                .enumerate()
+                .map(|(index, ty)| (format!("p{}", index + 1), ty))
+                .collect();
+            self.module
+                .add_function(ir::Function::new(name, ret_ty, params));
+        }
+        for item in &comp_unit.items {
+            let res = match item {
+                Item::FuncDef(func) => self.gen_func_def(func),
+                Item::Decl(decl) => self.gen_decl(decl),
+            };
+            if let Err(err) = res {
+                self.report(err);
+                self.symbol.go_global();
+                self.is_folding_const = false;
+            }
+        }
+        // main函数检查，之前出错时main可能未被定义，不再重复报错
+        match self.symbol.get_func("main", comp_unit.end) {
+            Ok(main) => {
+                if !main.has_return || !main.params.is_empty() {
+                    let span = main.span;
+                    self.report(Diagnostic::error(
+                        ErrorCode::InvalidMain,
+                        "`main` must be declared as `int main()`".to_string(),
+                        span,
+                    ));
+                }
+            }
+            Err(err) => {
+                if self.errors.is_empty() {
+                    self.report(err);
+                }
+            }
+        }
+    }
+
+    fn gen_decl(&mut self, decl: &Decl) -> Result<(), Diagnostic> {
+        for def in &decl.defs {
+            if decl.is_const {
+                self.gen_const_def(decl.ty, def)?;
+            } else {
+                self.gen_var_def(decl.ty, def)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn gen_const_def(&mut self, ty: BType, def: &VarDef) -> Result<(), Diagnostic> {
+        let name = def.name.as_str();
+        // 形状
+        let mut shape: Vec<i32> = Vec::new();
+        for dim in &def.dims {
+            self.is_folding_const = true;
+            let dimension = self.gen_const_exp(dim, true)?;
+            self.is_folding_const = false;
+            if dimension < 0 {
+                return self.error(ErrorCode::InvalidArray, dim.span, "array size is negative");
+            }
+            shape.push(dimension);
+        }
+        // 语法分析保证常量一定有初始值
+        let init = def.init.as_ref().unwrap();
+        // 逻辑处理，分为全局和局部
+        if self.symbol.is_global() {
+            let init_val = self.gen_global_init(init, true, ty, &shape)?;
+            let value = match init_val {
+                Constant::Int(val) => Value::Int(val),
+                Constant::Float(val) => Value::float(val),
+                _ => Value::Undef,
+            };
+            let reg = self.add_global(name, true, ty, &shape, init_val);
+            self.symbol.insert_var(
+                Variable {
+                    name: name.to_string(),
+                    reg,
+                    is_const: true,
+                    ty,
+                    shape,
+                    value,
+                },
+                def.name_span,
+            )?;
+        } else if shape.is_empty() {
+            let exp = self.init_exp(init)?;
+            self.is_folding_const = true;
+            let var = self.gen_typed_exp(exp, true, ty)?;
+            let init_val = self.const_value(Some(var), exp.span)?;
+            self.is_folding_const = false;
+            let reg = self.add_alloca(Self::ir_type(ty));
+            self.symbol.insert_var(
+                Variable {
+                    name: name.to_string(),
+                    reg,
+                    is_const: true,
+                    ty,
+                    shape,
+                    value: init_val,
+                },
+                def.name_span,
+            )?;
+            self.add_store(init_val, reg);
+        } else {
+            let reg = self.add_alloca(Type::from_shape(&shape, Self::ir_type(ty)));
+            self.symbol.insert_var(
+                Variable {
+                    name: name.to_string(),
+                    reg,
+                    is_const: true,
+                    ty,
+                    shape: shape.clone(),
+                    value: Value::Undef,
+                },
+                def.name_span,
+            )?;
+            self.gen_local_init(init, true, vec![], &shape)?;
+        }
+        Ok(())
+    }
+
+    // 全局变量的初始值，shape是剩余的形状
+    fn gen_global_init(
+        &mut self,
+        init: &InitVal,
+        is_const: bool,
+        ty: BType,
+        shape: &[i32],
+    ) -> Result<Constant, Diagnostic> {
+        if shape.is_empty() {
+            let exp = self.init_exp(init)?;
+            let var = self.gen_typed_exp(exp, is_const, ty)?;
+            return Ok(match self.const_value(Some(var), exp.span)? {
+                Value::Float(bits) => Constant::Float(f32::from_bits(bits)),
+                val => Constant::Int(val.as_int().unwrap()),
+            });
+        }
+        let vals = self.init_list(init)?;
+        if vals.is_empty() {
+            return Ok(Constant::Zero);
+        }
+        let mut elems = vec![];
+        for index in 0..shape[0] as usize {
+            elems.push(match vals.get(index) {
+                Some(val) => self.gen_global_init(val, is_const, ty, &shape[1..])?,
+                None => Constant::Zero,
+            });
+        }
+        if let Some(val) = vals.get(shape[0] as usize) {
+            return self.error(
+                ErrorCode::InvalidArray,
+                val.span(),
+                "excess elements in array initializer",
+            );
+        }
+        Ok(Constant::Array(elems))
+    }
+
+    // 局部数组逐个元素store，front是已确定的下标，back是剩余的形状
+    fn gen_local_init(
+        &mut self,
+        init: &InitVal,
+        is_const: bool,
+        front: Vec<i32>,
+        back: &[i32],
+    ) -> Result<(), Diagnostic> {
+        if back.is_empty() {
+            let exp = self.init_exp(init)?;
+            let name = self.symbol.get_current_val().name.clone();
+            let pos: Vec<Value> = front.iter().map(|item| Value::Int(*item)).collect();
+            let var = self.get_elem_pos(&name, pos, exp.span)?;
+            let val = self.gen_typed_exp(exp, is_const, var.ty)?.reg;
+            self.add_store(val, var.reg);
+        } else {
+            let vals = self.init_list(init)?;
+            let mut new_front = front.clone();
+            new_front.push(0);
+            for (index, val) in vals.iter().enumerate() {
+                if index as i32 >= back[0] {
+                    return self.error(
+                        ErrorCode::InvalidArray,
+                        val.span(),
+                        "excess elements in array initializer",
+                    );
+                }
+                *new_front.last_mut().unwrap() = index as i32;
+                self.gen_local_init(val, is_const, new_front.clone(), &back[1..])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn gen_var_def(&mut self, ty: BType, def: &VarDef) -> Result<(), Diagnostic> {
+        let name = def.name.as_str();
+        // 形状
+        let mut shape: Vec<i32> = Vec::new();
+        for dim in &def.dims {
+            self.is_folding_const = true;
+            let dimension = self.gen_const_exp(dim, true)?;
+            self.is_folding_const = false;
+            if dimension < 0 {
+                return self.error(ErrorCode::InvalidArray, dim.span, "array size is negative");
+            }
+            shape.push(dimension);
+        }
+        // 分为全局和局部，全局变量没有初始值时为0
+        if self.symbol.is_global() {
+            let init_val = match &def.init {
+                Some(init) => self.gen_global_init(init, false, ty, &shape)?,
+                None => Constant::Zero,
+            };
+            let reg = self.add_global(name, false, ty, &shape, init_val);
+            self.symbol.insert_var(
+                Variable {
+                    name: name.to_string(),
+                    reg,
+                    is_const: false,
+                    ty,
+                    shape,
+                    value: Value::Undef,
+                },
+                def.name_span,
+            )?;
+        } else {
+            let reg = self.add_alloca(Type::from_shape(&shape, Self::ir_type(ty)));
+            self.symbol.insert_var(
+                Variable {
+                    name: name.to_string(),
+                    reg,
+                    is_const: false,
+                    ty,
+                    shape: shape.clone(),
+                    value: Value::Undef,
+                },
+                def.name_span,
+            )?;
+            if let Some(init) = &def.init {
+                self.gen_local_init(init, false, vec![], &shape)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn gen_func_def(&mut self, func: &FuncDef) -> Result<(), Diagnostic> {
+        // 初始化
+        self.assigner.reset();
+        self.blocks.clear();
+        // 计算完参数的形状再进入作用域添加符号
+        let mut shapes = vec![];
+        for param in &func.params {
+            let mut shape = vec![];
+            for dim in &param.dims {
+                shape.push(match dim {
+                    Some(dim) => self.gen_const_exp(dim, true)?,
+                    None => 0,
+                });
+            }
+            shapes.push((param.ty, shape));
+        }
+        let params = shapes
+            .iter()
+            .enumerate()
+            .map(|(index, (ty, shape))| {
+                let ty = if shape.is_empty() {
+                    Self::ir_type(*ty)
+                } else {
+                    Type::from_shape(&shape[1..], Self::ir_type(*ty)).ptr()
+                };
+                (format!("p{}", index + 1), ty)
+            })
+            .collect();
+        let ret_ty = if func.has_return {
+            Self::ir_type(func.ty)
+        } else {
+            Type::Void
+        };
+        self.func = self
+            .module
+            .add_function(ir::Function::new(&func.name, ret_ty, params));
+        self.entry = self.func().new_block(None);
+        let entry = self.entry;
+        self.func().layout.push(entry);
+        self.assigner.go_next_block();
+        let first_block = self.assigner.get_current_block();
+        self.start_block(&first_block);
+        self.symbol.go_down();
+        // 处理形式参数
+        for (index, (param, (ty, shape))) in func.params.iter().zip(shapes.iter()).enumerate() {
+            let reg = if shape.is_empty() {
+                let reg = self.add_alloca(Self::ir_type(*ty));
+                self.add_store(Value::Param(index), reg);
+                reg
+            } else {
+                Value::Param(index)
+            };
+            self.symbol.insert_var(
+                Variable {
+                    name: param.name.clone(),
+                    reg,
+                    is_const: false,
+                    ty: *ty,
+                    shape: shape.clone(),
+                    value: Value::Undef,
+                },
+                param.span,
+            )?;
+        }
+        // 向符号表中插入函数
+        self.symbol.insert_func(
+            &func.name,
+            func.has_return,
+            func.ty,
+            &shapes,
+            func.name_span,
+        )?;
+        // 翻译
+        self.gen_func_block(&func.body);
+        let first_block = self.get_block(&first_block);
+        self.func()
+            .push_inst(entry, InstKind::Br(first_block), Type::Void, None);
+        let current = self.current;
+        if self.func().terminator(current).is_none() {
+            // main函数默认返回0，不必警告
+            if func.has_return && func.name != "main" {
+                self.warnings.push(Diagnostic::warning(
+                    ErrorCode::MissingReturn,
+                    format!("non-void function `{}` does not return a value", func.name),
+                    func.body.end,
+                ));
+            }
+            let ret_val = if func.has_return {
+                Some(Self::zero(func.ty))
+            } else {
+                None
+            };
+            self.add_inst(InstKind::Ret(ret_val), Type::Void, None);
+        }
+        Ok(())
+    }
+
+    fn gen_block(&mut self, block: &Block) {
+        self.symbol.go_down();
+        self.gen_block_items(&block.items);
+        self.symbol.go_up();
+    }
+
+    fn gen_func_block(&mut self, block: &Block) {
+        self.gen_block_items(&block.items);
+        self.symbol.go_up();
+    }
+
+    // 逐项翻译，出错时记录错误并继续翻译下一项
+    fn gen_block_items(&mut self, items: &[BlockItem]) {
+        for item in items {
+            let assigner = self.assigner.clone();
+            let res = match item {
+                BlockItem::Decl(decl) => self.gen_decl(decl),
+                BlockItem::Stmt(stmt) => self.gen_stmt(stmt),
+            };
+            if let Err(err) = res {
+                self.report(err);
+                self.assigner = assigner;
+                self.is_folding_const = false;
+            }
+        }
+    }
+
+    fn gen_stmt(&mut self, stmt: &Stmt) -> Result<(), Diagnostic> {
+        match &stmt.kind {
+            StmtKind::Return(value) => match value {
+                None => {
+                    if self.symbol.get_current_func().has_return {
+                        return self.error(
+                            ErrorCode::ReturnMismatch,
+                            stmt.span,
+                            "non-void function should return a value",
+                        );
+                    }
+                    self.add_inst(InstKind::Ret(None), Type::Void, None);
+                }
+                Some(exp) => {
+                    let ret_val = self.gen_value_exp(exp, false)?;
+                    if !self.symbol.get_current_func().has_return {
+                        return self.error(
+                            ErrorCode::ReturnMismatch,
+                            stmt.span,
+                            "void function should not return a value",
+                        );
+                    }
+                    if !ret_val.shape.is_empty() {
+                        return self.error(
+                            ErrorCode::ReturnMismatch,
+                            exp.span,
+                            "cannot return an array",
+                        );
+                    }
+                    let ty = self.symbol.get_current_func().ty;
+                    let ret_val = self.convert(ret_val, ty).reg;
+                    self.add_inst(InstKind::Ret(Some(ret_val)), Type::Void, None);
+                }
+            },
+            StmtKind::Expr(exp) => {
+                if let Some(exp) = exp {
+                    self.gen_add_exp(exp, false)?;
+                }
+            }
+            StmtKind::Block(block) => self.gen_block(block),
+            StmtKind::If(cond, then_stmt, else_stmt) => {
+                // 条件跳转，跳转到子块或下一块
+                let then_block = self.new_block();
+                let else_block = self.new_block();
+                self.gen_cond(cond, then_block, else_block)?;
+                let if_next_block = self.assigner.get_next_block();
+                let if_sub_block = self.assigner.get_sub_block();
+                self.name_block(then_block, &if_sub_block);
+                self.name_block(else_block, &if_next_block);
+                self.start_block(&if_sub_block);
+                self.assigner.go_sub_block();
+                // 翻译子块
+                self.gen_stmt(then_stmt)?;
+                // 跳转逻辑，跳转到下一块，分两种情况
+                self.assigner.go_parent_block();
+                self.assigner.go_next_block();
+                if let Some(else_stmt) = else_stmt {
+                    let else_next_block = self.assigner.get_next_block();
+                    self.add_br(&else_next_block);
+                    self.start_block(&if_next_block);
+                    let else_sub_block = self.assigner.get_sub_block();
+                    self.add_br(&else_sub_block);
+                    self.start_block(&else_sub_block);
+                    self.assigner.go_sub_block();
+                    self.gen_stmt(else_stmt)?;
+                    self.assigner.go_parent_block();
+                    self.assigner.go_next_block();
+                    self.add_br(&else_next_block);
+                    self.start_block(&else_next_block);
+                } else {
+                    self.add_br(&if_next_block);
+                    self.start_block(&if_next_block);
+                }
+            }
+            StmtKind::While(cond, body) => {
+                // 直接进入条件跳转控制块
+                let cond_block = self.assigner.get_next_block();
+                self.assigner.go_next_block();
+                self.assigner.new_while_block(&cond_block);
+                self.add_br(&cond_block);
+                self.start_block(&cond_block);
+                // 条件跳转，跳转到子块或与while同级的下一块
+                let body_block = self.new_block();
+                let exit_block = self.new_block();
+                self.gen_cond(cond, body_block, exit_block)?;
+                let sub_block = self.assigner.get_sub_block();
+                let next_block = self.assigner.get_next_block();
+                self.name_block(body_block, &sub_block);
+                self.name_block(exit_block, &next_block);
+                // 进入并翻译子块
+                self.start_block(&sub_block);
+                self.assigner.go_sub_block();
+                self.gen_stmt(body)?;
+                self.assigner.go_parent_block();
+                self.add_br(&cond_block);
+                // 进入与while同级的下一块
+                self.assigner.go_next_block();
+                self.start_block(&next_block);
+            }
+            StmtKind::For(init, cond, step, body) => {
+                // 初始化中的声明只在for中可见
+                self.symbol.go_down();
+                let res = self.gen_for(init.as_deref(), cond.as_ref(), step.as_ref(), body);
+                self.symbol.go_up();
+                res?;
+            }
+            StmtKind::DoWhile(body, cond) => {
+                // 子块的第一个位置留给条件跳转控制块，先进入第二个位置的循环体
+                self.assigner.go_next_block();
+                let cond_block = self.assigner.get_sub_block();
+                let next_block = self.assigner.get_next_block();
+                self.assigner.new_while_block(&cond_block);
+                self.assigner.go_sub_block();
+                self.assigner.go_next_block();
+                let sub_block = self.assigner.get_current_block();
+                self.add_br(&sub_block);
+                self.start_block(&sub_block);
+                self.gen_stmt(body)?;
+                // 条件跳转，跳转回循环体或与do同级的下一块
+                self.add_br(&cond_block);
+                self.start_block(&cond_block);
+                let body_block = self.get_block(&sub_block);
+                let exit_block = self.get_block(&next_block);
+                self.gen_cond(cond, body_block, exit_block)?;
+                self.assigner.go_parent_block();
+                // 进入与do同级的下一块
+                self.assigner.go_next_block();
+                self.start_block(&next_block);
+            }
+            StmtKind::Switch(exp, cases) => self.gen_switch(exp, cases)?,
+            StmtKind::Break => {
+                if !self.assigner.is_in_while() {
+                    return self.error(
+                        ErrorCode::LoopControl,
+                        stmt.span,
+                        "`break` statement not in loop or switch statement",
+                    );
+                }
+                // 直接进入与while同级的下一块
+                let break_block = self.assigner.get_break_block();
+                // 之后不可达的代码由add_inst放入新的无名块，不能占用之后语句的标号
+                self.add_br(&break_block);
+            }
+            StmtKind::Continue => {
+                if !self.assigner.is_in_loop() {
+                    return self.error(
+                        ErrorCode::LoopControl,
+                        stmt.span,
+                        "`continue` statement not in loop statement",
+                    );
+                }
+                // 直接进入条件跳转控制块
+                let continue_block = self.assigner.get_continue_block();
+                // 之后不可达的代码由add_inst放入新的无名块，不能占用之后语句的标号
+                self.add_br(&continue_block);
+            }
+        }
+        Ok(())
+    }
+
+    fn gen_for(
+        &mut self,
+        init: Option<&BlockItem>,
+        cond: Option<&Expr>,
+        step: Option<&Expr>,
+        body: &Stmt,
+    ) -> Result<(), Diagnostic> {
+        match init {
+            Some(BlockItem::Decl(decl)) => self.gen_decl(decl)?,
+            Some(BlockItem::Stmt(stmt)) => self.gen_stmt(stmt)?,
+            None => {}
+        }
+        // 直接进入条件跳转控制块
+        let cond_block = self.assigner.get_next_block();
+        self.assigner.go_next_block();
+        self.add_br(&cond_block);
+        self.start_block(&cond_block);
+        // 条件跳转，省略条件时直接进入循环体
+        let body_block = self.new_block();
+        let exit_block = self.new_block();
+        match cond {
+            Some(cond) => self.gen_cond(cond, body_block, exit_block)?,
+            None => {
+                self.add_inst(InstKind::Br(body_block), Type::Void, None);
+            }
+        }
+        // 子块的第一个位置留给步进块，continue跳转到步进块
+        let step_block = self.assigner.get_sub_block();
+        let next_block = self.assigner.get_next_block();
+        self.name_block(exit_block, &next_block);
+        self.assigner.new_while_block(&step_block);
+        self.assigner.go_sub_block();
+        self.assigner.go_next_block();
+        let sub_block = self.assigner.get_current_block();
+        self.name_block(body_block, &sub_block);
+        self.start_block(&sub_block);
+        self.gen_stmt(body)?;
+        // 步进之后回到条件跳转控制块
+        self.add_br(&step_block);
+        self.start_block(&step_block);
+        if let Some(step) = step {
+            self.gen_add_exp(step, false)?;
+        }
+        self.add_br(&cond_block);
+        self.assigner.go_parent_block();
+        // 进入与for同级的下一块
+        self.assigner.go_next_block();
+        self.start_block(&next_block);
+        Ok(())
+    }
+
+    fn gen_switch(&mut self, exp: &Expr, cases: &[Case]) -> Result<(), Diagnostic> {
+        let val = self.gen_value_exp(exp, false)?;
+        if val.ty != BType::Int || !val.shape.is_empty() {
+            return self.error(
+                ErrorCode::InvalidOperand,
+                exp.span,
+                "switch quantity is not an integer",
+            );
+        }
+        // 标号的值是互不相同的整数常量，default最多一个
+        let mut values: Vec<Option<i32>> = vec![];
+        for case in cases {
+            let value = match &case.value {
+                Some(value) => {
+                    self.is_folding_const = true;
+                    let var = self.gen_add_exp(value, true)?;
+                    let res = self.const_value(var, value.span)?;
+                    self.is_folding_const = false;
+                    match res {
+                        Value::Int(val) => Some(val),
+                        _ => {
+                            return self.error(
+                                ErrorCode::InvalidCase,
+                                value.span,
+                                "case label does not reduce to an integer constant",
+                            )
+                        }
+                    }
+                }
+                None => None,
+            };
+            if values.contains(&value) {
+                let message = match value {
+                    Some(val) => format!("duplicate case value `{}`", val),
+                    None => "multiple default labels in one switch".to_string(),
+                };
+                return self.error(ErrorCode::InvalidCase, case.span, &message);
+            }
+            values.push(value);
+        }
+        // 依次比较，比较失败时进入同级的下一块，都不相等时跳转到default或与switch同级的下一块
+        let case_blocks: Vec<BlockId> = cases.iter().map(|_| self.new_block()).collect();
+        let exit_block = self.new_block();
+        let mut default_block = exit_block;
+        for (value, block) in values.iter().zip(&case_blocks) {
+            match value {
+                Some(value) => {
+                    let next_block = self.assigner.get_next_block();
+                    self.assigner.go_next_block();
+                    let next = self.get_block(&next_block);
+                    let mut rhs = Variable::new();
+                    rhs.reg = Value::Int(*value);
+                    let cond = self.gen_compare(CmpOp::Eq, val.clone(), rhs);
+                    self.add_cond_br(cond, *block, next);
+                    self.start_block(&next_block);
+                }
+                None => default_block = *block,
+            }
+        }
+        self.add_inst(InstKind::Br(default_block), Type::Void, None);
+        let next_block = self.assigner.get_next_block();
+        self.name_block(exit_block, &next_block);
+        self.assigner.new_switch_block();
+        // 标号依次占用子块的位置，标号后的语句在更深一层，没有break时进入下一个标号
+        self.symbol.go_down();
+        self.assigner.go_sub_block();
+        for (index, (case, block)) in cases.iter().zip(case_blocks).enumerate() {
+            if index > 0 {
+                self.assigner.go_next_block();
+            }
+            let case_block = self.assigner.get_sub_block();
+            self.name_block(block, &case_block);
+            if index > 0 {
+                self.add_br(&case_block);
+            }
+            self.start_block(&case_block);
+            self.assigner.go_sub_block();
+            self.gen_block_items(&case.items);
+            self.assigner.go_parent_block();
+        }
+        self.symbol.go_up();
+        self.assigner.go_parent_block();
+        // 进入与switch同级的下一块
+        self.assigner.go_next_block();
+        self.add_br(&next_block);
+        self.start_block(&next_block);
+        Ok(())
+    }
+
+    fn gen_lval(&mut self, lval: &LVal) -> Result<Variable, Diagnostic> {
+        let name = lval.name.as_str();
+        if self.symbol.get_var(name, lval.name_span)?.is_const {
+            return self.error(
+                ErrorCode::AssignToConst,
+                lval.name_span,
+                "cannot assign to const variable",
+            );
+        }
+        let mut pos: Vec<Value> = vec![];
+        for index in &lval.indices {
+            pos.push(self.gen_value_exp(index, false)?.reg);
+        }
+        if pos.len() != self.symbol.get_var(name, lval.name_span)?.shape.len() {
+            return self.error(
+                ErrorCode::InvalidOperand,
+                lval.span,
+                "array type is not assignable",
+            );
+        }
+        self.get_elem_pos(name, pos, lval.name_span)
+    }
+
+    // 算术表达式，void函数调用的结果为None
+    fn gen_add_exp(&mut self, exp: &Expr, is_const: bool) -> Result<Option<Variable>, Diagnostic> {
+        match &exp.kind {
+            ExprKind::Number(num) => {
+                let mut res = Variable::new();
+                res.reg = Value::Int(*num);
+                Ok(Some(res))
+            }
+            ExprKind::Float(num) => {
+                let mut res = Variable::new();
+                res.reg = Value::float(*num);
+                res.ty = BType::Float;
+                Ok(Some(res))
+            }
+            ExprKind::Unary(UnaryOp::Plus, operand) => self.gen_add_exp(operand, is_const),
+            ExprKind::Unary(UnaryOp::Minus, operand) => {
+                let res = self.gen_add_exp(operand, is_const)?;
+                let mut res = self.expect_value(res, operand.span)?;
+                if !res.shape.is_empty() {
+                    return self.error(
+                        ErrorCode::InvalidOperand,
+                        operand.span,
+                        "invalid argument type to unary expression",
+                    );
+                }
+                // 全局域与常量中直接求值，浮点数取负为-0.0减去操作数
+                if self.symbol.is_global() || self.is_folding_const {
+                    res.reg = match self.const_value(Some(res.clone()), operand.span)? {
+                        Value::Int(val) => Value::Int(val.wrapping_neg()),
+                        val => Value::float(-val.as_float().unwrap()),
+                    };
+                } else {
+                    let new_reg = self.assigner.new_var();
+                    let kind = match res.ty {
+                        BType::Int => InstKind::Binary(BinOp::Sub, Value::Int(0), res.reg),
+                        BType::Float => InstKind::Binary(BinOp::FSub, Value::float(-0.0), res.reg),
+                    };
+                    res.reg = self.add_inst(kind, Self::ir_type(res.ty), Some(new_reg));
+                }
+                Ok(Some(res))
+            }
+            ExprKind::Unary(UnaryOp::BitNot, operand) => {
+                let res = self.gen_add_exp(operand, is_const)?;
+                let mut res = self.expect_value(res, operand.span)?;
+                if !res.shape.is_empty() || res.ty == BType::Float {
+                    return self.error(
+                        ErrorCode::InvalidOperand,
+                        operand.span,
+                        "invalid argument type to unary expression",
+                    );
+                }
+                // 按位取反即与-1异或
+                if self.symbol.is_global() || self.is_folding_const {
+                    let val = self.const_value(Some(res.clone()), operand.span)?;
+                    res.reg = Value::Int(!val.as_int().unwrap());
+                } else {
+                    let new_reg = self.assigner.new_var();
+                    res.reg = self.add_inst(
+                        InstKind::Binary(BinOp::Xor, res.reg, Value::Int(-1)),
+                        Type::I32,
+                        Some(new_reg),
+                    );
+                }
+                Ok(Some(res))
+            }
+            ExprKind::Unary(UnaryOp::Not, operand) => {
+                let res = self.gen_add_exp(operand, is_const)?;
+                let res = self.expect_value(res, operand.span)?;
+                if !res.shape.is_empty() {
+                    return self.error(
+                        ErrorCode::InvalidOperand,
+                        operand.span,
+                        "invalid argument type to unary expression",
+                    );
+                }
+                if self.symbol.is_global() || self.is_folding_const {
+                    let val = self.const_value(Some(res), operand.span)?;
+                    let mut res = Variable::new();
+                    res.reg = Value::Int(!Self::is_true(val) as i32);
+                    return Ok(Some(res));
+                }
+                // 与0比较后转换为i32
+                let cond = self.gen_compare(CmpOp::Eq, res.clone(), Self::zero_of(&res));
+                let var = self.assigner.new_var();
+                let mut res = Variable::new();
+                res.reg = self.add_inst(InstKind::Zext(cond), Type::I32, Some(var));
+                Ok(Some(res))
+            }
+            ExprKind::Call {
+                name,
+                name_span,
+                args,
+            } => {
+                // 全局域不能调用函数
+                if self.symbol.is_global() {
+                    return self.error(
+                        ErrorCode::NotConstant,
+                        *name_span,
+                        "function call in global scope",
+                    );
+                }
+                self.symbol.get_func(name, *name_span)?;
+                // 收集参数
+                let params = self.gen_func_rparams(args)?;
+                // 调用并返回
+                let func = self.symbol.get_func(name, *name_span)?;
+                func.check_call(&params, exp.span)?;
+                let (has_return, ty) = (func.has_return, func.ty);
+                let types: Vec<BType> = func.params.iter().map(|(ty, _)| *ty).collect();
+                let callee = self.module.get_function(name).unwrap();
+                let args = params
+                    .into_iter()
+                    .zip(types)
+                    .map(|(param, ty)| self.convert(param, ty).reg)
+                    .collect();
+                if has_return {
+                    let mut res = Variable::new();
+                    let var = self.assigner.new_var();
+                    res.reg =
+                        self.add_inst(InstKind::Call(callee, args), Self::ir_type(ty), Some(var));
+                    res.ty = ty;
+                    Ok(Some(res))
+                } else {
+                    self.add_inst(InstKind::Call(callee, args), Type::Void, None);
+                    Ok(None)
+                }
+            }
+            ExprKind::LVal(lval) => {
+                let name = lval.name.as_str();
+                let var = self.symbol.get_var(name, lval.name_span)?;
+                if is_const && !var.is_const {
+                    return self.error(
+                        ErrorCode::NotConstant,
+                        lval.name_span,
+                        "expression is not a compile-time constant",
+                    );
+                }
+                // 分为全局和局部
+                if self.symbol.is_global() {
+                    if !var.is_const || !var.shape.is_empty() {
+                        return self.error(
+                            ErrorCode::NotConstant,
+                            lval.name_span,
+                            "initializer element is not a compile-time constant",
+                        );
+                    }
+                    let mut res = Variable::new();
+                    res.reg = var.value;
+                    res.ty = var.ty;
+                    Ok(Some(res))
+                } else {
+                    let mut pos: Vec<Value> = vec![];
+                    for index in &lval.indices {
+                        pos.push(self.gen_value_exp(index, is_const)?.reg);
+                    }
+                    let mut var = self.get_elem_pos(name, pos, lval.span)?;
+                    if var.shape.is_empty() && !self.is_folding_const {
+                        var = self.load(&var);
+                    }
+                    if self.is_folding_const {
+                        var.reg = var.value;
+                    }
+                    Ok(Some(var))
+                }
+            }
+            ExprKind::Binary(BinaryOp::And | BinaryOp::Or, _, _)
+                if !self.symbol.is_global() && !self.is_folding_const =>
+            {
+                Ok(Some(self.gen_logic_value(exp)?))
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                let lhs_val = self.gen_add_exp(lhs, is_const)?;
+                let rhs_val = self.gen_add_exp(rhs, is_const)?;
+                self.check_shift_count(*op, rhs);
+                self.calc_binary_exp(*op, lhs_val, lhs.span, rhs_val, rhs.span)
+            }
+            ExprKind::Conditional(cond, then_exp, else_exp) => {
+                if self.symbol.is_global() || self.is_folding_const {
+                    self.fold_conditional(cond, then_exp, else_exp, is_const)
+                } else {
+                    self.gen_conditional(cond, then_exp, else_exp)
+                }
+            }
+            ExprKind::Assign(op, lval, rhs) => {
+                self.expect_runtime(exp.span)?;
+                // 左值的地址只计算一次，复合赋值先读出原来的值
+                let lhs = self.gen_lval(lval)?;
+                let res = match op {
+                    None => self.gen_typed_exp(rhs, false, lhs.ty)?,
+                    Some(op) => {
+                        let old = self.load(&lhs);
+                        let rhs_val = self.gen_add_exp(rhs, false)?;
+                        self.check_shift_count(*op, rhs);
+                        let res =
+                            self.calc_binary_exp(*op, Some(old), lval.span, rhs_val, rhs.span)?;
+                        self.convert(res.unwrap(), lhs.ty)
+                    }
+                };
+                self.add_store(res.reg, lhs.reg);
+                Ok(Some(res))
+            }
+            ExprKind::IncDec(op, lval) => {
+                self.expect_runtime(exp.span)?;
+                let lhs = self.gen_lval(lval)?;
+                let old = self.load(&lhs);
+                let mut one = Variable::new();
+                one.reg = Value::Int(1);
+                let res = self.calc_binary_exp(
+                    op.binary_op(),
+                    Some(old.clone()),
+                    lval.span,
+                    Some(one),
+                    lval.span,
+                )?;
+                let res = self.convert(res.unwrap(), lhs.ty);
+                self.add_store(res.reg, lhs.reg);
+                Ok(Some(if op.is_prefix() { res } else { old }))
+            }
+            ExprKind::Comma(lhs, rhs) => {
+                self.expect_runtime(exp.span)?;
+                self.gen_add_exp(lhs, false)?;
+                self.gen_add_exp(rhs, false)
+            }
+        }
+    }
+
+    // 运行时的移位量是常量表达式时检查范围，超出范围时结果未定义
+    fn check_shift_count(&mut self, op: BinaryOp, rhs: &Expr) {
+        if !Self::is_shift(op) || self.symbol.is_global() || self.is_folding_const {
+            return;
+        }
+        if let Some(Value::Int(count)) = self.try_fold(rhs) {
+            if !(0..32).contains(&count) {
+                self.warnings.push(Diagnostic::warning(
+                    ErrorCode::UndefinedShift,
+                    format!(
+                        "shift count {} is out of range, the result is undefined",
+                        count
+                    ),
+                    rhs.span,
+                ));
+            }
+        }
+    }
+
+    // 按常量求值只含字面量与标量的表达式，不是常量时为None，不生成指令也不报告错误
+    fn try_fold(&mut self, exp: &Expr) -> Option<Value> {
+        if !Self::is_foldable(exp) {
+            return None;
+        }
+        self.is_folding_const = true;
+        let res = self.gen_add_exp(exp, true);
+        self.is_folding_const = false;
+        self.const_value(res.ok()?, exp.span).ok()
+    }
+
+    // 数组元素与函数调用在求值时会生成指令
+    fn is_foldable(exp: &Expr) -> bool {
+        match &exp.kind {
+            ExprKind::Number(_) | ExprKind::Float(_) => true,
+            ExprKind::LVal(lval) => lval.indices.is_empty(),
+            ExprKind::Unary(_, operand) => Self::is_foldable(operand),
+            ExprKind::Binary(_, lhs, rhs) => Self::is_foldable(lhs) && Self::is_foldable(rhs),
+            ExprKind::Conditional(cond, then_exp, else_exp) => {
+                Self::is_foldable(cond)
+                    && Self::is_foldable(then_exp)
+                    && Self::is_foldable(else_exp)
+            }
+            ExprKind::Call { .. }
+            | ExprKind::Assign(..)
+            | ExprKind::IncDec(..)
+            | ExprKind::Comma(..) => false,
+        }
+    }
+
+    // 有副作用的表达式不能出现在全局域与常量中
+    fn expect_runtime(&self, span: Span) -> Result<(), Diagnostic> {
+        if self.symbol.is_global() || self.is_folding_const {
+            return self.error(
+                ErrorCode::NotConstant,
+                span,
+                "expression is not a compile-time constant",
+            );
+        }
+        Ok(())
+    }
+
+    // 读出标量左值的值
+    fn load(&mut self, var: &Variable) -> Variable {
+        let mut res = var.clone();
+        let new_reg = self.assigner.new_var();
+        res.reg = self.add_inst(
+            InstKind::Load(var.reg),
+            Self::ir_type(var.ty),
+            Some(new_reg),
+        );
+        res
+    }
+
+    fn gen_func_rparams(&mut self, args: &[Expr]) -> Result<Vec<Variable>, Diagnostic> {
+        let mut res = vec![];
+        for arg in args {
+            let mut var = self.gen_value_exp(arg, false)?;
+            if !var.shape.is_empty() && var.shape[0] != 0 {
+                let new_reg = self.assigner.new_var();
+                let ty = Type::from_shape(&var.shape[1..], Self::ir_type(var.ty)).ptr();
+                var.reg = self.add_inst(
+                    InstKind::Gep(var.reg, vec![Value::Int(0), Value::Int(0)]),
+                    ty,
+                    Some(new_reg),
+                );
+            }
+            res.push(var);
+        }
+        Ok(res)
+    }
+
+    // 二元运算的两个操作数都必须是标量
+    fn binary_operands(
+        &self,
+        op: BinaryOp,
+        lhs: Option<Variable>,
+        lhs_span: Span,
+        rhs: Option<Variable>,
+        rhs_span: Span,
+    ) -> Result<(Variable, Variable), Diagnostic> {
+        let lhs = self.expect_value(lhs, lhs_span)?;
+        let rhs = self.expect_value(rhs, rhs_span)?;
+        if !lhs.shape.is_empty() || !rhs.shape.is_empty() {
+            return self.error(
+                ErrorCode::InvalidOperand,
+                lhs_span.to(rhs_span),
+                format!("invalid operands to binary expression {}", op).as_str(),
+            );
+        }
+        Ok((lhs, rhs))
+    }
+
+    // 只能作用于整数的运算
+    fn is_int_op(op: BinaryOp) -> bool {
+        matches!(
+            op,
+            BinaryOp::Mod
+                | BinaryOp::Shl
+                | BinaryOp::Shr
+                | BinaryOp::BitAnd
+                | BinaryOp::BitXor
+                | BinaryOp::BitOr
+        )
+    }
+
+    fn is_shift(op: BinaryOp) -> bool {
+        matches!(op, BinaryOp::Shl | BinaryOp::Shr)
+    }
+
+    fn cmp_op(op: BinaryOp) -> Option<CmpOp> {
+        match op {
+            BinaryOp::Lt => Some(CmpOp::Slt),
+            BinaryOp::Gt => Some(CmpOp::Sgt),
+            BinaryOp::Le => Some(CmpOp::Sle),
+            BinaryOp::Ge => Some(CmpOp::Sge),
+            BinaryOp::Eq => Some(CmpOp::Eq),
+            BinaryOp::Ne => Some(CmpOp::Ne),
+            _ => None,
+        }
+    }
+
+    // 比较两个标量，有一个是浮点数时都转换为浮点数，结果为i1
+    fn gen_compare(&mut self, op: CmpOp, lhs: Variable, rhs: Variable) -> Value {
+        if lhs.ty == BType::Float || rhs.ty == BType::Float {
+            let lhs = self.convert(lhs, BType::Float).reg;
+            let rhs = self.convert(rhs, BType::Float).reg;
+            let var = self.assigner.new_var();
+            self.add_inst(InstKind::Fcmp(op, lhs, rhs), Type::I1, Some(var))
+        } else {
+            let var = self.assigner.new_var();
+            self.add_inst(InstKind::Icmp(op, lhs.reg, rhs.reg), Type::I1, Some(var))
+        }
+    }
+
+    // 计算二元表达式，全局域或常量中直接求值，比较结果为i32
+    // 有一个操作数是浮点数时按浮点数计算，浮点数不能取模与做位运算
+    fn calc_binary_exp(
+        &mut self,
+        op: BinaryOp,
+        lhs: Option<Variable>,
+        lhs_span: Span,
+        rhs: Option<Variable>,
+        rhs_span: Span,
+    ) -> Result<Option<Variable>, Diagnostic> {
+        let (lhs, rhs) = self.binary_operands(op, lhs, lhs_span, rhs, rhs_span)?;
+        let ty = if lhs.ty == BType::Float || rhs.ty == BType::Float {
+            BType::Float
+        } else {
+            BType::Int
+        };
+        if ty == BType::Float && Self::is_int_op(op) {
+            return self.error(
+                ErrorCode::InvalidOperand,
+                lhs_span.to(rhs_span),
+                format!("invalid operands to binary expression {}", op).as_str(),
+            );
+        }
+        let mut res = Variable::new();
+        if self.symbol.is_global() || self.is_folding_const {
+            let lhs = self.const_value(Some(lhs), lhs_span)?;
+            let rhs = self.const_value(Some(rhs), rhs_span)?;
+            res.reg = match (lhs, rhs) {
+                (Value::Int(lhs), Value::Int(rhs)) => {
+                    if rhs == 0 && (op == BinaryOp::Div || op == BinaryOp::Mod) {
+                        return self.error(
+                            ErrorCode::DivisionByZero,
+                            rhs_span,
+                            "division by zero in constant expression",
+                        );
+                    }
+                    // 与C一样，移位量为负数或不小于32时结果未定义
+                    if Self::is_shift(op) && !(0..32).contains(&rhs) {
+                        return self.error(
+                            ErrorCode::ShiftOutOfRange,
+                            rhs_span,
+                            format!("shift count {} is out of range in constant expression", rhs)
+                                .as_str(),
+                        );
+                    }
+                    Value::Int(match op {
+                        BinaryOp::Mul => lhs.wrapping_mul(rhs),
+                        BinaryOp::Div => lhs.wrapping_div(rhs),
+                        BinaryOp::Mod => lhs.wrapping_rem(rhs),
+                        BinaryOp::Add => lhs.wrapping_add(rhs),
+                        BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                        BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+                        BinaryOp::Shr => lhs.wrapping_shr(rhs as u32),
+                        BinaryOp::BitAnd => lhs & rhs,
+                        BinaryOp::BitXor => lhs ^ rhs,
+                        BinaryOp::BitOr => lhs | rhs,
+                        BinaryOp::Lt => (lhs < rhs) as i32,
+                        BinaryOp::Gt => (lhs > rhs) as i32,
+                        BinaryOp::Le => (lhs <= rhs) as i32,
+                        BinaryOp::Ge => (lhs >= rhs) as i32,
+                        BinaryOp::Eq => (lhs == rhs) as i32,
+                        BinaryOp::Ne => (lhs != rhs) as i32,
+                        BinaryOp::And => (lhs != 0 && rhs != 0) as i32,
+                        BinaryOp::Or => (lhs != 0 || rhs != 0) as i32,
+                    })
+                }
+                (lhs, rhs) => {
+                    let to_float = |val: Value| match val {
+                        Value::Int(val) => val as f32,
+                        val => val.as_float().unwrap(),
+                    };
+                    let (lhs, rhs) = (to_float(lhs), to_float(rhs));
+                    match op {
+                        BinaryOp::Mul => Value::float(lhs * rhs),
+                        BinaryOp::Div => Value::float(lhs / rhs),
+                        BinaryOp::Add => Value::float(lhs + rhs),
+                        BinaryOp::Sub => Value::float(lhs - rhs),
+                        BinaryOp::Lt => Value::Int((lhs < rhs) as i32),
+                        BinaryOp::Gt => Value::Int((lhs > rhs) as i32),
+                        BinaryOp::Le => Value::Int((lhs <= rhs) as i32),
+                        BinaryOp::Ge => Value::Int((lhs >= rhs) as i32),
+                        BinaryOp::Eq => Value::Int((lhs == rhs) as i32),
+                        BinaryOp::Ne => Value::Int((lhs != rhs) as i32),
+                        BinaryOp::And => Value::Int((lhs != 0.0 && rhs != 0.0) as i32),
+                        BinaryOp::Or => Value::Int((lhs != 0.0 || rhs != 0.0) as i32),
+                        _ => unreachable!(),
+                    }
+                }
+            };
+            if let Value::Float(_) = res.reg {
+                res.ty = BType::Float;
+            }
+        } else if let Some(cond) = Self::cmp_op(op) {
+            let cmp = self.gen_compare(cond, lhs, rhs);
+            let var = self.assigner.new_var();
+            res.reg = self.add_inst(InstKind::Zext(cmp), Type::I32, Some(var));
+        } else {
+            let lhs = self.convert(lhs, ty).reg;
+            let rhs = self.convert(rhs, ty).reg;
+            let reg = self.assigner.new_var();
+            let ins = match (op, ty) {
+                (BinaryOp::Mul, BType::Int) => BinOp::Mul,
+                (BinaryOp::Div, BType::Int) => BinOp::SDiv,
+                (BinaryOp::Mod, BType::Int) => BinOp::SRem,
+                (BinaryOp::Add, BType::Int) => BinOp::Add,
+                (BinaryOp::Sub, BType::Int) => BinOp::Sub,
+                (BinaryOp::Shl, BType::Int) => BinOp::Shl,
+                (BinaryOp::Shr, BType::Int) => BinOp::AShr,
+                (BinaryOp::BitAnd, BType::Int) => BinOp::And,
+                (BinaryOp::BitXor, BType::Int) => BinOp::Xor,
+                (BinaryOp::BitOr, BType::Int) => BinOp::Or,
+                (BinaryOp::Mul, BType::Float) => BinOp::FMul,
+                (BinaryOp::Div, BType::Float) => BinOp::FDiv,
+                (BinaryOp::Add, BType::Float) => BinOp::FAdd,
+                (BinaryOp::Sub, BType::Float) => BinOp::FSub,
+                _ => unreachable!(),
+            };
+            res.reg = self.add_inst(
+                InstKind::Binary(ins, lhs, rhs),
+                Self::ir_type(ty),
+                Some(reg),
+            );
+            res.ty = ty;
+        }
+        Ok(Some(res))
+    }
+
+    // 将左结合的同种运算展开为操作数序列
+    fn flatten(exp: &Expr, target: BinaryOp) -> Vec<&Expr> {
+        match &exp.kind {
+            ExprKind::Binary(op, lhs, rhs) if *op == target => {
+                let mut res = Self::flatten(lhs, target);
+                res.push(rhs);
+                res
+            }
+            _ => vec![exp],
+        }
+    }
+
+    // 条件翻译为跳转，为真时跳转到true_block，否则跳转到false_block
+    fn gen_cond(
+        &mut self,
+        exp: &Expr,
+        true_block: BlockId,
+        false_block: BlockId,
+    ) -> Result<(), Diagnostic> {
+        match &exp.kind {
+            ExprKind::Binary(op @ (BinaryOp::And | BinaryOp::Or), _, _) => {
+                let operands = Self::flatten(exp, *op);
+                let (last, front) = operands.split_last().unwrap();
+                for item in front {
+                    // 短路求值，需要计算下一个操作数时才进入新的块
+                    let next_block = self.assigner.get_next_block();
+                    self.assigner.go_next_block();
+                    let next = self.get_block(&next_block);
+                    if *op == BinaryOp::And {
+                        self.gen_cond(item, next, false_block)?;
+                    } else {
+                        self.gen_cond(item, true_block, next)?;
+                    }
+                    self.start_block(&next_block);
+                }
+                self.gen_cond(last, true_block, false_block)
+            }
+            ExprKind::Unary(UnaryOp::Not, operand) => {
+                self.gen_cond(operand, false_block, true_block)
+            }
+            ExprKind::Binary(op, lhs, rhs) if Self::cmp_op(*op).is_some() => {
+                let lhs_val = self.gen_add_exp(lhs, false)?;
+                let rhs_val = self.gen_add_exp(rhs, false)?;
+                let (lhs_val, rhs_val) =
+                    self.binary_operands(*op, lhs_val, lhs.span, rhs_val, rhs.span)?;
+                let cond = self.gen_compare(Self::cmp_op(*op).unwrap(), lhs_val, rhs_val);
+                self.add_cond_br(cond, true_block, false_block);
+                Ok(())
+            }
+            _ => {
+                let val = self.gen_value_exp(exp, false)?;
+                if !val.shape.is_empty() {
+                    return self.error(
+                        ErrorCode::InvalidOperand,
+                        exp.span,
+                        "array type is not a valid condition",
+                    );
+                }
+                let zero = Self::zero_of(&val);
+                let cond = self.gen_compare(CmpOp::Ne, val, zero);
+                self.add_cond_br(cond, true_block, false_block);
+                Ok(())
+            }
+        }
+    }
+
+    // 常量的真假
+    fn is_true(val: Value) -> bool {
+        match val {
+            Value::Int(val) => val != 0,
+            val => val.as_float().unwrap() != 0.0,
+        }
+    }
+
+    // 条件表达式的分支必须是标量，有一个是浮点数时结果为浮点数
+    fn conditional_operand(
+        &self,
+        var: Option<Variable>,
+        span: Span,
+    ) -> Result<Variable, Diagnostic> {
+        let var = self.expect_value(var, span)?;
+        if !var.shape.is_empty() {
+            return self.error(
+                ErrorCode::InvalidOperand,
+                span,
+                "invalid operand to conditional expression",
+            );
+        }
+        Ok(var)
+    }
+
+    // 全局域与常量中两个分支都必须是常量，按条件选取其中之一
+    fn fold_conditional(
+        &mut self,
+        cond: &Expr,
+        then_exp: &Expr,
+        else_exp: &Expr,
+        is_const: bool,
+    ) -> Result<Option<Variable>, Diagnostic> {
+        let cond_val = self.gen_add_exp(cond, is_const)?;
+        let cond_val = self.const_value(cond_val, cond.span)?;
+        let then_val = self.gen_add_exp(then_exp, is_const)?;
+        let then_val = self.conditional_operand(then_val, then_exp.span)?;
+        self.const_value(Some(then_val.clone()), then_exp.span)?;
+        let else_val = self.gen_add_exp(else_exp, is_const)?;
+        let else_val = self.conditional_operand(else_val, else_exp.span)?;
+        self.const_value(Some(else_val.clone()), else_exp.span)?;
+        let ty = if then_val.ty == BType::Float || else_val.ty == BType::Float {
+            BType::Float
+        } else {
+            BType::Int
+        };
+        let res = if Self::is_true(cond_val) {
+            then_val
+        } else {
+            else_val
+        };
+        Ok(Some(self.convert(res, ty)))
+    }
+
+    // 两个分支分别在各自的块中求值，转换为相同的类型后在出口块汇合
+    fn gen_conditional(
+        &mut self,
+        cond: &Expr,
+        then_exp: &Expr,
+        else_exp: &Expr,
+    ) -> Result<Option<Variable>, Diagnostic> {
+        let then_block = self.assigner.get_next_block();
+        self.assigner.go_next_block();
+        let else_block = self.assigner.get_next_block();
+        self.assigner.go_next_block();
+        let exit_block = self.assigner.get_next_block();
+        self.assigner.go_next_block();
+        let then_id = self.get_block(&then_block);
+        let else_id = self.get_block(&else_block);
+        self.gen_cond(cond, then_id, else_id)?;
+        self.start_block(&then_block);
+        let then_val = self.gen_add_exp(then_exp, false)?;
+        let then_val = self.conditional_operand(then_val, then_exp.span)?;
+        let then_end = self.current;
+        self.start_block(&else_block);
+        let else_val = self.gen_add_exp(else_exp, false)?;
+        let else_val = self.conditional_operand(else_val, else_exp.span)?;
+        let ty = if then_val.ty == BType::Float || else_val.ty == BType::Float {
+            BType::Float
+        } else {
+            BType::Int
+        };
+        // 类型转换放在各自分支的末尾
+        let else_val = self.convert(else_val, ty);
+        self.add_br(&exit_block);
+        let else_end = self.current;
+        self.current = then_end;
+        let then_val = self.convert(then_val, ty);
+        self.add_br(&exit_block);
+        self.start_block(&exit_block);
+        let mut res = Variable::new();
+        let var = self.assigner.new_var();
+        res.reg = self.add_inst(
+            InstKind::Phi(vec![(then_val.reg, then_end), (else_val.reg, else_end)]),
+            Self::ir_type(ty),
+            Some(var),
+        );
+        res.ty = ty;
+        Ok(Some(res))
+    }
+
+    // 逻辑表达式作为值时，真假两个分支在出口块汇合
+    fn gen_logic_value(&mut self, exp: &Expr) -> Result<Variable, Diagnostic> {
+        let true_block = self.assigner.get_next_block();
+        self.assigner.go_next_block();
+        let false_block = self.assigner.get_next_block();
+        self.assigner.go_next_block();
+        let exit_block = self.assigner.get_next_block();
+        self.assigner.go_next_block();
+        let true_id = self.get_block(&true_block);
+        let false_id = self.get_block(&false_block);
+        self.gen_cond(exp, true_id, false_id)?;
+        self.start_block(&true_block);
+        self.add_br(&exit_block);
+        self.start_block(&false_block);
+        self.add_br(&exit_block);
+        self.start_block(&exit_block);
+        let mut res = Variable::new();
+        let var = self.assigner.new_var();
+        res.reg = self.add_inst(
+            InstKind::Phi(vec![(Value::Int(1), true_id), (Value::Int(0), false_id)]),
+            Type::I32,
+            Some(var),
+        );
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{codes, compile_errors, compile_source};
+    use crate::{pass, Options};
+
+    // 未优化的IR与基准逐字节相同，输出有意改变时需要同时更新baseline.ll
+    #[test]
+    fn llvm_output_matches_baseline() {
+        let options = Options {
+            passes: pass::Options {
+                mem2reg: false,
+                sccp: false,
+                dce: false,
+            },
+            ..Options::default()
+        };
+        let output = compile_source(include_str!("../tests/baseline.sy"), &options);
+        let ir = String::from_utf8(output.code).unwrap();
+        assert!(
+            ir == include_str!("../tests/baseline.ll"),
+            "IR differs from tests/baseline.ll:\n{}",
+            ir
+        );
+    }
+
+    // 常量表达式中移位量超出范围是错误
+    #[test]
+    fn rejects_constant_shift_out_of_range() {
+        let errors = compile_errors(
+            "int g = 1 << 33; int main() { return 0; }",
+            &Options::default(),
+        );
+        assert_eq!(codes(&errors), ["E0213"]);
+    }
+
+    // 运行时的移位量是超出范围的常量时只警告
+    #[test]
+    fn warns_on_shift_count_out_of_range() {
+        let source = "const int S = 40;
+            int main() { int a = getint(); return (a << S) + (a >> -1) + (a >> 32) + (a << 31); }";
+        let output = compile_source(source, &Options::default());
+        assert_eq!(codes(&output.warnings), ["W0002", "W0002", "W0002"]);
+    }
+
+    // 移位量按常量折叠后检查，复合赋值同样检查
+    #[test]
+    fn warns_on_folded_shift_count() {
+        let source = "const int S = 40;
+            int main() { int a = getint(), b = 2; a = a << (0 - 1); a >>= S - 8; a <<= S - 9; return a << b; }";
+        let output = compile_source(source, &Options::default());
+        let messages: Vec<&str> = output
+            .warnings
+            .iter()
+            .map(|diag| diag.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "shift count -1 is out of range, the result is undefined",
+                "shift count 32 is out of range, the result is undefined",
+            ]
+        );
+    }
+}
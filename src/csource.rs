@@ -0,0 +1,1313 @@
+use std::collections::{HashMap, HashSet};
+
+use super::ast::{
+    BType, BinaryOp, Block, BlockItem, CompUnit, Decl, Expr, ExprKind, FuncDef, InitVal, Item,
+    LVal, Stmt, StmtKind, UnaryOp, VarDef,
+};
+use super::ir::Value;
+
+// 把通过语义检查的语法树翻译为可读的C99源代码，保留数组的各维与常量的声明
+// C要求数组的维数与全局变量的初始值是常量表达式，其中引用了常量的表达式折叠为数值
+// 浮点数字面量加上后缀f，运算与SysY一样按float进行
+// 与C关键字同名的标识符以及与函数同名的变量改用不重复的新名字
+// C中有符号溢出是未定义行为，整数的加、减、乘、取负与左移改为unsigned运算再转换回int，移位量只取低5位
+// C中二元运算与函数参数的求值顺序不确定，有副作用时按SysY从左到右的顺序先把操作数存入临时变量
+// 赋值的右侧修改了左值时同样用临时变量，按SysY先读出左值再求右侧的值
+
+const RUNTIME: [&str; 10] = [
+    "int getint(void);",
+    "int getch(void);",
+    "float getfloat(void);",
+    "int getarray(int a[]);",
+    "int getfarray(float a[]);",
+    "void putint(int a);",
+    "void putch(int a);",
+    "void putfloat(float a);",
+    "void putarray(int n, int a[]);",
+    "void putfarray(int n, float a[]);",
+];
+
+const C_KEYWORDS: [&str; 32] = [
+    "auto",
+    "case",
+    "char",
+    "default",
+    "do",
+    "double",
+    "enum",
+    "extern",
+    "float",
+    "for",
+    "goto",
+    "inline",
+    "long",
+    "register",
+    "restrict",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "struct",
+    "switch",
+    "typedef",
+    "union",
+    "unsigned",
+    "volatile",
+    "_Bool",
+    "_Complex",
+    "_Imaginary",
+    "bool",
+    "true",
+    "false",
+    "NULL",
+];
+
+pub fn emit(comp_unit: &CompUnit) -> String {
+    // 函数的返回值类型，void函数记为int
+    let mut funcs: HashMap<String, BType> = RUNTIME
+        .iter()
+        .map(|decl| {
+            let head = decl.split('(').next().unwrap();
+            let ty = if head.starts_with("float") {
+                BType::Float
+            } else {
+                BType::Int
+            };
+            (head.rsplit(' ').next().unwrap().to_string(), ty)
+        })
+        .collect();
+    let mut vars = HashSet::new();
+    for item in &comp_unit.items {
+        match item {
+            Item::Decl(decl) => decl_names(decl, &mut vars),
+            Item::FuncDef(func) => {
+                funcs.insert(func.name.clone(), func.ty);
+                for param in &func.params {
+                    vars.insert(param.name.clone());
+                }
+                item_names(&func.body.items, &mut vars);
+            }
+        }
+    }
+    let mut writer = Writer {
+        names: funcs.keys().chain(&vars).cloned().collect(),
+        funcs,
+        func_names: HashMap::new(),
+        var_names: HashMap::new(),
+        scopes: vec![HashMap::new()],
+        temps: [vec![], vec![]],
+        temp_used: [0, 0],
+        temp_max: [0, 0],
+        lines: RUNTIME.iter().map(|line| line.to_string()).collect(),
+    };
+    // 按名字排序，新名字不随HashSet的顺序变化
+    let mut funcs: Vec<String> = writer.funcs.keys().cloned().collect();
+    funcs.sort();
+    for name in funcs {
+        if C_KEYWORDS.contains(&name.as_str()) {
+            let new_name = writer.fresh(&format!("{}_", name));
+            writer.func_names.insert(name, new_name);
+        }
+    }
+    let mut vars: Vec<String> = vars.into_iter().collect();
+    vars.sort();
+    for name in vars {
+        if C_KEYWORDS.contains(&name.as_str()) || writer.funcs.contains_key(&name) {
+            let new_name = writer.fresh(&format!("{}_", name));
+            writer.var_names.insert(name, new_name);
+        }
+    }
+    for item in &comp_unit.items {
+        writer.lines.push(String::new());
+        match item {
+            Item::Decl(decl) => writer.decl(decl, 0),
+            Item::FuncDef(func) => writer.func_def(func),
+        }
+    }
+    let mut res = writer.lines.join("\n");
+    res.push('\n');
+    res
+}
+
+// 源码中声明的所有标识符，生成的临时变量不能与它们重名
+fn decl_names(decl: &Decl, names: &mut HashSet<String>) {
+    for def in &decl.defs {
+        names.insert(def.name.clone());
+    }
+}
+
+fn item_names(items: &[BlockItem], names: &mut HashSet<String>) {
+    for item in items {
+        match item {
+            BlockItem::Decl(decl) => decl_names(decl, names),
+            BlockItem::Stmt(stmt) => stmt_names(stmt, names),
+        }
+    }
+}
+
+fn stmt_names(stmt: &Stmt, names: &mut HashSet<String>) {
+    match &stmt.kind {
+        StmtKind::Block(block) => item_names(&block.items, names),
+        StmtKind::If(_, then_stmt, else_stmt) => {
+            stmt_names(then_stmt, names);
+            if let Some(else_stmt) = else_stmt {
+                stmt_names(else_stmt, names);
+            }
+        }
+        StmtKind::While(_, body) | StmtKind::DoWhile(body, _) => stmt_names(body, names),
+        StmtKind::For(init, _, _, body) => {
+            if let Some(init) = init {
+                item_names(std::slice::from_ref(init), names);
+            }
+            stmt_names(body, names);
+        }
+        StmtKind::Switch(_, cases) => {
+            for case in cases {
+                item_names(&case.items, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+// C运算符的优先级，数值越大结合越紧
+fn precedence(op: BinaryOp) -> u32 {
+    match op {
+        BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => 13,
+        BinaryOp::Add | BinaryOp::Sub => 12,
+        BinaryOp::Shl | BinaryOp::Shr => 11,
+        BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge => 10,
+        BinaryOp::Eq | BinaryOp::Ne => 9,
+        BinaryOp::BitAnd => 8,
+        BinaryOp::BitXor => 7,
+        BinaryOp::BitOr => 6,
+        BinaryOp::And => 5,
+        BinaryOp::Or => 4,
+    }
+}
+
+fn decl_prefix(decl: &Decl) -> String {
+    if decl.is_const {
+        format!("const {} ", decl.ty)
+    } else {
+        format!("{} ", decl.ty)
+    }
+}
+
+const UNARY_PRECEDENCE: u32 = 14;
+const CONDITIONAL_PRECEDENCE: u32 = 3;
+const ASSIGN_PRECEDENCE: u32 = 2;
+const COMMA_PRECEDENCE: u32 = 1;
+
+fn binary_op(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Mod => "%",
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Shl => "<<",
+        BinaryOp::Shr => ">>",
+        BinaryOp::Lt => "<",
+        BinaryOp::Gt => ">",
+        BinaryOp::Le => "<=",
+        BinaryOp::Ge => ">=",
+        BinaryOp::Eq => "==",
+        BinaryOp::Ne => "!=",
+        BinaryOp::BitAnd => "&",
+        BinaryOp::BitXor => "^",
+        BinaryOp::BitOr => "|",
+        BinaryOp::And => "&&",
+        BinaryOp::Or => "||",
+    }
+}
+
+fn to_float(val: Value) -> f32 {
+    match val {
+        Value::Int(val) => val as f32,
+        val => val.as_float().unwrap(),
+    }
+}
+
+fn cast(val: Value, ty: BType) -> Value {
+    match (val, ty) {
+        (Value::Float(_), BType::Int) => Value::Int(to_float(val) as i32),
+        (Value::Int(val), BType::Float) => Value::float(val as f32),
+        _ => val,
+    }
+}
+
+// float类型的字面量，Debug格式是能还原出原值的最短十进制表示
+fn float_literal(val: f32) -> String {
+    if val.is_nan() {
+        "(0.0f / 0.0f)".to_string()
+    } else if val.is_infinite() {
+        format!("{}(1.0f / 0.0f)", if val < 0.0 { "-" } else { "" })
+    } else {
+        format!("{:?}f", val)
+    }
+}
+
+fn literal(val: Value) -> String {
+    match val {
+        Value::Int(val) => val.to_string(),
+        val => float_literal(to_float(val)),
+    }
+}
+
+fn parenthesize(text: String, own: u32, prec: u32) -> String {
+    if own < prec {
+        format!("({})", text)
+    } else {
+        text
+    }
+}
+
+fn has_lval(exp: &Expr) -> bool {
+    match &exp.kind {
+        ExprKind::Number(_) | ExprKind::Float(_) => false,
+        ExprKind::LVal(_)
+        | ExprKind::Call { .. }
+        | ExprKind::Assign(..)
+        | ExprKind::IncDec(..)
+        | ExprKind::Comma(..) => true,
+        ExprKind::Unary(_, operand) => has_lval(operand),
+        ExprKind::Binary(_, lhs, rhs) => has_lval(lhs) || has_lval(rhs),
+        ExprKind::Conditional(cond, then_exp, else_exp) => {
+            has_lval(cond) || has_lval(then_exp) || has_lval(else_exp)
+        }
+    }
+}
+
+// 有副作用的表达式
+fn has_effect(exp: &Expr) -> bool {
+    match &exp.kind {
+        ExprKind::Number(_) | ExprKind::Float(_) => false,
+        ExprKind::Call { .. } | ExprKind::Assign(..) | ExprKind::IncDec(..) => true,
+        ExprKind::LVal(lval) => lval.indices.iter().any(has_effect),
+        ExprKind::Unary(_, operand) => has_effect(operand),
+        ExprKind::Binary(_, lhs, rhs) | ExprKind::Comma(lhs, rhs) => {
+            has_effect(lhs) || has_effect(rhs)
+        }
+        ExprKind::Conditional(cond, then_exp, else_exp) => {
+            has_effect(cond) || has_effect(then_exp) || has_effect(else_exp)
+        }
+    }
+}
+
+// 两个左值可能是同一个变量，数组形参可能指向同一个数组
+fn aliases(lhs: &LVal, rhs: &LVal) -> bool {
+    lhs.name == rhs.name || (!lhs.indices.is_empty() && !rhs.indices.is_empty())
+}
+
+fn temp_index(ty: BType) -> usize {
+    match ty {
+        BType::Int => 0,
+        BType::Float => 1,
+    }
+}
+
+// 表达式的值的用途
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Form {
+    Value,
+    Unsigned,  // unsigned运算的操作数，回绕的运算不必再转换回int
+    Discarded, // 值不被使用，后缀自增自减可以写成前缀的形式
+}
+
+// 作用域中的变量，常量标量记录其值
+struct Symbol {
+    ty: BType,
+    dims: usize,
+    value: Option<Value>,
+}
+
+struct Writer {
+    funcs: HashMap<String, BType>,
+    func_names: HashMap<String, String>, // 改名的函数与变量
+    var_names: HashMap<String, String>,
+    scopes: Vec<HashMap<String, Symbol>>,
+    names: HashSet<String>,  // 已经使用的标识符
+    temps: [Vec<String>; 2], // int与float的临时变量名，各函数共用
+    temp_used: [usize; 2],   // 当前完整表达式用到的临时变量数
+    temp_max: [usize; 2],    // 当前函数用到的临时变量数
+    lines: Vec<String>,
+}
+
+impl Writer {
+    fn line(&mut self, depth: usize, text: String) {
+        self.lines.push(format!("{}{}", "    ".repeat(depth), text));
+    }
+
+    fn declare(&mut self, name: &str, ty: BType, dims: usize, value: Option<Value>) {
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.to_string(), Symbol { ty, dims, value });
+    }
+
+    fn lookup(&self, name: &str) -> &Symbol {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .unwrap()
+    }
+
+    // 不与任何标识符重名的新名字
+    fn fresh(&mut self, base: &str) -> String {
+        let mut name = base.to_string();
+        let mut count = 0;
+        while self.names.contains(&name) || C_KEYWORDS.contains(&name.as_str()) {
+            count += 1;
+            name = format!("{}{}", base, count);
+        }
+        self.names.insert(name.clone());
+        name
+    }
+
+    // 完整表达式开始时，之前的临时变量都可以重新使用
+    fn reset_temps(&mut self) {
+        self.temp_used = [0, 0];
+    }
+
+    fn temp(&mut self, ty: BType) -> String {
+        let index = temp_index(ty);
+        if self.temp_used[index] == self.temps[index].len() {
+            let name = self.fresh("tmp");
+            self.temps[index].push(name);
+        }
+        let name = self.temps[index][self.temp_used[index]].clone();
+        self.temp_used[index] += 1;
+        self.temp_max[index] = self.temp_max[index].max(self.temp_used[index]);
+        name
+    }
+
+    // 表达式的类型，有一个操作数是浮点数时算术运算的结果为浮点数
+    fn ty(&self, exp: &Expr) -> BType {
+        match &exp.kind {
+            ExprKind::Number(_) => BType::Int,
+            ExprKind::Float(_) => BType::Float,
+            ExprKind::LVal(lval) => self.lookup(&lval.name).ty,
+            ExprKind::Call { name, .. } => self.funcs[name],
+            ExprKind::Unary(UnaryOp::Not, _) => BType::Int,
+            ExprKind::Unary(_, operand) => self.ty(operand),
+            ExprKind::Binary(
+                BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod | BinaryOp::Add | BinaryOp::Sub,
+                lhs,
+                rhs,
+            )
+            | ExprKind::Conditional(_, lhs, rhs) => {
+                if self.ty(lhs) == BType::Float || self.ty(rhs) == BType::Float {
+                    BType::Float
+                } else {
+                    BType::Int
+                }
+            }
+            ExprKind::Binary(..) => BType::Int,
+            ExprKind::Assign(_, lval, _) | ExprKind::IncDec(_, lval) => self.lookup(&lval.name).ty,
+            ExprKind::Comma(_, rhs) => self.ty(rhs),
+        }
+    }
+
+    // 求值顺序不确定的一组操作数，其中有副作用时，除最后一个非常量的操作数外都按从左到右的顺序先存入临时变量
+    // 返回各操作数对应的临时变量，对临时变量的赋值加入prefix
+    fn sequence(&mut self, operands: &[&Expr], prefix: &mut Vec<String>) -> Vec<Option<String>> {
+        let mut res = vec![None; operands.len()];
+        if !operands.iter().any(|exp| has_effect(exp)) {
+            return res;
+        }
+        let last = operands.iter().rposition(|exp| has_lval(exp));
+        for (index, exp) in operands.iter().enumerate() {
+            if Some(index) == last {
+                break;
+            }
+            if has_lval(exp) {
+                res[index] = Some(self.hoist(exp, prefix));
+            }
+        }
+        res
+    }
+
+    fn hoist(&mut self, exp: &Expr, prefix: &mut Vec<String>) -> String {
+        let text = self.expr(exp, ASSIGN_PRECEDENCE);
+        let temp = self.temp(self.ty(exp));
+        prefix.push(format!("{} = {}", temp, text));
+        temp
+    }
+
+    // 作为实参的数组，按地址传递
+    fn array_arg<'a>(&self, arg: &'a Expr) -> Option<&'a LVal> {
+        match &arg.kind {
+            ExprKind::LVal(lval) if lval.indices.len() < self.lookup(&lval.name).dims => Some(lval),
+            _ => None,
+        }
+    }
+
+    fn var_name(&self, name: &str) -> String {
+        self.var_names
+            .get(name)
+            .map_or(name, String::as_str)
+            .to_string()
+    }
+
+    fn func_name(&self, name: &str) -> String {
+        self.func_names
+            .get(name)
+            .map_or(name, String::as_str)
+            .to_string()
+    }
+
+    // 常量表达式的值，语义检查保证表达式中只有常量
+    fn eval(&self, exp: &Expr) -> Value {
+        match &exp.kind {
+            ExprKind::Number(val) => Value::Int(*val),
+            ExprKind::Float(val) => Value::float(*val),
+            ExprKind::LVal(lval) => self.lookup(&lval.name).value.unwrap_or(Value::Int(0)),
+            // 有一个分支是浮点数时结果为浮点数
+            ExprKind::Conditional(cond, then_exp, else_exp) => {
+                let (then_val, else_val) = (self.eval(then_exp), self.eval(else_exp));
+                let res = if to_float(self.eval(cond)) != 0.0 {
+                    then_val
+                } else {
+                    else_val
+                };
+                match (then_val, else_val) {
+                    (Value::Int(_), Value::Int(_)) => res,
+                    _ => Value::float(to_float(res)),
+                }
+            }
+            ExprKind::Call { .. }
+            | ExprKind::Assign(..)
+            | ExprKind::IncDec(..)
+            | ExprKind::Comma(..) => Value::Int(0),
+            ExprKind::Unary(op, operand) => match (op, self.eval(operand)) {
+                (UnaryOp::Plus, val) => val,
+                (UnaryOp::Minus, Value::Int(val)) => Value::Int(val.wrapping_neg()),
+                (UnaryOp::Minus, val) => Value::float(-to_float(val)),
+                (UnaryOp::Not, val) => Value::Int((to_float(val) == 0.0) as i32),
+                (UnaryOp::BitNot, val) => Value::Int(!val.as_int().unwrap_or(0)),
+            },
+            ExprKind::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = match (self.eval(lhs), self.eval(rhs)) {
+                    (Value::Int(lhs), Value::Int(rhs)) => (lhs, rhs),
+                    (lhs, rhs) => {
+                        let (lhs, rhs) = (to_float(lhs), to_float(rhs));
+                        return match op {
+                            BinaryOp::Mul => Value::float(lhs * rhs),
+                            BinaryOp::Div => Value::float(lhs / rhs),
+                            BinaryOp::Add => Value::float(lhs + rhs),
+                            BinaryOp::Sub => Value::float(lhs - rhs),
+                            BinaryOp::Lt => Value::Int((lhs < rhs) as i32),
+                            BinaryOp::Gt => Value::Int((lhs > rhs) as i32),
+                            BinaryOp::Le => Value::Int((lhs <= rhs) as i32),
+                            BinaryOp::Ge => Value::Int((lhs >= rhs) as i32),
+                            BinaryOp::Eq => Value::Int((lhs == rhs) as i32),
+                            BinaryOp::Ne => Value::Int((lhs != rhs) as i32),
+                            BinaryOp::And => Value::Int((lhs != 0.0 && rhs != 0.0) as i32),
+                            BinaryOp::Or => Value::Int((lhs != 0.0 || rhs != 0.0) as i32),
+                            _ => Value::float(0.0),
+                        };
+                    }
+                };
+                Value::Int(match op {
+                    BinaryOp::Mul => lhs.wrapping_mul(rhs),
+                    BinaryOp::Div => lhs.checked_div(rhs).unwrap_or(0),
+                    BinaryOp::Mod => lhs.checked_rem(rhs).unwrap_or(0),
+                    BinaryOp::Add => lhs.wrapping_add(rhs),
+                    BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                    BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+                    BinaryOp::Shr => lhs.wrapping_shr(rhs as u32),
+                    BinaryOp::BitAnd => lhs & rhs,
+                    BinaryOp::BitXor => lhs ^ rhs,
+                    BinaryOp::BitOr => lhs | rhs,
+                    BinaryOp::Lt => (lhs < rhs) as i32,
+                    BinaryOp::Gt => (lhs > rhs) as i32,
+                    BinaryOp::Le => (lhs <= rhs) as i32,
+                    BinaryOp::Ge => (lhs >= rhs) as i32,
+                    BinaryOp::Eq => (lhs == rhs) as i32,
+                    BinaryOp::Ne => (lhs != rhs) as i32,
+                    BinaryOp::And => (lhs != 0 && rhs != 0) as i32,
+                    BinaryOp::Or => (lhs != 0 || rhs != 0) as i32,
+                })
+            }
+        }
+    }
+
+    // C中必须是常量表达式的位置，引用了标识符时折叠为数值
+    fn const_expr(&mut self, exp: &Expr) -> String {
+        if has_lval(exp) {
+            literal(self.eval(exp))
+        } else {
+            self.expr(exp, 0)
+        }
+    }
+
+    // 可能溢出的整数运算，只含字面量且结果在int范围内时照原样输出
+    fn wraps(&self, exp: &Expr) -> bool {
+        let arith = matches!(
+            exp.kind,
+            ExprKind::Binary(
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Shl,
+                ..
+            ) | ExprKind::Unary(UnaryOp::Minus, _)
+        );
+        arith && self.ty(exp) == BType::Int && (has_lval(exp) || self.exact(exp).is_none())
+    }
+
+    // 只含字面量的整数表达式在C中的值，运算结果超出int的范围时为None
+    fn exact(&self, exp: &Expr) -> Option<i64> {
+        let res = match &exp.kind {
+            ExprKind::Unary(UnaryOp::Plus, operand) => self.exact(operand)?,
+            ExprKind::Unary(UnaryOp::Minus, operand) => -self.exact(operand)?,
+            ExprKind::Binary(
+                op @ (BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Shl),
+                lhs,
+                rhs,
+            ) => {
+                let (lhs, rhs) = (self.exact(lhs)?, self.exact(rhs)?);
+                match op {
+                    BinaryOp::Add => lhs + rhs,
+                    BinaryOp::Sub => lhs - rhs,
+                    BinaryOp::Mul => lhs * rhs,
+                    _ if lhs >= 0 && (0..32).contains(&rhs) => lhs << rhs,
+                    _ => return None,
+                }
+            }
+            _ => self.eval(exp).as_int()? as i64,
+        };
+        (i32::MIN as i64..=i32::MAX as i64)
+            .contains(&res)
+            .then_some(res)
+    }
+
+    // 移位量只取低5位，范围内的常量照原样输出
+    fn shift_count(&mut self, rhs: &Expr, temp: Option<String>) -> String {
+        match temp {
+            Some(temp) => format!("({} & 31)", temp),
+            None if !has_lval(rhs) && matches!(self.eval(rhs), Value::Int(0..=31)) => {
+                self.expr(rhs, UNARY_PRECEDENCE)
+            }
+            None => format!("({} & 31)", self.expr(rhs, UNARY_PRECEDENCE)),
+        }
+    }
+
+    fn expr(&mut self, exp: &Expr, prec: u32) -> String {
+        self.expr_as(exp, prec, Form::Value)
+    }
+
+    // prec为所在位置要求的最低优先级，不满足时加括号
+    fn expr_as(&mut self, exp: &Expr, prec: u32, form: Form) -> String {
+        // 先于表达式本身求值的临时变量赋值，与表达式组成逗号表达式
+        let mut prefix = vec![];
+        // 结果是unsigned的回绕运算
+        let mut unsigned = false;
+        let (text, own) = match &exp.kind {
+            ExprKind::Number(val) => (val.to_string(), UNARY_PRECEDENCE + 1),
+            ExprKind::Float(val) => (float_literal(*val), UNARY_PRECEDENCE + 1),
+            ExprKind::LVal(lval) => {
+                let indices: Vec<&Expr> = lval.indices.iter().collect();
+                let temps = self.sequence(&indices, &mut prefix);
+                (self.lval(lval, &temps), UNARY_PRECEDENCE + 1)
+            }
+            // 数组实参按地址传递，参与排序的是其中的下标
+            ExprKind::Call { name, args, .. } => {
+                let mut operands = vec![];
+                for arg in args {
+                    match self.array_arg(arg) {
+                        Some(lval) => operands.extend(lval.indices.iter()),
+                        None => operands.push(arg),
+                    }
+                }
+                let temps = self.sequence(&operands, &mut prefix);
+                let mut temps = temps.into_iter();
+                let mut texts = vec![];
+                for arg in args {
+                    let text = match self.array_arg(arg) {
+                        Some(lval) => {
+                            let indices: Vec<Option<String>> =
+                                temps.by_ref().take(lval.indices.len()).collect();
+                            self.lval(lval, &indices)
+                        }
+                        None => match temps.next().unwrap() {
+                            Some(temp) => temp,
+                            None => self.expr(arg, ASSIGN_PRECEDENCE),
+                        },
+                    };
+                    texts.push(text);
+                }
+                (
+                    format!("{}({})", self.func_name(name), texts.join(", ")),
+                    UNARY_PRECEDENCE + 1,
+                )
+            }
+            ExprKind::Unary(UnaryOp::Minus, operand) if self.wraps(exp) => {
+                unsigned = true;
+                let operand = self.expr_as(operand, UNARY_PRECEDENCE, Form::Unsigned);
+                if operand.starts_with('-') {
+                    (format!("-({})", operand), UNARY_PRECEDENCE)
+                } else {
+                    (format!("-{}", operand), UNARY_PRECEDENCE)
+                }
+            }
+            ExprKind::Unary(op, operand) => {
+                let op = match op {
+                    UnaryOp::Plus => "+",
+                    UnaryOp::Minus => "-",
+                    UnaryOp::Not => "!",
+                    UnaryOp::BitNot => "~",
+                };
+                // 避免连续的正负号被识别为++与--
+                let operand = match &operand.kind {
+                    ExprKind::Unary(_, _) => format!("({})", self.expr(operand, 0)),
+                    ExprKind::IncDec(op, _) if op.is_prefix() => {
+                        format!("({})", self.expr(operand, 0))
+                    }
+                    _ => self.expr(operand, UNARY_PRECEDENCE),
+                };
+                (format!("{}{}", op, operand), UNARY_PRECEDENCE)
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                let own = precedence(*op);
+                // ||中的&&、比较中的比较以及移位与位运算中的其他二元运算加上括号，避免-Wparentheses警告
+                let (lhs_min, rhs_min) = match op {
+                    BinaryOp::Or => (own + 2, own + 2),
+                    BinaryOp::Lt
+                    | BinaryOp::Gt
+                    | BinaryOp::Le
+                    | BinaryOp::Ge
+                    | BinaryOp::Eq
+                    | BinaryOp::Ne => (precedence(BinaryOp::Shl), precedence(BinaryOp::Shl)),
+                    BinaryOp::Shl
+                    | BinaryOp::Shr
+                    | BinaryOp::BitAnd
+                    | BinaryOp::BitXor
+                    | BinaryOp::BitOr => match &lhs.kind {
+                        ExprKind::Binary(inner, _, _) if inner == op => (own, UNARY_PRECEDENCE),
+                        _ => (UNARY_PRECEDENCE, UNARY_PRECEDENCE),
+                    },
+                    _ => (own, own + 1),
+                };
+                // 逻辑运算的操作数有先后顺序，其余二元运算的操作数可能需要先存入临时变量
+                let temps = if matches!(op, BinaryOp::And | BinaryOp::Or) {
+                    vec![None, None]
+                } else {
+                    self.sequence(&[lhs, rhs], &mut prefix)
+                };
+                // 回绕的运算把左侧转换为unsigned，右侧随之按unsigned运算
+                unsigned = self.wraps(exp);
+                let lhs = match &temps[0] {
+                    Some(temp) if unsigned => format!("(unsigned){}", temp),
+                    Some(temp) => temp.clone(),
+                    None if unsigned => self.expr_as(lhs, lhs_min, Form::Unsigned),
+                    None => self.expr(lhs, lhs_min),
+                };
+                let rhs = match &temps[1] {
+                    _ if matches!(op, BinaryOp::Shl | BinaryOp::Shr) => {
+                        self.shift_count(rhs, temps[1].clone())
+                    }
+                    Some(temp) => temp.clone(),
+                    None if unsigned && self.wraps(rhs) => {
+                        self.expr_as(rhs, rhs_min, Form::Unsigned)
+                    }
+                    None => self.expr(rhs, rhs_min),
+                };
+                (format!("{} {} {}", lhs, binary_op(*op), rhs), own)
+            }
+            // 条件表达式是右结合的，中间的操作数不受优先级限制
+            ExprKind::Conditional(cond, then_exp, else_exp) => (
+                format!(
+                    "{} ? {} : {}",
+                    self.expr(cond, precedence(BinaryOp::Or)),
+                    self.expr(then_exp, 0),
+                    self.expr(else_exp, CONDITIONAL_PRECEDENCE)
+                ),
+                CONDITIONAL_PRECEDENCE,
+            ),
+            ExprKind::Assign(op, lval, rhs) => {
+                (self.assign(*op, lval, rhs, &mut prefix), ASSIGN_PRECEDENCE)
+            }
+            ExprKind::IncDec(op, lval) => {
+                let indices: Vec<&Expr> = lval.indices.iter().collect();
+                let temps = self.sequence(&indices, &mut prefix);
+                let ty = self.lookup(&lval.name).ty;
+                let lval = self.lval(lval, &temps);
+                let (assign, undo) = match op.binary_op() {
+                    BinaryOp::Add => ("+=", "-"),
+                    _ => ("-=", "+"),
+                };
+                // 整数加减unsigned的1时回绕，后缀形式的值再减去加上的1
+                match ty {
+                    BType::Float if op.is_prefix() => {
+                        (format!("{}{}", op.text(), lval), UNARY_PRECEDENCE)
+                    }
+                    BType::Float => (format!("{}{}", lval, op.text()), UNARY_PRECEDENCE + 1),
+                    BType::Int if op.is_prefix() || form == Form::Discarded => {
+                        (format!("{} {} 1u", lval, assign), ASSIGN_PRECEDENCE)
+                    }
+                    BType::Int => {
+                        unsigned = true;
+                        (
+                            format!("({} {} 1u) {} 1u", lval, assign, undo),
+                            precedence(BinaryOp::Add),
+                        )
+                    }
+                }
+            }
+            ExprKind::Comma(lhs, rhs) => {
+                let rhs_form = match form {
+                    Form::Discarded => Form::Discarded,
+                    _ => Form::Value,
+                };
+                (
+                    format!(
+                        "{}, {}",
+                        self.expr_as(lhs, COMMA_PRECEDENCE, Form::Discarded),
+                        self.expr_as(rhs, ASSIGN_PRECEDENCE, rhs_form)
+                    ),
+                    COMMA_PRECEDENCE,
+                )
+            }
+        };
+        let (text, own) = if prefix.is_empty() {
+            (text, own)
+        } else {
+            prefix.push(parenthesize(text, own, ASSIGN_PRECEDENCE));
+            (format!("({})", prefix.join(", ")), UNARY_PRECEDENCE + 1)
+        };
+        let (text, own) = match (unsigned, form) {
+            (true, Form::Unsigned) => (text, own),
+            (true, _) => (
+                format!("(int){}", parenthesize(text, own, UNARY_PRECEDENCE)),
+                UNARY_PRECEDENCE,
+            ),
+            (false, Form::Unsigned) => match exp.kind {
+                ExprKind::Number(val) if val >= 0 => (format!("{}u", val), own),
+                _ => (
+                    format!("(unsigned){}", parenthesize(text, own, UNARY_PRECEDENCE)),
+                    UNARY_PRECEDENCE,
+                ),
+            },
+            (false, _) => (text, own),
+        };
+        parenthesize(text, own, prec)
+    }
+
+    // 与SysY一致，先确定左值的位置，复合赋值接着读出原值，最后求右侧的值
+    fn assign(
+        &mut self,
+        op: Option<BinaryOp>,
+        lval: &LVal,
+        rhs: &Expr,
+        prefix: &mut Vec<String>,
+    ) -> String {
+        let ty = self.lookup(&lval.name).ty;
+        let int = ty == BType::Int && self.ty(rhs) == BType::Int;
+        let mut operands: Vec<&Expr> = lval.indices.iter().collect();
+        // 右侧可能修改左值时，原值要先于右侧读出；左移要读写两次左值，下标有副作用时也先读出原值
+        let ordered = op.is_some()
+            && (self.modifies(rhs, lval, true)
+                || (op == Some(BinaryOp::Shl) && lval.indices.iter().any(has_effect)));
+        let temps = if ordered {
+            let mut temps = vec![];
+            for index in operands {
+                temps.push(has_lval(index).then(|| self.hoist(index, prefix)));
+            }
+            temps
+        } else {
+            operands.push(rhs);
+            let mut temps = self.sequence(&operands, prefix);
+            temps.pop();
+            temps
+        };
+        let target = self.lval(lval, &temps);
+        let old = if ordered {
+            let temp = self.temp(ty);
+            prefix.push(format!("{} = {}", temp, target));
+            temp
+        } else {
+            target.clone()
+        };
+        // 右侧对左值的修改与赋值本身没有先后顺序，先存入临时变量
+        let hoisted = if self.modifies(rhs, lval, false) {
+            Some(self.hoist(rhs, prefix))
+        } else {
+            None
+        };
+        let op = match op {
+            Some(op) => op,
+            None => {
+                let rhs = hoisted.unwrap_or_else(|| self.expr(rhs, ASSIGN_PRECEDENCE));
+                return format!("{} = {}", target, rhs);
+            }
+        };
+        match op {
+            BinaryOp::Shl => {
+                let count = self.shift_count(rhs, hoisted);
+                format!("{} = (int)((unsigned){} << {})", target, old, count)
+            }
+            BinaryOp::Shr if ordered => {
+                let count = self.shift_count(rhs, hoisted);
+                format!("{} = {} >> {}", target, old, count)
+            }
+            BinaryOp::Shr => format!("{} >>= {}", target, self.shift_count(rhs, hoisted)),
+            // 复合赋值中unsigned的右侧使运算按unsigned进行
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul if int && !ordered => {
+                let rhs = self.expr_as(rhs, ASSIGN_PRECEDENCE, Form::Unsigned);
+                format!("{} {}= {}", target, binary_op(op), rhs)
+            }
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul if int => {
+                let form = if self.wraps(rhs) {
+                    Form::Unsigned
+                } else {
+                    Form::Value
+                };
+                let rhs = hoisted.unwrap_or_else(|| self.expr_as(rhs, precedence(op) + 1, form));
+                format!(
+                    "{} = (int)((unsigned){} {} {})",
+                    target,
+                    old,
+                    binary_op(op),
+                    rhs
+                )
+            }
+            _ if ordered => {
+                let rhs_min = match op {
+                    BinaryOp::Mul
+                    | BinaryOp::Div
+                    | BinaryOp::Mod
+                    | BinaryOp::Add
+                    | BinaryOp::Sub => precedence(op) + 1,
+                    _ => UNARY_PRECEDENCE,
+                };
+                let rhs = hoisted.unwrap_or_else(|| self.expr(rhs, rhs_min));
+                format!("{} = {} {} {}", target, old, binary_op(op), rhs)
+            }
+            _ => {
+                let rhs = self.expr(rhs, ASSIGN_PRECEDENCE);
+                format!("{} {}= {}", target, binary_op(op), rhs)
+            }
+        }
+    }
+
+    // exp可能修改lval的值，calls为false时不考虑被调用的函数，其中的副作用都先于调用的值完成
+    // 实参中的修改虽然也先于调用完成，但gcc会给出-Wsequence-point警告，同样算作修改
+    fn modifies(&self, exp: &Expr, lval: &LVal, calls: bool) -> bool {
+        let indices = |target: &LVal| {
+            target
+                .indices
+                .iter()
+                .any(|index| self.modifies(index, lval, calls))
+        };
+        match &exp.kind {
+            ExprKind::Number(_) | ExprKind::Float(_) => false,
+            ExprKind::Call { args, .. } => {
+                calls || args.iter().any(|arg| self.modifies(arg, lval, calls))
+            }
+            ExprKind::LVal(target) => indices(target),
+            ExprKind::Assign(_, target, rhs) => {
+                aliases(target, lval) || indices(target) || self.modifies(rhs, lval, calls)
+            }
+            ExprKind::IncDec(_, target) => aliases(target, lval) || indices(target),
+            ExprKind::Unary(_, operand) => self.modifies(operand, lval, calls),
+            ExprKind::Binary(_, lhs, rhs) | ExprKind::Comma(lhs, rhs) => {
+                self.modifies(lhs, lval, calls) || self.modifies(rhs, lval, calls)
+            }
+            ExprKind::Conditional(cond, then_exp, else_exp) => {
+                self.modifies(cond, lval, calls)
+                    || self.modifies(then_exp, lval, calls)
+                    || self.modifies(else_exp, lval, calls)
+            }
+        }
+    }
+
+    // temps为已经存入临时变量的下标，缺少的下标直接求值
+    fn lval(&mut self, lval: &LVal, temps: &[Option<String>]) -> String {
+        let mut res = self.var_name(&lval.name);
+        for (pos, index) in lval.indices.iter().enumerate() {
+            let text = match temps.get(pos) {
+                Some(Some(temp)) => temp.clone(),
+                _ => self.expr(index, 0),
+            };
+            res.push_str(&format!("[{}]", text));
+        }
+        res
+    }
+
+    fn init_val(&mut self, init: &InitVal, is_global: bool) -> String {
+        match init {
+            InitVal::Expr(exp) if is_global => self.const_expr(exp),
+            // 初始值中的逗号是分隔符，逗号表达式要加括号
+            InitVal::Expr(exp) => self.expr(exp, ASSIGN_PRECEDENCE),
+            // C99不允许空的初始化列表
+            InitVal::List(vals, _) if vals.is_empty() => "{0}".to_string(),
+            InitVal::List(vals, _) => {
+                let vals: Vec<String> = vals
+                    .iter()
+                    .map(|val| self.init_val(val, is_global))
+                    .collect();
+                format!("{{{}}}", vals.join(", "))
+            }
+        }
+    }
+
+    fn decl(&mut self, decl: &Decl, depth: usize) {
+        for def in &decl.defs {
+            self.reset_temps();
+            let text = format!("{}{};", decl_prefix(decl), self.var_def(decl, def));
+            self.line(depth, text);
+        }
+    }
+
+    // 不带类型的一个变量定义，同时加入当前作用域
+    fn var_def(&mut self, decl: &Decl, def: &VarDef) -> String {
+        let is_global = self.scopes.len() == 1;
+        let mut text = self.var_name(&def.name);
+        for dim in &def.dims {
+            text.push_str(&format!("[{}]", self.const_expr(dim)));
+        }
+        if let Some(init) = &def.init {
+            text.push_str(&format!(" = {}", self.init_val(init, is_global)));
+        }
+        let value = match &def.init {
+            Some(InitVal::Expr(exp)) if decl.is_const && def.dims.is_empty() => {
+                Some(cast(self.eval(exp), decl.ty))
+            }
+            _ => None,
+        };
+        self.declare(&def.name, decl.ty, def.dims.len(), value);
+        text
+    }
+
+    fn func_def(&mut self, func: &FuncDef) {
+        let mut params = vec![];
+        for param in &func.params {
+            let mut text = format!("{} {}", param.ty, self.var_name(&param.name));
+            for dim in &param.dims {
+                match dim {
+                    Some(dim) => text.push_str(&format!("[{}]", self.const_expr(dim))),
+                    None => text.push_str("[]"),
+                }
+            }
+            params.push(text);
+        }
+        let params = if params.is_empty() {
+            "void".to_string()
+        } else {
+            params.join(", ")
+        };
+        let ret_ty = if func.has_return {
+            func.ty.to_string()
+        } else {
+            "void".to_string()
+        };
+        self.line(
+            0,
+            format!("{} {}({}) {{", ret_ty, self.func_name(&func.name), params),
+        );
+        // 函数体用到的临时变量在开头声明
+        let temp_decl = self.lines.len();
+        self.temp_max = [0, 0];
+        // 参数与函数体在同一个作用域
+        self.scopes.push(HashMap::new());
+        for param in &func.params {
+            self.declare(&param.name, param.ty, param.dims.len(), None);
+        }
+        self.block_items(&func.body.items, 1);
+        self.scopes.pop();
+        for ty in [BType::Float, BType::Int] {
+            let index = temp_index(ty);
+            if self.temp_max[index] != 0 {
+                let temps = self.temps[index][..self.temp_max[index]].join(", ");
+                self.lines
+                    .insert(temp_decl, format!("    {} {};", ty, temps));
+            }
+        }
+        // 与LLVM IR一致，没有return时返回0
+        let returns = matches!(
+            func.body.items.last(),
+            Some(BlockItem::Stmt(stmt)) if matches!(stmt.kind, StmtKind::Return(_))
+        );
+        if func.has_return && func.name != "main" && !returns {
+            self.line(1, "return 0;".to_string());
+        }
+        self.line(0, "}".to_string());
+    }
+
+    fn block_items(&mut self, items: &[BlockItem], depth: usize) {
+        for item in items {
+            match item {
+                BlockItem::Decl(decl) => self.decl(decl, depth),
+                BlockItem::Stmt(stmt) => self.stmt(stmt, depth),
+            }
+        }
+    }
+
+    fn block(&mut self, block: &Block, depth: usize) {
+        self.scopes.push(HashMap::new());
+        self.block_items(&block.items, depth);
+        self.scopes.pop();
+    }
+
+    // 复合语句的子语句，是块时左大括号与head在同一行
+    fn body(&mut self, head: String, stmt: &Stmt, depth: usize) -> bool {
+        match &stmt.kind {
+            StmtKind::Block(block) => {
+                self.line(depth, format!("{} {{", head));
+                self.block(block, depth + 1);
+                true
+            }
+            // 嵌套的if总是加上花括号，避免else的歧义
+            StmtKind::If(_, _, _) => {
+                self.line(depth, format!("{} {{", head));
+                self.stmt(stmt, depth + 1);
+                true
+            }
+            _ => {
+                self.line(depth, head);
+                self.stmt(stmt, depth + 1);
+                false
+            }
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, depth: usize) {
+        self.reset_temps();
+        match &stmt.kind {
+            StmtKind::Expr(Some(exp)) => {
+                let text = format!("{};", self.expr_as(exp, 0, Form::Discarded));
+                self.line(depth, text);
+            }
+            StmtKind::Expr(None) => self.line(depth, ";".to_string()),
+            StmtKind::Block(block) => {
+                self.line(depth, "{".to_string());
+                self.block(block, depth + 1);
+                self.line(depth, "}".to_string());
+            }
+            StmtKind::If(cond, then_stmt, else_stmt) => {
+                self.if_stmt("", cond, then_stmt, else_stmt.as_deref(), depth)
+            }
+            StmtKind::While(cond, body) => {
+                let head = format!("while ({})", self.expr(cond, 0));
+                if self.body(head, body, depth) {
+                    self.line(depth, "}".to_string());
+                }
+            }
+            StmtKind::For(init, cond, step, body) => {
+                // 初始化中的声明只在for中可见
+                self.scopes.push(HashMap::new());
+                let init = match init.as_deref() {
+                    Some(BlockItem::Decl(decl)) => {
+                        let defs: Vec<String> = decl
+                            .defs
+                            .iter()
+                            .map(|def| self.var_def(decl, def))
+                            .collect();
+                        format!("{}{}", decl_prefix(decl), defs.join(", "))
+                    }
+                    Some(BlockItem::Stmt(stmt)) => match &stmt.kind {
+                        StmtKind::Expr(Some(exp)) => self.expr_as(exp, 0, Form::Discarded),
+                        _ => unreachable!(),
+                    },
+                    None => String::new(),
+                };
+                // 省略的部分不留空格，如for (;;)
+                let cond = match cond {
+                    Some(cond) => format!(" {}", self.expr(cond, 0)),
+                    None => String::new(),
+                };
+                let step = match step {
+                    Some(step) => format!(" {}", self.expr_as(step, 0, Form::Discarded)),
+                    None => String::new(),
+                };
+                let head = format!("for ({};{};{})", init, cond, step);
+                if self.body(head, body, depth) {
+                    self.line(depth, "}".to_string());
+                }
+                self.scopes.pop();
+            }
+            StmtKind::DoWhile(body, cond) => {
+                let tail = format!("while ({});", self.expr(cond, 0));
+                if self.body("do".to_string(), body, depth) {
+                    self.line(depth, format!("}} {}", tail));
+                } else {
+                    self.line(depth, tail);
+                }
+            }
+            StmtKind::Switch(exp, cases) => {
+                let head = format!("switch ({}) {{", self.expr(exp, 0));
+                self.line(depth, head);
+                self.scopes.push(HashMap::new());
+                for (index, case) in cases.iter().enumerate() {
+                    let mut label = match &case.value {
+                        Some(value) => format!("case {}:", self.const_expr(value)),
+                        None => "default:".to_string(),
+                    };
+                    // C99中标号后必须是语句，不能是声明或右花括号
+                    let is_last = index + 1 == cases.len();
+                    match case.items.first() {
+                        Some(BlockItem::Decl(_)) => label.push(';'),
+                        None if is_last => label.push(';'),
+                        _ => {}
+                    }
+                    self.line(depth + 1, label);
+                    self.block_items(&case.items, depth + 2);
+                }
+                self.scopes.pop();
+                self.line(depth, "}".to_string());
+            }
+            StmtKind::Break => self.line(depth, "break;".to_string()),
+            StmtKind::Continue => self.line(depth, "continue;".to_string()),
+            StmtKind::Return(Some(exp)) => {
+                let text = format!("return {};", self.expr(exp, 0));
+                self.line(depth, text);
+            }
+            StmtKind::Return(None) => self.line(depth, "return;".to_string()),
+        }
+    }
+
+    // else if连写在同一行，prefix为前面的"} else "或"else "
+    fn if_stmt(
+        &mut self,
+        prefix: &str,
+        cond: &Expr,
+        then_stmt: &Stmt,
+        else_stmt: Option<&Stmt>,
+        depth: usize,
+    ) {
+        let head = format!("{}if ({})", prefix, self.expr(cond, 0));
+        let braced = self.body(head, then_stmt, depth);
+        let prefix = if braced { "} else" } else { "else" };
+        match else_stmt.map(|stmt| &stmt.kind) {
+            None if braced => self.line(depth, "}".to_string()),
+            None => {}
+            Some(StmtKind::If(cond, then_stmt, else_stmt)) => {
+                let prefix = format!("{} ", prefix);
+                self.if_stmt(&prefix, cond, then_stmt, else_stmt.as_deref(), depth);
+            }
+            Some(_) => {
+                let braced = self.body(prefix.to_string(), else_stmt.unwrap(), depth);
+                if braced {
+                    self.line(depth, "}".to_string());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::emit;
+    use crate::testing::analyze_source;
+
+    fn emit_c(source: &str) -> String {
+        emit(&analyze_source(source).0)
+    }
+
+    // 有副作用的实参与操作数按从左到右的顺序先存入临时变量
+    #[test]
+    fn sequences_side_effects() {
+        let c = emit_c(
+            "int g;
+            int f(int x) { g = g * 10 + x; return x; }
+            int add(int x, int y) { return x + y; }
+            int main() { int a[2]; putint(add(f(1), f(2))); return g - f(3) ^ a[0]; }",
+        );
+        assert!(c.contains("    int tmp, tmp1;\n"), "{}", c);
+        assert!(c.contains("putint((tmp = f(1), add(tmp, f(2))));"), "{}", c);
+        assert!(
+            c.contains("return (tmp1 = (int)(tmp = g, (unsigned)tmp - f(3)), tmp1 ^ a[0]);"),
+            "{}",
+            c
+        );
+        // 没有副作用时不使用临时变量
+        assert!(c.contains("g = (int)((unsigned)g * 10 + x);"), "{}", c);
+    }
+
+    // 赋值先确定左值并读出原值，右侧修改了左值时用临时变量排定顺序
+    #[test]
+    fn sequences_assignments() {
+        let c = emit_c(
+            "int g;
+            int bump() { g = g + 10; return 1; }
+            int main() { int x = 0, i = 1, a[2] = {}; x += x++; a[i] -= a[i]--; g *= bump(); a[i++] = i; return x; }",
+        );
+        assert!(
+            c.contains("(tmp = x, tmp1 = (int)((x += 1u) - 1u), x = (int)((unsigned)tmp + tmp1));"),
+            "{}",
+            c
+        );
+        assert!(
+            c.contains(
+                "(tmp = i, tmp1 = a[tmp], tmp2 = (int)((a[i] -= 1u) + 1u), \
+                 a[tmp] = (int)((unsigned)tmp1 - tmp2));"
+            ),
+            "{}",
+            c
+        );
+        assert!(
+            c.contains("(tmp = g, g = (int)((unsigned)tmp * bump()));"),
+            "{}",
+            c
+        );
+        assert!(
+            c.contains("(tmp = (int)((i += 1u) - 1u), a[tmp] = i);"),
+            "{}",
+            c
+        );
+    }
+
+    // 有符号溢出按补码回绕，不依赖-fwrapv
+    #[test]
+    fn wraps_signed_overflow() {
+        let c = emit_c(
+            "int main() {
+                int i = 1, n = 0, a[4] = {};
+                while (i > 0) { i = i + 1; n++; }
+                a[n & 3] = -i * 2 - 3 << n >> (n - 1);
+                for (i = 0; i < 1 << 2; i++, n--) a[i] <<= i;
+                return 2147483647 + 1 + (1 + 2) * 3;
+            }",
+        );
+        assert!(c.contains("i = (int)((unsigned)i + 1);"), "{}", c);
+        assert!(c.contains("        n += 1u;\n"), "{}", c);
+        assert!(
+            c.contains("a[n & 3] = (int)((-(unsigned)i * 2 - 3) << (n & 31)) >> ((int)((unsigned)n - 1) & 31);"),
+            "{}",
+            c
+        );
+        assert!(
+            c.contains("for (i = 0; i < 1 << 2; i += 1u, n -= 1u)"),
+            "{}",
+            c
+        );
+        assert!(
+            c.contains("a[i] = (int)((unsigned)a[i] << (i & 31));"),
+            "{}",
+            c
+        );
+        // 只含字面量且不溢出的运算照原样输出
+        assert!(
+            c.contains("return (int)(2147483647u + 1 + (1 + 2) * 3);"),
+            "{}",
+            c
+        );
+    }
+
+    // 与关键字或函数重名的变量改用未被使用的名字
+    #[test]
+    fn renames_without_collision() {
+        let c = emit_c(
+            "int f() { return 1; }
+            int main() { int char = 1; int char_ = 2; int f = 3; return char + char_ + f; }",
+        );
+        assert!(c.contains("int char_1 = 1;"), "{}", c);
+        assert!(c.contains("int char_ = 2;"), "{}", c);
+        assert!(c.contains("int f_ = 3;"), "{}", c);
+        assert!(
+            c.contains("return (int)((unsigned)char_1 + char_ + f_);"),
+            "{}",
+            c
+        );
+    }
+}
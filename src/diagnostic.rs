@@ -0,0 +1,142 @@
+use std::fmt::{self, Write};
+
+use super::span::{SourceMap, Span};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// 错误码，编号规则见README
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorCode {
+    UnexpectedCharacter,
+    InvalidLiteral,
+    UnterminatedComment,
+    UnexpectedToken,
+    UndeclaredIdentifier,
+    Redefinition,
+    ArgumentMismatch,
+    ReturnMismatch,
+    NotConstant,
+    AssignToConst,
+    InvalidOperand,
+    LoopControl,
+    InvalidMain,
+    InvalidArray,
+    DivisionByZero,
+    InvalidCase,
+    ShiftOutOfRange,
+    RuntimeDivision,
+    IndexOutOfBounds,
+    StackOverflow,
+    MissingReturn,
+    UndefinedShift,
+}
+
+impl ErrorCode {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedCharacter => "E0001",
+            ErrorCode::InvalidLiteral => "E0002",
+            ErrorCode::UnterminatedComment => "E0003",
+            ErrorCode::UnexpectedToken => "E0101",
+            ErrorCode::UndeclaredIdentifier => "E0201",
+            ErrorCode::Redefinition => "E0202",
+            ErrorCode::ArgumentMismatch => "E0203",
+            ErrorCode::ReturnMismatch => "E0204",
+            ErrorCode::NotConstant => "E0205",
+            ErrorCode::AssignToConst => "E0206",
+            ErrorCode::InvalidOperand => "E0207",
+            ErrorCode::LoopControl => "E0208",
+            ErrorCode::InvalidMain => "E0209",
+            ErrorCode::InvalidArray => "E0210",
+            ErrorCode::DivisionByZero => "E0211",
+            ErrorCode::InvalidCase => "E0212",
+            ErrorCode::ShiftOutOfRange => "E0213",
+            ErrorCode::RuntimeDivision => "E0301",
+            ErrorCode::IndexOutOfBounds => "E0302",
+            ErrorCode::StackOverflow => "E0303",
+            ErrorCode::MissingReturn => "W0001",
+            ErrorCode::UndefinedShift => "W0002",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: ErrorCode,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn error(code: ErrorCode, message: String, span: Span) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message,
+            span,
+        }
+    }
+
+    pub fn warning(code: ErrorCode, message: String, span: Span) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            code,
+            message,
+            span,
+        }
+    }
+
+    // 生成形如 file:line:col 的位置以及带^下划线的源码片段
+    pub fn render(&self, sources: &SourceMap) -> String {
+        let file = sources.get(self.span.file);
+        let span = self.span;
+        let line_text = file.text.lines().nth(span.line - 1).unwrap_or("");
+        let line_no = span.line.to_string();
+        let pad = " ".repeat(line_no.len());
+        let line_start = file.text[..span.start.min(file.text.len())]
+            .rfind('\n')
+            .map_or(0, |pos| pos + 1);
+        let line_end = line_start + line_text.len();
+        // 跨行的区间只标注到行尾
+        let underline_end = span.end.min(line_end).max(span.start);
+        let width = file
+            .text
+            .get(span.start..underline_end)
+            .map_or(1, |text| text.chars().count())
+            .max(1);
+        let mut res = String::new();
+        writeln!(res, "{}", self).unwrap();
+        writeln!(
+            res,
+            "{}--> {}:{}:{}",
+            pad, file.name, span.line, span.column
+        )
+        .unwrap();
+        writeln!(res, "{} |", pad).unwrap();
+        writeln!(res, "{} | {}", line_no, line_text.replace('\t', " ")).unwrap();
+        write!(
+            res,
+            "{} | {}{}",
+            pad,
+            " ".repeat(span.column - 1),
+            "^".repeat(width)
+        )
+        .unwrap();
+        res
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}[{}]: {}", severity, self.code.code(), self.message)
+    }
+}
@@ -0,0 +1,773 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::thread;
+
+use super::ast::{
+    BType, BinaryOp, Block, BlockItem, Case, CompUnit, Decl, Expr, ExprKind, FuncDef, InitVal,
+    Item, LVal, Stmt, StmtKind, UnaryOp,
+};
+use super::diagnostic::{Diagnostic, ErrorCode};
+use super::runtime::Runtime;
+use super::span::Span;
+
+// 直接遍历通过语义检查的语法树解释执行，运算按i32补码回绕，与生成的IR一致
+// 变量都放在一块以i32为单位的内存中，浮点数按位存放，全局变量在最前面，局部变量随作用域的进出压栈与弹栈
+// 浮点数转换为整数时超出范围的结果取最接近的值，NaN取0
+
+// 函数调用深度超过MAX_DEPTH时报告栈溢出
+// 解释器递归求值，在单独的线程中运行，线程的栈按每层调用FRAME_SIZE估计，优化编译时每层调用只用1～2KB
+// 用掉的栈超过估计的大小时同样报告栈溢出，而不是让解释器本身崩溃；未优化编译时每层调用占用的栈更多，会更早报告
+const MAX_DEPTH: usize = 1 << 17;
+const FRAME_SIZE: usize = 4 << 10;
+const STACK_RESERVE: usize = 1 << 20;
+const STACK_SIZE: usize = MAX_DEPTH * FRAME_SIZE + STACK_RESERVE;
+
+#[derive(Clone)]
+struct Var {
+    addr: usize,
+    ty: BType,
+    dims: Rc<[usize]>, // 数组参数的第一维为0
+}
+
+// 表达式的值
+#[derive(Clone, Copy)]
+enum Num {
+    Int(i32),
+    Float(f32),
+}
+
+impl Num {
+    fn int(self) -> i32 {
+        match self {
+            Num::Int(val) => val,
+            Num::Float(val) => val as i32,
+        }
+    }
+
+    fn float(self) -> f32 {
+        match self {
+            Num::Int(val) => val as f32,
+            Num::Float(val) => val,
+        }
+    }
+
+    fn is_true(self) -> bool {
+        match self {
+            Num::Int(val) => val != 0,
+            Num::Float(val) => val != 0.0,
+        }
+    }
+
+    fn to(self, ty: BType) -> Num {
+        match ty {
+            BType::Int => Num::Int(self.int()),
+            BType::Float => Num::Float(self.float()),
+        }
+    }
+
+    // 存入内存的位模式
+    fn bits(self) -> i32 {
+        match self {
+            Num::Int(val) => val,
+            Num::Float(val) => val.to_bits() as i32,
+        }
+    }
+
+    fn from_bits(bits: i32, ty: BType) -> Num {
+        match ty {
+            BType::Int => Num::Int(bits),
+            BType::Float => Num::Float(f32::from_bits(bits as u32)),
+        }
+    }
+}
+
+// 函数的实参，数组按地址传递
+enum Arg {
+    Value(Num),
+    Array(usize),
+}
+
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Num),
+}
+
+// 错误装箱以减小递归求值的栈帧
+type Result<T> = std::result::Result<T, Box<Diagnostic>>;
+
+// 当前栈的位置，用于估计已经使用的栈空间
+fn stack_pos() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+pub struct Interpreter<'a> {
+    funcs: HashMap<&'a str, &'a FuncDef>,
+    params: HashMap<&'a str, Rc<[Rc<[usize]>]>>, // 各函数形参的形状
+    globals: HashMap<&'a str, Var>,
+    locals: Vec<(&'a str, Var)>,
+    frame: usize, // 当前函数的局部变量在locals中的起点
+    mem: Vec<i32>,
+    stack_base: usize,  // 解释器线程栈底附近的地址
+    stack_limit: usize, // 最多使用的栈空间
+    depth: usize,       // 当前的函数调用深度
+    max_depth: usize,
+    runtime: Runtime,
+}
+
+impl<'a> Interpreter<'a> {
+    // 返回main函数的返回值
+    pub fn run(comp_unit: &CompUnit) -> std::result::Result<i32, Diagnostic> {
+        Interpreter::run_with(comp_unit, MAX_DEPTH, STACK_SIZE)
+    }
+
+    // 调用深度与线程的栈的上限，测试中可以单独设置
+    fn run_with(
+        comp_unit: &CompUnit,
+        max_depth: usize,
+        stack_size: usize,
+    ) -> std::result::Result<i32, Diagnostic> {
+        thread::scope(|scope| {
+            let handle = thread::Builder::new()
+                .stack_size(stack_size)
+                .spawn_scoped(scope, || {
+                    Interpreter::run_in_thread(comp_unit, max_depth, stack_size)
+                });
+            match handle {
+                Ok(handle) => handle.join().unwrap(),
+                // 虚拟内存受限时可能无法分配线程的栈，错误位置指向main函数
+                Err(err) => {
+                    let span = comp_unit
+                        .items
+                        .iter()
+                        .find_map(|item| match item {
+                            Item::FuncDef(func) if func.name == "main" => Some(func.name_span),
+                            _ => None,
+                        })
+                        .unwrap();
+                    let message = format!(
+                        "stack overflow: cannot allocate {} MiB of stack for the interpreter: {}",
+                        stack_size >> 20,
+                        err
+                    );
+                    Err(Box::new(Diagnostic::error(
+                        ErrorCode::StackOverflow,
+                        message,
+                        span,
+                    )))
+                }
+            }
+        })
+        .map_err(|err| *err)
+    }
+
+    fn run_in_thread(comp_unit: &'a CompUnit, max_depth: usize, stack_size: usize) -> Result<i32> {
+        let mut interp = Interpreter {
+            funcs: HashMap::new(),
+            params: HashMap::new(),
+            globals: HashMap::new(),
+            locals: vec![],
+            frame: 0,
+            mem: vec![],
+            stack_base: stack_pos(),
+            stack_limit: stack_size.saturating_sub(STACK_RESERVE),
+            depth: 0,
+            max_depth,
+            runtime: Runtime::new(),
+        };
+        let res = interp.run_main(comp_unit);
+        interp.runtime.flush();
+        res
+    }
+
+    fn run_main(&mut self, comp_unit: &'a CompUnit) -> Result<i32> {
+        for item in &comp_unit.items {
+            match item {
+                Item::Decl(decl) => self.decl(decl, true)?,
+                Item::FuncDef(func) => {
+                    let mut shapes = vec![];
+                    for param in &func.params {
+                        let mut dims = vec![];
+                        for dim in &param.dims {
+                            dims.push(match dim {
+                                Some(exp) => self.eval(exp)?.int() as usize,
+                                None => 0,
+                            });
+                        }
+                        shapes.push(Rc::from(dims));
+                    }
+                    self.funcs.insert(&func.name, func);
+                    self.params.insert(&func.name, Rc::from(shapes));
+                }
+            }
+        }
+        Ok(self.call("main", vec![], comp_unit.end)?.int())
+    }
+
+    fn error<T>(&self, code: ErrorCode, span: Span, message: &str) -> Result<T> {
+        Err(Box::new(Diagnostic::error(code, message.to_string(), span)))
+    }
+
+    fn lookup(&self, name: &str) -> Var {
+        match self.locals[self.frame..]
+            .iter()
+            .rev()
+            .find(|(local, _)| *local == name)
+        {
+            Some((_, var)) => var.clone(),
+            None => self.globals[name].clone(),
+        }
+    }
+
+    fn alloc(&mut self, size: usize) -> usize {
+        let addr = self.mem.len();
+        self.mem.resize(addr + size, 0);
+        addr
+    }
+
+    fn decl(&mut self, decl: &'a Decl, is_global: bool) -> Result<()> {
+        for def in &decl.defs {
+            let mut dims = vec![];
+            for dim in &def.dims {
+                dims.push(self.eval(dim)?.int() as usize);
+            }
+            let addr = self.alloc(dims.iter().product());
+            let dims: Rc<[usize]> = Rc::from(dims);
+            let var = Var {
+                addr,
+                ty: decl.ty,
+                dims: dims.clone(),
+            };
+            // 与生成IR时一致，除了标量常量，局部变量在求初始值之前就已经声明
+            let declare_first = !(is_global || decl.is_const && dims.is_empty());
+            if declare_first {
+                self.locals.push((&def.name, var.clone()));
+            }
+            if let Some(init) = &def.init {
+                self.init(addr, decl.ty, &dims, init)?;
+            }
+            if is_global {
+                self.globals.insert(&def.name, var);
+            } else if !declare_first {
+                self.locals.push((&def.name, var));
+            }
+        }
+        Ok(())
+    }
+
+    // 初始值列表严格嵌套，每一项对应去掉第一维后的子数组
+    fn init(&mut self, addr: usize, ty: BType, dims: &[usize], init: &'a InitVal) -> Result<()> {
+        match init {
+            InitVal::Expr(exp) => self.mem[addr] = self.eval(exp)?.to(ty).bits(),
+            InitVal::List(vals, _) => {
+                let stride: usize = dims[1..].iter().product();
+                for (index, val) in vals.iter().enumerate() {
+                    self.init(addr + index * stride, ty, &dims[1..], val)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, block: &'a Block) -> Result<Flow> {
+        let (locals, mem) = (self.locals.len(), self.mem.len());
+        let mut flow = Flow::Normal;
+        for item in &block.items {
+            match item {
+                BlockItem::Decl(decl) => self.decl(decl, false)?,
+                BlockItem::Stmt(stmt) => flow = self.stmt(stmt)?,
+            }
+            if !matches!(flow, Flow::Normal) {
+                break;
+            }
+        }
+        self.locals.truncate(locals);
+        self.mem.truncate(mem);
+        Ok(flow)
+    }
+
+    fn stmt(&mut self, stmt: &'a Stmt) -> Result<Flow> {
+        match &stmt.kind {
+            StmtKind::Expr(Some(exp)) => {
+                self.eval(exp)?;
+            }
+            StmtKind::Expr(None) => {}
+            StmtKind::Block(block) => return self.block(block),
+            StmtKind::If(cond, then_stmt, else_stmt) => {
+                if self.eval(cond)?.is_true() {
+                    return self.stmt(then_stmt);
+                } else if let Some(else_stmt) = else_stmt {
+                    return self.stmt(else_stmt);
+                }
+            }
+            StmtKind::While(cond, body) => {
+                while self.eval(cond)?.is_true() {
+                    match self.stmt(body)? {
+                        Flow::Break => break,
+                        Flow::Return(val) => return Ok(Flow::Return(val)),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                }
+            }
+            StmtKind::For(init, cond, step, body) => {
+                // 初始化中的声明只在for中可见
+                let (locals, mem) = (self.locals.len(), self.mem.len());
+                match init.as_deref() {
+                    Some(BlockItem::Decl(decl)) => self.decl(decl, false)?,
+                    Some(BlockItem::Stmt(stmt)) => {
+                        self.stmt(stmt)?;
+                    }
+                    None => {}
+                }
+                let mut flow = Flow::Normal;
+                loop {
+                    // 省略条件时为真
+                    if let Some(cond) = cond {
+                        if !self.eval(cond)?.is_true() {
+                            break;
+                        }
+                    }
+                    match self.stmt(body)? {
+                        Flow::Break => break,
+                        Flow::Return(val) => {
+                            flow = Flow::Return(val);
+                            break;
+                        }
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                    if let Some(step) = step {
+                        self.eval(step)?;
+                    }
+                }
+                self.locals.truncate(locals);
+                self.mem.truncate(mem);
+                return Ok(flow);
+            }
+            StmtKind::DoWhile(body, cond) => loop {
+                match self.stmt(body)? {
+                    Flow::Break => break,
+                    Flow::Return(val) => return Ok(Flow::Return(val)),
+                    Flow::Normal | Flow::Continue => {}
+                }
+                if !self.eval(cond)?.is_true() {
+                    break;
+                }
+            },
+            StmtKind::Switch(exp, cases) => return self.switch(exp, cases),
+            StmtKind::Break => return Ok(Flow::Break),
+            StmtKind::Continue => return Ok(Flow::Continue),
+            StmtKind::Return(Some(exp)) => return Ok(Flow::Return(self.eval(exp)?)),
+            StmtKind::Return(None) => return Ok(Flow::Return(Num::Int(0))),
+        }
+        Ok(Flow::Normal)
+    }
+
+    // 从匹配的标号开始顺序执行到break，跳过的标号后的变量仍然可见，但不初始化
+    fn switch(&mut self, exp: &'a Expr, cases: &'a [Case]) -> Result<Flow> {
+        let val = self.eval(exp)?.int();
+        let mut start = cases.iter().position(|case| case.value.is_none());
+        for (index, case) in cases.iter().enumerate() {
+            if let Some(value) = &case.value {
+                if self.eval(value)?.int() == val {
+                    start = Some(index);
+                    break;
+                }
+            }
+        }
+        let start = match start {
+            Some(start) => start,
+            None => return Ok(Flow::Normal),
+        };
+        let (locals, mem) = (self.locals.len(), self.mem.len());
+        for case in &cases[..start] {
+            for item in &case.items {
+                if let BlockItem::Decl(decl) = item {
+                    self.skip_decl(decl)?;
+                }
+            }
+        }
+        let mut flow = Flow::Normal;
+        'cases: for case in &cases[start..] {
+            for item in &case.items {
+                match item {
+                    BlockItem::Decl(decl) => self.decl(decl, false)?,
+                    BlockItem::Stmt(stmt) => flow = self.stmt(stmt)?,
+                }
+                if !matches!(flow, Flow::Normal) {
+                    break 'cases;
+                }
+            }
+        }
+        self.locals.truncate(locals);
+        self.mem.truncate(mem);
+        // break只结束switch，continue属于外层的循环
+        Ok(match flow {
+            Flow::Break => Flow::Normal,
+            flow => flow,
+        })
+    }
+
+    // 与生成IR时一致，跳过的常量也没有初始值
+    fn skip_decl(&mut self, decl: &'a Decl) -> Result<()> {
+        for def in &decl.defs {
+            let mut dims = vec![];
+            for dim in &def.dims {
+                dims.push(self.eval(dim)?.int() as usize);
+            }
+            let addr = self.alloc(dims.iter().product());
+            let var = Var {
+                addr,
+                ty: decl.ty,
+                dims: Rc::from(dims),
+            };
+            self.locals.push((&def.name, var));
+        }
+        Ok(())
+    }
+
+    // 左值的地址、元素类型与剩余的维数，下标越界时报错
+    fn locate(&mut self, lval: &'a LVal) -> Result<(usize, BType, usize)> {
+        let var = self.lookup(&lval.name);
+        let mut addr = var.addr;
+        for (pos, index) in lval.indices.iter().enumerate() {
+            let val = self.eval(index)?.int();
+            let dim = var.dims[pos];
+            // 数组参数的第一维未知，只检查下标非负
+            if val < 0 || (dim != 0 && val as usize >= dim) {
+                let message = if dim == 0 {
+                    format!("index {} is out of bounds", val)
+                } else {
+                    format!(
+                        "index {} is out of bounds for dimension of size {}",
+                        val, dim
+                    )
+                };
+                return self.error(ErrorCode::IndexOutOfBounds, index.span, &message);
+            }
+            let stride: usize = var.dims[pos + 1..].iter().product();
+            addr += val as usize * stride;
+        }
+        if lval.indices.len() == var.dims.len() && addr >= self.mem.len() {
+            return self.error(
+                ErrorCode::IndexOutOfBounds,
+                lval.span,
+                "array access is out of bounds",
+            );
+        }
+        Ok((addr, var.ty, var.dims.len() - lval.indices.len()))
+    }
+
+    fn eval(&mut self, exp: &'a Expr) -> Result<Num> {
+        match &exp.kind {
+            ExprKind::Number(val) => Ok(Num::Int(*val)),
+            ExprKind::Float(val) => Ok(Num::Float(*val)),
+            ExprKind::LVal(lval) => {
+                let (addr, ty, _) = self.locate(lval)?;
+                Ok(Num::from_bits(self.mem[addr], ty))
+            }
+            ExprKind::Call {
+                name,
+                name_span,
+                args,
+            } => {
+                let mut vals = vec![];
+                for arg in args {
+                    vals.push(match &arg.kind {
+                        ExprKind::LVal(lval) => {
+                            let (addr, ty, rest) = self.locate(lval)?;
+                            if rest == 0 {
+                                Arg::Value(Num::from_bits(self.mem[addr], ty))
+                            } else {
+                                Arg::Array(addr)
+                            }
+                        }
+                        _ => Arg::Value(self.eval(arg)?),
+                    });
+                }
+                self.call(name, vals, *name_span)
+            }
+            ExprKind::Unary(op, operand) => {
+                let val = self.eval(operand)?;
+                Ok(match (op, val) {
+                    (UnaryOp::Plus, _) => val,
+                    (UnaryOp::Minus, Num::Int(val)) => Num::Int(val.wrapping_neg()),
+                    (UnaryOp::Minus, Num::Float(val)) => Num::Float(-val),
+                    (UnaryOp::Not, _) => Num::Int(!val.is_true() as i32),
+                    (UnaryOp::BitNot, Num::Int(val)) => Num::Int(!val),
+                    (UnaryOp::BitNot, Num::Float(_)) => unreachable!(),
+                })
+            }
+            ExprKind::Binary(BinaryOp::And, lhs, rhs) => Ok(Num::Int(
+                (self.eval(lhs)?.is_true() && self.eval(rhs)?.is_true()) as i32,
+            )),
+            ExprKind::Binary(BinaryOp::Or, lhs, rhs) => Ok(Num::Int(
+                (self.eval(lhs)?.is_true() || self.eval(rhs)?.is_true()) as i32,
+            )),
+            ExprKind::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (self.eval(lhs)?, self.eval(rhs)?);
+                self.binary(*op, lhs, rhs, exp.span)
+            }
+            // 只求值选中的分支，再转换为两个分支的公共类型
+            ExprKind::Conditional(cond, then_exp, else_exp) => {
+                let ty = self.ty(exp);
+                let val = if self.eval(cond)?.is_true() {
+                    self.eval(then_exp)?
+                } else {
+                    self.eval(else_exp)?
+                };
+                Ok(val.to(ty))
+            }
+            // 左值的地址只计算一次，复合赋值先读出原来的值
+            ExprKind::Assign(op, lval, rhs) => {
+                let (addr, ty, _) = self.locate(lval)?;
+                let val = match op {
+                    None => self.eval(rhs)?,
+                    Some(op) => {
+                        let old = Num::from_bits(self.mem[addr], ty);
+                        let rhs = self.eval(rhs)?;
+                        self.binary(*op, old, rhs, exp.span)?
+                    }
+                };
+                let val = val.to(ty);
+                self.mem[addr] = val.bits();
+                Ok(val)
+            }
+            ExprKind::IncDec(op, lval) => {
+                let (addr, ty, _) = self.locate(lval)?;
+                let old = Num::from_bits(self.mem[addr], ty);
+                let val = self.binary(op.binary_op(), old, Num::Int(1), exp.span)?;
+                self.mem[addr] = val.bits();
+                Ok(if op.is_prefix() { val } else { old })
+            }
+            ExprKind::Comma(lhs, rhs) => {
+                self.eval(lhs)?;
+                self.eval(rhs)
+            }
+        }
+    }
+
+    // 表达式的静态类型，有一个操作数是浮点数时算术运算的结果为浮点数
+    fn ty(&self, exp: &Expr) -> BType {
+        match &exp.kind {
+            ExprKind::Number(_) => BType::Int,
+            ExprKind::Float(_) => BType::Float,
+            ExprKind::LVal(lval) => self.lookup(&lval.name).ty,
+            ExprKind::Call { name, .. } => match self.funcs.get(name.as_str()) {
+                Some(func) => func.ty,
+                None if name == "getfloat" => BType::Float,
+                None => BType::Int,
+            },
+            ExprKind::Unary(UnaryOp::Not, _) => BType::Int,
+            ExprKind::Unary(_, operand) => self.ty(operand),
+            ExprKind::Binary(
+                BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod | BinaryOp::Add | BinaryOp::Sub,
+                lhs,
+                rhs,
+            )
+            | ExprKind::Conditional(_, lhs, rhs) => {
+                if self.ty(lhs) == BType::Float || self.ty(rhs) == BType::Float {
+                    BType::Float
+                } else {
+                    BType::Int
+                }
+            }
+            ExprKind::Binary(..) => BType::Int,
+            ExprKind::Assign(_, lval, _) | ExprKind::IncDec(_, lval) => self.lookup(&lval.name).ty,
+            ExprKind::Comma(_, rhs) => self.ty(rhs),
+        }
+    }
+
+    fn binary(&self, op: BinaryOp, lhs: Num, rhs: Num, span: Span) -> Result<Num> {
+        let (lhs, rhs) = match (lhs, rhs) {
+            (Num::Int(lhs), Num::Int(rhs)) => (lhs, rhs),
+            // 有一个操作数是浮点数时按浮点数计算，语义检查保证没有取模与位运算
+            (lhs, rhs) => {
+                let (lhs, rhs) = (lhs.float(), rhs.float());
+                return Ok(match op {
+                    BinaryOp::Mul => Num::Float(lhs * rhs),
+                    BinaryOp::Div => Num::Float(lhs / rhs),
+                    BinaryOp::Add => Num::Float(lhs + rhs),
+                    BinaryOp::Sub => Num::Float(lhs - rhs),
+                    BinaryOp::Lt => Num::Int((lhs < rhs) as i32),
+                    BinaryOp::Gt => Num::Int((lhs > rhs) as i32),
+                    BinaryOp::Le => Num::Int((lhs <= rhs) as i32),
+                    BinaryOp::Ge => Num::Int((lhs >= rhs) as i32),
+                    BinaryOp::Eq => Num::Int((lhs == rhs) as i32),
+                    BinaryOp::Ne => Num::Int((lhs != rhs) as i32),
+                    _ => unreachable!(),
+                });
+            }
+        };
+        if rhs == 0 && matches!(op, BinaryOp::Div | BinaryOp::Mod) {
+            return self.error(ErrorCode::RuntimeDivision, span, "division by zero");
+        }
+        Ok(Num::Int(match op {
+            BinaryOp::Mul => lhs.wrapping_mul(rhs),
+            BinaryOp::Div => lhs.wrapping_div(rhs),
+            BinaryOp::Mod => lhs.wrapping_rem(rhs),
+            BinaryOp::Add => lhs.wrapping_add(rhs),
+            BinaryOp::Sub => lhs.wrapping_sub(rhs),
+            // 移位量超出范围时结果未定义，与其他执行方式一致只取低5位
+            BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+            BinaryOp::Shr => lhs.wrapping_shr(rhs as u32),
+            BinaryOp::BitAnd => lhs & rhs,
+            BinaryOp::BitXor => lhs ^ rhs,
+            BinaryOp::BitOr => lhs | rhs,
+            BinaryOp::Lt => (lhs < rhs) as i32,
+            BinaryOp::Gt => (lhs > rhs) as i32,
+            BinaryOp::Le => (lhs <= rhs) as i32,
+            BinaryOp::Ge => (lhs >= rhs) as i32,
+            BinaryOp::Eq => (lhs == rhs) as i32,
+            BinaryOp::Ne => (lhs != rhs) as i32,
+            BinaryOp::And | BinaryOp::Or => unreachable!(),
+        }))
+    }
+
+    fn call(&mut self, name: &str, args: Vec<Arg>, span: Span) -> Result<Num> {
+        if let Some(val) = self.builtin(name, &args) {
+            return Ok(val);
+        }
+        if self.depth == self.max_depth || self.stack_base.abs_diff(stack_pos()) > self.stack_limit
+        {
+            return self.error(
+                ErrorCode::StackOverflow,
+                span,
+                "stack overflow: recursion is too deep",
+            );
+        }
+        let (func, shapes) = (self.funcs[name], self.params[name].clone());
+        let (frame, locals, mem) = (self.frame, self.locals.len(), self.mem.len());
+        self.frame = locals;
+        for ((param, dims), arg) in func.params.iter().zip(shapes.iter()).zip(args) {
+            let addr = match arg {
+                Arg::Value(val) => {
+                    let addr = self.alloc(1);
+                    self.mem[addr] = val.to(param.ty).bits();
+                    addr
+                }
+                Arg::Array(addr) => addr,
+            };
+            let var = Var {
+                addr,
+                ty: param.ty,
+                dims: dims.clone(),
+            };
+            self.locals.push((&param.name, var));
+        }
+        self.depth += 1;
+        let flow = self.block(&func.body);
+        self.depth -= 1;
+        self.frame = frame;
+        self.locals.truncate(locals);
+        self.mem.truncate(mem);
+        // 非void函数缺少返回值时与生成的IR一样返回0
+        match flow? {
+            Flow::Return(val) => Ok(val.to(func.ty)),
+            _ => Ok(Num::Int(0).to(func.ty)),
+        }
+    }
+
+    // 运行时库函数，不是库函数时返回None
+    fn builtin(&mut self, name: &str, args: &[Arg]) -> Option<Num> {
+        let value = |index: usize| match args[index] {
+            Arg::Value(val) => val,
+            Arg::Array(addr) => Num::Int(addr as i32),
+        };
+        let res = match name {
+            "getint" => self.runtime.getint(),
+            "getch" => self.runtime.getch(),
+            "getfloat" => return Some(Num::Float(self.runtime.getfloat())),
+            "getarray" | "getfarray" => {
+                let (addr, count) = (value(0).int() as usize, self.runtime.getint());
+                for index in 0..count.max(0) as usize {
+                    let val = if name == "getarray" {
+                        self.runtime.getint()
+                    } else {
+                        self.runtime.getfloat().to_bits() as i32
+                    };
+                    if let Some(elem) = self.mem.get_mut(addr + index) {
+                        *elem = val;
+                    }
+                }
+                count
+            }
+            "putint" => {
+                self.runtime.putint(value(0).int());
+                0
+            }
+            "putch" => {
+                self.runtime.putch(value(0).int());
+                0
+            }
+            "putfloat" => {
+                self.runtime.putfloat(value(0).float());
+                0
+            }
+            "putarray" | "putfarray" => {
+                let (count, addr) = (value(0).int(), value(1).int() as usize);
+                let end = (addr + count.max(0) as usize).min(self.mem.len());
+                let vals = &self.mem[addr.min(end)..end];
+                if name == "putarray" {
+                    self.runtime.putarray(count, vals);
+                } else {
+                    let vals: Vec<f32> =
+                        vals.iter().map(|val| f32::from_bits(*val as u32)).collect();
+                    self.runtime.putfarray(count, &vals);
+                }
+                0
+            }
+            _ => return None,
+        };
+        Some(Num::Int(res))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Interpreter, MAX_DEPTH};
+    use crate::testing::analyze_source;
+
+    fn run(source: &str) -> Result<i32, &'static str> {
+        let (comp_unit, _) = analyze_source(source);
+        Interpreter::run(&comp_unit).map_err(|err| err.code.code())
+    }
+
+    fn run_with(source: &str, max_depth: usize, stack_size: usize) -> Result<i32, &'static str> {
+        let (comp_unit, _) = analyze_source(source);
+        Interpreter::run_with(&comp_unit, max_depth, stack_size).map_err(|err| err.code.code())
+    }
+
+    // 未优化编译时每层调用占用的栈比FRAME_SIZE多，这里的深度不到MAX_DEPTH的一半
+    #[test]
+    fn deep_recursion() {
+        let source = "int f(int n) { if (n == 0) return 0; return f(n - 1) + 1; }
+            int main() { return f(50000) - 50000; }";
+        assert_eq!(run(source), Ok(0));
+    }
+
+    // 栈足够大时由调用深度的上限报告栈溢出
+    #[test]
+    fn reports_max_depth() {
+        let source = "int f(int n) { if (n == 0) return 0; return f(n - 1) + 1; }
+            int main() { return f(1000); }";
+        assert_eq!(run_with(source, 2000, 256 << 20), Ok(1000));
+        assert_eq!(run_with(source, 1000, 256 << 20), Err("E0303"));
+    }
+
+    // 栈不够用时在解释器崩溃之前报告栈溢出
+    #[test]
+    fn reports_stack_exhaustion() {
+        let source = "int f(int n) { if (n == 0) return 0; return f(n - 1) + 1; }
+            int main() { return f(100000); }";
+        assert_eq!(run_with(source, MAX_DEPTH, 4 << 20), Err("E0303"));
+    }
+
+    // 无法分配线程的栈时报告错误而不是panic
+    #[test]
+    fn reports_spawn_failure() {
+        let source = "int main() { return 0; }";
+        assert_eq!(run_with(source, MAX_DEPTH, 1 << 60), Err("E0303"));
+    }
+}
@@ -0,0 +1,735 @@
+use std::fmt;
+
+use super::super::runtime::Runtime;
+use super::{
+    BinOp, BlockId, CmpOp, Constant, FuncId, Function, InstId, InstKind, Module, Type, Value,
+};
+
+// 不依赖LLVM直接执行calcium生成的IR，遇到越界访问、除以零等未定义行为时停止并报告出错的指令
+// 内存以i32为单位，每个全局变量与alloca是一个对象，指针记录所在对象的范围以检查越界
+// 浮点数按位存放在i32中，由指令决定如何解释
+// 函数调用使用显式的栈，寄存器与alloca的总数超过STACK_CELLS时报告栈溢出
+
+const STACK_CELLS: usize = 1 << 24;
+
+#[derive(Clone, Copy)]
+struct Ptr {
+    base: usize, // 对象的起点
+    size: usize,
+    offset: isize, // 相对对象起点的偏移，可能越界
+}
+
+#[derive(Clone, Copy)]
+enum Val {
+    Int(i32),
+    Ptr(Ptr),
+}
+
+pub struct Trap {
+    pub func: String,
+    pub inst: String,
+    pub message: String,
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\n  in @{}: {}", self.message, self.func, self.inst)
+    }
+}
+
+struct Frame {
+    func: FuncId,
+    regs: Vec<Val>, // 以InstId为下标
+    args: Vec<Val>,
+    block: BlockId,
+    pos: usize,   // 下一条指令在基本块中的位置
+    mem: usize,   // 进入函数时的内存大小，返回时释放alloca
+    cells: usize, // 进入函数前栈上的值的数量
+}
+
+impl Frame {
+    fn value(&self, globals: &[Ptr], value: Value) -> Val {
+        match value {
+            Value::Undef => Val::Int(0),
+            Value::Int(val) => Val::Int(val),
+            Value::Float(bits) => Val::Int(bits as i32),
+            Value::Bool(val) => Val::Int(val as i32),
+            Value::Inst(inst) => self.regs[inst.0],
+            Value::Param(index) => self.args[index],
+            Value::Global(global) => Val::Ptr(globals[global.0]),
+        }
+    }
+}
+
+// 类型占用的i32个数
+fn cells(ty: &Type) -> usize {
+    match ty {
+        Type::Void => 0,
+        Type::I1 | Type::I32 | Type::Float | Type::Pointer(_) => 1,
+        Type::Array(len, elem) => len * cells(elem),
+    }
+}
+
+struct Executor<'a> {
+    module: &'a Module,
+    geps: Vec<Vec<Vec<(usize, usize)>>>, // 每条getelementptr各下标的步长与上界，第一个下标没有上界
+    globals: Vec<Ptr>,
+    mem: Vec<i32>,
+    frames: Vec<Frame>,
+    cells: usize, // 栈上的值的数量
+    phis: Vec<(InstId, Val)>,
+    runtime: Runtime,
+}
+
+// 执行main函数，返回其返回值
+pub fn run(module: &Module) -> Result<i32, Trap> {
+    run_with(module, Runtime::new())
+}
+
+fn run_with(module: &Module, runtime: Runtime) -> Result<i32, Trap> {
+    let mut executor = Executor {
+        module,
+        geps: vec![],
+        globals: vec![],
+        mem: vec![],
+        frames: vec![],
+        cells: 0,
+        phis: vec![],
+        runtime,
+    };
+    let res = executor.run();
+    executor.runtime.flush();
+    res
+}
+
+impl<'a> Executor<'a> {
+    fn run(&mut self) -> Result<i32, Trap> {
+        for func in &self.module.functions {
+            let mut geps = vec![vec![]; func.insts.len()];
+            for (index, inst) in func.insts.iter().enumerate() {
+                if let InstKind::Gep(base, indices) = &inst.kind {
+                    let mut ty = self.module.value_type(func, *base).pointee().clone();
+                    let mut steps = vec![(cells(&ty), usize::MAX)];
+                    for _ in 1..indices.len() {
+                        ty = match ty {
+                            Type::Array(len, elem) => {
+                                steps.push((cells(&elem), len));
+                                *elem
+                            }
+                            _ => panic!("getelementptr into {}", ty),
+                        };
+                    }
+                    geps[index] = steps;
+                }
+            }
+            self.geps.push(geps);
+        }
+        for global in &self.module.globals {
+            let base = self.mem.len();
+            let size = cells(&global.ty);
+            self.mem.resize(base + size, 0);
+            Self::init(&mut self.mem[base..], &global.ty, &global.init);
+            self.globals.push(Ptr {
+                base,
+                size,
+                offset: 0,
+            });
+        }
+        let main = self.module.get_function("main").expect("no main function");
+        self.enter(main, vec![]);
+        loop {
+            let frame = self.frames.last().unwrap();
+            let func = self.module.function(frame.func);
+            let inst = match func.block(frame.block).insts.get(frame.pos) {
+                Some(inst) => *inst,
+                None => panic!("block without terminator in @{}", func.name),
+            };
+            self.frames.last_mut().unwrap().pos += 1;
+            if let Some(code) = self.step(func, inst)? {
+                return Ok(code);
+            }
+        }
+    }
+
+    fn init(mem: &mut [i32], ty: &Type, init: &Constant) {
+        match (init, ty) {
+            (Constant::Int(val), _) => mem[0] = *val,
+            (Constant::Float(val), _) => mem[0] = val.to_bits() as i32,
+            (Constant::Zero, _) => {}
+            (Constant::Array(elems), Type::Array(_, elem_ty)) => {
+                let size = cells(elem_ty);
+                for (index, elem) in elems.iter().enumerate() {
+                    Self::init(&mut mem[index * size..], elem_ty, elem);
+                }
+            }
+            (Constant::Array(_), _) => panic!("array constant of type {}", ty),
+        }
+    }
+
+    fn trap<T>(&self, func: &Function, inst: InstId, message: String) -> Result<T, Trap> {
+        Err(Trap {
+            func: func.name.clone(),
+            inst: self.module.inst_text(func, inst),
+            message,
+        })
+    }
+
+    fn value(&self, value: Value) -> Val {
+        self.frames.last().unwrap().value(&self.globals, value)
+    }
+
+    fn int(&self, func: &Function, inst: InstId, value: Value) -> Result<i32, Trap> {
+        match self.value(value) {
+            Val::Int(val) => Ok(val),
+            Val::Ptr(_) => self.trap(
+                func,
+                inst,
+                "expected an integer, found a pointer".to_string(),
+            ),
+        }
+    }
+
+    fn float(&self, func: &Function, inst: InstId, value: Value) -> Result<f32, Trap> {
+        Ok(f32::from_bits(self.int(func, inst, value)? as u32))
+    }
+
+    fn ptr(&self, func: &Function, inst: InstId, value: Value) -> Result<Ptr, Trap> {
+        match self.value(value) {
+            Val::Ptr(ptr) => Ok(ptr),
+            Val::Int(_) => self.trap(
+                func,
+                inst,
+                "expected a pointer, found an integer".to_string(),
+            ),
+        }
+    }
+
+    // 指针指向的连续count个i32在对象内时返回其起点
+    fn addr(&self, func: &Function, inst: InstId, ptr: Ptr, count: usize) -> Result<usize, Trap> {
+        if ptr.offset < 0 || ptr.offset as usize + count > ptr.size {
+            return self.trap(
+                func,
+                inst,
+                format!(
+                    "out-of-bounds access at offset {} of an object of {} elements",
+                    ptr.offset, ptr.size
+                ),
+            );
+        }
+        Ok(ptr.base + ptr.offset as usize)
+    }
+
+    fn set(&mut self, inst: InstId, val: Val) {
+        self.frames.last_mut().unwrap().regs[inst.0] = val;
+    }
+
+    fn alloc(&mut self, size: usize) -> Option<usize> {
+        if self.cells + size > STACK_CELLS {
+            return None;
+        }
+        self.cells += size;
+        let base = self.mem.len();
+        self.mem.resize(base + size, 0);
+        Some(base)
+    }
+
+    // 栈空间不足时返回false
+    fn enter(&mut self, callee: FuncId, args: Vec<Val>) -> bool {
+        let func = self.module.function(callee);
+        let (mem, cells) = (self.mem.len(), self.cells);
+        if self.cells + func.insts.len() > STACK_CELLS {
+            return false;
+        }
+        self.cells += func.insts.len();
+        self.frames.push(Frame {
+            func: callee,
+            regs: vec![Val::Int(0); func.insts.len()],
+            args,
+            block: func.entry(),
+            pos: 0,
+            mem,
+            cells,
+        });
+        true
+    }
+
+    // 跳转到target，并行地为开头的phi赋值
+    fn jump(&mut self, func: &Function, target: BlockId) -> Result<(), Trap> {
+        let from = self.frames.last().unwrap().block;
+        self.phis.clear();
+        for phi in &func.block(target).insts {
+            let incoming = match &func.inst(*phi).kind {
+                InstKind::Phi(incoming) => incoming,
+                _ => break,
+            };
+            match incoming.iter().find(|(_, pred)| *pred == from) {
+                Some((val, _)) => {
+                    let val = self.value(*val);
+                    self.phis.push((*phi, val));
+                }
+                None => {
+                    return self.trap(
+                        func,
+                        *phi,
+                        "phi has no incoming value for the predecessor".to_string(),
+                    )
+                }
+            }
+        }
+        let frame = self.frames.last_mut().unwrap();
+        for (phi, val) in &self.phis {
+            frame.regs[phi.0] = *val;
+        }
+        frame.block = target;
+        frame.pos = self.phis.len();
+        Ok(())
+    }
+
+    // 执行一条指令，main函数返回时得到其返回值
+    fn step(&mut self, func: &'a Function, inst: InstId) -> Result<Option<i32>, Trap> {
+        match &func.inst(inst).kind {
+            InstKind::Alloca(ty) => {
+                let size = cells(ty);
+                let base = match self.alloc(size) {
+                    Some(base) => base,
+                    None => return self.trap(func, inst, "stack overflow".to_string()),
+                };
+                self.set(
+                    inst,
+                    Val::Ptr(Ptr {
+                        base,
+                        size,
+                        offset: 0,
+                    }),
+                );
+            }
+            InstKind::Load(ptr) => {
+                let ptr = self.ptr(func, inst, *ptr)?;
+                let addr = self.addr(func, inst, ptr, 1)?;
+                self.set(inst, Val::Int(self.mem[addr]));
+            }
+            InstKind::Store(val, ptr) => {
+                let val = self.int(func, inst, *val)?;
+                let ptr = self.ptr(func, inst, *ptr)?;
+                let addr = self.addr(func, inst, ptr, 1)?;
+                self.mem[addr] = val;
+            }
+            InstKind::Binary(op, lhs, rhs) if op.is_float() => {
+                let lhs = self.float(func, inst, *lhs)?;
+                let rhs = self.float(func, inst, *rhs)?;
+                let res = match op {
+                    BinOp::FAdd => lhs + rhs,
+                    BinOp::FSub => lhs - rhs,
+                    BinOp::FMul => lhs * rhs,
+                    _ => lhs / rhs,
+                };
+                self.set(inst, Val::Int(res.to_bits() as i32));
+            }
+            InstKind::Binary(op, lhs, rhs) => {
+                let lhs = self.int(func, inst, *lhs)?;
+                let rhs = self.int(func, inst, *rhs)?;
+                let res = match op {
+                    BinOp::Add => lhs.wrapping_add(rhs),
+                    BinOp::Sub => lhs.wrapping_sub(rhs),
+                    BinOp::Mul => lhs.wrapping_mul(rhs),
+                    BinOp::SDiv | BinOp::SRem if rhs == 0 => {
+                        return self.trap(func, inst, "division by zero".to_string())
+                    }
+                    BinOp::SDiv | BinOp::SRem if lhs == i32::MIN && rhs == -1 => {
+                        return self.trap(func, inst, "signed division overflow".to_string())
+                    }
+                    BinOp::SDiv => lhs / rhs,
+                    BinOp::SRem => lhs % rhs,
+                    BinOp::And => lhs & rhs,
+                    BinOp::Or => lhs | rhs,
+                    BinOp::Xor => lhs ^ rhs,
+                    // 移位量超出范围时LLVM的结果是poison，这里与x86一样只取低5位，程序不应依赖这个结果
+                    BinOp::Shl => lhs.wrapping_shl(rhs as u32),
+                    _ => lhs.wrapping_shr(rhs as u32),
+                };
+                self.set(inst, Val::Int(res));
+            }
+            InstKind::Icmp(op, lhs, rhs) => {
+                let lhs = self.int(func, inst, *lhs)?;
+                let rhs = self.int(func, inst, *rhs)?;
+                let res = match op {
+                    CmpOp::Eq => lhs == rhs,
+                    CmpOp::Ne => lhs != rhs,
+                    CmpOp::Slt => lhs < rhs,
+                    CmpOp::Sgt => lhs > rhs,
+                    CmpOp::Sle => lhs <= rhs,
+                    CmpOp::Sge => lhs >= rhs,
+                };
+                self.set(inst, Val::Int(res as i32));
+            }
+            InstKind::Fcmp(op, lhs, rhs) => {
+                let lhs = self.float(func, inst, *lhs)?;
+                let rhs = self.float(func, inst, *rhs)?;
+                let res = match op {
+                    CmpOp::Eq => lhs == rhs,
+                    CmpOp::Ne => lhs != rhs,
+                    CmpOp::Slt => lhs < rhs,
+                    CmpOp::Sgt => lhs > rhs,
+                    CmpOp::Sle => lhs <= rhs,
+                    CmpOp::Sge => lhs >= rhs,
+                };
+                self.set(inst, Val::Int(res as i32));
+            }
+            InstKind::Zext(val) => {
+                let val = self.int(func, inst, *val)?;
+                self.set(inst, Val::Int(val));
+            }
+            InstKind::Sitofp(val) => {
+                let val = self.int(func, inst, *val)? as f32;
+                self.set(inst, Val::Int(val.to_bits() as i32));
+            }
+            InstKind::Fptosi(val) => {
+                let val = self.float(func, inst, *val)?;
+                if !(val > -2147483904.0 && val < 2147483648.0) {
+                    return self.trap(
+                        func,
+                        inst,
+                        format!("{} is out of range for the integer conversion", val),
+                    );
+                }
+                self.set(inst, Val::Int(val as i32));
+            }
+            InstKind::Gep(base, indices) => {
+                let mut ptr = self.ptr(func, inst, *base)?;
+                let func_id = self.frames.last().unwrap().func;
+                for (index, (stride, bound)) in indices.iter().zip(&self.geps[func_id.0][inst.0]) {
+                    let index = self.int(func, inst, *index)?;
+                    if *bound != usize::MAX && (index < 0 || index as usize >= *bound) {
+                        return self.trap(
+                            func,
+                            inst,
+                            format!(
+                                "index {} is out of bounds for an array of {} elements",
+                                index, bound
+                            ),
+                        );
+                    }
+                    ptr.offset += index as isize * *stride as isize;
+                }
+                // 允许指向对象末尾之后，访问时再检查
+                if ptr.offset < 0 || ptr.offset as usize > ptr.size {
+                    return self.trap(
+                        func,
+                        inst,
+                        format!(
+                            "pointer offset {} is outside an object of {} elements",
+                            ptr.offset, ptr.size
+                        ),
+                    );
+                }
+                self.set(inst, Val::Ptr(ptr));
+            }
+            InstKind::Call(callee, args) => {
+                let args: Vec<Val> = args.iter().map(|arg| self.value(*arg)).collect();
+                let callee_ref = self.module.function(*callee);
+                if callee_ref.is_declaration() {
+                    let res = self.builtin(func, inst, &callee_ref.name, &args)?;
+                    self.set(inst, Val::Int(res));
+                } else if !self.enter(*callee, args) {
+                    return self.trap(func, inst, "stack overflow".to_string());
+                }
+            }
+            InstKind::Phi(_) => {
+                return self.trap(func, inst, "phi after a non-phi instruction".to_string())
+            }
+            InstKind::Br(target) => self.jump(func, *target)?,
+            InstKind::CondBr(cond, then_block, else_block) => {
+                let target = if self.int(func, inst, *cond)? != 0 {
+                    *then_block
+                } else {
+                    *else_block
+                };
+                self.jump(func, target)?;
+            }
+            InstKind::Ret(val) => {
+                let val = match val {
+                    Some(val) => Some(self.int(func, inst, *val)?),
+                    None => None,
+                };
+                let frame = self.frames.pop().unwrap();
+                self.mem.truncate(frame.mem);
+                self.cells = frame.cells;
+                let caller = match self.frames.last_mut() {
+                    Some(caller) => caller,
+                    None => return Ok(Some(val.unwrap_or(0))),
+                };
+                let call =
+                    self.module.function(caller.func).block(caller.block).insts[caller.pos - 1];
+                caller.regs[call.0] = Val::Int(val.unwrap_or(0));
+            }
+        }
+        Ok(None)
+    }
+
+    // 运行时库函数
+    fn builtin(
+        &mut self,
+        func: &Function,
+        inst: InstId,
+        name: &str,
+        args: &[Val],
+    ) -> Result<i32, Trap> {
+        let int = |index: usize| match args[index] {
+            Val::Int(val) => val,
+            Val::Ptr(_) => 0,
+        };
+        let float = |index: usize| f32::from_bits(int(index) as u32);
+        let ptr = |index: usize| match args[index] {
+            Val::Ptr(ptr) => ptr,
+            Val::Int(_) => Ptr {
+                base: 0,
+                size: 0,
+                offset: 0,
+            },
+        };
+        let res = match name {
+            "getint" => self.runtime.getint(),
+            "getch" => self.runtime.getch(),
+            "getfloat" => self.runtime.getfloat().to_bits() as i32,
+            "getarray" => {
+                let count = self.runtime.getint();
+                let addr = self.addr(func, inst, ptr(0), count.max(0) as usize)?;
+                for index in 0..count.max(0) as usize {
+                    self.mem[addr + index] = self.runtime.getint();
+                }
+                count
+            }
+            "getfarray" => {
+                let count = self.runtime.getint();
+                let addr = self.addr(func, inst, ptr(0), count.max(0) as usize)?;
+                for index in 0..count.max(0) as usize {
+                    self.mem[addr + index] = self.runtime.getfloat().to_bits() as i32;
+                }
+                count
+            }
+            "putint" => {
+                self.runtime.putint(int(0));
+                0
+            }
+            "putch" => {
+                self.runtime.putch(int(0));
+                0
+            }
+            "putfloat" => {
+                self.runtime.putfloat(float(0));
+                0
+            }
+            "putarray" => {
+                let count = int(0);
+                let addr = self.addr(func, inst, ptr(1), count.max(0) as usize)?;
+                self.runtime
+                    .putarray(count, &self.mem[addr..addr + count.max(0) as usize]);
+                0
+            }
+            "putfarray" => {
+                let count = int(0);
+                let addr = self.addr(func, inst, ptr(1), count.max(0) as usize)?;
+                let vals: Vec<f32> = self.mem[addr..addr + count.max(0) as usize]
+                    .iter()
+                    .map(|val| f32::from_bits(*val as u32))
+                    .collect();
+                self.runtime.putfarray(count, &vals);
+                0
+            }
+            _ => return self.trap(func, inst, format!("call to undefined function @{}", name)),
+        };
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+
+    use super::run_with;
+    use crate::backend::{self, Target};
+    use crate::pass;
+    use crate::runtime::Runtime;
+    use crate::testing::analyze_source;
+
+    // 与Runtime共享的输出缓冲区
+    struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // -O0、只有mem2reg、-O1与-O2对应的优化选项
+    fn levels() -> Vec<pass::Options> {
+        vec![
+            pass::Options {
+                mem2reg: false,
+                sccp: false,
+                dce: false,
+            },
+            pass::Options {
+                mem2reg: true,
+                sccp: false,
+                dce: false,
+            },
+            pass::Options {
+                mem2reg: true,
+                sccp: false,
+                dce: true,
+            },
+            pass::Options::default(),
+        ]
+    }
+
+    // 编译并执行程序，返回退出码或陷阱的消息，以及程序的输出
+    fn exec(source: &str, input: &str, passes: &pass::Options) -> (Result<i32, String>, String) {
+        let (_, mut module) = analyze_source(source);
+        pass::run(&mut module, passes);
+        // 输出文本时会检查引用的指令是否存在，每个标号只能定义一次
+        let text = backend::emit(&module, Target::Llvm);
+        for func in text.split("define ") {
+            let mut labels: Vec<&str> = func.lines().filter(|line| line.ends_with(':')).collect();
+            let count = labels.len();
+            labels.sort_unstable();
+            labels.dedup();
+            assert_eq!(labels.len(), count, "duplicate labels in\n{}", func);
+        }
+        let output = Rc::new(RefCell::new(vec![]));
+        let runtime = Runtime::with_io(input.as_bytes(), Box::new(Buffer(output.clone())));
+        let res = run_with(&module, runtime).map_err(|trap| trap.message);
+        let output = String::from_utf8(output.take()).unwrap();
+        (res, output)
+    }
+
+    // 每个优化级别下的结果都应当相同
+    fn check(source: &str, input: &str, expected: Result<i32, &str>, output: &str) {
+        for passes in levels() {
+            let (res, out) = exec(source, input, &passes);
+            match (&res, expected) {
+                (Ok(code), Ok(expected)) => assert_eq!(*code, expected),
+                (Err(message), Err(expected)) => assert!(
+                    message.contains(expected),
+                    "trap `{}` does not mention `{}`",
+                    message,
+                    expected
+                ),
+                _ => panic!("expected {:?}, got {:?}", expected, res),
+            }
+            assert_eq!(out, output);
+        }
+    }
+
+    #[test]
+    fn runs_program() {
+        check(
+            "int a[5];
+            int sum(int b[], int n) {
+                int i = 0, s = 0;
+                while (i < n) { s = s + b[i]; i = i + 1; }
+                return s;
+            }
+            int main() {
+                int n = getint(), i = 0;
+                while (i < n) { a[i] = getint(); i = i + 1; }
+                putint(sum(a, n));
+                putch(10);
+                return n;
+            }",
+            "3 4 5 6",
+            Ok(3),
+            "15\n",
+        );
+    }
+
+    #[test]
+    fn traps_on_out_of_bounds_index() {
+        check(
+            "int a[3]; int main() { int i = getint(); return a[i]; }",
+            "5",
+            Err("index 5 is out of bounds for an array of 3 elements"),
+            "",
+        );
+    }
+
+    #[test]
+    fn traps_on_out_of_bounds_array_param() {
+        check(
+            "int f(int b[]) { return b[4]; } int main() { int a[2] = {1, 2}; return f(a); }",
+            "",
+            Err("pointer offset 4 is outside an object of 2 elements"),
+            "",
+        );
+    }
+
+    #[test]
+    fn traps_on_division_by_zero() {
+        check(
+            "int main() { int d = getint(); putint(1); return 10 / d; }",
+            "0",
+            Err("division by zero"),
+            "1",
+        );
+        check(
+            "int main() { int d = getint(); return 10 % d; }",
+            "0",
+            Err("division by zero"),
+            "",
+        );
+    }
+
+    #[test]
+    fn traps_on_stack_overflow() {
+        check(
+            "int f(int n) { return f(n + 1) + 1; } int main() { return f(0); }",
+            "",
+            Err("stack overflow"),
+            "",
+        );
+    }
+
+    #[test]
+    fn conditional_uses_promoted_phi() {
+        let source = "int main() {
+            int c = getint(), b = 1;
+            if (c) b = 2;
+            putint(c ? b : 3);
+            return 0;
+        }";
+        check(source, "1", Ok(0), "2");
+        check(source, "0", Ok(0), "3");
+    }
+
+    #[test]
+    fn short_circuit_after_jump() {
+        check(
+            "int main() {
+                int a = 1;
+                while (a) { a = 0; break; if (a || a) putint(a); }
+                return a;
+            }",
+            "",
+            Ok(0),
+            "",
+        );
+        check(
+            "int main() {
+                int a = 3, n = 0;
+                while (a) { a = a - 1; n = n + 1; continue; if (a && a) putint(a); }
+                putint(n);
+                return a;
+            }",
+            "",
+            Ok(0),
+            "3",
+        );
+    }
+}
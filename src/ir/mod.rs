@@ -0,0 +1,469 @@
+// 内存中的LLVM IR，codegen生成Module，printer将其序列化为文本
+// 函数内的基本块与指令分别存放在数组中，通过编号互相引用
+
+pub mod exec;
+mod printer;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Type {
+    Void,
+    I1,
+    I32,
+    Float,
+    Pointer(Box<Type>),
+    Array(usize, Box<Type>),
+}
+
+impl Type {
+    pub fn ptr(self) -> Type {
+        Type::Pointer(Box::new(self))
+    }
+
+    // 指针指向的类型
+    pub fn pointee(&self) -> &Type {
+        match self {
+            Type::Pointer(ty) => ty,
+            _ => panic!("{} is not a pointer", self),
+        }
+    }
+
+    // 由符号表中的形状与元素类型得到类型，如[2, 3]对应[2 x [3 x i32]]
+    pub fn from_shape(shape: &[i32], elem: Type) -> Type {
+        shape
+            .iter()
+            .rev()
+            .fold(elem, |ty, dim| Type::Array(*dim as usize, Box::new(ty)))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct FuncId(pub usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct GlobalId(pub usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct BlockId(pub usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct InstId(pub usize);
+
+// 指令的操作数，浮点常量按位存储以便比较与哈希
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Value {
+    #[default]
+    Undef,
+    Int(i32),
+    Float(u32),
+    Bool(bool),
+    Inst(InstId),
+    Param(usize),
+    Global(GlobalId),
+}
+
+impl Value {
+    pub fn as_int(&self) -> Option<i32> {
+        match self {
+            Value::Int(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn float(val: f32) -> Value {
+        Value::Float(val.to_bits())
+    }
+
+    pub fn as_float(&self) -> Option<f32> {
+        match self {
+            Value::Float(bits) => Some(f32::from_bits(*bits)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    SDiv,
+    SRem,
+    And,
+    Or,
+    Xor,
+    Shl,
+    AShr,
+    FAdd,
+    FSub,
+    FMul,
+    FDiv,
+}
+
+impl BinOp {
+    pub fn is_float(self) -> bool {
+        matches!(self, BinOp::FAdd | BinOp::FSub | BinOp::FMul | BinOp::FDiv)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Slt,
+    Sgt,
+    Sle,
+    Sge,
+}
+
+#[derive(Clone, Debug)]
+pub enum InstKind {
+    Alloca(Type),
+    Load(Value),
+    Store(Value, Value), // 值，地址
+    Binary(BinOp, Value, Value),
+    Icmp(CmpOp, Value, Value),
+    Fcmp(CmpOp, Value, Value), // 有符号比较的谓词表示有序比较，Ne表示无序不等
+    Zext(Value),
+    Sitofp(Value),
+    Fptosi(Value),
+    Gep(Value, Vec<Value>),
+    Call(FuncId, Vec<Value>),
+    Phi(Vec<(Value, BlockId)>),
+    Br(BlockId),
+    CondBr(Value, BlockId, BlockId),
+    Ret(Option<Value>),
+}
+
+impl InstKind {
+    pub fn operands(&self) -> Vec<Value> {
+        match self {
+            InstKind::Alloca(_) | InstKind::Br(_) => vec![],
+            InstKind::Load(val)
+            | InstKind::Zext(val)
+            | InstKind::Sitofp(val)
+            | InstKind::Fptosi(val)
+            | InstKind::CondBr(val, _, _) => vec![*val],
+            InstKind::Store(lhs, rhs)
+            | InstKind::Binary(_, lhs, rhs)
+            | InstKind::Icmp(_, lhs, rhs)
+            | InstKind::Fcmp(_, lhs, rhs) => vec![*lhs, *rhs],
+            InstKind::Gep(base, indices) => {
+                let mut res = vec![*base];
+                res.extend(indices.iter().copied());
+                res
+            }
+            InstKind::Call(_, args) => args.clone(),
+            InstKind::Phi(incoming) => incoming.iter().map(|(val, _)| *val).collect(),
+            InstKind::Ret(val) => val.iter().copied().collect(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            InstKind::Alloca(_) | InstKind::Br(_) => vec![],
+            InstKind::Load(val)
+            | InstKind::Zext(val)
+            | InstKind::Sitofp(val)
+            | InstKind::Fptosi(val)
+            | InstKind::CondBr(val, _, _) => vec![val],
+            InstKind::Store(lhs, rhs)
+            | InstKind::Binary(_, lhs, rhs)
+            | InstKind::Icmp(_, lhs, rhs)
+            | InstKind::Fcmp(_, lhs, rhs) => vec![lhs, rhs],
+            InstKind::Gep(base, indices) => {
+                let mut res = vec![base];
+                res.extend(indices.iter_mut());
+                res
+            }
+            InstKind::Call(_, args) => args.iter_mut().collect(),
+            InstKind::Phi(incoming) => incoming.iter_mut().map(|(val, _)| val).collect(),
+            InstKind::Ret(val) => val.iter_mut().collect(),
+        }
+    }
+
+    pub fn is_terminator(&self) -> bool {
+        matches!(
+            self,
+            InstKind::Br(_) | InstKind::CondBr(_, _, _) | InstKind::Ret(_)
+        )
+    }
+
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            InstKind::Br(target) => vec![*target],
+            InstKind::CondBr(_, then_block, else_block) => vec![*then_block, *else_block],
+            _ => vec![],
+        }
+    }
+}
+
+pub struct Instruction {
+    pub kind: InstKind,
+    pub ty: Type, // 结果类型，没有结果时为void
+    pub name: Option<String>,
+    pub block: BlockId,
+    pub users: Vec<InstId>, // 使用该指令结果的指令，使用多次的出现多次
+}
+
+pub struct BasicBlock {
+    pub name: Option<String>,
+    pub insts: Vec<InstId>,
+}
+
+pub struct Function {
+    pub name: String,
+    pub ret_ty: Type,
+    pub params: Vec<(String, Type)>,
+    pub blocks: Vec<BasicBlock>,
+    pub insts: Vec<Instruction>,
+    pub layout: Vec<BlockId>, // 基本块的排列顺序，第一个为入口，为空时是函数声明
+}
+
+impl Function {
+    pub fn new(name: &str, ret_ty: Type, params: Vec<(String, Type)>) -> Function {
+        Function {
+            name: name.to_string(),
+            ret_ty,
+            params,
+            blocks: vec![],
+            insts: vec![],
+            layout: vec![],
+        }
+    }
+
+    pub fn is_declaration(&self) -> bool {
+        self.layout.is_empty()
+    }
+
+    pub fn entry(&self) -> BlockId {
+        self.layout[0]
+    }
+
+    // 新建的基本块需要放入layout才会出现在函数中
+    pub fn new_block(&mut self, name: Option<String>) -> BlockId {
+        self.blocks.push(BasicBlock {
+            name,
+            insts: vec![],
+        });
+        BlockId(self.blocks.len() - 1)
+    }
+
+    pub fn block(&self, block: BlockId) -> &BasicBlock {
+        &self.blocks[block.0]
+    }
+
+    pub fn inst(&self, inst: InstId) -> &Instruction {
+        &self.insts[inst.0]
+    }
+
+    // 在基本块的index处插入指令
+    pub fn insert_inst(
+        &mut self,
+        block: BlockId,
+        index: usize,
+        kind: InstKind,
+        ty: Type,
+        name: Option<String>,
+    ) -> InstId {
+        let id = InstId(self.insts.len());
+        self.add_uses(id, &kind);
+        self.insts.push(Instruction {
+            kind,
+            ty,
+            name,
+            block,
+            users: vec![],
+        });
+        self.blocks[block.0].insts.insert(index, id);
+        id
+    }
+
+    pub fn push_inst(
+        &mut self,
+        block: BlockId,
+        kind: InstKind,
+        ty: Type,
+        name: Option<String>,
+    ) -> InstId {
+        let index = self.blocks[block.0].insts.len();
+        self.insert_inst(block, index, kind, ty, name)
+    }
+
+    // 从基本块中删除指令，结果不应再被使用
+    pub fn remove_inst(&mut self, inst: InstId) {
+        let kind = std::mem::replace(&mut self.insts[inst.0].kind, InstKind::Ret(None));
+        self.remove_uses(inst, &kind);
+        self.insts[inst.0].kind = kind;
+        let block = self.insts[inst.0].block;
+        self.blocks[block.0].insts.retain(|item| *item != inst);
+    }
+
+    // 替换指令的内容并维护use-def链
+    pub fn set_kind(&mut self, inst: InstId, kind: InstKind) {
+        let old = std::mem::replace(&mut self.insts[inst.0].kind, InstKind::Ret(None));
+        self.remove_uses(inst, &old);
+        self.add_uses(inst, &kind);
+        self.insts[inst.0].kind = kind;
+    }
+
+    pub fn replace_all_uses(&mut self, inst: InstId, new: Value) {
+        let users = std::mem::take(&mut self.insts[inst.0].users);
+        for user in users {
+            for operand in self.insts[user.0].kind.operands_mut() {
+                if *operand == Value::Inst(inst) {
+                    *operand = new;
+                    if let Value::Inst(new) = new {
+                        self.insts[new.0].users.push(user);
+                    }
+                    // 同一条指令多次使用时，每次出现都对应一个user
+                    break;
+                }
+            }
+        }
+    }
+
+    fn add_uses(&mut self, user: InstId, kind: &InstKind) {
+        for operand in kind.operands() {
+            if let Value::Inst(inst) = operand {
+                self.insts[inst.0].users.push(user);
+            }
+        }
+    }
+
+    fn remove_uses(&mut self, user: InstId, kind: &InstKind) {
+        for operand in kind.operands() {
+            if let Value::Inst(inst) = operand {
+                let users = &mut self.insts[inst.0].users;
+                if let Some(pos) = users.iter().position(|item| *item == user) {
+                    users.remove(pos);
+                }
+            }
+        }
+    }
+
+    // 删除phi中来自pred的入口
+    pub fn remove_phi_incoming(&mut self, block: BlockId, pred: BlockId) {
+        for inst in self.blocks[block.0].insts.clone() {
+            if let InstKind::Phi(incoming) = &self.insts[inst.0].kind {
+                let incoming = incoming
+                    .iter()
+                    .filter(|(_, from)| *from != pred)
+                    .copied()
+                    .collect();
+                self.set_kind(inst, InstKind::Phi(incoming));
+            }
+        }
+    }
+
+    // 删除一组基本块，其中的值只能在这组块内使用
+    pub fn remove_blocks(&mut self, dead: &[BlockId]) {
+        let mut is_dead = vec![false; self.blocks.len()];
+        for block in dead {
+            is_dead[block.0] = true;
+        }
+        for block in dead {
+            for succ in self.successors(*block) {
+                if !is_dead[succ.0] {
+                    self.remove_phi_incoming(succ, *block);
+                }
+            }
+        }
+        // 块之间可能互相引用，先断开所有操作数
+        for block in dead {
+            for inst in self.blocks[block.0].insts.clone() {
+                self.set_kind(inst, InstKind::Ret(None));
+            }
+        }
+        for block in dead {
+            self.blocks[block.0].insts.clear();
+        }
+        self.layout.retain(|block| !is_dead[block.0]);
+    }
+
+    pub fn terminator(&self, block: BlockId) -> Option<InstId> {
+        self.blocks[block.0]
+            .insts
+            .last()
+            .copied()
+            .filter(|inst| self.insts[inst.0].kind.is_terminator())
+    }
+
+    pub fn successors(&self, block: BlockId) -> Vec<BlockId> {
+        self.terminator(block)
+            .map_or(vec![], |inst| self.insts[inst.0].kind.successors())
+    }
+
+    // 以BlockId为下标的前驱表，只统计layout中的基本块
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut preds = vec![vec![]; self.blocks.len()];
+        for block in &self.layout {
+            for succ in self.successors(*block) {
+                preds[succ.0].push(*block);
+            }
+        }
+        preds
+    }
+}
+
+pub enum Constant {
+    Int(i32),
+    Float(f32),
+    Zero,
+    Array(Vec<Constant>),
+}
+
+pub struct GlobalVariable {
+    pub name: String,
+    pub ty: Type, // 变量本身的类型，其值@name是指向它的指针
+    pub is_const: bool,
+    pub init: Constant,
+}
+
+#[derive(Default)]
+pub struct Module {
+    pub functions: Vec<Function>,
+    pub globals: Vec<GlobalVariable>,
+}
+
+impl Module {
+    pub fn new() -> Module {
+        Module::default()
+    }
+
+    pub fn add_function(&mut self, func: Function) -> FuncId {
+        self.functions.push(func);
+        FuncId(self.functions.len() - 1)
+    }
+
+    pub fn add_global(&mut self, global: GlobalVariable) -> GlobalId {
+        self.globals.push(global);
+        GlobalId(self.globals.len() - 1)
+    }
+
+    pub fn get_function(&self, name: &str) -> Option<FuncId> {
+        self.functions
+            .iter()
+            .position(|func| func.name == name)
+            .map(FuncId)
+    }
+
+    pub fn function(&self, func: FuncId) -> &Function {
+        &self.functions[func.0]
+    }
+
+    pub fn function_mut(&mut self, func: FuncId) -> &mut Function {
+        &mut self.functions[func.0]
+    }
+
+    // 操作数的类型，常量按其字面类型
+    pub fn value_type(&self, func: &Function, value: Value) -> Type {
+        match value {
+            Value::Undef | Value::Int(_) => Type::I32,
+            Value::Float(_) => Type::Float,
+            Value::Bool(_) => Type::I1,
+            Value::Inst(inst) => func.inst(inst).ty.clone(),
+            Value::Param(index) => func.params[index].1.clone(),
+            Value::Global(global) => self.globals[global.0].ty.clone().ptr(),
+        }
+    }
+}
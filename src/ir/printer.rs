@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{BinOp, BlockId, CmpOp, Constant, Function, InstId, InstKind, Module, Type, Value};
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Void => write!(f, "void"),
+            Type::I1 => write!(f, "i1"),
+            Type::I32 => write!(f, "i32"),
+            Type::Float => write!(f, "float"),
+            Type::Pointer(ty) => write!(f, "{}*", ty),
+            Type::Array(len, ty) => write!(f, "[{} x {}]", len, ty),
+        }
+    }
+}
+
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            BinOp::Add => "add",
+            BinOp::Sub => "sub",
+            BinOp::Mul => "mul",
+            BinOp::SDiv => "sdiv",
+            BinOp::SRem => "srem",
+            BinOp::And => "and",
+            BinOp::Or => "or",
+            BinOp::Xor => "xor",
+            BinOp::Shl => "shl",
+            BinOp::AShr => "ashr",
+            BinOp::FAdd => "fadd",
+            BinOp::FSub => "fsub",
+            BinOp::FMul => "fmul",
+            BinOp::FDiv => "fdiv",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+impl fmt::Display for CmpOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            CmpOp::Eq => "eq",
+            CmpOp::Ne => "ne",
+            CmpOp::Slt => "slt",
+            CmpOp::Sgt => "sgt",
+            CmpOp::Sle => "sle",
+            CmpOp::Sge => "sge",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+// fcmp的谓词，不等为无序比较，与C的!=一致
+fn fcmp_op(op: CmpOp) -> &'static str {
+    match op {
+        CmpOp::Eq => "oeq",
+        CmpOp::Ne => "une",
+        CmpOp::Slt => "olt",
+        CmpOp::Sgt => "ogt",
+        CmpOp::Sle => "ole",
+        CmpOp::Sge => "oge",
+    }
+}
+
+// LLVM用double的十六进制位模式表示float常量
+fn float_text(val: f32) -> String {
+    format!("0x{:016X}", (val as f64).to_bits())
+}
+
+// 带类型的常量，如[2 x i32] [i32 1, i32 0]
+fn fmt_constant(f: &mut fmt::Formatter, ty: &Type, constant: &Constant) -> fmt::Result {
+    write!(f, "{} ", ty)?;
+    match (constant, ty) {
+        (Constant::Int(val), _) => write!(f, "{}", val),
+        (Constant::Float(val), _) => write!(f, "{}", float_text(*val)),
+        (Constant::Zero, Type::I32) => write!(f, "0"),
+        (Constant::Zero, Type::Float) => write!(f, "{}", float_text(0.0)),
+        (Constant::Zero, _) => write!(f, "zeroinitializer"),
+        (Constant::Array(elems), Type::Array(_, elem_ty)) => {
+            write!(f, "[")?;
+            for (index, elem) in elems.iter().enumerate() {
+                if index != 0 {
+                    write!(f, ", ")?;
+                }
+                fmt_constant(f, elem_ty, elem)?;
+            }
+            write!(f, "]")
+        }
+        (Constant::Array(_), _) => panic!("array constant of type {}", ty),
+    }
+}
+
+// 按LLVM的规则为无名的基本块和指令编号，入口块为%0
+struct Namer<'a> {
+    module: &'a Module,
+    func: &'a Function,
+    blocks: HashMap<BlockId, usize>,
+    insts: HashMap<InstId, usize>,
+}
+
+impl<'a> Namer<'a> {
+    fn new(module: &'a Module, func: &'a Function) -> Namer<'a> {
+        let mut namer = Namer {
+            module,
+            func,
+            blocks: HashMap::new(),
+            insts: HashMap::new(),
+        };
+        let mut next = 0;
+        for block in &func.layout {
+            if func.block(*block).name.is_none() {
+                namer.blocks.insert(*block, next);
+                next += 1;
+            }
+            for inst in &func.block(*block).insts {
+                let inst_ref = func.inst(*inst);
+                if inst_ref.name.is_none() && inst_ref.ty != Type::Void {
+                    namer.insts.insert(*inst, next);
+                    next += 1;
+                }
+            }
+        }
+        namer
+    }
+
+    fn block(&self, block: BlockId) -> String {
+        match &self.func.block(block).name {
+            Some(name) => format!("%{}", name),
+            None => format!("%{}", self.blocks[&block]),
+        }
+    }
+
+    fn value(&self, value: Value) -> String {
+        match value {
+            Value::Undef => "undef".to_string(),
+            Value::Int(val) => val.to_string(),
+            Value::Float(bits) => float_text(f32::from_bits(bits)),
+            Value::Bool(val) => val.to_string(),
+            Value::Inst(inst) => match &self.func.inst(inst).name {
+                Some(name) => format!("%{}", name),
+                None => format!("%{}", self.insts[&inst]),
+            },
+            Value::Param(index) => format!("%{}", self.func.params[index].0),
+            Value::Global(global) => format!("@{}", self.module.globals[global.0].name),
+        }
+    }
+
+    fn typed(&self, value: Value) -> String {
+        format!(
+            "{} {}",
+            self.module.value_type(self.func, value),
+            self.value(value)
+        )
+    }
+
+    fn inst(&self, inst: InstId) -> String {
+        let inst_ref = self.func.inst(inst);
+        let body = match &inst_ref.kind {
+            InstKind::Alloca(ty) => format!("alloca {}", ty),
+            InstKind::Load(ptr) => format!("load {}, {}", inst_ref.ty, self.typed(*ptr)),
+            InstKind::Store(val, ptr) => {
+                format!("store {}, {}", self.typed(*val), self.typed(*ptr))
+            }
+            InstKind::Binary(op, lhs, rhs) => {
+                format!("{} {}, {}", op, self.typed(*lhs), self.value(*rhs))
+            }
+            InstKind::Icmp(op, lhs, rhs) => {
+                format!("icmp {} {}, {}", op, self.typed(*lhs), self.value(*rhs))
+            }
+            InstKind::Fcmp(op, lhs, rhs) => format!(
+                "fcmp {} {}, {}",
+                fcmp_op(*op),
+                self.typed(*lhs),
+                self.value(*rhs)
+            ),
+            InstKind::Zext(val) => format!("zext {} to {}", self.typed(*val), inst_ref.ty),
+            InstKind::Sitofp(val) => format!("sitofp {} to {}", self.typed(*val), inst_ref.ty),
+            InstKind::Fptosi(val) => format!("fptosi {} to {}", self.typed(*val), inst_ref.ty),
+            InstKind::Gep(base, indices) => {
+                let base_ty = self.module.value_type(self.func, *base);
+                let mut res = format!(
+                    "getelementptr {}, {} {}",
+                    base_ty.pointee(),
+                    base_ty,
+                    self.value(*base)
+                );
+                for index in indices {
+                    res += format!(", {}", self.typed(*index)).as_str();
+                }
+                res
+            }
+            InstKind::Call(callee, args) => {
+                let callee = self.module.function(*callee);
+                let args: Vec<String> = args.iter().map(|arg| self.typed(*arg)).collect();
+                format!(
+                    "call {} @{}({})",
+                    callee.ret_ty,
+                    callee.name,
+                    args.join(", ")
+                )
+            }
+            InstKind::Phi(incoming) => {
+                let incoming: Vec<String> = incoming
+                    .iter()
+                    .map(|(val, block)| format!("[ {}, {} ]", self.value(*val), self.block(*block)))
+                    .collect();
+                format!("phi {} {}", inst_ref.ty, incoming.join(", "))
+            }
+            InstKind::Br(target) => format!("br label {}", self.block(*target)),
+            InstKind::CondBr(cond, then_block, else_block) => format!(
+                "br {}, label {}, label {}",
+                self.typed(*cond),
+                self.block(*then_block),
+                self.block(*else_block)
+            ),
+            InstKind::Ret(None) => "ret void".to_string(),
+            InstKind::Ret(Some(val)) => format!("ret {}", self.typed(*val)),
+        };
+        if inst_ref.ty == Type::Void {
+            body
+        } else {
+            format!("{} = {}", self.value(Value::Inst(inst)), body)
+        }
+    }
+}
+
+fn fmt_function(f: &mut fmt::Formatter, module: &Module, func: &Function) -> fmt::Result {
+    if func.is_declaration() {
+        let params: Vec<String> = func.params.iter().map(|(_, ty)| ty.to_string()).collect();
+        return writeln!(
+            f,
+            "declare {} @{}({})",
+            func.ret_ty,
+            func.name,
+            params.join(", ")
+        );
+    }
+    let params: Vec<String> = func
+        .params
+        .iter()
+        .map(|(name, ty)| format!("{} %{}", ty, name))
+        .collect();
+    writeln!(
+        f,
+        "define {} @{}({}) {{",
+        func.ret_ty,
+        func.name,
+        params.join(", ")
+    )?;
+    let namer = Namer::new(module, func);
+    for block in &func.layout {
+        // 无名的基本块紧跟在终结指令之后，省略标号
+        if let Some(name) = &func.block(*block).name {
+            writeln!(f, "{}:", name)?;
+        }
+        for inst in &func.block(*block).insts {
+            writeln!(f, "    {}", namer.inst(*inst))?;
+        }
+    }
+    writeln!(f, "}}")
+}
+
+impl Module {
+    // 单条指令的文本，用于报告错误
+    pub fn inst_text(&self, func: &Function, inst: InstId) -> String {
+        Namer::new(self, func).inst(inst)
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for func in self.functions.iter().filter(|func| func.is_declaration()) {
+            fmt_function(f, self, func)?;
+        }
+        writeln!(f)?;
+        for global in &self.globals {
+            write!(
+                f,
+                "@{} = {} ",
+                global.name,
+                if global.is_const {
+                    "constant"
+                } else {
+                    "global"
+                }
+            )?;
+            fmt_constant(f, &global.ty, &global.init)?;
+            writeln!(f)?;
+        }
+        writeln!(f)?;
+        for func in self.functions.iter().filter(|func| !func.is_declaration()) {
+            fmt_function(f, self, func)?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+// calcium的库接口，命令行程序只是对它的包装
+// compile完成从源码到目标代码的整个过程；各阶段也可以单独调用：Tokenizer、Parser、SymbolTable等
+
+mod assigner;
+pub mod ast;
+pub mod backend;
+pub mod bytecode;
+mod codegen;
+mod csource;
+pub mod diagnostic;
+pub mod interp;
+pub mod ir;
+pub mod parser;
+pub mod pass;
+mod reader;
+mod runtime;
+pub mod span;
+pub mod symbol;
+#[cfg(test)]
+mod testing;
+pub mod token;
+pub mod tokenizer;
+
+pub use backend::Target;
+pub use diagnostic::Diagnostic;
+pub use parser::Parser;
+pub use span::SourceMap;
+pub use symbol::SymbolTable;
+pub use token::Token;
+pub use tokenizer::Tokenizer;
+
+use std::fmt;
+
+use ast::CompUnit;
+use codegen::Codegen;
+use ir::Module;
+
+// 默认最多报告的错误数量，0表示不限制
+pub const DEFAULT_ERROR_LIMIT: usize = 20;
+
+// 输出的内容
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Emit {
+    Tokens,
+    Ast,
+    Llvm,
+    Asm, // 由Options::target决定
+    C,
+    Bytecode,
+}
+
+pub struct Options {
+    pub emit: Emit,
+    pub target: Target,
+    pub passes: pass::Options,
+    pub error_limit: usize,
+}
+
+impl Options {
+    // 与命令行一致：汇编需要本机目标，其余输出只能使用默认的Target::Llvm
+    pub fn validate(&self) -> Result<(), String> {
+        match (self.emit, self.target) {
+            (Emit::Asm, Target::Llvm) => Err("`Emit::Asm` requires a native target".to_string()),
+            (Emit::Asm, _) | (_, Target::Llvm) => Ok(()),
+            (emit, target) => Err(format!(
+                "`Target::{:?}` cannot be used with `Emit::{:?}`",
+                target, emit
+            )),
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            emit: Emit::Llvm,
+            target: Target::Llvm,
+            passes: pass::Options::default(),
+            error_limit: DEFAULT_ERROR_LIMIT,
+        }
+    }
+}
+
+// 字节码是二进制的，其余输出都是文本
+pub struct Output {
+    pub code: Vec<u8>,
+    pub warnings: Vec<Diagnostic>,
+}
+
+// 编译失败：选项无效，或源码有错误（此时警告也在其中）
+#[derive(Debug)]
+pub enum Error {
+    InvalidOptions(String),
+    Diagnostics(Vec<Diagnostic>),
+}
+
+impl From<Vec<Diagnostic>> for Error {
+    fn from(diagnostics: Vec<Diagnostic>) -> Error {
+        Error::Diagnostics(diagnostics)
+    }
+}
+
+// 诊断信息需要SourceMap才能渲染出源码片段，这里只输出消息
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidOptions(message) => write!(f, "invalid options: {}", message),
+            Error::Diagnostics(diagnostics) => {
+                for (i, diagnostic) in diagnostics.iter().enumerate() {
+                    if i != 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", diagnostic)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// 词法分析、语法分析与语义检查，同时生成IR
+pub fn analyze(
+    sources: &SourceMap,
+    file: usize,
+    error_limit: usize,
+    warnings: &mut Vec<Diagnostic>,
+) -> Result<(CompUnit, Module), Vec<Diagnostic>> {
+    let (tokens, errors) = Tokenizer::tokenize(sources, file);
+    let (comp_unit, errors) = Parser::parse(&tokens, errors, error_limit);
+    let module = Codegen::generate(&comp_unit, errors, error_limit, warnings)?;
+    Ok((comp_unit, module))
+}
+
+// 同时返回只含这份源码（名为<input>）的SourceMap，用于渲染警告与错误
+pub fn compile(source: &str, options: &Options) -> (SourceMap, Result<Output, Error>) {
+    let mut sources = SourceMap::new();
+    let file = sources.add("<input>", source.to_string());
+    let res = compile_file(&sources, file, options);
+    (sources, res)
+}
+
+pub fn compile_file(sources: &SourceMap, file: usize, options: &Options) -> Result<Output, Error> {
+    options.validate().map_err(Error::InvalidOptions)?;
+    let mut warnings = vec![];
+    let code = match options.emit {
+        // 词法单元与语法树只经过对应的阶段
+        Emit::Tokens | Emit::Ast => {
+            let (tokens, mut errors) = Tokenizer::tokenize(sources, file);
+            if options.emit == Emit::Tokens {
+                if !errors.is_empty() {
+                    if options.error_limit != 0 {
+                        errors.truncate(options.error_limit);
+                    }
+                    return Err(errors.into());
+                }
+                let mut res = String::new();
+                for token in &tokens {
+                    res += &format!("{}:{} {}\n", token.span.line, token.span.column, token.kind);
+                }
+                res
+            } else {
+                let (comp_unit, errors) = Parser::parse(&tokens, errors, options.error_limit);
+                if !errors.is_empty() {
+                    return Err(errors.into());
+                }
+                comp_unit.dump()
+            }
+        }
+        _ => {
+            let res = analyze(sources, file, options.error_limit, &mut warnings);
+            // 有错误时警告随错误一起返回
+            let (comp_unit, mut module) = res.map_err(|mut errors| {
+                errors.splice(0..0, warnings.drain(..));
+                errors
+            })?;
+            match options.emit {
+                Emit::C => csource::emit(&comp_unit),
+                Emit::Bytecode => {
+                    return Ok(Output {
+                        code: bytecode::compiler::compile(&comp_unit).encode(),
+                        warnings,
+                    })
+                }
+                Emit::Llvm => {
+                    pass::run(&mut module, &options.passes);
+                    backend::emit(&module, Target::Llvm)
+                }
+                _ => {
+                    pass::run(&mut module, &options.passes);
+                    backend::emit(&module, options.target)
+                }
+            }
+        }
+    };
+    Ok(Output {
+        code: code.into_bytes(),
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compile, Emit, Error, Options, Target};
+
+    fn options(emit: Emit, target: Target) -> Options {
+        Options {
+            emit,
+            target,
+            ..Options::default()
+        }
+    }
+
+    // 与命令行一致：汇编需要本机目标，其余输出不能指定目标
+    #[test]
+    fn rejects_invalid_options() {
+        let source = "int main() { return 0; }";
+        for (emit, target) in [
+            (Emit::Asm, Target::Llvm),
+            (Emit::Llvm, Target::X86_64),
+            (Emit::Tokens, Target::Riscv64),
+            (Emit::Ast, Target::Armv7),
+            (Emit::C, Target::Wasm),
+            (Emit::Bytecode, Target::X86_64),
+        ] {
+            let (_, res) = compile(source, &options(emit, target));
+            assert!(
+                matches!(res, Err(Error::InvalidOptions(_))),
+                "{:?} with {:?} should be rejected",
+                emit,
+                target
+            );
+        }
+        for target in [Target::X86_64, Target::Riscv64, Target::Armv7, Target::Wasm] {
+            let (_, res) = compile(source, &options(Emit::Asm, target));
+            assert!(res.is_ok());
+        }
+    }
+
+    // 返回的SourceMap可以渲染诊断信息
+    #[test]
+    fn renders_with_returned_source_map() {
+        let (sources, res) = compile("int main() {\n  return a;\n}", &Options::default());
+        let errors = match res {
+            Err(Error::Diagnostics(errors)) => errors,
+            _ => panic!("expected compile errors"),
+        };
+        let rendered = errors[0].render(&sources);
+        assert!(rendered.contains("--> <input>:2:10"), "{}", rendered);
+        assert!(rendered.contains("return a;"), "{}", rendered);
+    }
+}
@@ -1,17 +1,342 @@
-mod assigner;
-mod parser;
-mod reader;
-mod symbol;
-mod token;
-mod tokenizer;
+use std::io::{self, Read, Write};
+use std::process;
 
-use parser::Parser;
-use tokenizer::Tokenizer;
+use calcium::bytecode;
+use calcium::diagnostic::Severity;
+use calcium::interp::Interpreter;
+use calcium::{analyze, compile_file, ir, pass};
+use calcium::{Diagnostic, Emit, Error, Options, SourceMap, Target, DEFAULT_ERROR_LIMIT};
+
+// 退出码，含义见README
+const EXIT_COMPILE_ERROR: i32 = 1;
+const EXIT_IO_ERROR: i32 = 2;
+const EXIT_RUNTIME_ERROR: i32 = 3;
+
+const HELP: &str = "\
+calcium: a compiler for miniSysY
+
+usage: calcium [options] <input> [<output>]
+       calcium run [options] <input|input.cbc>
+
+<input> and <output> may be `-` for stdin and stdout; the output defaults to stdout.
+
+options:
+  -o <output>          write the output to <output>
+  --emit <kind>        tokens, ast, llvm, asm, c or cbc
+                       (default: llvm, or asm when --target names a native target)
+  --target <target>    llvm, x86_64, riscv64, armv7 or wasm (default for asm: x86_64)
+  -O0, -O1, -O2        optimization level (default: -O2)
+  --no-mem2reg, --no-sccp, --no-dce
+                       disable a single optimization
+  --error-limit=<n>    stop after <n> errors, 0 for no limit (default: 20)
+  --ir                 run: execute the optimized IR instead of the syntax tree
+  --vm                 run: compile to bytecode and execute it on the VM
+  -h, --help           print this help
+  -V, --version        print the version
+";
+
+// 命令行参数，--emit与--target在解析完之后再一起检查
+struct Args {
+    is_run: bool,
+    error_limit: usize,
+    options: pass::Options,
+    emit: Option<String>,
+    target: Option<String>,
+    use_ir: bool,
+    use_vm: bool,
+    output: Option<String>,
+    paths: Vec<String>,
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    eprintln!("try `calcium --help` for more information");
+    process::exit(EXIT_IO_ERROR);
+}
+
+fn parse_args(args: &[String]) -> Args {
+    let is_run = args.get(1).map(|arg| arg.as_str()) == Some("run");
+    let mut res = Args {
+        is_run,
+        error_limit: DEFAULT_ERROR_LIMIT,
+        options: pass::Options::default(),
+        emit: None,
+        target: None,
+        use_ir: false,
+        use_vm: false,
+        output: None,
+        paths: vec![],
+    };
+    let mut iter = args.iter().skip(if is_run { 2 } else { 1 });
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| match iter.next() {
+            Some(value) => value.clone(),
+            None => usage_error(&format!("`{}` requires an argument", name)),
+        };
+        match arg.as_str() {
+            "-h" | "--help" => {
+                print!("{}", HELP);
+                process::exit(0);
+            }
+            "-V" | "--version" => {
+                println!("calcium {}", env!("CARGO_PKG_VERSION"));
+                process::exit(0);
+            }
+            "-o" => res.output = Some(value("-o")),
+            "--emit" => res.emit = Some(value("--emit")),
+            "--target" => res.target = Some(value("--target")),
+            "-O0" => {
+                res.options = pass::Options {
+                    mem2reg: false,
+                    sccp: false,
+                    dce: false,
+                }
+            }
+            "-O1" => {
+                res.options = pass::Options {
+                    sccp: false,
+                    ..pass::Options::default()
+                }
+            }
+            "-O2" => res.options = pass::Options::default(),
+            "--no-mem2reg" => res.options.mem2reg = false,
+            "--no-sccp" => res.options.sccp = false,
+            "--no-dce" => res.options.dce = false,
+            "--ir" if is_run => res.use_ir = true,
+            "--vm" if is_run => res.use_vm = true,
+            "--ir" | "--vm" => usage_error(&format!("`{}` can only be used with `run`", arg)),
+            "-" => res.paths.push(arg.clone()),
+            _ => {
+                if let Some(limit) = arg.strip_prefix("--error-limit=") {
+                    res.error_limit = match limit.parse() {
+                        Ok(limit) => limit,
+                        Err(_) => usage_error(&format!("invalid error limit `{}`", limit)),
+                    };
+                } else if arg.starts_with('-') {
+                    usage_error(&format!("unknown option `{}`", arg));
+                } else {
+                    res.paths.push(arg.clone());
+                }
+            }
+        }
+    }
+    res
+}
+
+// 没有指定--emit时，--target为本机目标则输出汇编，否则输出LLVM IR；汇编默认为x86_64
+fn resolve_emit(kind: Option<&str>, target: Option<&str>) -> (Emit, Target) {
+    let target = target.map(|name| match Target::from_name(name) {
+        Some(target) => (target, name),
+        None => usage_error(&format!("unknown target `{}`", name)),
+    });
+    let emit = match kind {
+        None => match target {
+            Some((Target::Llvm, _)) | None => Emit::Llvm,
+            Some(_) => Emit::Asm,
+        },
+        Some("tokens") => Emit::Tokens,
+        Some("ast") => Emit::Ast,
+        Some("llvm") => Emit::Llvm,
+        Some("asm") => Emit::Asm,
+        Some("c") => Emit::C,
+        Some("cbc") => Emit::Bytecode,
+        Some(kind) => usage_error(&format!("unknown output kind `{}`", kind)),
+    };
+    match (emit, target) {
+        (Emit::Llvm, None) | (Emit::Llvm, Some((Target::Llvm, _))) => (emit, Target::Llvm),
+        (Emit::Asm, None) => (emit, Target::X86_64),
+        (Emit::Asm, Some((Target::Llvm, _))) => {
+            usage_error("`--emit asm` requires a native target")
+        }
+        (Emit::Asm, Some((target, _))) => (emit, target),
+        (_, None) => (emit, Target::Llvm),
+        (_, Some((_, name))) => usage_error(&format!(
+            "`--target {}` cannot be used with `--emit {}`",
+            name,
+            kind.unwrap_or("llvm")
+        )),
+    }
+}
+
+// 路径为-时读取标准输入
+fn read_file(path: &str) -> Vec<u8> {
+    let mut bytes = vec![];
+    let res = if path == "-" {
+        io::stdin().read_to_end(&mut bytes).map(|_| ())
+    } else {
+        std::fs::read(path).map(|res| bytes = res)
+    };
+    if let Err(err) = res {
+        eprintln!("error: cannot read `{}`: {}", path, err);
+        process::exit(EXIT_IO_ERROR);
+    }
+    bytes
+}
+
+// 路径为-时写到标准输出
+fn write_file(path: &str, bytes: &[u8]) {
+    let res = if path == "-" {
+        let mut stdout = io::stdout();
+        stdout.write_all(bytes).and_then(|_| stdout.flush())
+    } else {
+        std::fs::write(path, bytes)
+    };
+    if let Err(err) = res {
+        eprintln!("error: cannot write `{}`: {}", path, err);
+        process::exit(EXIT_IO_ERROR);
+    }
+}
+
+fn decode_source(path: &str, bytes: Vec<u8>) -> String {
+    match String::from_utf8(bytes) {
+        Ok(source) => source,
+        Err(_) => {
+            eprintln!(
+                "error: cannot read `{}`: stream did not contain valid UTF-8",
+                path
+            );
+            process::exit(EXIT_IO_ERROR);
+        }
+    }
+}
+
+// 标准输入在诊断信息中显示为<stdin>
+fn source_name(path: &str) -> &str {
+    if path == "-" {
+        "<stdin>"
+    } else {
+        path
+    }
+}
+
+fn run_bytecode(program: &bytecode::Program) -> ! {
+    match bytecode::vm::run(program) {
+        Ok(code) => process::exit(code),
+        Err(trap) => {
+            eprintln!("error: {}", trap);
+            process::exit(EXIT_RUNTIME_ERROR);
+        }
+    }
+}
+
+// 输出警告，有错误时输出错误并退出
+fn report<T>(
+    sources: &SourceMap,
+    error_limit: usize,
+    warnings: &[Diagnostic],
+    res: Result<T, Vec<Diagnostic>>,
+) -> T {
+    for warning in warnings {
+        eprintln!("{}", warning.render(sources));
+    }
+    match res {
+        Ok(output) => output,
+        Err(diagnostics) => {
+            // 出错时警告与错误一起返回
+            let (warnings, errors): (Vec<_>, Vec<_>) = diagnostics
+                .iter()
+                .partition(|diagnostic| diagnostic.severity == Severity::Warning);
+            for warning in warnings {
+                eprintln!("{}", warning.render(sources));
+            }
+            for error in &errors {
+                eprintln!("{}\n", error.render(sources));
+            }
+            if error_limit != 0 && errors.len() >= error_limit {
+                eprintln!("error: too many errors emitted, stopping now");
+            }
+            eprintln!(
+                "error: aborting due to {} previous error{}",
+                errors.len(),
+                if errors.len() == 1 { "" } else { "s" }
+            );
+            process::exit(EXIT_COMPILE_ERROR);
+        }
+    }
+}
+
+// calcium run：检查后直接解释执行，退出码为main函数的返回值
+// 默认遍历语法树，使用--ir时执行优化后的IR，使用--vm时编译为字节码执行；输入是.cbc文件时直接执行其中的字节码
+fn run(args: Args) -> ! {
+    if args.emit.is_some() || args.target.is_some() || args.output.is_some() {
+        usage_error("`--emit`, `--target` and `-o` cannot be used with `run`");
+    }
+    if args.use_ir && args.use_vm {
+        usage_error("`--ir` and `--vm` cannot be used together");
+    }
+    let path = match args.paths.as_slice() {
+        [path] => path.as_str(),
+        [] => usage_error("no input file"),
+        [_, extra, ..] => usage_error(&format!("unexpected argument `{}`", extra)),
+    };
+    let bytes = read_file(path);
+    if bytecode::is_bytecode(&bytes) {
+        match bytecode::Program::decode(&bytes) {
+            Ok(program) => run_bytecode(&program),
+            Err(err) => {
+                eprintln!("error: cannot load `{}`: {}", path, err);
+                process::exit(EXIT_IO_ERROR);
+            }
+        }
+    }
+    let mut sources = SourceMap::new();
+    let file = sources.add(source_name(path), decode_source(path, bytes));
+    let mut warnings = vec![];
+    let res = analyze(&sources, file, args.error_limit, &mut warnings);
+    let (comp_unit, mut module) = report(&sources, args.error_limit, &warnings, res);
+    if args.use_vm {
+        run_bytecode(&bytecode::compiler::compile(&comp_unit));
+    }
+    if args.use_ir {
+        pass::run(&mut module, &args.options);
+        match ir::exec::run(&module) {
+            Ok(code) => process::exit(code),
+            Err(trap) => {
+                eprintln!("error: {}", trap);
+                process::exit(EXIT_RUNTIME_ERROR);
+            }
+        }
+    }
+    match Interpreter::run(&comp_unit) {
+        Ok(code) => process::exit(code),
+        Err(err) => {
+            eprintln!("{}", err.render(&sources));
+            process::exit(EXIT_RUNTIME_ERROR);
+        }
+    }
+}
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
-    let source = std::fs::read_to_string(&args[1]).unwrap();
-    let tokens = Tokenizer::tokenize(&source);
-    let output = Parser::parse(&tokens);
-    std::fs::write(&args[2], output).unwrap();
+    let args = parse_args(&args);
+    if args.is_run {
+        run(args);
+    }
+    let (emit, target) = resolve_emit(args.emit.as_deref(), args.target.as_deref());
+    // 输出文件可以用-o指定，也可以作为第二个路径，都没有时写到标准输出
+    let (input, output_path) = match (args.paths.as_slice(), &args.output) {
+        ([], _) => usage_error("no input file"),
+        ([input], Some(output)) | ([input, output], None) => (input.as_str(), output.as_str()),
+        ([input], None) => (input.as_str(), "-"),
+        ([_, _], Some(_)) => usage_error("multiple output files"),
+        ([_, _, extra, ..], _) => usage_error(&format!("unexpected argument `{}`", extra)),
+    };
+    let mut sources = SourceMap::new();
+    let file = sources.add(source_name(input), decode_source(input, read_file(input)));
+    let options = Options {
+        emit,
+        target,
+        passes: args.options,
+        error_limit: args.error_limit,
+    };
+    // resolve_emit已经检查过选项，这里不会出现InvalidOptions
+    let res = compile_file(&sources, file, &options).map_err(|err| match err {
+        Error::InvalidOptions(message) => usage_error(&message),
+        Error::Diagnostics(diagnostics) => diagnostics,
+    });
+    let output = report(&sources, args.error_limit, &[], res);
+    for warning in &output.warnings {
+        eprintln!("{}", warning.render(&sources));
+    }
+    write_file(output_path, &output.code);
 }
@@ -1,1205 +1,875 @@
-use core::panic;
 use std::collections::{linked_list::Iter, LinkedList};
-use std::vec;
 
-use crate::symbol::Variable;
-
-use super::assigner::Assigner;
-use super::symbol::SymbolTable;
-use super::token::Token;
+use super::ast::{
+    BType, BinaryOp, Block, BlockItem, Case, CompUnit, Decl, Expr, ExprKind, FuncDef, FuncParam,
+    IncDecOp, InitVal, Item, LVal, Stmt, StmtKind, UnaryOp, VarDef,
+};
+use super::diagnostic::{Diagnostic, ErrorCode};
+use super::span::Span;
+use super::token::{Token, TokenKind};
 
 pub struct Parser<'a> {
     iter: Iter<'a, Token>,
-    symbol: SymbolTable,
-    assigner: Assigner,
-    pre_code: String,       // alloca部分，递归过程中添加代码
-    block_code: String,     // 基本块部分，递归过程中添加代码
-    global_code: String, // 全局变量部分，递归过程中添加代码，其实可以综合成Code类，不过这样得小重构一波
-    is_parsing_const: bool, // 是否正在解析局部常量数组维度
+    eof: &'a Token,
+    prev_span: Span, // 上一个被消费的token的位置
+    errors: Vec<Diagnostic>,
+    error_limit: usize,  // 为0时不限制错误数量
+    salvaged: Vec<Decl>, // 出错的声明中已解析出的变量，仍加入语法树以免后续误报未声明
 }
 
 impl<'a> Parser<'a> {
-    fn consume_token(&mut self, token: Token) {
-        let var = self.iter.next().unwrap();
-        if var != &token {
-            panic!("syntax error, expect {:?}, but get {:?}", token, var);
-        }
+    fn error<T>(&self, code: ErrorCode, span: Span, message: &str) -> Result<T, Diagnostic> {
+        Err(Diagnostic::error(code, message.to_string(), span))
     }
 
-    fn add_block_ins(&mut self, ins: String) {
-        self.block_code += format!("    {}\n", ins).as_str();
+    fn peek(&self) -> &'a TokenKind {
+        &self.iter.clone().next().unwrap_or(self.eof).kind
     }
 
-    fn add_pre_ins(&mut self, ins: String) {
-        self.pre_code += format!("    {}\n", ins).as_str();
+    fn peek_nth(&self, n: usize) -> &'a TokenKind {
+        &self.iter.clone().nth(n).unwrap_or(self.eof).kind
     }
 
-    fn get_elem_pos(&mut self, var_name: String, pos: Vec<String>) -> Variable {
-        let mut var = self.symbol.get_var(&var_name).clone();
-        if var.shape.len() < pos.len() {
-            panic!("syntax error!");
-        }
-        for index in 0..pos.len() {
-            let new_reg = self.assigner.new_var();
-            if index == 0 && var.shape[0] == 0 {
-                var.shape.remove(0);
-                let shape_str = Variable::get_shape_from_vec(&var.shape);
-                self.add_block_ins(format!(
-                    "{} = getelementptr {}, {}* {}, i32 {}",
-                    new_reg, shape_str, shape_str, var.reg, pos[index]
-                ));
-            } else {
-                let shape_str = Variable::get_shape_from_vec(&var.shape);
-                self.add_block_ins(format!(
-                    "{} = getelementptr {}, {}* {}, i32 0, i32 {}",
-                    new_reg, shape_str, shape_str, var.reg, pos[index]
-                ));
-                var.shape.remove(0);
-            }
-            var.reg = new_reg;
-        }
-        var
+    fn peek_span(&self) -> Span {
+        self.iter.clone().next().unwrap_or(self.eof).span
     }
-}
 
-impl<'a> Parser<'a> {
-    pub fn parse(tokens: &LinkedList<Token>) -> String {
-        if tokens.is_empty() {
-            panic!("syntax error!");
-        }
-        let mut parser = Parser {
-            iter: tokens.iter(),
-            symbol: SymbolTable::new(),
-            assigner: Assigner::new(),
-            pre_code: String::new(),
-            block_code: String::new(),
-            global_code: String::new(),
-            is_parsing_const: false,
-        };
-        parser.parse_comp_unit()
+    // 从start到上一个被消费的token
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.prev_span)
     }
 
-    fn parse_comp_unit(&mut self) -> String {
-        let mut func_code = String::from("");
-        while self.iter.clone().next() != None {
-            if self.iter.clone().nth(2).unwrap() == &Token::LParen {
-                func_code = func_code + self.parse_func_def().as_str() + "\n";
-            } else {
-                self.parse_decl();
-            }
-        }
-        // main函数检查
-        let main = self.symbol.get_func(&String::from("main"));
-        if !main.has_return || !main.params.is_empty() {
-            panic!("main function syntax error!");
-        }
-        String::from(
-            "declare i32 @getint()\n\
-        declare i32 @getch()\n\
-        declare i32 @getarray(i32*)\n\
-        declare void @putch(i32)\n\
-        declare void @putint(i32)\n\
-        declare void @putarray(i32, i32*)\n\n",
-        ) + self.global_code.clone().as_str()
-            + "\n"
-            + func_code.as_str()
+    fn next_token(&mut self) -> &'a Token {
+        let token = self.iter.next().unwrap_or(self.eof);
+        self.prev_span = token.span;
+        token
     }
 
-    fn parse_decl(&mut self) {
-        match self.iter.clone().next().unwrap() {
-            Token::Const => self.parse_const_decl(),
-            _ => self.parse_var_decl(),
-        }
+    // 不匹配时不消费token，便于错误恢复时从该token开始同步
+    fn consume_token(&mut self, kind: TokenKind) -> Result<(), Diagnostic> {
+        if self.peek() != &kind {
+            return self.error(
+                ErrorCode::UnexpectedToken,
+                self.peek_span(),
+                format!("expect {}, but get {}", kind, self.peek()).as_str(),
+            );
+        }
+        self.next_token();
+        Ok(())
     }
 
-    fn parse_const_decl(&mut self) {
-        self.consume_token(Token::Const);
-        self.consume_token(Token::Int);
-        self.parse_const_def();
-        while self.iter.clone().next().unwrap() == &Token::Comma {
-            self.consume_token(Token::Comma);
-            self.parse_const_def();
+    fn consume_ident(&mut self) -> Result<&'a String, Diagnostic> {
+        match self.peek() {
+            TokenKind::Ident(ident) => {
+                self.next_token();
+                Ok(ident)
+            }
+            kind => self.error(
+                ErrorCode::UnexpectedToken,
+                self.peek_span(),
+                format!("expect identifier, but get {}", kind).as_str(),
+            ),
         }
-        self.consume_token(Token::Semicolon);
     }
 
-    fn parse_const_def(&mut self) {
-        // 标识符
-        let name = match self.iter.next().unwrap() {
-            Token::Ident(ident) => ident,
-            _ => panic!("syntax error!"),
-        };
-        // 形状
-        let mut shape: Vec<i32> = Vec::new();
-        while self.iter.clone().next().unwrap() == &Token::LBracket {
-            self.consume_token(Token::LBracket);
-            let dimension = atoi(&self.parse_add_exp(true).unwrap().reg, 10);
-            if dimension < 0 {
-                panic!("syntax error!");
-            } else {
-                shape.push(dimension);
-            }
-            self.consume_token(Token::RBracket);
-        }
-        // 消费赋值号
-        self.consume_token(Token::Assign);
-        // 逻辑处理，分为全局和局部
-        if self.symbol.is_global() {
-            let reg = format!("@{}", name);
-            let init_val = self.parse_const_init_val(vec![], shape.clone());
-            if shape.is_empty() {
-                self.symbol
-                    .insert_var(&name, &reg, true, &shape, atoi(&init_val, 10));
-                self.global_code += format!("{} = constant i32 {}\n", reg, init_val).as_str();
-            } else {
-                self.symbol.insert_var(&name, &reg, true, &shape, 0);
-                self.global_code += format!("{} = constant {}\n", reg, init_val).as_str();
-            }
-        } else {
-            if shape.is_empty() {
-                let reg = self.assigner.new_pre_var();
-                self.is_parsing_const=true;
-                let init_val = atoi(&self.parse_add_exp(true).unwrap().reg,10);
-                self.is_parsing_const=false;
-                self.symbol.insert_var(&name, &reg, true, &shape, init_val);
-                self.add_pre_ins(format!("{} = alloca i32", reg));
-                self.add_block_ins(format!("store i32 {}, i32* {}", init_val, reg));
-            } else {
-                let reg = self.assigner.new_pre_var();
-                self.symbol.insert_var(&name, &reg, true, &shape, 0);
-                let init_val = self.parse_const_init_val(vec![], shape.clone());
-                self.add_pre_ins(format!("{} = alloca {}", reg, init_val));
-            }
+    // 记录错误，达到上限后丢弃剩余的token，使解析尽快结束
+    fn report(&mut self, diagnostic: Diagnostic) {
+        if self.error_limit != 0 && self.errors.len() >= self.error_limit {
+            self.iter.by_ref().for_each(drop);
+            return;
         }
+        self.errors.push(diagnostic);
     }
 
-    fn parse_const_init_val(&mut self, front: Vec<i32>, back: Vec<i32>) -> String {
-        if self.symbol.is_global() {
-            if back.is_empty() {
-                self.parse_add_exp(true).unwrap().reg
-            } else {
-                let mut res = Variable::get_shape_from_vec(&back);
-                self.consume_token(Token::LBrace);
-                if self.iter.clone().next().unwrap() == &Token::RBrace {
-                    self.consume_token(Token::RBrace);
-                    res += " zeroinitializer";
-                } else {
-                    res += " [";
-                    let mut new_front = front.clone();
-                    new_front.push(0);
-                    let mut new_back = back.clone();
-                    new_back.remove(0);
-                    if new_back.is_empty() {
-                        res += "i32 ";
-                    }
-                    res += self
-                        .parse_const_init_val(new_front.clone(), new_back.clone())
-                        .as_str();
-                    while *new_front.last().unwrap() < back[0] - 1 {
-                        res += ", ";
-                        *new_front.last_mut().unwrap() += 1;
-                        match self.iter.clone().next().unwrap() {
-                            Token::Comma => {
-                                self.consume_token(Token::Comma);
-                                if new_back.is_empty() {
-                                    res += "i32 ";
-                                }
-                                res += self
-                                    .parse_const_init_val(new_front.clone(), new_back.clone())
-                                    .as_str();
-                            }
-                            _ => {
-                                if new_back.is_empty() {
-                                    res += "i32 0";
-                                } else {
-                                    res += Variable::get_shape_from_vec(&new_back).as_str();
-                                    res += " zeroinitializer";
-                                }
-                            }
-                        }
-                    }
-                    self.consume_token(Token::RBrace);
-                    res += "]";
-                }
-                res
-            }
-        } else {
-            if back.is_empty() {
-                let name = self.symbol.get_current_val().name.clone();
-                let mut pos: Vec<String> = vec![];
-                for item in front.clone() {
-                    pos.push(item.to_string());
+    // 语句级同步：跳过到;或配对的}之后，或停在}、int、float、const之前
+    // remaining为出错语句开始时剩余的token数，保证至少前进一个token
+    fn synchronize_stmt(&mut self, remaining: usize) {
+        let mut depth: usize = 0;
+        let mut progressed = self.iter.len() != remaining;
+        loop {
+            match self.peek() {
+                TokenKind::Eof => break,
+                TokenKind::RBrace
+                | TokenKind::Int
+                | TokenKind::Float
+                | TokenKind::Const
+                | TokenKind::Case
+                | TokenKind::Default
+                    if depth == 0 && progressed =>
+                {
+                    break
                 }
-                let var = self.get_elem_pos(name, pos);
-                let val = self.parse_add_exp(true).unwrap().reg;
-                self.add_block_ins(format!("store i32 {}, i32* {}", val, var.reg));
-            } else {
-                self.consume_token(Token::LBrace);
-                if self.iter.clone().next().unwrap() != &Token::RBrace {
-                    let mut new_front = front.clone();
-                    new_front.push(0);
-                    let mut new_back = back.clone();
-                    new_back.remove(0);
-                    self.parse_const_init_val(new_front.clone(), new_back.clone())
-                        .as_str();
-                    while self.iter.clone().next().unwrap() == &Token::Comma {
-                        self.consume_token(Token::Comma);
-                        *new_front.last_mut().unwrap() += 1;
-                        self.parse_const_init_val(new_front.clone(), new_back.clone())
-                            .as_str();
-                    }
-                    if *new_front.last().unwrap()
-                        >= self.symbol.get_current_val().shape[new_front.len() - 1]
-                    {
-                        panic!("syntax error!");
+                _ => progressed = true,
+            }
+            match self.next_token().kind {
+                TokenKind::Semicolon if depth == 0 => break,
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 && self.peek() != &TokenKind::Else {
+                        break;
                     }
                 }
-                self.consume_token(Token::RBrace);
+                _ => {}
             }
-            Variable::get_shape_from_vec(&back)
         }
     }
 
-    fn parse_var_decl(&mut self) {
-        self.consume_token(Token::Int);
-        self.parse_var_def();
-        while self.iter.clone().next().unwrap() == &Token::Comma {
-            self.consume_token(Token::Comma);
-            self.parse_var_def();
+    // 顶层同步：停在大括号之外的int、float、void、const之前
+    fn synchronize_decl(&mut self, remaining: usize) {
+        let mut depth: usize = 0;
+        let mut progressed = self.iter.len() != remaining;
+        loop {
+            match self.peek() {
+                TokenKind::Eof => break,
+                TokenKind::Int | TokenKind::Float | TokenKind::Void | TokenKind::Const
+                    if depth == 0 && progressed =>
+                {
+                    break
+                }
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            progressed = true;
+            self.next_token();
         }
-        self.consume_token(Token::Semicolon);
     }
+}
 
-    fn parse_var_def(&mut self) {
-        // 标识符
-        let name = match self.iter.next().unwrap() {
-            Token::Ident(ident) => ident,
-            _ => panic!("syntax error!"),
+impl<'a> Parser<'a> {
+    // 只做语法分析，出错的声明和语句会被跳过，不出现在语法树中
+    // errors为词法分析阶段的错误，语法错误追加在其后，共同受error_limit限制，最后按位置排序
+    pub fn parse(
+        tokens: &'a LinkedList<Token>,
+        mut errors: Vec<Diagnostic>,
+        error_limit: usize,
+    ) -> (CompUnit, Vec<Diagnostic>) {
+        if error_limit != 0 {
+            errors.truncate(error_limit);
+        }
+        let eof = tokens.back().unwrap();
+        let mut parser = Parser {
+            iter: tokens.iter(),
+            eof,
+            prev_span: Span::default(),
+            errors,
+            error_limit,
+            salvaged: vec![],
         };
-        // 形状
-        let mut shape: Vec<i32> = Vec::new();
-        while self.iter.clone().next().unwrap() == &Token::LBracket {
-            self.consume_token(Token::LBracket);
-            self.is_parsing_const = true;
-            let dimension = atoi(&self.parse_add_exp(true).unwrap().reg, 10);
-            self.is_parsing_const = false;
-            if dimension < 0 {
-                panic!("syntax error!");
-            } else {
-                shape.push(dimension);
-            }
-            self.consume_token(Token::RBracket);
-        }
-        // 根据是否有赋值号分成两类
-        if self.iter.clone().next().unwrap() == &Token::Assign {
-            self.consume_token(Token::Assign);
-            // 进一步分为全局和局部
-            if self.symbol.is_global() {
-                let reg = format!("@{}", name);
-                let init_val = self.parse_init_val(vec![], shape.clone());
-                if shape.is_empty() {
-                    self.symbol
-                        .insert_var(&name, &reg, false, &shape, atoi(&init_val, 10));
-                    self.global_code += format!("{} = global i32 {}\n", reg, init_val).as_str();
-                } else {
-                    self.symbol.insert_var(&name, &reg, false, &shape, 0);
-                    self.global_code += format!("{} = global {}\n", reg, init_val).as_str();
-                }
-            } else {
-                let reg = self.assigner.new_pre_var();
-                self.symbol.insert_var(&name, &reg, false, &shape, 0);
-                let init_val = self.parse_init_val(vec![], shape.clone());
-                self.add_pre_ins(format!("{} = alloca {}", reg, init_val));
-            }
-        } else {
-            // 进一步分为全局和局部
-            if self.symbol.is_global() {
-                let reg = format!("@{}", name);
-                self.symbol.insert_var(&name, &reg, false, &shape, 0);
-                let shape_str = Variable::get_shape_from_vec(&shape);
-                let val_str = if shape.is_empty() {
-                    "0"
-                } else {
-                    "zeroinitializer"
-                };
-                self.global_code +=
-                    format!("{} = global {} {}\n", reg, shape_str, val_str).as_str();
-            } else {
-                let reg = self.assigner.new_pre_var();
-                self.symbol.insert_var(&name, &reg, false, &shape, 0);
-                let shape_str = Variable::get_shape_from_vec(&shape);
-                self.add_pre_ins(format!("{} = alloca {}", reg, shape_str));
-            }
+        if tokens.len() == 1 {
+            parser.report(Diagnostic::error(
+                ErrorCode::UnexpectedToken,
+                "expect declaration, but get end of file".to_string(),
+                eof.span,
+            ));
         }
+        let comp_unit = parser.parse_comp_unit();
+        parser
+            .errors
+            .sort_by_key(|err| (err.span.file, err.span.start));
+        (comp_unit, parser.errors)
     }
 
-    fn parse_init_val(&mut self, front: Vec<i32>, back: Vec<i32>) -> String {
-        if self.symbol.is_global() {
-            if back.is_empty() {
-                self.parse_add_exp(false).unwrap().reg
+    fn parse_comp_unit(&mut self) -> CompUnit {
+        let mut items = vec![];
+        while self.peek() != &TokenKind::Eof {
+            let remaining = self.iter.len();
+            let res = if self.peek_nth(2) == &TokenKind::LParen {
+                self.parse_func_def().map(Item::FuncDef)
             } else {
-                let mut res = Variable::get_shape_from_vec(&back);
-                self.consume_token(Token::LBrace);
-                if self.iter.clone().next().unwrap() == &Token::RBrace {
-                    self.consume_token(Token::RBrace);
-                    res += " zeroinitializer";
-                } else {
-                    res += " [";
-                    let mut new_front = front.clone();
-                    new_front.push(0);
-                    let mut new_back = back.clone();
-                    new_back.remove(0);
-                    if new_back.is_empty() {
-                        res += "i32 ";
-                    }
-                    res += self
-                        .parse_init_val(new_front.clone(), new_back.clone())
-                        .as_str();
-                    while *new_front.last().unwrap() < back[0] - 1 {
-                        res += ", ";
-                        *new_front.last_mut().unwrap() += 1;
-                        match self.iter.clone().next().unwrap() {
-                            Token::Comma => {
-                                self.consume_token(Token::Comma);
-                                if new_back.is_empty() {
-                                    res += "i32 ";
-                                }
-                                res += self
-                                    .parse_init_val(new_front.clone(), new_back.clone())
-                                    .as_str();
-                            }
-                            _ => {
-                                if new_back.is_empty() {
-                                    res += "i32 0";
-                                } else {
-                                    res += Variable::get_shape_from_vec(&new_back).as_str();
-                                    res += " zeroinitializer";
-                                }
-                            }
-                        }
-                    }
-                    self.consume_token(Token::RBrace);
-                    res += "]";
+                self.parse_decl().map(Item::Decl)
+            };
+            match res {
+                Ok(item) => items.push(item),
+                Err(err) => {
+                    self.report(err);
+                    items.extend(self.salvaged.drain(..).map(Item::Decl));
+                    self.synchronize_decl(remaining);
                 }
-                res
             }
-        } else {
-            if back.is_empty() {
-                let name = self.symbol.get_current_val().name.clone();
-                let mut pos: Vec<String> = vec![];
-                for item in front.clone() {
-                    pos.push(item.to_string());
-                }
-                let var = self.get_elem_pos(name, pos);
-                let val = self.parse_add_exp(false).unwrap().reg;
-                self.add_block_ins(format!("store i32 {}, i32* {}", val, var.reg));
-            } else {
-                self.consume_token(Token::LBrace);
-                if self.iter.clone().next().unwrap() != &Token::RBrace {
-                    let mut new_front = front.clone();
-                    new_front.push(0);
-                    let mut new_back = back.clone();
-                    new_back.remove(0);
-                    self.parse_init_val(new_front.clone(), new_back.clone())
-                        .as_str();
-                    while self.iter.clone().next().unwrap() == &Token::Comma {
-                        self.consume_token(Token::Comma);
-                        *new_front.last_mut().unwrap() += 1;
-                        self.parse_init_val(new_front.clone(), new_back.clone())
-                            .as_str();
-                    }
-                    if *new_front.last().unwrap()
-                        >= self.symbol.get_current_val().shape[new_front.len() - 1]
-                    {
-                        panic!("syntax error!");
-                    }
-                }
-                self.consume_token(Token::RBrace);
+        }
+        CompUnit {
+            items,
+            end: self.eof.span,
+        }
+    }
+
+    fn parse_decl(&mut self) -> Result<Decl, Diagnostic> {
+        let is_const = self.peek() == &TokenKind::Const;
+        if is_const {
+            self.consume_token(TokenKind::Const)?;
+        }
+        let ty = self.parse_btype()?;
+        let mut defs = vec![];
+        let res = self.parse_var_defs(is_const, ty, &mut defs);
+        if let Err(err) = res {
+            if !defs.is_empty() {
+                self.salvaged.push(Decl { is_const, ty, defs });
             }
-            Variable::get_shape_from_vec(&back)
+            return Err(err);
         }
+        Ok(Decl { is_const, ty, defs })
     }
 
-    fn parse_func_def(&mut self) -> String {
-        // 初始化
-        self.assigner.reset();
-        self.pre_code.clear();
-        self.block_code.clear();
-        self.block_code.push_str("b_1:\n");
-        // 声明解析
-        let func_type = match self.iter.next().unwrap() {
-            Token::Void => "void",
-            Token::Int => "i32",
-            _ => panic!("syntax error!"),
-        };
-        let func_name = match self.iter.next().unwrap() {
-            Token::Ident(name) => name,
-            _ => panic!("syntax error!"),
+    fn parse_btype(&mut self) -> Result<BType, Diagnostic> {
+        let token = self.next_token();
+        match token.kind {
+            TokenKind::Int => Ok(BType::Int),
+            TokenKind::Float => Ok(BType::Float),
+            _ => self.error(
+                ErrorCode::UnexpectedToken,
+                token.span,
+                format!("expect `int` or `float`, but get {}", token.kind).as_str(),
+            ),
+        }
+    }
+
+    fn parse_var_defs(
+        &mut self,
+        is_const: bool,
+        ty: BType,
+        defs: &mut Vec<VarDef>,
+    ) -> Result<(), Diagnostic> {
+        defs.push(self.parse_var_def(is_const, ty)?);
+        while self.peek() == &TokenKind::Comma {
+            self.consume_token(TokenKind::Comma)?;
+            defs.push(self.parse_var_def(is_const, ty)?);
+        }
+        self.consume_token(TokenKind::Semicolon)
+    }
+
+    fn parse_var_def(&mut self, is_const: bool, ty: BType) -> Result<VarDef, Diagnostic> {
+        // 标识符
+        let name = self.consume_ident()?.clone();
+        let mut def = VarDef {
+            name,
+            name_span: self.prev_span,
+            dims: vec![],
+            init: None,
         };
-        // 解析参数
-        self.consume_token(Token::LParen);
-        let func_params = match self.iter.clone().next().unwrap() {
-            Token::RParen => {
-                self.symbol.go_down();
-                // 添加短路求值需要的局部变量
-                let pre_var = self.assigner.new_pre_var();
-                self.add_pre_ins(format!("{} = alloca i1", pre_var));
-                self.add_block_ins(format!("store i1 0, i1* {}", pre_var));
-                self.symbol.insert_var(
-                    &"#impossible#".to_string(),
-                    &"%1".to_string(),
-                    false,
-                    &vec![],
-                    0,
-                );
-                vec![]
+        // 出错时按没有初始值的变量保留
+        match self.parse_var_def_rest(is_const, &mut def) {
+            Ok(()) => Ok(def),
+            Err(err) => {
+                self.salvaged.push(Decl {
+                    is_const: false,
+                    ty,
+                    defs: vec![def],
+                });
+                Err(err)
             }
-            _ => self.parse_func_fparams(),
-        };
-        self.consume_token(Token::RParen);
-        // 向符号表中插入函数
-        self.symbol
-            .insert_func(func_name, func_type.eq("i32"), &func_params);
-        // 翻译并返回
-        self.assigner.go_next_block();
-        self.parse_func_block();
-        self.add_pre_ins("br label %b_1".to_string());
-        let last_ins = self.block_code.trim().split("\n").last().unwrap();
-        if !last_ins.starts_with("    br") && !last_ins.starts_with("    ret") {
-            self.add_block_ins(format!(
-                "ret {}",
-                if self.symbol.get_current_func().has_return {
-                    "i32 0"
-                } else {
-                    "void"
-                }
-            ));
         }
-        self.symbol.get_func(func_name).get_definition()
-            + self.pre_code.as_str()
-            + self.block_code.as_str()
-            + "}\n"
     }
 
-    fn parse_func_fparams(&mut self) -> Vec<Vec<i32>> {
-        let mut vars: Vec<Variable> = vec![];
-        vars.push(self.parse_func_fparam());
-        while self.iter.clone().next().unwrap() == &Token::Comma {
-            self.consume_token(Token::Comma);
-            vars.push(self.parse_func_fparam());
+    fn parse_var_def_rest(&mut self, is_const: bool, def: &mut VarDef) -> Result<(), Diagnostic> {
+        // 形状
+        while self.peek() == &TokenKind::LBracket {
+            self.consume_token(TokenKind::LBracket)?;
+            def.dims.push(self.parse_cond_exp()?);
+            self.consume_token(TokenKind::RBracket)?;
         }
-        // 计算完参数再进入作用域添加符号
-        let mut res = vec![];
-        self.symbol.go_down();
-        // 添加短路求值需要的局部变量
-        let pre_var = self.assigner.new_pre_var();
-        self.add_pre_ins(format!("{} = alloca i1", pre_var));
-        self.add_block_ins(format!("store i1 0, i1* {}", pre_var));
-        self.symbol.insert_var(
-            &"#impossible#".to_string(),
-            &"%1".to_string(),
-            false,
-            &vec![],
-            0,
-        );
-        // 处理形式参数
-        for index in 0..vars.len() {
-            res.push(vars[index].shape.clone());
-            if vars[index].shape.is_empty() {
-                let pre_var = self.assigner.new_pre_var();
-                self.add_pre_ins(format!("{} = alloca i32", pre_var));
-                self.add_block_ins(format!("store i32 %p{}, i32* {}", index + 1, pre_var));
-                self.symbol.insert_var(
-                    &vars[index].name,
-                    &format!("{}", pre_var),
-                    false,
-                    &vars[index].shape,
-                    0,
-                );
-            } else {
-                self.symbol.insert_var(
-                    &vars[index].name,
-                    &format!("%p{}", index + 1),
-                    false,
-                    &vars[index].shape,
-                    0,
-                );
+        // 常量必须有初始值
+        if is_const || self.peek() == &TokenKind::Assign {
+            self.consume_token(TokenKind::Assign)?;
+            def.init = Some(self.parse_init_val()?);
+        }
+        Ok(())
+    }
+
+    fn parse_init_val(&mut self) -> Result<InitVal, Diagnostic> {
+        if self.peek() != &TokenKind::LBrace {
+            return Ok(InitVal::Expr(self.parse_assign_exp()?));
+        }
+        let start = self.peek_span();
+        self.consume_token(TokenKind::LBrace)?;
+        let mut vals = vec![];
+        if self.peek() != &TokenKind::RBrace {
+            vals.push(self.parse_init_val()?);
+            while self.peek() == &TokenKind::Comma {
+                self.consume_token(TokenKind::Comma)?;
+                vals.push(self.parse_init_val()?);
             }
         }
-        res
+        self.consume_token(TokenKind::RBrace)?;
+        Ok(InitVal::List(vals, self.span_from(start)))
     }
 
-    fn parse_func_fparam(&mut self) -> Variable {
-        self.consume_token(Token::Int);
-        let mut var = Variable::new();
-        var.name = match self.iter.next().unwrap() {
-            Token::Ident(ident) => ident.clone(),
-            _ => panic!("syntax error!"),
-        };
-        var.shape = match self.iter.clone().next().unwrap() {
-            Token::LBracket => {
-                self.consume_token(Token::LBracket);
-                self.consume_token(Token::RBracket);
-                vec![0]
+    fn parse_func_def(&mut self) -> Result<FuncDef, Diagnostic> {
+        let token = self.next_token();
+        let (has_return, ty) = match token.kind {
+            TokenKind::Void => (false, BType::Int),
+            TokenKind::Int => (true, BType::Int),
+            TokenKind::Float => (true, BType::Float),
+            _ => {
+                return self.error(
+                    ErrorCode::UnexpectedToken,
+                    token.span,
+                    format!("expect `int`, `float` or `void`, but get {}", token.kind).as_str(),
+                )
             }
-            _ => vec![],
         };
-        while self.iter.clone().next().unwrap() == &Token::LBracket {
-            self.consume_token(Token::LBracket);
-            var.shape
-                .push(atoi(&self.parse_add_exp(true).unwrap().reg, 10));
-            self.consume_token(Token::RBracket);
-        }
-        var
+        let name = self.consume_ident()?.clone();
+        let name_span = self.prev_span;
+        // 解析参数
+        self.consume_token(TokenKind::LParen)?;
+        let mut params = vec![];
+        if self.peek() != &TokenKind::RParen {
+            params.push(self.parse_func_param()?);
+            while self.peek() == &TokenKind::Comma {
+                self.consume_token(TokenKind::Comma)?;
+                params.push(self.parse_func_param()?);
+            }
+        }
+        self.consume_token(TokenKind::RParen)?;
+        let body = self.parse_block()?;
+        Ok(FuncDef {
+            has_return,
+            ty,
+            name,
+            name_span,
+            params,
+            body,
+        })
     }
 
-    fn parse_block(&mut self) {
-        self.symbol.go_down();
-        self.consume_token(Token::LBrace);
-        while self.iter.clone().next().unwrap() != &Token::RBrace {
-            self.parse_block_item();
-        }
-        self.consume_token(Token::RBrace);
-        self.symbol.go_up();
+    fn parse_func_param(&mut self) -> Result<FuncParam, Diagnostic> {
+        let ty = self.parse_btype()?;
+        let name = self.consume_ident()?.clone();
+        let span = self.prev_span;
+        let mut dims = vec![];
+        if self.peek() == &TokenKind::LBracket {
+            self.consume_token(TokenKind::LBracket)?;
+            self.consume_token(TokenKind::RBracket)?;
+            dims.push(None);
+            while self.peek() == &TokenKind::LBracket {
+                self.consume_token(TokenKind::LBracket)?;
+                dims.push(Some(self.parse_cond_exp()?));
+                self.consume_token(TokenKind::RBracket)?;
+            }
+        }
+        Ok(FuncParam {
+            ty,
+            name,
+            span,
+            dims,
+        })
+    }
+
+    fn parse_block(&mut self) -> Result<Block, Diagnostic> {
+        self.consume_token(TokenKind::LBrace)?;
+        let items = self.parse_block_items();
+        self.consume_token(TokenKind::RBrace)?;
+        Ok(Block {
+            items,
+            end: self.prev_span,
+        })
     }
 
-    fn parse_func_block(&mut self) {
-        self.consume_token(Token::LBrace);
-        while self.iter.clone().next().unwrap() != &Token::RBrace {
-            self.parse_block_item();
+    // 逐项解析，出错时记录错误并同步到下一条语句，遇到switch中的标号时停止
+    fn parse_block_items(&mut self) -> Vec<BlockItem> {
+        let mut items = vec![];
+        while !matches!(
+            self.peek(),
+            TokenKind::RBrace | TokenKind::Eof | TokenKind::Case | TokenKind::Default
+        ) {
+            let remaining = self.iter.len();
+            match self.parse_block_item() {
+                Ok(item) => items.push(item),
+                Err(err) => {
+                    self.report(err);
+                    items.extend(self.salvaged.drain(..).map(BlockItem::Decl));
+                    self.synchronize_stmt(remaining);
+                }
+            }
         }
-        self.consume_token(Token::RBrace);
-        self.symbol.go_up();
+        items
     }
 
-    fn parse_block_item(&mut self) {
-        let next = self.iter.clone().next().unwrap();
-        if next == &Token::Const || next == &Token::Int {
-            self.parse_decl();
+    fn parse_block_item(&mut self) -> Result<BlockItem, Diagnostic> {
+        let next = self.peek();
+        if matches!(next, TokenKind::Const | TokenKind::Int | TokenKind::Float) {
+            Ok(BlockItem::Decl(self.parse_decl()?))
         } else {
-            self.parse_stmt();
+            Ok(BlockItem::Stmt(Box::new(self.parse_stmt()?)))
         }
     }
 
-    fn parse_stmt(&mut self) {
-        match self.iter.clone().next().unwrap() {
-            Token::Return => {
-                self.consume_token(Token::Return);
-                if self.iter.clone().next().unwrap() == &Token::Semicolon {
-                    self.consume_token(Token::Semicolon);
-                    if self.symbol.get_current_func().has_return {
-                        panic!("return value mismatches!")
-                    }
-                    self.add_block_ins("ret void".to_string());
+    fn parse_stmt(&mut self) -> Result<Stmt, Diagnostic> {
+        let start = self.peek_span();
+        let kind = match self.peek() {
+            TokenKind::Return => {
+                self.consume_token(TokenKind::Return)?;
+                let value = if self.peek() == &TokenKind::Semicolon {
+                    None
                 } else {
-                    let ret_val = self.parse_add_exp(false).unwrap().reg;
-                    if !self.symbol.get_current_func().has_return {
-                        panic!("return value mismatches!")
+                    Some(self.parse_exp()?)
+                };
+                self.consume_token(TokenKind::Semicolon)?;
+                StmtKind::Return(value)
+            }
+            TokenKind::LBrace => StmtKind::Block(self.parse_block()?),
+            TokenKind::If => {
+                self.consume_token(TokenKind::If)?;
+                self.consume_token(TokenKind::LParen)?;
+                let cond = self.parse_exp()?;
+                self.consume_token(TokenKind::RParen)?;
+                let then_stmt = self.parse_stmt()?;
+                let else_stmt = if self.peek() == &TokenKind::Else {
+                    self.consume_token(TokenKind::Else)?;
+                    Some(Box::new(self.parse_stmt()?))
+                } else {
+                    None
+                };
+                StmtKind::If(cond, Box::new(then_stmt), else_stmt)
+            }
+            TokenKind::While => {
+                self.consume_token(TokenKind::While)?;
+                self.consume_token(TokenKind::LParen)?;
+                let cond = self.parse_exp()?;
+                self.consume_token(TokenKind::RParen)?;
+                StmtKind::While(cond, Box::new(self.parse_stmt()?))
+            }
+            TokenKind::For => {
+                self.consume_token(TokenKind::For)?;
+                self.consume_token(TokenKind::LParen)?;
+                let init = match self.peek() {
+                    TokenKind::Semicolon => {
+                        self.consume_token(TokenKind::Semicolon)?;
+                        None
                     }
-                    self.add_block_ins(format!("ret i32 {}", ret_val));
-                    self.consume_token(Token::Semicolon);
-                }
-            }
-            Token::Ident(_ident) => {
-                match self
-                    .iter
-                    .clone()
-                    .find(|&item| item == &Token::Assign || item == &Token::Semicolon)
-                    .unwrap()
-                {
-                    Token::Assign => {
-                        let lhs = self.parse_lval();
-                        self.consume_token(Token::Assign);
-                        let rhs = self.parse_add_exp(false);
-                        self.consume_token(Token::Semicolon);
-                        self.add_block_ins(format!("store i32 {}, i32* {}", rhs.unwrap().reg, lhs));
+                    TokenKind::Const | TokenKind::Int | TokenKind::Float => {
+                        Some(Box::new(BlockItem::Decl(self.parse_decl()?)))
                     }
-                    Token::Semicolon => {
-                        self.parse_add_exp(false);
-                        self.consume_token(Token::Semicolon);
+                    _ => {
+                        let exp = self.parse_exp()?;
+                        let span = exp.span;
+                        self.consume_token(TokenKind::Semicolon)?;
+                        let kind = StmtKind::Expr(Some(exp));
+                        Some(Box::new(BlockItem::Stmt(Box::new(Stmt { kind, span }))))
                     }
-                    _ => panic!("bug occurs, unreachable code!"),
-                }
-            }
-            Token::LBrace => {
-                self.parse_block();
-            }
-            Token::If => {
-                // 计算条件变量
-                self.consume_token(Token::If);
-                self.consume_token(Token::LParen);
-                let cond = self.parse_or_exp();
-                self.consume_token(Token::RParen);
-                // 跳转逻辑，跳转到子块
-                let if_next_block = self.assigner.get_next_block();
-                let if_sub_block = self.assigner.get_sub_block();
-                self.add_block_ins(format!(
-                    "br i1 {}, label %{}, label %{}",
-                    cond, if_sub_block, if_next_block
-                ));
-                self.block_code += format!("{}:\n", if_sub_block).as_str();
-                self.assigner.go_sub_block();
-                // 解析子块
-                self.parse_stmt();
-                // 跳转逻辑，跳转到下一块，分两种情况
-                self.assigner.go_parent_block();
-                self.assigner.go_next_block();
-                if self.iter.clone().next().unwrap() == &Token::Else {
-                    self.consume_token(Token::Else);
-                    let else_next_block = self.assigner.get_next_block();
-                    self.add_block_ins(format!("br label %{}", else_next_block));
-                    self.block_code += format!("{}:\n", if_next_block).as_str();
-                    let else_sub_block = self.assigner.get_sub_block();
-                    self.add_block_ins(format!("br label %{}", else_sub_block));
-                    self.block_code += format!("{}:\n", else_sub_block).as_str();
-                    self.assigner.go_sub_block();
-                    self.parse_stmt();
-                    self.assigner.go_parent_block();
-                    self.assigner.go_next_block();
-                    self.add_block_ins(format!("br label %{}", else_next_block));
-                    self.block_code += format!("{}:\n", else_next_block).as_str();
+                };
+                let cond = if self.peek() == &TokenKind::Semicolon {
+                    None
                 } else {
-                    self.add_block_ins(format!("br label %{}", if_next_block));
-                    self.block_code += format!("{}:\n", if_next_block).as_str();
+                    Some(self.parse_exp()?)
+                };
+                self.consume_token(TokenKind::Semicolon)?;
+                let step = if self.peek() == &TokenKind::RParen {
+                    None
+                } else {
+                    Some(self.parse_exp()?)
+                };
+                self.consume_token(TokenKind::RParen)?;
+                StmtKind::For(init, cond, step, Box::new(self.parse_stmt()?))
+            }
+            TokenKind::Do => {
+                self.consume_token(TokenKind::Do)?;
+                let body = self.parse_stmt()?;
+                self.consume_token(TokenKind::While)?;
+                self.consume_token(TokenKind::LParen)?;
+                let cond = self.parse_exp()?;
+                self.consume_token(TokenKind::RParen)?;
+                self.consume_token(TokenKind::Semicolon)?;
+                StmtKind::DoWhile(Box::new(body), cond)
+            }
+            TokenKind::Switch => {
+                self.consume_token(TokenKind::Switch)?;
+                self.consume_token(TokenKind::LParen)?;
+                let exp = self.parse_exp()?;
+                self.consume_token(TokenKind::RParen)?;
+                self.consume_token(TokenKind::LBrace)?;
+                let mut cases = vec![];
+                while self.peek() != &TokenKind::RBrace && self.peek() != &TokenKind::Eof {
+                    let start = self.peek_span();
+                    let value = match self.peek() {
+                        TokenKind::Case => {
+                            self.consume_token(TokenKind::Case)?;
+                            Some(self.parse_cond_exp()?)
+                        }
+                        TokenKind::Default => {
+                            self.consume_token(TokenKind::Default)?;
+                            None
+                        }
+                        kind => {
+                            return self.error(
+                                ErrorCode::UnexpectedToken,
+                                start,
+                                format!("expect `case` or `default`, but get {}", kind).as_str(),
+                            )
+                        }
+                    };
+                    let span = self.span_from(start);
+                    self.consume_token(TokenKind::Colon)?;
+                    cases.push(Case {
+                        value,
+                        span,
+                        items: self.parse_block_items(),
+                    });
                 }
+                self.consume_token(TokenKind::RBrace)?;
+                StmtKind::Switch(exp, cases)
             }
-            Token::While => {
-                // 直接进入条件跳转控制块
-                let cond_block = self.assigner.get_next_block();
-                self.assigner.go_next_block();
-                self.assigner.new_while_block();
-                self.add_block_ins(format!("br label %{}", cond_block));
-                self.block_code += format!("{}:\n", cond_block).as_str();
-                // 解析条件
-                self.consume_token(Token::While);
-                self.consume_token(Token::LParen);
-                let cond = self.parse_or_exp();
-                self.consume_token(Token::RParen);
-                // 添加条件跳转指令
-                let sub_block = self.assigner.get_sub_block();
-                let next_block = self.assigner.get_next_block();
-                self.add_block_ins(format!(
-                    "br i1 {}, label %{}, label %{}",
-                    cond, sub_block, next_block
-                ));
-                // 进入并解析子块
-                self.block_code += format!("{}:\n", sub_block).as_str();
-                self.assigner.go_sub_block();
-                self.parse_stmt();
-                self.assigner.go_parent_block();
-                self.add_block_ins(format!("br label %{}", cond_block));
-                // 进入与while同级的下一块
-                self.assigner.go_next_block();
-                self.block_code += format!("{}:\n", next_block).as_str();
+            TokenKind::Break => {
+                self.consume_token(TokenKind::Break)?;
+                self.consume_token(TokenKind::Semicolon)?;
+                StmtKind::Break
             }
-            Token::Break => {
-                self.consume_token(Token::Break);
-                // 直接进入与while同级的下一块
-                let break_block = self.assigner.get_break_block();
-                self.add_block_ins(format!("br label %{}", break_block));
-                // 解析无效代码
-                let next_block = self.assigner.get_next_block();
-                self.block_code += format!("{}:\n", next_block).as_str();
-            }
-            Token::Continue => {
-                self.consume_token(Token::Continue);
-                // 直接进入条件跳转控制块
-                let continue_block = self.assigner.get_continue_block();
-                self.add_block_ins(format!("br label %{}", continue_block));
-                // 解析无效代码
-                let next_block = self.assigner.get_next_block();
-                self.block_code += format!("{}:\n", next_block).as_str();
+            TokenKind::Continue => {
+                self.consume_token(TokenKind::Continue)?;
+                self.consume_token(TokenKind::Semicolon)?;
+                StmtKind::Continue
             }
             _ => {
-                if self.iter.clone().next().unwrap() != &Token::Semicolon {
-                    self.parse_add_exp(false);
-                }
-                self.consume_token(Token::Semicolon);
+                let exp = if self.peek() != &TokenKind::Semicolon {
+                    Some(self.parse_exp()?)
+                } else {
+                    None
+                };
+                self.consume_token(TokenKind::Semicolon)?;
+                StmtKind::Expr(exp)
             }
-        }
+        };
+        Ok(Stmt {
+            kind,
+            span: self.span_from(start),
+        })
     }
 
-    fn parse_lval(&mut self) -> String {
-        let name = match self.iter.next().unwrap() {
-            Token::Ident(ident) => ident,
-            _ => panic!("syntax error!"),
-        };
-        if self.symbol.get_var(name).is_const {
-            panic!("const val can't be assigned!")
-        }
-        let mut pos: Vec<String> = vec![];
-        while self.iter.clone().next().unwrap() == &Token::LBracket {
-            self.consume_token(Token::LBracket);
-            pos.push(self.parse_add_exp(false).unwrap().reg);
-            self.consume_token(Token::RBracket);
-        }
-        if pos.len() != self.symbol.get_var(name).shape.len() {
-            panic!("syntax error!");
+    // 赋值表达式的左边必须是左值
+    fn expect_lval(&self, exp: Expr) -> Result<LVal, Diagnostic> {
+        match exp.kind {
+            ExprKind::LVal(lval) => Ok(lval),
+            _ => self.error(
+                ErrorCode::InvalidOperand,
+                exp.span,
+                "expression is not assignable",
+            ),
         }
-        self.get_elem_pos(name.clone(), pos).reg
     }
 
-    fn parse_unary_exp(&mut self, is_const: bool) -> Option<Variable> {
-        match self.iter.next().unwrap() {
-            Token::Number(num) => {
-                let mut res = Variable::new();
-                res.reg = num.to_string();
-                Some(res)
+    fn parse_unary_exp(&mut self) -> Result<Expr, Diagnostic> {
+        let start = self.peek_span();
+        let token = match self.peek() {
+            TokenKind::Number(_)
+            | TokenKind::FloatNumber(_)
+            | TokenKind::LParen
+            | TokenKind::Plus
+            | TokenKind::Minus
+            | TokenKind::Not
+            | TokenKind::BitNot
+            | TokenKind::Increment
+            | TokenKind::Decrement
+            | TokenKind::Ident(_) => self.next_token(),
+            kind => {
+                return self.error(
+                    ErrorCode::UnexpectedToken,
+                    self.peek_span(),
+                    format!("expect expression, but get {}", kind).as_str(),
+                )
             }
-            Token::LParen => {
-                let res = self.parse_add_exp(is_const);
-                self.consume_token(Token::RParen);
-                res
-            }
-            Token::Plus => self.parse_unary_exp(is_const),
-            Token::Minus => {
-                let mut res = self.parse_unary_exp(is_const).unwrap();
-                // 分为全局和局部
-                if self.symbol.is_global() {
-                    res.reg = (-atoi(&res.reg, 10)).to_string();
+        };
+        let kind = match &token.kind {
+            TokenKind::Number(num) => ExprKind::Number(*num),
+            TokenKind::FloatNumber(num) => ExprKind::Float(*num),
+            TokenKind::LParen => {
+                let res = self.parse_exp()?;
+                self.consume_token(TokenKind::RParen)?;
+                res.kind
+            }
+            TokenKind::Plus => ExprKind::Unary(UnaryOp::Plus, Box::new(self.parse_unary_exp()?)),
+            TokenKind::Minus => ExprKind::Unary(UnaryOp::Minus, Box::new(self.parse_unary_exp()?)),
+            TokenKind::Not => ExprKind::Unary(UnaryOp::Not, Box::new(self.parse_unary_exp()?)),
+            TokenKind::BitNot => {
+                ExprKind::Unary(UnaryOp::BitNot, Box::new(self.parse_unary_exp()?))
+            }
+            TokenKind::Increment | TokenKind::Decrement => {
+                let op = if token.kind == TokenKind::Increment {
+                    IncDecOp::PreInc
                 } else {
-                    let new_reg = self.assigner.new_var();
-                    self.add_block_ins(format!("{} = sub i32 0, {}", new_reg, res.reg));
-                    res.reg = new_reg;
-                }
-                Some(res)
-            }
-            Token::Not => {
-                // 文法中令!仅在Cond中出现
-                // 比较
-                let mut operand = self.parse_unary_exp(is_const).unwrap();
-                let mut var = self.assigner.new_var();
-                self.add_block_ins(format!("{} = icmp ne i32 {}, 0", var, operand.reg));
-                operand.reg = var;
-                // 取反
-                var = self.assigner.new_var();
-                self.add_block_ins(format!("{} = xor i1 {}, true", var, operand.reg));
-                operand.reg = var;
-                // 类型转换
-                var = self.assigner.new_var();
-                self.add_block_ins(format!("{} = zext i1 {} to i32", var, operand.reg));
-                operand.reg = var;
-                // 返回
-                Some(operand)
-            }
-            Token::Ident(ident) => {
-                // 函数调用和普通表达式计算
-                if self.iter.clone().next().unwrap() == &Token::LParen {
-                    // 全局域不能调用函数
-                    if self.symbol.is_global() {
-                        panic!("call function on global scope!");
+                    IncDecOp::PreDec
+                };
+                let operand = self.parse_unary_exp()?;
+                ExprKind::IncDec(op, self.expect_lval(operand)?)
+            }
+            TokenKind::Ident(ident) => {
+                // 函数调用和普通变量
+                if self.peek() == &TokenKind::LParen {
+                    self.consume_token(TokenKind::LParen)?;
+                    let mut args = vec![];
+                    if self.peek() != &TokenKind::RParen {
+                        args.push(self.parse_assign_exp()?);
+                        while self.peek() == &TokenKind::Comma {
+                            self.consume_token(TokenKind::Comma)?;
+                            args.push(self.parse_assign_exp()?);
+                        }
                     }
-                    // 收集参数
-                    self.consume_token(Token::LParen);
-                    let params = match self.iter.clone().next().unwrap() {
-                        Token::RParen => Vec::new(),
-                        _ => self.parse_func_rparams(),
-                    };
-                    self.consume_token(Token::RParen);
-                    // 调用并返回
-                    if self.symbol.get_func(ident).has_return {
-                        let mut res = Variable::new();
-                        res.reg = self.assigner.new_var();
-                        self.add_block_ins(format!(
-                            "{} = {}",
-                            res.reg,
-                            self.symbol.get_func(ident).get_call_instruction(&params)
-                        ));
-                        Some(res)
-                    } else {
-                        self.add_block_ins(
-                            self.symbol.get_func(ident).get_call_instruction(&params),
-                        );
-                        None
+                    self.consume_token(TokenKind::RParen)?;
+                    ExprKind::Call {
+                        name: ident.clone(),
+                        name_span: token.span,
+                        args,
                     }
                 } else {
-                    if is_const && !self.symbol.get_var(ident).is_const {
-                        panic!("var occurs in const expression!");
-                    }
-                    // 分为全局和局部
-                    if self.symbol.is_global() {
-                        if !self.symbol.get_var(ident).is_const {
-                            panic!("initializer element is not a compile-time constant!");
-                        }
-                        if !self.symbol.get_var(ident).shape.is_empty() {
-                            panic!("initializer element is not a compile-time constant!");
-                        }
-                        let mut res = Variable::new();
-                        res.reg = self.symbol.get_var(ident).value.to_string();
-                        Some(res)
-                    } else {
-                        let mut pos: Vec<String> = vec![];
-                        while self.iter.clone().next().unwrap() == &Token::LBracket {
-                            self.consume_token(Token::LBracket);
-                            pos.push(self.parse_add_exp(is_const).unwrap().reg);
-                            self.consume_token(Token::RBracket);
-                        }
-                        if pos.len() > self.symbol.get_var(ident).shape.len() {
-                            panic!("syntax error!");
-                        }
-                        let mut var = self.get_elem_pos(ident.clone(), pos);
-                        if var.shape.is_empty() && !self.is_parsing_const {
-                            let new_reg = self.assigner.new_var();
-                            self.add_block_ins(format!("{} = load i32, i32* {}", new_reg, var.reg));
-                            var.reg = new_reg;
-                        }
-                        if self.is_parsing_const {
-                            var.reg = var.value.to_string();
-                        }
-                        Some(var)
+                    let mut indices = vec![];
+                    while self.peek() == &TokenKind::LBracket {
+                        self.consume_token(TokenKind::LBracket)?;
+                        indices.push(self.parse_assign_exp()?);
+                        self.consume_token(TokenKind::RBracket)?;
                     }
+                    ExprKind::LVal(LVal {
+                        name: ident.clone(),
+                        name_span: token.span,
+                        indices,
+                        span: self.span_from(start),
+                    })
                 }
             }
-            _ => panic!("syntax error!"),
-        }
+            _ => unreachable!(),
+        };
+        let mut exp = Expr {
+            kind,
+            span: self.span_from(start),
+        };
+        // 后缀的自增自减只作用于前面的基本表达式，比前缀的一元运算结合得更紧
+        let is_primary = !matches!(
+            token.kind,
+            TokenKind::Plus
+                | TokenKind::Minus
+                | TokenKind::Not
+                | TokenKind::BitNot
+                | TokenKind::Increment
+                | TokenKind::Decrement
+        );
+        while is_primary && matches!(self.peek(), TokenKind::Increment | TokenKind::Decrement) {
+            let op = if self.next_token().kind == TokenKind::Increment {
+                IncDecOp::PostInc
+            } else {
+                IncDecOp::PostDec
+            };
+            exp = Expr {
+                kind: ExprKind::IncDec(op, self.expect_lval(exp)?),
+                span: self.span_from(start),
+            };
+        }
+        Ok(exp)
     }
 
-    fn parse_func_rparams(&mut self) -> Vec<Variable> {
-        let mut res = vec![];
-        let mut var = self.parse_add_exp(false).unwrap();
-        if !var.shape.is_empty() && var.shape[0] != 0 {
-            let new_reg = self.assigner.new_var();
-            let shape_str = Variable::get_shape_from_vec(&var.shape);
-            self.add_block_ins(format!(
-                "{} = getelementptr {}, {}* {}, i32 0, i32 0",
-                new_reg, shape_str, shape_str, var.reg
-            ));
-            var.reg = new_reg;
-        }
-        res.push(var.clone());
-        while self.iter.clone().next().unwrap() == &Token::Comma {
-            self.consume_token(Token::Comma);
-            var = self.parse_add_exp(false).unwrap();
-            if !var.shape.is_empty() && var.shape[0] != 0 {
-                let new_reg = self.assigner.new_var();
-                let shape_str = Variable::get_shape_from_vec(&var.shape);
-                self.add_block_ins(format!(
-                    "{} = getelementptr {}, {}* {}, i32 0, i32 0",
-                    new_reg, shape_str, shape_str, var.reg
-                ));
-                var.reg = new_reg;
-            }
-            res.push(var.clone());
-        }
-        res
+    // 左结合的二元表达式，operand解析下一优先级的操作数
+    fn parse_binary_exp(
+        &mut self,
+        ops: &[(TokenKind, BinaryOp)],
+        operand: fn(&mut Self) -> Result<Expr, Diagnostic>,
+    ) -> Result<Expr, Diagnostic> {
+        let start = self.peek_span();
+        let mut lhs = operand(self)?;
+        while let Some((token, op)) = ops.iter().find(|(token, _)| token == self.peek()) {
+            self.consume_token(token.clone())?;
+            let rhs = operand(self)?;
+            lhs = Expr {
+                kind: ExprKind::Binary(*op, Box::new(lhs), Box::new(rhs)),
+                span: self.span_from(start),
+            };
+        }
+        Ok(lhs)
     }
 
-    fn parse_mul_exp(&mut self, is_const: bool) -> Option<Variable> {
-        let mut operand = self.parse_unary_exp(is_const);
-        loop {
-            match self.iter.clone().next() {
-                Some(Token::Multiply) => {
-                    self.consume_token(Token::Multiply);
-                    let tmp = self.parse_unary_exp(is_const).unwrap();
-                    if self.symbol.is_global() || self.is_parsing_const {
-                        let mut res = operand.unwrap();
-                        res.reg = (atoi(&res.reg, 10) * atoi(&tmp.reg, 10)).to_string();
-                        operand = Some(res);
-                    } else {
-                        let reg = self.assigner.new_var();
-                        self.add_block_ins(format!(
-                            "{} = mul i32 {}, {}",
-                            reg,
-                            operand.clone().unwrap().reg,
-                            tmp.reg
-                        ));
-                        let mut res = operand.unwrap();
-                        res.reg = reg;
-                        operand = Some(res);
-                    }
-                }
-                Some(Token::Divide) => {
-                    self.consume_token(Token::Divide);
-                    let tmp = self.parse_unary_exp(is_const).unwrap();
-                    if self.symbol.is_global() || self.is_parsing_const {
-                        let mut res = operand.unwrap();
-                        res.reg = (atoi(&res.reg, 10) / atoi(&tmp.reg, 10)).to_string();
-                        operand = Some(res);
-                    } else {
-                        let reg = self.assigner.new_var();
-                        self.add_block_ins(format!(
-                            "{} = sdiv i32 {}, {}",
-                            reg,
-                            operand.clone().unwrap().reg,
-                            tmp.reg
-                        ));
-                        let mut res = operand.unwrap();
-                        res.reg = reg;
-                        operand = Some(res);
-                    }
-                }
-                Some(Token::Mod) => {
-                    self.consume_token(Token::Mod);
-                    let tmp = self.parse_unary_exp(is_const).unwrap();
-                    if self.symbol.is_global() || self.is_parsing_const {
-                        let mut res = operand.unwrap();
-                        res.reg = (atoi(&res.reg, 10) % atoi(&tmp.reg, 10)).to_string();
-                        operand = Some(res);
-                    } else {
-                        let reg = self.assigner.new_var();
-                        self.add_block_ins(format!(
-                            "{} = srem i32 {}, {}",
-                            reg,
-                            operand.clone().unwrap().reg,
-                            tmp.reg
-                        ));
-                        let mut res = operand.unwrap();
-                        res.reg = reg;
-                        operand = Some(res);
-                    }
-                }
-                _ => break,
-            }
-        }
-        operand
+    fn parse_mul_exp(&mut self) -> Result<Expr, Diagnostic> {
+        self.parse_binary_exp(
+            &[
+                (TokenKind::Multiply, BinaryOp::Mul),
+                (TokenKind::Divide, BinaryOp::Div),
+                (TokenKind::Mod, BinaryOp::Mod),
+            ],
+            Self::parse_unary_exp,
+        )
     }
 
-    fn parse_add_exp(&mut self, is_const: bool) -> Option<Variable> {
-        let mut operand = self.parse_mul_exp(is_const);
-        loop {
-            match self.iter.clone().next() {
-                Some(Token::Plus) => {
-                    self.consume_token(Token::Plus);
-                    let tmp = self.parse_mul_exp(is_const).unwrap();
-                    if self.symbol.is_global() || self.is_parsing_const {
-                        let mut res = operand.unwrap();
-                        res.reg = (atoi(&res.reg, 10) + atoi(&tmp.reg, 10)).to_string();
-                        operand = Some(res);
-                    } else {
-                        let reg = self.assigner.new_var();
-                        self.add_block_ins(format!(
-                            "{} = add i32 {}, {}",
-                            reg,
-                            operand.clone().unwrap().reg,
-                            tmp.reg
-                        ));
-                        let mut res = operand.unwrap();
-                        res.reg = reg;
-                        operand = Some(res);
-                    }
-                }
-                Some(Token::Minus) => {
-                    self.consume_token(Token::Minus);
-                    let tmp = self.parse_mul_exp(is_const).unwrap();
-                    if self.symbol.is_global() || self.is_parsing_const {
-                        let mut res = operand.unwrap();
-                        res.reg = (atoi(&res.reg, 10) - atoi(&tmp.reg, 10)).to_string();
-                        operand = Some(res);
-                    } else {
-                        let reg = self.assigner.new_var();
-                        self.add_block_ins(format!(
-                            "{} = sub i32 {}, {}",
-                            reg,
-                            operand.clone().unwrap().reg,
-                            tmp.reg
-                        ));
-                        let mut res = operand.unwrap();
-                        res.reg = reg;
-                        operand = Some(res);
-                    }
-                }
-                _ => break,
-            }
-        }
-        operand
+    fn parse_add_exp(&mut self) -> Result<Expr, Diagnostic> {
+        self.parse_binary_exp(
+            &[
+                (TokenKind::Plus, BinaryOp::Add),
+                (TokenKind::Minus, BinaryOp::Sub),
+            ],
+            Self::parse_mul_exp,
+        )
     }
 
-    fn parse_rel_exp(&mut self) -> String {
-        let mut operand = self.parse_add_exp(false).unwrap().reg;
-        loop {
-            match self.iter.clone().next().unwrap() {
-                Token::Less => {
-                    self.consume_token(Token::Less);
-                    // 计算
-                    let mut var = self.assigner.new_var();
-                    let tmp = self.parse_rel_exp();
-                    self.add_block_ins(format!("{} = icmp slt i32 {}, {}", var, operand, tmp));
-                    operand = var;
-                    // 类型转换
-                    var = self.assigner.new_var();
-                    self.add_block_ins(format!("{} = zext i1 {} to i32", var, operand));
-                    operand = var;
-                }
-                Token::Greater => {
-                    self.consume_token(Token::Greater);
-                    // 计算
-                    let mut var = self.assigner.new_var();
-                    let tmp = self.parse_rel_exp();
-                    self.add_block_ins(format!("{} = icmp sgt i32 {}, {}", var, operand, tmp));
-                    operand = var;
-                    // 类型转换
-                    var = self.assigner.new_var();
-                    self.add_block_ins(format!("{} = zext i1 {} to i32", var, operand));
-                    operand = var;
-                }
-                Token::LessOrEqual => {
-                    self.consume_token(Token::LessOrEqual);
-                    // 计算
-                    let mut var = self.assigner.new_var();
-                    let tmp = self.parse_rel_exp();
-                    self.add_block_ins(format!("{} = icmp sle i32 {}, {}", var, operand, tmp));
-                    operand = var;
-                    // 类型转换
-                    var = self.assigner.new_var();
-                    self.add_block_ins(format!("{} = zext i1 {} to i32", var, operand));
-                    operand = var;
-                }
-                Token::GreaterOrEqual => {
-                    self.consume_token(Token::GreaterOrEqual);
-                    // 计算
-                    let mut var = self.assigner.new_var();
-                    let tmp = self.parse_rel_exp();
-                    self.add_block_ins(format!("{} = icmp sge i32 {}, {}", var, operand, tmp));
-                    operand = var;
-                    // 类型转换
-                    var = self.assigner.new_var();
-                    self.add_block_ins(format!("{} = zext i1 {} to i32", var, operand));
-                    operand = var;
-                }
-                _ => break,
-            }
-        }
-        operand
+    fn parse_shift_exp(&mut self) -> Result<Expr, Diagnostic> {
+        self.parse_binary_exp(
+            &[
+                (TokenKind::Shl, BinaryOp::Shl),
+                (TokenKind::Shr, BinaryOp::Shr),
+            ],
+            Self::parse_add_exp,
+        )
     }
 
-    fn parse_eq_exp(&mut self) -> String {
-        let mut operand = self.parse_rel_exp();
-        loop {
-            match self.iter.clone().next().unwrap() {
-                Token::Equal => {
-                    self.consume_token(Token::Equal);
-                    // 计算
-                    let mut var = self.assigner.new_var();
-                    let tmp = self.parse_rel_exp();
-                    self.add_block_ins(format!("{} = icmp eq i32 {}, {}", var, operand, tmp));
-                    operand = var;
-                    // 类型转换
-                    var = self.assigner.new_var();
-                    self.add_block_ins(format!("{} = zext i1 {} to i32", var, operand));
-                    operand = var;
-                }
-                Token::NotEqual => {
-                    self.consume_token(Token::NotEqual);
-                    // 计算
-                    let mut var = self.assigner.new_var();
-                    let tmp = self.parse_rel_exp();
-                    self.add_block_ins(format!("{} = icmp ne i32 {}, {}", var, operand, tmp));
-                    operand = var;
-                    // 类型转换
-                    var = self.assigner.new_var();
-                    self.add_block_ins(format!("{} = zext i1 {} to i32", var, operand));
-                    operand = var;
-                }
-                _ => break,
-            }
-        }
-        let var = self.assigner.new_var();
-        self.add_block_ins(format!("{} = icmp ne i32 {}, 0", var, operand));
-        operand = var;
-        operand
+    fn parse_rel_exp(&mut self) -> Result<Expr, Diagnostic> {
+        self.parse_binary_exp(
+            &[
+                (TokenKind::Less, BinaryOp::Lt),
+                (TokenKind::Greater, BinaryOp::Gt),
+                (TokenKind::LessOrEqual, BinaryOp::Le),
+                (TokenKind::GreaterOrEqual, BinaryOp::Ge),
+            ],
+            Self::parse_shift_exp,
+        )
     }
 
-    fn parse_and_exp(&mut self) -> String {
-        // 当前的逻辑表达式计算结果
-        let mut operand = self.parse_eq_exp();
-        // 短路求值用到的固定block
-        let true_block = self.assigner.get_next_block();
-        self.assigner.go_next_block();
-        let false_block = self.assigner.get_next_block();
-        self.assigner.go_next_block();
-        let exit_block = self.assigner.get_next_block();
-        self.assigner.go_next_block();
-        // 计算下一个逻辑表达式用到的block
-        let mut next_block = self.assigner.get_next_block();
-        self.assigner.go_next_block();
-        // 循环计算逻辑表达式最终结果
-        self.add_block_ins(format!(
-            "br i1 {}, label %{}, label %{}",
-            operand, next_block, false_block
-        ));
-        self.block_code += format!("{}:\n", next_block).as_str();
-        while self.iter.clone().next().unwrap() == &Token::And {
-            self.consume_token(Token::And);
-            let var = self.assigner.new_var();
-            let tmp = self.parse_eq_exp();
-            self.add_block_ins(format!("{} = and i1 {},{}", var, operand, tmp));
-            operand = var;
-            // 短路求值
-            next_block = self.assigner.get_next_block();
-            self.assigner.go_next_block();
-            self.add_block_ins(format!(
-                "br i1 {}, label %{}, label %{}",
-                operand, next_block, false_block
-            ));
-            self.block_code += format!("{}:\n", next_block).as_str();
-        }
-        self.add_block_ins(format!("br label %{}", true_block));
-        // 返回true的情况
-        self.block_code += format!("{}:\n", true_block).as_str();
-        self.add_block_ins(format!("store i1 1, i1* %1"));
-        self.add_block_ins(format!("br label %{}", exit_block));
-        // 返回false的情况
-        self.block_code += format!("{}:\n", false_block).as_str();
-        self.add_block_ins(format!("store i1 0, i1* %1"));
-        self.add_block_ins(format!("br label %{}", exit_block));
-        // 唯一的出口block
-        self.block_code += format!("{}:\n", exit_block).as_str();
-        let var = self.assigner.new_var();
-        self.add_block_ins(format!("{} = load i1, i1* %1", var));
-        var
+    fn parse_eq_exp(&mut self) -> Result<Expr, Diagnostic> {
+        self.parse_binary_exp(
+            &[
+                (TokenKind::Equal, BinaryOp::Eq),
+                (TokenKind::NotEqual, BinaryOp::Ne),
+            ],
+            Self::parse_rel_exp,
+        )
     }
 
-    fn parse_or_exp(&mut self) -> String {
-        // 当前的逻辑表达式计算结果
-        let mut operand = self.parse_and_exp();
-        // 短路求值用到的固定block
-        let true_block = self.assigner.get_next_block();
-        self.assigner.go_next_block();
-        let false_block = self.assigner.get_next_block();
-        self.assigner.go_next_block();
-        let exit_block = self.assigner.get_next_block();
-        self.assigner.go_next_block();
-        // 计算下一个逻辑表达式用到的block
-        let mut next_block = self.assigner.get_next_block();
-        self.assigner.go_next_block();
-        // 循环计算逻辑表达式最终结果
-        self.add_block_ins(format!(
-            "br i1 {}, label %{}, label %{}",
-            operand, true_block, next_block
-        ));
-        self.block_code += format!("{}:\n", next_block).as_str();
-        while self.iter.clone().next().unwrap() == &Token::Or {
-            self.consume_token(Token::Or);
-            let var = self.assigner.new_var();
-            let tmp = self.parse_and_exp();
-            self.add_block_ins(format!("{} = or i1 {},{}", var, operand, tmp));
-            operand = var;
-            // 短路求值
-            next_block = self.assigner.get_next_block();
-            self.assigner.go_next_block();
-            self.add_block_ins(format!(
-                "br i1 {}, label %{}, label %{}",
-                operand, true_block, next_block
-            ));
-            self.block_code += format!("{}:\n", next_block).as_str();
-        }
-        self.add_block_ins(format!("br label %{}", false_block));
-        // 返回true的情况
-        self.block_code += format!("{}:\n", true_block).as_str();
-        self.add_block_ins(format!("store i1 1, i1* %1"));
-        self.add_block_ins(format!("br label %{}", exit_block));
-        // 返回false的情况
-        self.block_code += format!("{}:\n", false_block).as_str();
-        self.add_block_ins(format!("store i1 0, i1* %1"));
-        self.add_block_ins(format!("br label %{}", exit_block));
-        // 唯一的出口block
-        self.block_code += format!("{}:\n", exit_block).as_str();
-        let var = self.assigner.new_var();
-        self.add_block_ins(format!("{} = load i1, i1* %1", var));
-        var
+    fn parse_bit_and_exp(&mut self) -> Result<Expr, Diagnostic> {
+        self.parse_binary_exp(&[(TokenKind::BitAnd, BinaryOp::BitAnd)], Self::parse_eq_exp)
+    }
+
+    fn parse_bit_xor_exp(&mut self) -> Result<Expr, Diagnostic> {
+        self.parse_binary_exp(
+            &[(TokenKind::BitXor, BinaryOp::BitXor)],
+            Self::parse_bit_and_exp,
+        )
+    }
+
+    fn parse_bit_or_exp(&mut self) -> Result<Expr, Diagnostic> {
+        self.parse_binary_exp(
+            &[(TokenKind::BitOr, BinaryOp::BitOr)],
+            Self::parse_bit_xor_exp,
+        )
+    }
+
+    fn parse_and_exp(&mut self) -> Result<Expr, Diagnostic> {
+        self.parse_binary_exp(&[(TokenKind::And, BinaryOp::And)], Self::parse_bit_or_exp)
+    }
+
+    fn parse_or_exp(&mut self) -> Result<Expr, Diagnostic> {
+        self.parse_binary_exp(&[(TokenKind::Or, BinaryOp::Or)], Self::parse_and_exp)
+    }
+
+    // 条件表达式是右结合的，中间的操作数可以是任意表达式
+    fn parse_cond_exp(&mut self) -> Result<Expr, Diagnostic> {
+        let start = self.peek_span();
+        let cond = self.parse_or_exp()?;
+        if self.peek() != &TokenKind::Question {
+            return Ok(cond);
+        }
+        self.consume_token(TokenKind::Question)?;
+        let then_exp = self.parse_exp()?;
+        self.consume_token(TokenKind::Colon)?;
+        let else_exp = self.parse_cond_exp()?;
+        Ok(Expr {
+            kind: ExprKind::Conditional(Box::new(cond), Box::new(then_exp), Box::new(else_exp)),
+            span: self.span_from(start),
+        })
+    }
+
+    // 赋值是右结合的，复合赋值记下对应的二元运算
+    fn parse_assign_exp(&mut self) -> Result<Expr, Diagnostic> {
+        let start = self.peek_span();
+        let lhs = self.parse_cond_exp()?;
+        let op = match self.peek() {
+            TokenKind::Assign => None,
+            TokenKind::PlusAssign => Some(BinaryOp::Add),
+            TokenKind::MinusAssign => Some(BinaryOp::Sub),
+            TokenKind::MultiplyAssign => Some(BinaryOp::Mul),
+            TokenKind::DivideAssign => Some(BinaryOp::Div),
+            TokenKind::ModAssign => Some(BinaryOp::Mod),
+            TokenKind::BitAndAssign => Some(BinaryOp::BitAnd),
+            TokenKind::BitOrAssign => Some(BinaryOp::BitOr),
+            TokenKind::BitXorAssign => Some(BinaryOp::BitXor),
+            TokenKind::ShlAssign => Some(BinaryOp::Shl),
+            TokenKind::ShrAssign => Some(BinaryOp::Shr),
+            _ => return Ok(lhs),
+        };
+        self.next_token();
+        let lval = self.expect_lval(lhs)?;
+        let rhs = self.parse_assign_exp()?;
+        Ok(Expr {
+            kind: ExprKind::Assign(op, lval, Box::new(rhs)),
+            span: self.span_from(start),
+        })
+    }
+
+    // 逗号表达式只出现在语句、条件、括号、for与?:的中间，函数实参与初始值中的逗号是分隔符
+    fn parse_exp(&mut self) -> Result<Expr, Diagnostic> {
+        let start = self.peek_span();
+        let mut lhs = self.parse_assign_exp()?;
+        while self.peek() == &TokenKind::Comma {
+            self.consume_token(TokenKind::Comma)?;
+            let rhs = self.parse_assign_exp()?;
+            lhs = Expr {
+                kind: ExprKind::Comma(Box::new(lhs), Box::new(rhs)),
+                span: self.span_from(start),
+            };
+        }
+        Ok(lhs)
     }
 }
 
-fn atoi(str: &String, radix: u32) -> i32 {
-    i32::from_str_radix(str.as_str(), radix).unwrap()
+#[cfg(test)]
+mod tests {
+    use crate::testing::{codes, compile_errors};
+    use crate::Options;
+
+    // 非法字符被跳过，词法错误与语义错误按位置合并
+    #[test]
+    fn recovers_from_lexical_errors() {
+        let source = "int main() {
+            int a = 1; @
+            int b = 09;
+            # return a + c;
+        }";
+        let errors = compile_errors(source, &Options::default());
+        assert_eq!(codes(&errors), ["E0001", "E0002", "E0001", "E0201"]);
+    }
+
+    // 出错的语句被跳过，之后的语句继续分析
+    #[test]
+    fn recovers_from_bad_statements() {
+        let source = "int main() {
+            int a = 1
+            a = a + ;
+            if (a) { a = ; } else a = 2;
+            while (a) a = a *;
+            return b;
+        }";
+        let errors = compile_errors(source, &Options::default());
+        assert_eq!(codes(&errors), ["E0101", "E0101", "E0101", "E0201"]);
+        let lines: Vec<usize> = errors.iter().map(|err| err.span.line).collect();
+        assert_eq!(lines, [3, 4, 5, 6]);
+    }
+
+    // 出错的声明被跳过，之后的声明继续分析
+    #[test]
+    fn recovers_from_bad_declarations() {
+        let source = "int f( { return 1; }
+            int g = ;
+            const x = 1;
+            int main() { return h; }";
+        let errors = compile_errors(source, &Options::default());
+        assert_eq!(codes(&errors), ["E0101", "E0101", "E0101", "E0201"]);
+        let lines: Vec<usize> = errors.iter().map(|err| err.span.line).collect();
+        assert_eq!(lines, [1, 2, 3, 4]);
+    }
+
+    // 词法错误与语法错误共同受error_limit限制
+    #[test]
+    fn limits_merged_errors() {
+        let source = "int main() { int a = 1 $ a = ; return a @ 1; }";
+        let options = Options {
+            error_limit: 2,
+            ..Options::default()
+        };
+        let errors = compile_errors(source, &options);
+        assert_eq!(codes(&errors), ["E0001", "E0001"]);
+        let errors = compile_errors(source, &Options::default());
+        assert_eq!(codes(&errors), ["E0001", "E0101", "E0001", "E0101"]);
+    }
 }
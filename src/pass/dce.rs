@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+
+use super::super::ir::{BlockId, Function, InstId, InstKind, Module, Value};
+
+// 没有副作用的函数，以FuncId为下标
+// 函数内只写自己的局部数组且只调用无副作用的函数时才视为无副作用，外部声明的函数都有副作用
+// 含有循环或在调用图的环上的函数可能不终止，删除对它的调用会改变程序的行为，也视为有副作用
+pub fn pure_functions(module: &Module) -> Vec<bool> {
+    let recursive = recursive_functions(module);
+    let mut pure: Vec<bool> = module
+        .functions
+        .iter()
+        .zip(recursive)
+        .map(|(func, recursive)| !func.is_declaration() && !recursive && !has_cycle(func))
+        .collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (index, func) in module.functions.iter().enumerate() {
+            if !pure[index] {
+                continue;
+            }
+            let has_effect = func.layout.iter().any(|block| {
+                func.block(*block)
+                    .insts
+                    .iter()
+                    .any(|inst| match &func.inst(*inst).kind {
+                        InstKind::Store(_, ptr) => local_alloca(func, *ptr).is_none(),
+                        InstKind::Call(callee, _) => !pure[callee.0],
+                        _ => false,
+                    })
+            });
+            if has_effect {
+                pure[index] = false;
+                changed = true;
+            }
+        }
+    }
+    pure
+}
+
+// 控制流图中从入口可达的部分是否有环
+fn has_cycle(func: &Function) -> bool {
+    // 0为未访问，1为在深度优先搜索的栈上，2为已经完成
+    let mut state = vec![0u8; func.blocks.len()];
+    let entry = func.entry();
+    state[entry.0] = 1;
+    let mut stack = vec![(entry, func.successors(entry), 0)];
+    while let Some((block, succs, next)) = stack.last_mut() {
+        match succs.get(*next).copied() {
+            Some(succ) => {
+                *next += 1;
+                match state[succ.0] {
+                    0 => {
+                        state[succ.0] = 1;
+                        stack.push((succ, func.successors(succ), 0));
+                    }
+                    1 => return true,
+                    _ => {}
+                }
+            }
+            None => {
+                state[block.0] = 2;
+                stack.pop();
+            }
+        }
+    }
+    false
+}
+
+// 在调用图的环上的函数，以FuncId为下标
+fn recursive_functions(module: &Module) -> Vec<bool> {
+    let callees: Vec<Vec<usize>> = module
+        .functions
+        .iter()
+        .map(|func| {
+            let mut callees = vec![];
+            for block in &func.layout {
+                for inst in &func.block(*block).insts {
+                    if let InstKind::Call(callee, _) = func.inst(*inst).kind {
+                        callees.push(callee.0);
+                    }
+                }
+            }
+            callees
+        })
+        .collect();
+    (0..callees.len())
+        .map(|func| {
+            // 从func的被调用者出发能否回到func
+            let mut visited = vec![false; callees.len()];
+            let mut stack = callees[func].clone();
+            while let Some(callee) = stack.pop() {
+                if callee == func {
+                    return true;
+                }
+                if !visited[callee] {
+                    visited[callee] = true;
+                    stack.extend(callees[callee].iter().copied());
+                }
+            }
+            false
+        })
+        .collect()
+}
+
+// 指针指向函数自己的alloca时，返回该alloca
+fn local_alloca(func: &Function, mut ptr: Value) -> Option<InstId> {
+    while let Value::Inst(inst) = ptr {
+        match &func.inst(inst).kind {
+            InstKind::Alloca(_) => return Some(inst),
+            InstKind::Gep(base, _) => ptr = *base,
+            _ => return None,
+        }
+    }
+    None
+}
+
+pub fn run(func: &mut Function, pure: &[bool]) {
+    remove_unreachable_blocks(func);
+    remove_overwritten_stores(func);
+    remove_dead_insts(func, pure);
+}
+
+fn remove_unreachable_blocks(func: &mut Function) {
+    let mut reachable = vec![false; func.blocks.len()];
+    let mut stack = vec![func.entry()];
+    reachable[func.entry().0] = true;
+    while let Some(block) = stack.pop() {
+        for succ in func.successors(block) {
+            if !reachable[succ.0] {
+                reachable[succ.0] = true;
+                stack.push(succ);
+            }
+        }
+    }
+    let dead: Vec<BlockId> = func
+        .layout
+        .iter()
+        .copied()
+        .filter(|block| !reachable[block.0])
+        .collect();
+    func.remove_blocks(&dead);
+}
+
+// 基本块内对同一标量alloca的两次store之间没有load时，前一次store是多余的
+fn remove_overwritten_stores(func: &mut Function) {
+    let mut scalar = vec![false; func.insts.len()];
+    for inst in &func.block(func.entry()).insts {
+        let inst_ref = func.inst(*inst);
+        if !matches!(inst_ref.kind, InstKind::Alloca(_)) {
+            continue;
+        }
+        let ptr = Value::Inst(*inst);
+        scalar[inst.0] = inst_ref
+            .users
+            .iter()
+            .all(|user| match func.inst(*user).kind {
+                InstKind::Load(_) => true,
+                InstKind::Store(val, _) => val != ptr,
+                _ => false,
+            });
+    }
+    for block in func.layout.clone() {
+        // alloca上尚未被读取的store
+        let mut pending: HashMap<InstId, InstId> = HashMap::new();
+        for inst in func.block(block).insts.clone() {
+            match func.inst(inst).kind {
+                InstKind::Load(Value::Inst(ptr)) if scalar[ptr.0] => {
+                    pending.remove(&ptr);
+                }
+                InstKind::Store(_, Value::Inst(ptr)) if scalar[ptr.0] => {
+                    if let Some(prev) = pending.insert(ptr, inst) {
+                        func.remove_inst(prev);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// 从有副作用的指令出发标记活跃指令，其余指令全部删除
+fn remove_dead_insts(func: &mut Function, pure: &[bool]) {
+    let read = read_allocas(func);
+    let mut live = vec![false; func.insts.len()];
+    let mut worklist = vec![];
+    for block in &func.layout {
+        for inst in &func.block(*block).insts {
+            let has_effect = match &func.inst(*inst).kind {
+                // 写入从未被读取的局部变量没有作用
+                InstKind::Store(_, ptr) => {
+                    local_alloca(func, *ptr).is_none_or(|alloca| read[alloca.0])
+                }
+                InstKind::Call(callee, _) => !pure[callee.0],
+                kind => kind.is_terminator(),
+            };
+            if has_effect {
+                live[inst.0] = true;
+                worklist.push(*inst);
+            }
+        }
+    }
+    while let Some(inst) = worklist.pop() {
+        for operand in func.inst(inst).kind.operands() {
+            if let Value::Inst(operand) = operand {
+                if !live[operand.0] {
+                    live[operand.0] = true;
+                    worklist.push(operand);
+                }
+            }
+        }
+    }
+    let dead: Vec<InstId> = func
+        .layout
+        .iter()
+        .flat_map(|block| func.block(*block).insts.iter().copied())
+        .filter(|inst| !live[inst.0])
+        .collect();
+    // 先清空操作数，再统一删除，避免互相引用的指令
+    for inst in &dead {
+        func.set_kind(*inst, InstKind::Ret(None));
+    }
+    for inst in dead {
+        func.remove_inst(inst);
+    }
+}
+
+// 被load读取或作为参数传出的alloca，以InstId为下标
+fn read_allocas(func: &Function) -> Vec<bool> {
+    let mut read = vec![false; func.insts.len()];
+    for block in &func.layout {
+        for inst in &func.block(*block).insts {
+            let ptrs = match &func.inst(*inst).kind {
+                InstKind::Load(ptr) => vec![*ptr],
+                InstKind::Call(_, args) => args.clone(),
+                _ => continue,
+            };
+            for ptr in ptrs {
+                if let Some(alloca) = local_alloca(func, ptr) {
+                    read[alloca.0] = true;
+                }
+            }
+        }
+    }
+    read
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::{self, Target};
+    use crate::pass;
+    use crate::testing::analyze_source;
+
+    // 默认优化后的LLVM IR
+    fn optimize(source: &str) -> String {
+        let (_, mut module) = analyze_source(source);
+        pass::run(&mut module, &pass::Options::default());
+        backend::emit(&module, Target::Llvm)
+    }
+
+    #[test]
+    fn removes_unused_pure_call() {
+        let ir = optimize(
+            "int square(int x) { int a[2]; a[0] = x * x; return a[0]; }
+            int main() { square(3); return 0; }",
+        );
+        assert!(!ir.contains("call i32 @square"), "{}", ir);
+    }
+
+    #[test]
+    fn keeps_unused_impure_call() {
+        let ir = optimize(
+            "int g; int set() { g = 1; return 0; }
+            int show() { putint(1); return 0; }
+            int main() { set(); show(); return 0; }",
+        );
+        assert!(ir.contains("call i32 @set"), "{}", ir);
+        assert!(ir.contains("call i32 @show"), "{}", ir);
+    }
+
+    // 可能不终止的函数即使不写内存也不能删除
+    #[test]
+    fn keeps_call_that_may_not_return() {
+        let ir = optimize(
+            "int spin() { while (1) {} return 0; }
+            int main() { spin(); putint(1); return 0; }",
+        );
+        assert!(ir.contains("call i32 @spin"), "{}", ir);
+        let ir = optimize(
+            "int f(int n) { if (n) return f(n); return 0; }
+            int main() { f(1); return 0; }",
+        );
+        assert!(ir.contains("call i32 @f"), "{}", ir);
+    }
+}
@@ -0,0 +1,136 @@
+use super::super::ir::{BlockId, Function};
+
+// 支配树，使用Cooper-Harvey-Kennedy迭代算法计算，只包含从入口可达的基本块
+pub struct DomTree {
+    pub rpo: Vec<BlockId>,           // 可达基本块的逆后序
+    pub children: Vec<Vec<BlockId>>, // 以BlockId为下标
+    order: Vec<usize>,               // 基本块在逆后序中的位置，不可达为usize::MAX
+    idom: Vec<Option<BlockId>>,      // 入口与不可达块为None
+}
+
+impl DomTree {
+    pub fn new(func: &Function) -> DomTree {
+        let count = func.blocks.len();
+        // 非递归的深度优先遍历求后序
+        let mut post = vec![];
+        let mut visited = vec![false; count];
+        let mut stack = vec![(func.entry(), 0)];
+        visited[func.entry().0] = true;
+        while let Some((block, index)) = stack.pop() {
+            let succs = func.successors(block);
+            if index < succs.len() {
+                stack.push((block, index + 1));
+                let succ = succs[index];
+                if !visited[succ.0] {
+                    visited[succ.0] = true;
+                    stack.push((succ, 0));
+                }
+            } else {
+                post.push(block);
+            }
+        }
+        let rpo: Vec<BlockId> = post.into_iter().rev().collect();
+        let mut order = vec![usize::MAX; count];
+        for (index, block) in rpo.iter().enumerate() {
+            order[block.0] = index;
+        }
+        let preds = func.predecessors();
+        let mut idom: Vec<Option<BlockId>> = vec![None; count];
+        idom[rpo[0].0] = Some(rpo[0]);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in &rpo[1..] {
+                let mut new_idom: Option<BlockId> = None;
+                for pred in &preds[block.0] {
+                    if idom[pred.0].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => *pred,
+                        Some(cur) => Self::intersect(&idom, &order, *pred, cur),
+                    });
+                }
+                if new_idom != idom[block.0] {
+                    idom[block.0] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        idom[rpo[0].0] = None;
+        let mut children = vec![vec![]; count];
+        for block in &rpo[1..] {
+            children[idom[block.0].unwrap().0].push(*block);
+        }
+        DomTree {
+            rpo,
+            children,
+            order,
+            idom,
+        }
+    }
+
+    fn intersect(
+        idom: &[Option<BlockId>],
+        order: &[usize],
+        mut lhs: BlockId,
+        mut rhs: BlockId,
+    ) -> BlockId {
+        while lhs != rhs {
+            while order[lhs.0] > order[rhs.0] {
+                lhs = idom[lhs.0].unwrap();
+            }
+            while order[rhs.0] > order[lhs.0] {
+                rhs = idom[rhs.0].unwrap();
+            }
+        }
+        lhs
+    }
+
+    pub fn is_reachable(&self, block: BlockId) -> bool {
+        self.order[block.0] != usize::MAX
+    }
+
+    pub fn idom(&self, block: BlockId) -> Option<BlockId> {
+        self.idom[block.0]
+    }
+
+    pub fn dominates(&self, lhs: BlockId, mut rhs: BlockId) -> bool {
+        loop {
+            if lhs == rhs {
+                return true;
+            }
+            rhs = match self.idom(rhs) {
+                Some(idom) => idom,
+                None => return false,
+            };
+        }
+    }
+
+    // 支配边界，以BlockId为下标
+    pub fn frontiers(&self, func: &Function) -> Vec<Vec<BlockId>> {
+        let preds = func.predecessors();
+        let mut res: Vec<Vec<BlockId>> = vec![vec![]; func.blocks.len()];
+        for block in &self.rpo {
+            if preds[block.0].len() < 2 {
+                continue;
+            }
+            for pred in &preds[block.0] {
+                if !self.is_reachable(*pred) {
+                    continue;
+                }
+                let mut runner = *pred;
+                while Some(runner) != self.idom(*block) {
+                    if !res[runner.0].contains(block) {
+                        res[runner.0].push(*block);
+                    }
+                    runner = match self.idom(runner) {
+                        Some(idom) => idom,
+                        None => break,
+                    };
+                }
+            }
+        }
+        res
+    }
+}
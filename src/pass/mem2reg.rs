@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use super::super::ir::{BlockId, Function, InstId, InstKind, Type, Value};
+use super::dominator::DomTree;
+
+// 将只被load/store使用的标量alloca提升为SSA寄存器
+pub fn run(func: &mut Function) {
+    let allocas = promotable_allocas(func);
+    if allocas.is_empty() {
+        return;
+    }
+    let dom = DomTree::new(func);
+    let index: HashMap<InstId, usize> = allocas
+        .iter()
+        .enumerate()
+        .map(|(index, alloca)| (*alloca, index))
+        .collect();
+    let phis = insert_phis(func, &dom, &allocas);
+    rename(func, &dom, &allocas, &index, &phis);
+    // 不可达的前驱也需要在phi中出现
+    let preds = func.predecessors();
+    for (phi, alloca) in &phis {
+        let block = func.inst(*phi).block;
+        let zero = zero_value(func.inst(allocas[*alloca]).ty.pointee());
+        if let InstKind::Phi(incoming) = &func.inst(*phi).kind {
+            let mut incoming = incoming.clone();
+            for pred in &preds[block.0] {
+                if !dom.is_reachable(*pred) {
+                    incoming.push((zero, *pred));
+                }
+            }
+            func.set_kind(*phi, InstKind::Phi(incoming));
+        }
+    }
+    remove_dead_phis(func, &phis);
+    for alloca in allocas {
+        func.remove_inst(alloca);
+    }
+}
+
+// 未初始化的变量取0
+fn zero_value(ty: &Type) -> Value {
+    match ty {
+        Type::I1 => Value::Bool(false),
+        Type::Float => Value::float(0.0),
+        _ => Value::Int(0),
+    }
+}
+
+fn promotable_allocas(func: &Function) -> Vec<InstId> {
+    let mut res = vec![];
+    for inst in &func.block(func.entry()).insts {
+        let inst_ref = func.inst(*inst);
+        if !matches!(
+            inst_ref.kind,
+            InstKind::Alloca(Type::I32 | Type::I1 | Type::Float)
+        ) {
+            continue;
+        }
+        let ptr = Value::Inst(*inst);
+        let promotable = inst_ref
+            .users
+            .iter()
+            .all(|user| match func.inst(*user).kind {
+                InstKind::Load(_) => true,
+                InstKind::Store(val, _) => val != ptr,
+                _ => false,
+            });
+        if promotable {
+            res.push(*inst);
+        }
+    }
+    res
+}
+
+// 在定值点的迭代支配边界处插入phi，返回phi及其对应的alloca下标
+fn insert_phis(func: &mut Function, dom: &DomTree, allocas: &[InstId]) -> Vec<(InstId, usize)> {
+    let frontiers = dom.frontiers(func);
+    let mut res = vec![];
+    for (index, alloca) in allocas.iter().enumerate() {
+        let ty = func.inst(*alloca).ty.pointee().clone();
+        let mut has_phi = vec![false; func.blocks.len()];
+        let mut worklist: Vec<BlockId> = vec![];
+        for user in &func.inst(*alloca).users {
+            let block = func.inst(*user).block;
+            if matches!(func.inst(*user).kind, InstKind::Store(_, _))
+                && dom.is_reachable(block)
+                && !worklist.contains(&block)
+            {
+                worklist.push(block);
+            }
+        }
+        while let Some(block) = worklist.pop() {
+            for frontier in &frontiers[block.0] {
+                if has_phi[frontier.0] {
+                    continue;
+                }
+                has_phi[frontier.0] = true;
+                let phi = func.insert_inst(*frontier, 0, InstKind::Phi(vec![]), ty.clone(), None);
+                res.push((phi, index));
+                worklist.push(*frontier);
+            }
+        }
+    }
+    res
+}
+
+// 沿支配树重命名，load替换为到达的定值，store删除
+fn rename(
+    func: &mut Function,
+    dom: &DomTree,
+    allocas: &[InstId],
+    index: &HashMap<InstId, usize>,
+    phis: &[(InstId, usize)],
+) {
+    let phi_alloca: HashMap<InstId, usize> = phis.iter().copied().collect();
+    let zeros: Vec<Value> = allocas
+        .iter()
+        .map(|alloca| zero_value(func.inst(*alloca).ty.pointee()))
+        .collect();
+    let mut stack = vec![(func.entry(), zeros.clone())];
+    while let Some((block, mut vals)) = stack.pop() {
+        for inst in func.block(block).insts.clone() {
+            match func.inst(inst).kind {
+                InstKind::Phi(_) => {
+                    if let Some(alloca) = phi_alloca.get(&inst) {
+                        vals[*alloca] = Value::Inst(inst);
+                    }
+                }
+                InstKind::Load(Value::Inst(ptr)) => {
+                    if let Some(alloca) = index.get(&ptr) {
+                        func.replace_all_uses(inst, vals[*alloca]);
+                        func.remove_inst(inst);
+                    }
+                }
+                InstKind::Store(val, Value::Inst(ptr)) => {
+                    if let Some(alloca) = index.get(&ptr) {
+                        vals[*alloca] = val;
+                        func.remove_inst(inst);
+                    }
+                }
+                _ => {}
+            }
+        }
+        for succ in func.successors(block) {
+            for inst in func.block(succ).insts.clone() {
+                let alloca = match phi_alloca.get(&inst) {
+                    Some(alloca) => *alloca,
+                    None => continue,
+                };
+                if let InstKind::Phi(incoming) = &func.inst(inst).kind {
+                    let mut incoming = incoming.clone();
+                    incoming.push((vals[alloca], block));
+                    func.set_kind(inst, InstKind::Phi(incoming));
+                }
+            }
+        }
+        for child in &dom.children[block.0] {
+            stack.push((*child, vals.clone()));
+        }
+    }
+    // 不可达块中的访问不影响结果
+    for block in func.layout.clone() {
+        if dom.is_reachable(block) {
+            continue;
+        }
+        for inst in func.block(block).insts.clone() {
+            match func.inst(inst).kind {
+                InstKind::Load(Value::Inst(ptr)) => {
+                    if let Some(alloca) = index.get(&ptr) {
+                        func.replace_all_uses(inst, zeros[*alloca]);
+                        func.remove_inst(inst);
+                    }
+                }
+                InstKind::Store(_, Value::Inst(ptr)) if index.contains_key(&ptr) => {
+                    func.remove_inst(inst);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// 删除结果只被插入的phi使用的phi，codegen生成的phi与其他指令一样算作使用
+fn remove_dead_phis(func: &mut Function, phis: &[(InstId, usize)]) {
+    let mut live = vec![false; func.insts.len()];
+    let mut is_inserted = vec![false; func.insts.len()];
+    for (phi, _) in phis {
+        is_inserted[phi.0] = true;
+    }
+    let mut worklist = vec![];
+    for (phi, _) in phis {
+        let used = func
+            .inst(*phi)
+            .users
+            .iter()
+            .any(|user| !is_inserted[user.0]);
+        if used {
+            live[phi.0] = true;
+            worklist.push(*phi);
+        }
+    }
+    while let Some(phi) = worklist.pop() {
+        for operand in func.inst(phi).kind.operands() {
+            if let Value::Inst(inst) = operand {
+                if is_inserted[inst.0] && !live[inst.0] {
+                    live[inst.0] = true;
+                    worklist.push(inst);
+                }
+            }
+        }
+    }
+    // 先清空操作数，再统一删除，避免互相引用的phi
+    for (phi, _) in phis {
+        if !live[phi.0] {
+            func.set_kind(*phi, InstKind::Phi(vec![]));
+        }
+    }
+    for (phi, _) in phis {
+        if !live[phi.0] {
+            func.remove_inst(*phi);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+mod dce;
+pub mod dominator;
+mod mem2reg;
+mod sccp;
+
+use super::ir::Module;
+
+// 优化选项，默认开启所有pass
+pub struct Options {
+    pub mem2reg: bool,
+    pub sccp: bool,
+    pub dce: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            mem2reg: true,
+            sccp: true,
+            dce: true,
+        }
+    }
+}
+
+pub fn run(module: &mut Module, options: &Options) {
+    for func in module
+        .functions
+        .iter_mut()
+        .filter(|func| !func.is_declaration())
+    {
+        if options.mem2reg {
+            mem2reg::run(func);
+        }
+        if options.sccp {
+            sccp::run(func);
+        }
+    }
+    if options.dce {
+        let pure = dce::pure_functions(module);
+        for func in module
+            .functions
+            .iter_mut()
+            .filter(|func| !func.is_declaration())
+        {
+            dce::run(func, &pure);
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::HashSet;
+
+use super::super::ir::{BinOp, BlockId, CmpOp, Function, InstId, InstKind, Value};
+
+// 稀疏条件常量传播(Wegman-Zadeck)，折叠常量与常量条件的分支，并删除不会执行的块
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Lattice {
+    Top,          // 尚未确定
+    Const(Value), // Int、Float或Bool常量
+    Bottom,       // 不是常量
+}
+
+impl Lattice {
+    fn meet(self, other: Lattice) -> Lattice {
+        match (self, other) {
+            (Lattice::Top, res) | (res, Lattice::Top) => res,
+            (Lattice::Const(lhs), Lattice::Const(rhs)) if lhs == rhs => self,
+            _ => Lattice::Bottom,
+        }
+    }
+}
+
+struct Solver<'a> {
+    func: &'a Function,
+    values: Vec<Lattice>,
+    executable: Vec<bool>,
+    edges: HashSet<(BlockId, BlockId)>,
+    flow_worklist: Vec<(BlockId, BlockId)>,
+    ssa_worklist: Vec<InstId>,
+}
+
+impl<'a> Solver<'a> {
+    fn lattice(&self, value: Value) -> Lattice {
+        match value {
+            Value::Int(_) | Value::Float(_) | Value::Bool(_) => Lattice::Const(value),
+            Value::Inst(inst) => self.values[inst.0],
+            _ => Lattice::Bottom,
+        }
+    }
+
+    fn solve(&mut self) {
+        let entry = self.func.entry();
+        self.executable[entry.0] = true;
+        self.visit_block(entry);
+        loop {
+            if let Some((from, to)) = self.flow_worklist.pop() {
+                if !self.edges.insert((from, to)) {
+                    continue;
+                }
+                if self.executable[to.0] {
+                    // 新的可执行边只影响phi
+                    for inst in &self.func.block(to).insts {
+                        if matches!(self.func.inst(*inst).kind, InstKind::Phi(_)) {
+                            self.visit(*inst);
+                        }
+                    }
+                } else {
+                    self.executable[to.0] = true;
+                    self.visit_block(to);
+                }
+            } else if let Some(inst) = self.ssa_worklist.pop() {
+                if self.executable[self.func.inst(inst).block.0] {
+                    self.visit(inst);
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn visit_block(&mut self, block: BlockId) {
+        for inst in &self.func.block(block).insts {
+            self.visit(*inst);
+        }
+    }
+
+    fn visit(&mut self, inst: InstId) {
+        let inst_ref = self.func.inst(inst);
+        let block = inst_ref.block;
+        let res = match &inst_ref.kind {
+            InstKind::Phi(incoming) => incoming.iter().fold(Lattice::Top, |res, (val, pred)| {
+                if self.edges.contains(&(*pred, block)) {
+                    res.meet(self.lattice(*val))
+                } else {
+                    res
+                }
+            }),
+            InstKind::Binary(op, lhs, rhs) => match (self.lattice(*lhs), self.lattice(*rhs)) {
+                (Lattice::Const(lhs), Lattice::Const(rhs)) => {
+                    fold_binary(*op, lhs, rhs).map_or(Lattice::Bottom, Lattice::Const)
+                }
+                (Lattice::Bottom, _) | (_, Lattice::Bottom) => Lattice::Bottom,
+                _ => Lattice::Top,
+            },
+            InstKind::Icmp(op, lhs, rhs) => match (self.lattice(*lhs), self.lattice(*rhs)) {
+                (Lattice::Const(Value::Int(lhs)), Lattice::Const(Value::Int(rhs))) => {
+                    Lattice::Const(Value::Bool(fold_icmp(*op, lhs, rhs)))
+                }
+                (Lattice::Bottom, _) | (_, Lattice::Bottom) => Lattice::Bottom,
+                _ => Lattice::Top,
+            },
+            InstKind::Fcmp(op, lhs, rhs) => match (self.lattice(*lhs), self.lattice(*rhs)) {
+                (Lattice::Const(lhs), Lattice::Const(rhs)) => {
+                    let (lhs, rhs) = (lhs.as_float().unwrap(), rhs.as_float().unwrap());
+                    Lattice::Const(Value::Bool(fold_fcmp(*op, lhs, rhs)))
+                }
+                (Lattice::Bottom, _) | (_, Lattice::Bottom) => Lattice::Bottom,
+                _ => Lattice::Top,
+            },
+            InstKind::Sitofp(val) => match self.lattice(*val) {
+                Lattice::Const(Value::Int(val)) => Lattice::Const(Value::float(val as f32)),
+                Lattice::Top => Lattice::Top,
+                _ => Lattice::Bottom,
+            },
+            // 超出i32范围的转换结果未定义，不折叠
+            InstKind::Fptosi(val) => match self.lattice(*val) {
+                Lattice::Const(Value::Float(bits)) => {
+                    let val = f32::from_bits(bits);
+                    if val > -2147483904.0 && val < 2147483648.0 {
+                        Lattice::Const(Value::Int(val as i32))
+                    } else {
+                        Lattice::Bottom
+                    }
+                }
+                Lattice::Top => Lattice::Top,
+                _ => Lattice::Bottom,
+            },
+            InstKind::Zext(val) => match self.lattice(*val) {
+                Lattice::Const(Value::Bool(val)) => Lattice::Const(Value::Int(val as i32)),
+                Lattice::Top => Lattice::Top,
+                _ => Lattice::Bottom,
+            },
+            InstKind::Br(target) => {
+                self.flow_worklist.push((block, *target));
+                return;
+            }
+            InstKind::CondBr(cond, then_block, else_block) => {
+                match self.lattice(*cond) {
+                    Lattice::Const(Value::Bool(true)) => {
+                        self.flow_worklist.push((block, *then_block))
+                    }
+                    Lattice::Const(Value::Bool(false)) => {
+                        self.flow_worklist.push((block, *else_block))
+                    }
+                    // 条件尚未确定时也按两个分支都可能执行处理
+                    _ => {
+                        self.flow_worklist.push((block, *then_block));
+                        self.flow_worklist.push((block, *else_block));
+                    }
+                }
+                return;
+            }
+            _ => Lattice::Bottom,
+        };
+        let old = self.values[inst.0];
+        let res = old.meet(res);
+        if res != old {
+            self.values[inst.0] = res;
+            self.ssa_worklist.extend(inst_ref.users.iter().copied());
+        }
+    }
+}
+
+// 除以0与溢出的除法在运行时出错，不折叠；浮点运算按IEEE舍入，总可以折叠
+fn fold_binary(op: BinOp, lhs: Value, rhs: Value) -> Option<Value> {
+    if op.is_float() {
+        let (lhs, rhs) = (lhs.as_float()?, rhs.as_float()?);
+        return Some(Value::float(match op {
+            BinOp::FAdd => lhs + rhs,
+            BinOp::FSub => lhs - rhs,
+            BinOp::FMul => lhs * rhs,
+            _ => lhs / rhs,
+        }));
+    }
+    let (lhs, rhs) = (lhs.as_int()?, rhs.as_int()?);
+    match op {
+        BinOp::Add => Some(lhs.wrapping_add(rhs)),
+        BinOp::Sub => Some(lhs.wrapping_sub(rhs)),
+        BinOp::Mul => Some(lhs.wrapping_mul(rhs)),
+        BinOp::SDiv => lhs.checked_div(rhs),
+        BinOp::SRem => lhs.checked_rem(rhs),
+        BinOp::And => Some(lhs & rhs),
+        BinOp::Or => Some(lhs | rhs),
+        BinOp::Xor => Some(lhs ^ rhs),
+        // 移位量超出范围时结果是poison，取任意值都可以，这里与运行时一致只取低5位
+        BinOp::Shl => Some(lhs.wrapping_shl(rhs as u32)),
+        BinOp::AShr => Some(lhs.wrapping_shr(rhs as u32)),
+        _ => unreachable!(),
+    }
+    .map(Value::Int)
+}
+
+fn fold_icmp(op: CmpOp, lhs: i32, rhs: i32) -> bool {
+    match op {
+        CmpOp::Eq => lhs == rhs,
+        CmpOp::Ne => lhs != rhs,
+        CmpOp::Slt => lhs < rhs,
+        CmpOp::Sgt => lhs > rhs,
+        CmpOp::Sle => lhs <= rhs,
+        CmpOp::Sge => lhs >= rhs,
+    }
+}
+
+fn fold_fcmp(op: CmpOp, lhs: f32, rhs: f32) -> bool {
+    match op {
+        CmpOp::Eq => lhs == rhs,
+        CmpOp::Ne => lhs != rhs,
+        CmpOp::Slt => lhs < rhs,
+        CmpOp::Sgt => lhs > rhs,
+        CmpOp::Sle => lhs <= rhs,
+        CmpOp::Sge => lhs >= rhs,
+    }
+}
+
+pub fn run(func: &mut Function) {
+    let mut solver = Solver {
+        func,
+        values: vec![Lattice::Top; func.insts.len()],
+        executable: vec![false; func.blocks.len()],
+        edges: HashSet::new(),
+        flow_worklist: vec![],
+        ssa_worklist: vec![],
+    };
+    solver.solve();
+    let Solver {
+        values, executable, ..
+    } = solver;
+    // 用常量替换指令的结果
+    for block in func.layout.clone() {
+        if !executable[block.0] {
+            continue;
+        }
+        for inst in func.block(block).insts.clone() {
+            if let Lattice::Const(val) = values[inst.0] {
+                func.replace_all_uses(inst, val);
+                func.remove_inst(inst);
+            }
+        }
+        // 条件确定的分支改为直接跳转
+        let term = match func.terminator(block) {
+            Some(term) => term,
+            None => continue,
+        };
+        if let InstKind::CondBr(Value::Bool(cond), then_block, else_block) = func.inst(term).kind {
+            let (target, other) = if cond {
+                (then_block, else_block)
+            } else {
+                (else_block, then_block)
+            };
+            func.set_kind(term, InstKind::Br(target));
+            if other != target {
+                func.remove_phi_incoming(other, block);
+            }
+        }
+    }
+    let dead: Vec<BlockId> = func
+        .layout
+        .iter()
+        .copied()
+        .filter(|block| !executable[block.0])
+        .collect();
+    func.remove_blocks(&dead);
+    simplify_phis(func);
+}
+
+// 所有入口的值都相同的phi直接替换为该值
+fn simplify_phis(func: &mut Function) {
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in func.layout.clone() {
+            for inst in func.block(block).insts.clone() {
+                let incoming = match &func.inst(inst).kind {
+                    InstKind::Phi(incoming) => incoming.clone(),
+                    _ => break,
+                };
+                let mut vals = incoming
+                    .iter()
+                    .map(|(val, _)| *val)
+                    .filter(|val| *val != Value::Inst(inst));
+                let first = match vals.next() {
+                    Some(first) => first,
+                    None => continue,
+                };
+                if vals.all(|val| val == first) {
+                    func.replace_all_uses(inst, first);
+                    func.remove_inst(inst);
+                    changed = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::{self, Target};
+    use crate::pass;
+    use crate::testing::analyze_source;
+
+    // 只运行mem2reg与sccp，不可达的块由sccp自己删除
+    fn propagate(source: &str) -> String {
+        let (_, mut module) = analyze_source(source);
+        let options = pass::Options {
+            dce: false,
+            ..pass::Options::default()
+        };
+        pass::run(&mut module, &options);
+        backend::emit(&module, Target::Llvm)
+    }
+
+    #[test]
+    fn folds_constant_branch() {
+        let ir = propagate(
+            "int main() {
+                int a = 3, b = a * 2;
+                if (b > 5) putint(b); else putint(getint());
+                int i = 1;
+                while (i < 0) i = i + b;
+                return b - 6 + i;
+            }",
+        );
+        assert!(ir.contains("call void @putint(i32 6)"), "{}", ir);
+        assert!(ir.contains("ret i32 1"), "{}", ir);
+        // 条件为假的分支与循环体都被删除
+        assert!(!ir.contains("br i1"), "{}", ir);
+        assert!(!ir.contains("call i32 @getint"), "{}", ir);
+        assert!(!ir.contains("phi"), "{}", ir);
+    }
+}
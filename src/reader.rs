@@ -1,24 +1,51 @@
-use std::collections::LinkedList;
+use super::span::Position;
 
 pub struct Reader {
-    buffer: LinkedList<char>,
+    buffer: Vec<(char, Position)>,
+    index: usize,
+    end: Position,
 }
 
 impl Reader {
-    pub fn new(str: &String) -> Reader {
-        let mut reader = Reader {
-            buffer: LinkedList::new(),
+    pub fn new(str: &str) -> Reader {
+        let mut buffer = Vec::new();
+        let mut pos = Position {
+            offset: 0,
+            line: 1,
+            column: 1,
         };
-        reader.buffer.extend(str.chars());
-        reader
+        for chr in str.chars() {
+            buffer.push((chr, pos));
+            pos.offset += chr.len_utf8();
+            if chr == '\n' {
+                pos.line += 1;
+                pos.column = 1;
+            } else {
+                pos.column += 1;
+            }
+        }
+        Reader {
+            buffer,
+            index: 0,
+            end: pos,
+        }
     }
     pub fn getc(&mut self) -> char {
-        self.buffer.pop_front().unwrap()
+        self.index += 1;
+        self.buffer[self.index - 1].0
     }
-    pub fn ungetc(&mut self, chr: &char) {
-        self.buffer.push_front(chr.clone());
+    // 退回上一个读入的字符
+    pub fn ungetc(&mut self) {
+        self.index -= 1;
     }
-    pub fn has_next(&mut self) -> bool {
-        !self.buffer.is_empty()
+    pub fn has_next(&self) -> bool {
+        self.index < self.buffer.len()
+    }
+    // 下一个待读字符的位置
+    pub fn pos(&self) -> Position {
+        match self.buffer.get(self.index) {
+            Some((_, pos)) => *pos,
+            None => self.end,
+        }
     }
 }
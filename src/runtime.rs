@@ -0,0 +1,211 @@
+use std::io::{self, BufWriter, Read, Write};
+
+use super::tokenizer::parse_hex_float;
+
+// 解释器共用的运行时库，行为与sylib一致：按scanf的%d、%a与getchar读取标准输入，按printf的格式输出
+pub struct Runtime {
+    input: Option<Vec<u8>>, // 第一次读取时才读入整个标准输入
+    pos: usize,
+    output: Box<dyn Write>,
+}
+
+impl Runtime {
+    pub fn new() -> Runtime {
+        Runtime {
+            input: None,
+            pos: 0,
+            output: Box::new(BufWriter::new(io::stdout())),
+        }
+    }
+
+    // 测试时从给定的输入读取，输出写到output
+    #[cfg(test)]
+    pub fn with_io(input: &[u8], output: Box<dyn Write>) -> Runtime {
+        Runtime {
+            input: Some(input.to_vec()),
+            pos: 0,
+            output,
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        let input = self.input.get_or_insert_with(|| {
+            let mut bytes = vec![];
+            let _ = io::stdin().read_to_end(&mut bytes);
+            bytes
+        });
+        input.get(self.pos).copied()
+    }
+
+    pub fn getch(&mut self) -> i32 {
+        match self.peek() {
+            Some(ch) => {
+                self.pos += 1;
+                ch as i32
+            }
+            None => -1,
+        }
+    }
+
+    // 读不到整数时返回0
+    pub fn getint(&mut self) -> i32 {
+        while self.peek().is_some_and(|ch| ch.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+        let is_neg = match self.peek() {
+            Some(ch @ b'+') | Some(ch @ b'-') => {
+                self.pos += 1;
+                ch == b'-'
+            }
+            _ => false,
+        };
+        let mut res: i32 = 0;
+        while let Some(ch @ b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+            res = res.wrapping_mul(10).wrapping_add((ch - b'0') as i32);
+        }
+        if is_neg {
+            res.wrapping_neg()
+        } else {
+            res
+        }
+    }
+
+    // 读取十进制或十六进制浮点数，以及inf与nan，读不到数字时返回0
+    pub fn getfloat(&mut self) -> f32 {
+        while self.peek().is_some_and(|ch| ch.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+        let mut sign = 1.0;
+        if let Some(ch @ (b'+' | b'-')) = self.peek() {
+            self.pos += 1;
+            if ch == b'-' {
+                sign = -1.0;
+            }
+        }
+        let start = self.pos;
+        let input = self.input.as_ref().unwrap();
+        for word in ["infinity", "inf", "nan"] {
+            let end = start + word.len();
+            if end <= input.len() && input[start..end].eq_ignore_ascii_case(word.as_bytes()) {
+                self.pos = end;
+                return sign
+                    * if word == "nan" {
+                        f32::NAN
+                    } else {
+                        f32::INFINITY
+                    };
+            }
+        }
+        let is_hex = input.len() >= start + 2
+            && input[start] == b'0'
+            && input[start + 1].eq_ignore_ascii_case(&b'x');
+        if is_hex {
+            self.pos += 2;
+        }
+        let mut text = String::new();
+        let (digits, exp) = if is_hex { (16, b'p') } else { (10, b'e') };
+        let mut has_digit = false;
+        let mut has_dot = false;
+        while let Some(ch) = self.peek() {
+            if (ch as char).is_digit(digits) {
+                has_digit = true;
+            } else if ch == b'.' && !has_dot {
+                has_dot = true;
+            } else {
+                break;
+            }
+            self.pos += 1;
+            text.push(ch as char);
+        }
+        if !has_digit {
+            return 0.0;
+        }
+        // 指数部分必须有数字，否则不读入
+        if self.peek().is_some_and(|ch| ch.to_ascii_lowercase() == exp) {
+            let save = self.pos;
+            let mut exp_text = String::from("p");
+            self.pos += 1;
+            if let Some(ch @ (b'+' | b'-')) = self.peek() {
+                self.pos += 1;
+                exp_text.push(ch as char);
+            }
+            let len = exp_text.len();
+            while let Some(ch @ b'0'..=b'9') = self.peek() {
+                self.pos += 1;
+                exp_text.push(ch as char);
+            }
+            if exp_text.len() == len {
+                self.pos = save;
+            } else {
+                text += &exp_text;
+            }
+        }
+        if is_hex {
+            if !text.contains('p') {
+                text += "p0";
+            }
+            sign * parse_hex_float(&text)
+        } else {
+            sign * text.replace('p', "e").parse().unwrap_or(0.0)
+        }
+    }
+
+    pub fn putint(&mut self, val: i32) {
+        let _ = write!(self.output, "{}", val);
+    }
+
+    pub fn putch(&mut self, val: i32) {
+        let _ = self.output.write_all(&[val as u8]);
+    }
+
+    // count为负数时不输出元素
+    pub fn putarray(&mut self, count: i32, vals: &[i32]) {
+        let _ = write!(self.output, "{}:", count);
+        for val in vals {
+            let _ = write!(self.output, " {}", val);
+        }
+        let _ = writeln!(self.output);
+    }
+
+    // 与printf的%a相同，float先提升为double
+    pub fn putfloat(&mut self, val: f32) {
+        let _ = write!(self.output, "{}", format_hex_float(val as f64));
+    }
+
+    pub fn putfarray(&mut self, count: i32, vals: &[f32]) {
+        let _ = write!(self.output, "{}:", count);
+        for val in vals {
+            let _ = write!(self.output, " {}", format_hex_float(*val as f64));
+        }
+        let _ = writeln!(self.output);
+    }
+
+    pub fn flush(&mut self) {
+        let _ = self.output.flush();
+    }
+}
+
+// glibc中printf("%a")的输出，如1.5为0x1.8p+0
+fn format_hex_float(val: f64) -> String {
+    let sign = if val.is_sign_negative() { "-" } else { "" };
+    if val.is_nan() {
+        return format!("{}nan", sign);
+    }
+    if val.is_infinite() {
+        return format!("{}inf", sign);
+    }
+    if val == 0.0 {
+        return format!("{}0x0p+0", sign);
+    }
+    // float提升得到的double总是规格化数
+    let bits = val.to_bits();
+    let exp = ((bits >> 52) & 0x7ff) as i64 - 1023;
+    let digits = format!("{:013x}", bits & ((1 << 52) - 1));
+    let digits = digits.trim_end_matches('0');
+    if digits.is_empty() {
+        format!("{}0x1p{:+}", sign, exp)
+    } else {
+        format!("{}0x1.{}p{:+}", sign, digits, exp)
+    }
+}
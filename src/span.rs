@@ -0,0 +1,66 @@
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Position {
+    pub offset: usize, // 字节偏移
+    pub line: usize,   // 从1开始
+    pub column: usize, // 从1开始，按字符计
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Span {
+    pub file: usize, // SourceMap中的文件编号
+    pub line: usize,
+    pub column: usize,
+    pub start: usize, // 字节范围[start, end)
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(file: usize, start: Position, end: Position) -> Span {
+        Span {
+            file,
+            line: start.line,
+            column: start.column,
+            start: start.offset,
+            end: end.offset,
+        }
+    }
+
+    // 合并两个区间，结果从self开始到other结束
+    pub fn to(&self, other: Span) -> Span {
+        if other.end <= self.start {
+            return *self;
+        }
+        Span {
+            end: other.end,
+            ..*self
+        }
+    }
+}
+
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+}
+
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap { files: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: &str, text: String) -> usize {
+        self.files.push(SourceFile {
+            name: name.to_string(),
+            text,
+        });
+        self.files.len() - 1
+    }
+
+    pub fn get(&self, file: usize) -> &SourceFile {
+        &self.files[file]
+    }
+}
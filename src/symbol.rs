@@ -1,5 +1,10 @@
 use std::collections::{HashMap, LinkedList};
 
+use super::ast::BType;
+use super::diagnostic::{Diagnostic, ErrorCode};
+use super::ir::Value;
+use super::span::Span;
+
 pub struct SymbolTable {
     func_table: HashMap<String, Function>,
     var_table: LinkedList<HashMap<String, Variable>>,
@@ -15,12 +20,29 @@ impl SymbolTable {
             current_func: String::from(""),
             current_val: String::from(""),
         };
-        table.insert_func(&"getint".to_string(), true, &vec![]);
-        table.insert_func(&"getch".to_string(), true, &vec![]);
-        table.insert_func(&"getarray".to_string(), true, &vec![vec![0]]);
-        table.insert_func(&"putint".to_string(), false, &vec![vec![]]);
-        table.insert_func(&"putch".to_string(), false, &vec![vec![]]);
-        table.insert_func(&"putarray".to_string(), false, &vec![vec![], vec![0]]);
+        let (int, float) = (BType::Int, BType::Float);
+        let builtins = [
+            ("getint", true, int, vec![]),
+            ("getch", true, int, vec![]),
+            ("getfloat", true, float, vec![]),
+            ("getarray", true, int, vec![(int, vec![0])]),
+            ("getfarray", true, int, vec![(float, vec![0])]),
+            ("putint", false, int, vec![(int, vec![])]),
+            ("putch", false, int, vec![(int, vec![])]),
+            ("putfloat", false, int, vec![(float, vec![])]),
+            ("putarray", false, int, vec![(int, vec![]), (int, vec![0])]),
+            (
+                "putfarray",
+                false,
+                int,
+                vec![(int, vec![]), (float, vec![0])],
+            ),
+        ];
+        for (name, has_return, ty, params) in builtins.iter() {
+            table
+                .insert_func(name, *has_return, *ty, params, Span::default())
+                .unwrap();
+        }
         table.var_table.push_front(HashMap::new());
         table
     }
@@ -30,11 +52,11 @@ impl SymbolTable {
     }
 
     pub fn get_current_func(&self) -> &Function {
-        self.get_func(&self.current_func)
+        self.func_table.get(&self.current_func).unwrap()
     }
 
     pub fn get_current_val(&self) -> &Variable {
-        self.get_var(&self.current_val)
+        self.get_var(&self.current_val, Span::default()).unwrap()
     }
 
     pub fn go_down(&mut self) {
@@ -48,153 +70,146 @@ impl SymbolTable {
         self.var_table.pop_front();
     }
 
-    pub fn get_func(&self, func_name: &String) -> &Function {
-        self.func_table.get(func_name).unwrap()
+    // 错误恢复时退出所有局部作用域
+    pub fn go_global(&mut self) {
+        while self.var_table.len() > 1 {
+            self.var_table.pop_front();
+        }
     }
 
-    pub fn get_var(&self, var_name: &String) -> &Variable {
+    pub fn get_func(&self, func_name: &str, span: Span) -> Result<&Function, Diagnostic> {
+        self.func_table.get(func_name).ok_or_else(|| {
+            Diagnostic::error(
+                ErrorCode::UndeclaredIdentifier,
+                format!("use of undeclared function `{}`", func_name),
+                span,
+            )
+        })
+    }
+
+    pub fn get_var(&self, var_name: &str, span: Span) -> Result<&Variable, Diagnostic> {
         self.var_table
             .iter()
-            .find(|table| table.contains_key(var_name))
-            .unwrap()
-            .get(var_name)
-            .unwrap()
+            .find_map(|table| table.get(var_name))
+            .ok_or_else(|| {
+                Diagnostic::error(
+                    ErrorCode::UndeclaredIdentifier,
+                    format!("use of undeclared identifier `{}`", var_name),
+                    span,
+                )
+            })
     }
 
-    pub fn insert_func(&mut self, func_name: &String, has_return: bool, params: &Vec<Vec<i32>>) {
+    pub fn insert_func(
+        &mut self,
+        func_name: &str,
+        has_return: bool,
+        ty: BType,
+        params: &[(BType, Vec<i32>)],
+        span: Span,
+    ) -> Result<(), Diagnostic> {
         if self.func_table.contains_key(func_name) {
-            panic!("redefinition of function!");
+            return Err(Diagnostic::error(
+                ErrorCode::Redefinition,
+                format!("redefinition of function `{}`", func_name),
+                span,
+            ));
         }
-        self.current_func = func_name.clone();
+        self.current_func = func_name.to_string();
         self.func_table.insert(
-            func_name.clone(),
+            func_name.to_string(),
             Function {
-                name: func_name.clone(),
+                name: func_name.to_string(),
                 has_return,
-                params: params.clone(),
+                ty,
+                params: params.to_vec(),
+                span,
             },
         );
+        Ok(())
     }
 
-    pub fn insert_var(
-        &mut self,
-        name: &String,
-        reg: &String,
-        is_const: bool,
-        shape: &Vec<i32>,
-        value: i32,
-    ) {
-        if self.var_table.front().unwrap().contains_key(name) {
-            panic!("redefinition of variable!");
+    pub fn insert_var(&mut self, var: Variable, span: Span) -> Result<(), Diagnostic> {
+        if self.var_table.front().unwrap().contains_key(&var.name) {
+            return Err(Diagnostic::error(
+                ErrorCode::Redefinition,
+                format!("redefinition of variable `{}`", var.name),
+                span,
+            ));
         }
-        self.current_val = name.clone();
-        self.var_table.front_mut().unwrap().insert(
-            name.clone(),
-            Variable {
-                name: name.clone(),
-                reg: reg.clone(),
-                is_const,
-                shape: shape.clone(),
-                value,
-            },
-        );
+        self.current_val = var.name.clone();
+        self.var_table
+            .front_mut()
+            .unwrap()
+            .insert(var.name.clone(), var);
+        Ok(())
+    }
+}
+
+impl Default for SymbolTable {
+    fn default() -> SymbolTable {
+        SymbolTable::new()
     }
 }
 
 pub struct Function {
     pub name: String,
     pub has_return: bool,
-    pub params: Vec<Vec<i32>>,
+    pub ty: BType, // 返回值类型
+    pub params: Vec<(BType, Vec<i32>)>,
+    pub span: Span,
 }
 
 impl Function {
-    pub fn get_definition(&self) -> String {
-        let mut params: Vec<String> = vec![];
-        for item in &self.params {
-            if item.is_empty() {
-                params.push(format!("i32 %p{}", params.len() + 1));
-            } else {
-                let mut res = item.clone();
-                res.remove(0);
-                params.push(format!(
-                    "{}* %p{}",
-                    Variable::get_shape_from_vec(&res),
-                    params.len() + 1
-                ));
-            }
-        }
-        format!(
-            "define {} @{}({}) {{\n",
-            if self.has_return { "i32" } else { "void" },
-            self.name,
-            params.join(", ")
-        )
-    }
-
-    pub fn get_call_instruction(&self, param: &Vec<Variable>) -> String {
-        let mut params: Vec<String> = vec![];
+    // 检查实参的个数与形状，标量实参可以隐式转换，数组实参的元素类型必须相同
+    pub fn check_call(&self, param: &[Variable], span: Span) -> Result<(), Diagnostic> {
         if self.params.len() != param.len() {
-            panic!("param count mismatches!");
+            return Err(Diagnostic::error(
+                ErrorCode::ArgumentMismatch,
+                format!(
+                    "function `{}` takes {} arguments but {} were supplied",
+                    self.name,
+                    self.params.len(),
+                    param.len()
+                ),
+                span,
+            ));
         }
-        for index in 0..param.len() {
-            if self.params[index].len() != param[index].shape.len() {
-                panic!("param type mismatches!");
-            }
-            for item in 0..self.params[index].len() {
-                if self.params[index][item] != 0
-                    && self.params[index][item] != param[index].shape[item]
-                {
-                    panic!("param type mismatches!");
-                }
-            }
-            if param[index].shape.is_empty() {
-                params.push(format!("i32 {}", param[index].reg));
-            } else {
-                let mut tmp = param[index].clone();
-                tmp.shape.remove(0);
-                params.push(format!(
-                    "{}* {}",
-                    Variable::get_shape_from_vec(&tmp.shape),
-                    tmp.reg
+        for (index, ((ty, expected), actual)) in self.params.iter().zip(param.iter()).enumerate() {
+            let matches = expected.len() == actual.shape.len()
+                && (expected.is_empty() || *ty == actual.ty)
+                && expected
+                    .iter()
+                    .zip(actual.shape.iter())
+                    .all(|(dim, real)| *dim == 0 || dim == real);
+            if !matches {
+                return Err(Diagnostic::error(
+                    ErrorCode::ArgumentMismatch,
+                    format!(
+                        "mismatched type of argument {} in call to `{}`",
+                        index + 1,
+                        self.name
+                    ),
+                    span,
                 ));
             }
         }
-        format!(
-            "call {} @{}({})",
-            if self.has_return { "i32" } else { "void" },
-            self.name,
-            params.join(", ")
-        )
+        Ok(())
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct Variable {
     pub is_const: bool,
     pub name: String,
-    pub reg: String,
+    pub reg: Value,
+    pub ty: BType, // 标量或数组元素的类型
     pub shape: Vec<i32>,
-    pub value: i32,
+    pub value: Value, // 常量标量的值
 }
 
 impl Variable {
     pub fn new() -> Variable {
-        Variable {
-            is_const: false,
-            name: String::from(""),
-            reg: String::from(""),
-            shape: vec![],
-            value: 0,
-        }
-    }
-
-    pub fn get_shape_from_vec(dimensions: &Vec<i32>) -> String {
-        let mut front = String::from("");
-        let mut back = String::from("");
-        for item in dimensions {
-            front += format!("[{} x ", item).as_str();
-            back += "]";
-        }
-        front + "i32" + back.as_str()
+        Variable::default()
     }
 }
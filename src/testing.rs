@@ -0,0 +1,50 @@
+// 各模块测试共用的辅助函数，源码有编译错误时输出渲染后的诊断信息再panic
+use super::ast::CompUnit;
+use super::ir::Module;
+use super::{analyze, compile_file, Diagnostic, Error, Options, Output, SourceMap};
+
+fn source_map(source: &str) -> (SourceMap, usize) {
+    let mut sources = SourceMap::new();
+    let file = sources.add("<test>", source.to_string());
+    (sources, file)
+}
+
+fn fail(sources: &SourceMap, diagnostics: &[Diagnostic]) -> ! {
+    let rendered: Vec<String> = diagnostics
+        .iter()
+        .map(|diag| diag.render(sources))
+        .collect();
+    panic!("unexpected compile errors:\n{}", rendered.join("\n"));
+}
+
+pub fn codes(diagnostics: &[Diagnostic]) -> Vec<&'static str> {
+    diagnostics.iter().map(|diag| diag.code.code()).collect()
+}
+
+// 语法树与未优化的IR
+pub fn analyze_source(source: &str) -> (CompUnit, Module) {
+    let (sources, file) = source_map(source);
+    match analyze(&sources, file, 0, &mut vec![]) {
+        Ok(res) => res,
+        Err(errors) => fail(&sources, &errors),
+    }
+}
+
+pub fn compile_source(source: &str, options: &Options) -> Output {
+    let (sources, file) = source_map(source);
+    match compile_file(&sources, file, options) {
+        Ok(output) => output,
+        Err(Error::Diagnostics(errors)) => fail(&sources, &errors),
+        Err(err) => panic!("{}", err),
+    }
+}
+
+// 源码应当有编译错误，返回全部诊断信息
+pub fn compile_errors(source: &str, options: &Options) -> Vec<Diagnostic> {
+    let (sources, file) = source_map(source);
+    match compile_file(&sources, file, options) {
+        Ok(_) => panic!("expected compile errors in\n{}", source),
+        Err(Error::Diagnostics(errors)) => errors,
+        Err(err) => panic!("{}", err),
+    }
+}
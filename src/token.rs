@@ -1,18 +1,31 @@
-#[derive(PartialEq, Eq, Debug)]
-pub enum Token {
+use std::fmt;
+
+use super::span::Span;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum TokenKind {
     Ident(String),
     Number(i32),
+    FloatNumber(f32),
     Const,
     Int,
+    Float,
     Void,
     If,
     Else,
     While,
+    For,
+    Do,
+    Switch,
+    Case,
+    Default,
     Break,
     Continue,
     Return,
     Comma,
     Semicolon,
+    Colon,
+    Question,
     LParen,
     RParen,
     LBracket,
@@ -22,6 +35,18 @@ pub enum Token {
     Equal,
     NotEqual,
     Assign,
+    PlusAssign,
+    MinusAssign,
+    MultiplyAssign,
+    DivideAssign,
+    ModAssign,
+    BitAndAssign,
+    BitOrAssign,
+    BitXorAssign,
+    ShlAssign,
+    ShrAssign,
+    Increment,
+    Decrement,
     Plus,
     Minus,
     Not,
@@ -34,4 +59,87 @@ pub enum Token {
     GreaterOrEqual,
     And,
     Or,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    Shl,
+    Shr,
+    Eof,
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            TokenKind::Ident(ident) => return write!(f, "identifier `{}`", ident),
+            TokenKind::Number(num) => return write!(f, "number `{}`", num),
+            TokenKind::FloatNumber(num) => return write!(f, "number `{:?}`", num),
+            TokenKind::Eof => return write!(f, "end of file"),
+            TokenKind::Const => "const",
+            TokenKind::Int => "int",
+            TokenKind::Float => "float",
+            TokenKind::Void => "void",
+            TokenKind::If => "if",
+            TokenKind::Else => "else",
+            TokenKind::While => "while",
+            TokenKind::For => "for",
+            TokenKind::Do => "do",
+            TokenKind::Switch => "switch",
+            TokenKind::Case => "case",
+            TokenKind::Default => "default",
+            TokenKind::Break => "break",
+            TokenKind::Continue => "continue",
+            TokenKind::Return => "return",
+            TokenKind::Comma => ",",
+            TokenKind::Semicolon => ";",
+            TokenKind::Colon => ":",
+            TokenKind::Question => "?",
+            TokenKind::LParen => "(",
+            TokenKind::RParen => ")",
+            TokenKind::LBracket => "[",
+            TokenKind::RBracket => "]",
+            TokenKind::LBrace => "{",
+            TokenKind::RBrace => "}",
+            TokenKind::Equal => "==",
+            TokenKind::NotEqual => "!=",
+            TokenKind::Assign => "=",
+            TokenKind::PlusAssign => "+=",
+            TokenKind::MinusAssign => "-=",
+            TokenKind::MultiplyAssign => "*=",
+            TokenKind::DivideAssign => "/=",
+            TokenKind::ModAssign => "%=",
+            TokenKind::BitAndAssign => "&=",
+            TokenKind::BitOrAssign => "|=",
+            TokenKind::BitXorAssign => "^=",
+            TokenKind::ShlAssign => "<<=",
+            TokenKind::ShrAssign => ">>=",
+            TokenKind::Increment => "++",
+            TokenKind::Decrement => "--",
+            TokenKind::Plus => "+",
+            TokenKind::Minus => "-",
+            TokenKind::Not => "!",
+            TokenKind::Multiply => "*",
+            TokenKind::Divide => "/",
+            TokenKind::Mod => "%",
+            TokenKind::Less => "<",
+            TokenKind::Greater => ">",
+            TokenKind::LessOrEqual => "<=",
+            TokenKind::GreaterOrEqual => ">=",
+            TokenKind::And => "&&",
+            TokenKind::Or => "||",
+            TokenKind::BitAnd => "&",
+            TokenKind::BitOr => "|",
+            TokenKind::BitXor => "^",
+            TokenKind::BitNot => "~",
+            TokenKind::Shl => "<<",
+            TokenKind::Shr => ">>",
+        };
+        write!(f, "`{}`", text)
+    }
 }
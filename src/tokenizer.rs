@@ -1,184 +1,97 @@
 use std::collections::LinkedList;
 
+use super::diagnostic::{Diagnostic, ErrorCode};
 use super::reader::Reader;
-use super::token::Token;
+use super::span::{Position, SourceMap, Span};
+use super::token::{Token, TokenKind};
 
 pub struct Tokenizer;
 
 impl Tokenizer {
-    pub fn tokenize(source: &String) -> LinkedList<Token> {
+    // 出错时记录错误并继续：跳过非法字符，非法的数字字面量当作0
+    pub fn tokenize(sources: &SourceMap, file: usize) -> (LinkedList<Token>, Vec<Diagnostic>) {
         let mut tokens: LinkedList<Token> = LinkedList::new();
-        let mut reader = Reader::new(source);
+        let mut errors = vec![];
+        let mut reader = Reader::new(&sources.get(file).text);
         while reader.has_next() {
+            let start = reader.pos();
             let mut chr = reader.getc();
-            match chr {
-                ',' => tokens.push_back(Token::Comma),
-                ';' => tokens.push_back(Token::Semicolon),
-                '(' => tokens.push_back(Token::LParen),
-                ')' => tokens.push_back(Token::RParen),
-                '[' => tokens.push_back(Token::LBracket),
-                ']' => tokens.push_back(Token::RBracket),
-                '{' => tokens.push_back(Token::LBrace),
-                '}' => tokens.push_back(Token::RBrace),
-                '+' => tokens.push_back(Token::Plus),
-                '-' => tokens.push_back(Token::Minus),
-                '%' => tokens.push_back(Token::Mod),
-                '*' => tokens.push_back(Token::Multiply),
-                '=' => {
-                    if reader.has_next() {
-                        chr = reader.getc();
-                        if chr == '=' {
-                            tokens.push_back(Token::Equal);
-                        } else {
-                            reader.ungetc(&chr);
-                            tokens.push_back(Token::Assign);
-                        }
-                    } else {
-                        tokens.push_back(Token::Assign);
-                    }
-                }
-                '!' => {
-                    if reader.has_next() {
-                        chr = reader.getc();
-                        if chr == '=' {
-                            tokens.push_back(Token::NotEqual);
-                        } else {
-                            reader.ungetc(&chr);
-                            tokens.push_back(Token::Not);
-                        }
-                    } else {
-                        tokens.push_back(Token::Not);
-                    }
-                }
-                '<' => {
-                    if reader.has_next() {
-                        chr = reader.getc();
-                        if chr == '=' {
-                            tokens.push_back(Token::LessOrEqual);
-                        } else {
-                            reader.ungetc(&chr);
-                            tokens.push_back(Token::Less);
-                        }
-                    } else {
-                        tokens.push_back(Token::Less);
-                    }
-                }
-                '>' => {
-                    if reader.has_next() {
-                        chr = reader.getc();
-                        if chr == '=' {
-                            tokens.push_back(Token::GreaterOrEqual);
-                        } else {
-                            reader.ungetc(&chr);
-                            tokens.push_back(Token::Greater);
-                        }
-                    } else {
-                        tokens.push_back(Token::Greater);
-                    }
-                }
-                '|' => {
-                    if reader.has_next() && reader.getc() == '|' {
-                        tokens.push_back(Token::Or);
-                    } else {
-                        panic!("syntax error!");
-                    }
-                }
-                '&' => {
-                    if reader.has_next() && reader.getc() == '&' {
-                        tokens.push_back(Token::And);
-                    } else {
-                        panic!("syntax error!");
-                    }
+            let kind = match chr {
+                ',' => TokenKind::Comma,
+                ';' => TokenKind::Semicolon,
+                ':' => TokenKind::Colon,
+                '?' => TokenKind::Question,
+                '(' => TokenKind::LParen,
+                ')' => TokenKind::RParen,
+                '[' => TokenKind::LBracket,
+                ']' => TokenKind::RBracket,
+                '{' => TokenKind::LBrace,
+                '}' => TokenKind::RBrace,
+                '+' | '-' => Self::with_sign(&mut reader, chr),
+                '%' => Self::with_equal(&mut reader, TokenKind::Mod, TokenKind::ModAssign),
+                '*' => {
+                    Self::with_equal(&mut reader, TokenKind::Multiply, TokenKind::MultiplyAssign)
                 }
+                '=' => Self::with_equal(&mut reader, TokenKind::Assign, TokenKind::Equal),
+                '!' => Self::with_equal(&mut reader, TokenKind::Not, TokenKind::NotEqual),
+                '^' => Self::with_equal(&mut reader, TokenKind::BitXor, TokenKind::BitXorAssign),
+                '~' => TokenKind::BitNot,
+                '<' | '>' => Self::with_angle(&mut reader, chr),
+                '&' => Self::with_double(
+                    &mut reader,
+                    '&',
+                    [TokenKind::BitAnd, TokenKind::And, TokenKind::BitAndAssign],
+                ),
+                '|' => Self::with_double(
+                    &mut reader,
+                    '|',
+                    [TokenKind::BitOr, TokenKind::Or, TokenKind::BitOrAssign],
+                ),
                 '/' => {
                     if reader.has_next() {
                         chr = reader.getc();
                         match chr {
-                            '/' => while reader.has_next() && reader.getc() != '\n' {},
-                            '*' => loop {
-                                while reader.has_next() && reader.getc() != '*' {}
-                                if !reader.has_next() || reader.getc() == '/' {
-                                    break;
+                            '=' => TokenKind::DivideAssign,
+                            '/' => {
+                                while reader.has_next() && reader.getc() != '\n' {}
+                                continue;
+                            }
+                            '*' => {
+                                loop {
+                                    while reader.has_next() && reader.getc() != '*' {}
+                                    if !reader.has_next() {
+                                        errors.push(Diagnostic::error(
+                                            ErrorCode::UnterminatedComment,
+                                            "unterminated block comment".to_string(),
+                                            Span::new(file, start, reader.pos()),
+                                        ));
+                                        break;
+                                    }
+                                    if reader.getc() == '/' {
+                                        break;
+                                    }
+                                    reader.ungetc();
                                 }
-                            },
+                                continue;
+                            }
                             _ => {
-                                reader.ungetc(&chr);
-                                tokens.push_back(Token::Divide);
+                                reader.ungetc();
+                                TokenKind::Divide
                             }
                         }
                     } else {
-                        tokens.push_back(Token::Divide);
+                        TokenKind::Divide
                     }
                 }
                 _ => {
                     if chr.is_ascii_whitespace() {
                         continue;
-                    } else if chr.is_ascii_digit() {
-                        let mut str: String = chr.to_string();
-                        if chr != '0' {
-                            while reader.has_next() {
-                                chr = reader.getc();
-                                if chr.is_ascii_digit() {
-                                    str.push(chr);
-                                } else {
-                                    reader.ungetc(&chr);
-                                    break;
-                                }
-                            }
-                            tokens.push_back(Token::Number(
-                                i32::from_str_radix(str.as_ref(), 10).unwrap(),
-                            ));
-                            continue;
-                        }
-                        if !reader.has_next() {
-                            tokens.push_back(Token::Number(0));
-                            continue;
-                        }
-                        chr = reader.getc();
-                        if chr.is_ascii_digit() {
-                            str.push(chr);
-                            while reader.has_next() {
-                                chr = reader.getc();
-                                if chr.is_ascii_digit() {
-                                    str.push(chr);
-                                } else {
-                                    reader.ungetc(&chr);
-                                    break;
-                                }
-                            }
-                            tokens.push_back(Token::Number(
-                                i32::from_str_radix(str.as_ref(), 8).unwrap(),
-                            ));
-                        } else if chr == 'x' || chr == 'X' {
-                            if !reader.has_next() {
-                                tokens.push_back(Token::Number(0));
-                                tokens.push_back(Token::Ident(chr.to_string()));
-                                continue;
-                            }
-                            let mut tmp_chr = reader.getc();
-                            if tmp_chr.is_ascii_hexdigit() {
-                                str.push(tmp_chr);
-                                while reader.has_next() {
-                                    tmp_chr = reader.getc();
-                                    if tmp_chr.is_ascii_hexdigit() {
-                                        str.push(tmp_chr);
-                                    } else {
-                                        reader.ungetc(&tmp_chr);
-                                        break;
-                                    }
-                                }
-                                tokens.push_back(Token::Number(
-                                    i32::from_str_radix(str.as_ref(), 16).unwrap(),
-                                ));
-                            } else {
-                                reader.ungetc(&tmp_chr);
-                                reader.ungetc(&chr);
-                                tokens.push_back(Token::Number(0));
-                            }
-                        } else {
-                            reader.ungetc(&chr);
-                            tokens.push_back(Token::Number(0));
-                        }
+                    } else if chr.is_ascii_digit() || chr == '.' && Self::digit_follows(&mut reader)
+                    {
+                        Self::read_number(file, &mut reader, start, chr).unwrap_or_else(|err| {
+                            errors.push(err);
+                            TokenKind::Number(0)
+                        })
                     } else if chr.is_ascii_alphabetic() || chr == '_' {
                         let mut str: String = chr.to_string();
                         while reader.has_next() {
@@ -186,28 +99,293 @@ impl Tokenizer {
                             if chr.is_ascii_alphanumeric() || chr == '_' {
                                 str.push(chr);
                             } else {
-                                reader.ungetc(&chr);
+                                reader.ungetc();
                                 break;
                             }
                         }
                         match str.as_str() {
-                            "const" => tokens.push_back(Token::Const),
-                            "int" => tokens.push_back(Token::Int),
-                            "void" => tokens.push_back(Token::Void),
-                            "if" => tokens.push_back(Token::If),
-                            "else" => tokens.push_back(Token::Else),
-                            "while" => tokens.push_back(Token::While),
-                            "break" => tokens.push_back(Token::Break),
-                            "continue" => tokens.push_back(Token::Continue),
-                            "return" => tokens.push_back(Token::Return),
-                            _ => tokens.push_back(Token::Ident(str)),
+                            "const" => TokenKind::Const,
+                            "int" => TokenKind::Int,
+                            "float" => TokenKind::Float,
+                            "void" => TokenKind::Void,
+                            "if" => TokenKind::If,
+                            "else" => TokenKind::Else,
+                            "while" => TokenKind::While,
+                            "for" => TokenKind::For,
+                            "do" => TokenKind::Do,
+                            "switch" => TokenKind::Switch,
+                            "case" => TokenKind::Case,
+                            "default" => TokenKind::Default,
+                            "break" => TokenKind::Break,
+                            "continue" => TokenKind::Continue,
+                            "return" => TokenKind::Return,
+                            _ => TokenKind::Ident(str),
                         }
                     } else {
-                        panic!("syntax error!");
+                        let mut span = Span::new(file, start, start);
+                        span.end += chr.len_utf8();
+                        errors.push(Diagnostic::error(
+                            ErrorCode::UnexpectedCharacter,
+                            format!("unexpected character `{}`", chr),
+                            span,
+                        ));
+                        continue;
                     }
                 }
+            };
+            tokens.push_back(Token {
+                kind,
+                span: Span::new(file, start, reader.pos()),
+            });
+        }
+        let end = reader.pos();
+        tokens.push_back(Token {
+            kind: TokenKind::Eof,
+            span: Span::new(file, end, end),
+        });
+        (tokens, errors)
+    }
+
+    fn with_equal(reader: &mut Reader, single: TokenKind, double: TokenKind) -> TokenKind {
+        if reader.has_next() {
+            if reader.getc() == '=' {
+                return double;
+            }
+            reader.ungetc();
+        }
+        single
+    }
+
+    // +与-可以重复为自增自减，或者与=组成复合赋值
+    fn with_sign(reader: &mut Reader, sign: char) -> TokenKind {
+        if sign == '+' {
+            Self::with_double(
+                reader,
+                sign,
+                [TokenKind::Plus, TokenKind::Increment, TokenKind::PlusAssign],
+            )
+        } else {
+            Self::with_double(
+                reader,
+                sign,
+                [
+                    TokenKind::Minus,
+                    TokenKind::Decrement,
+                    TokenKind::MinusAssign,
+                ],
+            )
+        }
+    }
+
+    // kinds依次为单个字符、重复字符与后跟=时的记号
+    fn with_double(reader: &mut Reader, chr: char, kinds: [TokenKind; 3]) -> TokenKind {
+        let [single, double, assign] = kinds;
+        if reader.has_next() {
+            let next = reader.getc();
+            if next == chr {
+                return double;
+            } else if next == '=' {
+                return assign;
+            }
+            reader.ungetc();
+        }
+        single
+    }
+
+    // <与>可以后跟=，或者重复为移位并再后跟=组成复合赋值
+    fn with_angle(reader: &mut Reader, chr: char) -> TokenKind {
+        let (single, equal, shift, assign) = if chr == '<' {
+            (
+                TokenKind::Less,
+                TokenKind::LessOrEqual,
+                TokenKind::Shl,
+                TokenKind::ShlAssign,
+            )
+        } else {
+            (
+                TokenKind::Greater,
+                TokenKind::GreaterOrEqual,
+                TokenKind::Shr,
+                TokenKind::ShrAssign,
+            )
+        };
+        match Self::with_double(reader, chr, [single, shift, equal]) {
+            kind @ (TokenKind::Shl | TokenKind::Shr) => Self::with_equal(reader, kind, assign),
+            kind => kind,
+        }
+    }
+
+    fn digit_follows(reader: &mut Reader) -> bool {
+        if !reader.has_next() {
+            return false;
+        }
+        let is_digit = reader.getc().is_ascii_digit();
+        reader.ungetc();
+        is_digit
+    }
+
+    // 读取连续的数字，十六进制时包括a-f
+    fn read_digits(reader: &mut Reader, str: &mut String, hex: bool) {
+        while reader.has_next() {
+            let chr = reader.getc();
+            if chr.is_ascii_hexdigit() && hex || chr.is_ascii_digit() {
+                str.push(chr);
+            } else {
+                reader.ungetc();
+                break;
             }
         }
-        tokens
     }
+
+    fn read_number(
+        file: usize,
+        reader: &mut Reader,
+        start: Position,
+        first: char,
+    ) -> Result<TokenKind, Diagnostic> {
+        let mut str = String::new();
+        let mut radix = 10;
+        if first == '0' && reader.has_next() {
+            let chr = reader.getc();
+            if chr == 'x' || chr == 'X' {
+                if !reader.has_next() {
+                    reader.ungetc();
+                    return Ok(TokenKind::Number(0));
+                }
+                let chr = reader.getc();
+                let is_hex = chr.is_ascii_hexdigit() || chr == '.';
+                reader.ungetc();
+                if !is_hex {
+                    // 0x后没有十六进制数字，只识别出0
+                    reader.ungetc();
+                    return Ok(TokenKind::Number(0));
+                }
+                radix = 16;
+            } else {
+                reader.ungetc();
+                radix = 8;
+            }
+        } else {
+            str.push(first);
+        }
+        Self::read_digits(reader, &mut str, radix == 16);
+        // 小数点或指数部分说明是浮点数
+        let exp_chars = if radix == 16 { ['p', 'P'] } else { ['e', 'E'] };
+        let mut is_float = first == '.';
+        if reader.has_next() {
+            let chr = reader.getc();
+            if chr == '.' && first != '.' {
+                str.push(chr);
+                Self::read_digits(reader, &mut str, radix == 16);
+                is_float = true;
+            } else {
+                reader.ungetc();
+            }
+        }
+        if reader.has_next() {
+            let chr = reader.getc();
+            if exp_chars.contains(&chr) {
+                str.push('p');
+                if reader.has_next() {
+                    let sign = reader.getc();
+                    if sign == '+' || sign == '-' {
+                        str.push(sign);
+                    } else {
+                        reader.ungetc();
+                    }
+                }
+                let len = str.len();
+                Self::read_digits(reader, &mut str, false);
+                if str.len() == len {
+                    return Err(Diagnostic::error(
+                        ErrorCode::InvalidLiteral,
+                        "exponent has no digits".to_string(),
+                        Span::new(file, start, reader.pos()),
+                    ));
+                }
+                is_float = true;
+            } else {
+                reader.ungetc();
+            }
+        }
+        let span = Span::new(file, start, reader.pos());
+        if is_float {
+            if radix == 16 && !str.contains('p') {
+                return Err(Diagnostic::error(
+                    ErrorCode::InvalidLiteral,
+                    "hexadecimal floating literal requires an exponent".to_string(),
+                    span,
+                ));
+            }
+            return Ok(TokenKind::FloatNumber(if radix == 16 {
+                parse_hex_float(&str)
+            } else {
+                // 八进制前缀的0不在str中
+                format!("0{}", str.replace('p', "e")).parse().unwrap()
+            }));
+        }
+        if str.is_empty() {
+            return Ok(TokenKind::Number(0));
+        }
+        if radix == 8 && str.contains(['8', '9']) {
+            return Err(Diagnostic::error(
+                ErrorCode::InvalidLiteral,
+                "invalid digit in octal literal".to_string(),
+                span,
+            ));
+        }
+        match u32::from_str_radix(str.as_str(), radix) {
+            // 2147483648只在取负时合法，按补码存储
+            Ok(num) if num <= 1 << 31 => Ok(TokenKind::Number(num as i32)),
+            _ => Err(Diagnostic::error(
+                ErrorCode::InvalidLiteral,
+                "integer literal is too large".to_string(),
+                span,
+            )),
+        }
+    }
+}
+
+// 十六进制浮点数，形如1f.8p-3(不含0x)，按IEEE舍入到f32
+// 尾数保留53位并把截去的位并入最低位，转为f64时精确，转为f32时只舍入一次
+pub fn parse_hex_float(str: &str) -> f32 {
+    let (mantissa, exp) = str.split_once(['p', 'P']).unwrap();
+    let mut exp: i64 = exp.parse().unwrap_or(if exp.starts_with('-') {
+        i64::MIN / 2
+    } else {
+        i64::MAX / 2
+    });
+    let mut bits: u64 = 0;
+    let mut sticky = false;
+    let mut is_frac = false;
+    for chr in mantissa.chars() {
+        if chr == '.' {
+            is_frac = true;
+            continue;
+        }
+        let digit = chr.to_digit(16).unwrap() as u64;
+        if bits >> 56 == 0 {
+            bits = bits << 4 | digit;
+            if is_frac {
+                exp -= 4;
+            }
+        } else {
+            sticky |= digit != 0;
+            if !is_frac {
+                exp += 4;
+            }
+        }
+    }
+    if bits == 0 {
+        return 0.0;
+    }
+    while bits >> 53 != 0 {
+        sticky |= bits & 1 != 0;
+        bits >>= 1;
+        exp += 1;
+    }
+    bits |= sticky as u64;
+    // 超出范围时结果必然是0或无穷大，2的幂在f64中仍是精确的
+    let exp = exp.clamp(-400, 300) as i32;
+    (bits as f64 * 2f64.powi(exp)) as f32
 }
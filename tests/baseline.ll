@@ -0,0 +1,170 @@
+declare i32 @getint()
+declare i32 @getch()
+declare float @getfloat()
+declare i32 @getarray(i32*)
+declare i32 @getfarray(float*)
+declare void @putch(i32)
+declare void @putint(i32)
+declare void @putfloat(float)
+declare void @putarray(i32, i32*)
+declare void @putfarray(i32, float*)
+
+@g = global [3 x [2 x i32]] [[2 x i32] [i32 1, i32 2], [2 x i32] [i32 3, i32 0], [2 x i32] zeroinitializer]
+@N = constant i32 5
+
+define i32 @fib(i32 %p1) {
+    %1 = alloca i32
+    br label %b_1
+b_1:
+    store i32 %p1, i32* %1
+    %x1 = load i32, i32* %1
+    %x2 = icmp slt i32 %x1, 2
+    br i1 %x2, label %b_1_1, label %b_2
+b_1_1:
+    %x3 = load i32, i32* %1
+    ret i32 %x3
+    br label %b_2
+b_2:
+    %x4 = load i32, i32* %1
+    %x5 = sub i32 %x4, 1
+    %x6 = call i32 @fib(i32 %x5)
+    %x7 = load i32, i32* %1
+    %x8 = sub i32 %x7, 2
+    %x9 = call i32 @fib(i32 %x8)
+    %x10 = add i32 %x6, %x9
+    ret i32 %x10
+}
+
+define i32 @sum([2 x i32]* %p1, i32 %p2) {
+    %1 = alloca i32
+    %2 = alloca i32
+    %3 = alloca i32
+    br label %b_1
+b_1:
+    store i32 %p2, i32* %1
+    store i32 0, i32* %2
+    store i32 0, i32* %3
+    br label %b_2
+b_2:
+    %x1 = load i32, i32* %3
+    %x2 = load i32, i32* %1
+    %x3 = icmp slt i32 %x1, %x2
+    br i1 %x3, label %b_2_1, label %b_3
+b_2_1:
+    %x4 = load i32, i32* %2
+    %x5 = load i32, i32* %3
+    %x6 = getelementptr [2 x i32], [2 x i32]* %p1, i32 %x5
+    %x7 = getelementptr [2 x i32], [2 x i32]* %x6, i32 0, i32 0
+    %x8 = load i32, i32* %x7
+    %x9 = add i32 %x4, %x8
+    %x10 = load i32, i32* %3
+    %x11 = getelementptr [2 x i32], [2 x i32]* %p1, i32 %x10
+    %x12 = getelementptr [2 x i32], [2 x i32]* %x11, i32 0, i32 1
+    %x13 = load i32, i32* %x12
+    %x14 = add i32 %x9, %x13
+    store i32 %x14, i32* %2
+    %x15 = load i32, i32* %3
+    %x16 = add i32 %x15, 1
+    store i32 %x16, i32* %3
+    br label %b_2
+b_3:
+    %x17 = load i32, i32* %2
+    ret i32 %x17
+}
+
+define void @skip(i32 %p1) {
+    %1 = alloca i32
+    %2 = alloca i32
+    br label %b_1
+b_1:
+    store i32 %p1, i32* %1
+    store i32 0, i32* %2
+    br label %b_2
+b_2:
+    %x1 = icmp ne i32 1, 0
+    br i1 %x1, label %b_2_1, label %b_3
+b_2_1:
+    %x2 = load i32, i32* %2
+    %x3 = add i32 %x2, 1
+    store i32 %x3, i32* %2
+    %x4 = load i32, i32* %2
+    %x5 = load i32, i32* %1
+    %x6 = icmp sgt i32 %x4, %x5
+    br i1 %x6, label %b_2_1_1, label %b_2_2
+b_2_1_1:
+    br label %b_3
+    br label %b_2_2
+b_2_2:
+    %x7 = load i32, i32* %2
+    %x8 = srem i32 %x7, 2
+    %x9 = icmp ne i32 %x8, 0
+    br i1 %x9, label %b_2_2_1, label %b_2_3
+b_2_2_1:
+    br label %b_2
+    br label %b_2_3
+b_2_3:
+    %x10 = load i32, i32* %2
+    call void @putint(i32 %x10)
+    br label %b_2
+b_3:
+    ret void
+}
+
+define i32 @main() {
+    %1 = alloca [5 x i32]
+    %2 = alloca i32
+    br label %b_1
+b_1:
+    store i32 0, i32* %2
+    br label %b_2
+b_2:
+    %x1 = load i32, i32* %2
+    %x2 = load i32, i32* @N
+    %x3 = icmp slt i32 %x1, %x2
+    br i1 %x3, label %b_2_1, label %b_3
+b_2_1:
+    %x4 = load i32, i32* %2
+    %x5 = getelementptr [5 x i32], [5 x i32]* %1, i32 0, i32 %x4
+    %x6 = load i32, i32* %2
+    %x7 = add i32 %x6, 5
+    %x8 = call i32 @fib(i32 %x7)
+    store i32 %x8, i32* %x5
+    %x9 = load i32, i32* %2
+    %x10 = add i32 %x9, 1
+    store i32 %x10, i32* %2
+    br label %b_2
+b_3:
+    %x11 = load i32, i32* @N
+    %x12 = getelementptr [5 x i32], [5 x i32]* %1, i32 0, i32 0
+    call void @putarray(i32 %x11, i32* %x12)
+    %x13 = getelementptr [3 x [2 x i32]], [3 x [2 x i32]]* @g, i32 0, i32 0
+    %x14 = call i32 @sum([2 x i32]* %x13, i32 3)
+    call void @putint(i32 %x14)
+    %x15 = load i32, i32* %2
+    %x16 = icmp eq i32 %x15, 5
+    br i1 %x16, label %b_5, label %b_4
+b_5:
+    %x17 = load i32, i32* %2
+    %x18 = icmp slt i32 %x17, 0
+    br i1 %x18, label %b_6_1, label %b_4
+b_4:
+    %x19 = call i32 @fib(i32 3)
+    %x20 = icmp ne i32 %x19, 0
+    br i1 %x20, label %b_7, label %b_6
+b_6:
+    %x21 = load i32, i32* %2
+    %x22 = icmp ne i32 %x21, 2
+    br i1 %x22, label %b_6_1, label %b_7
+b_6_1:
+    call void @putint(i32 1)
+    br label %b_8
+b_7:
+    br label %b_7_1
+b_7_1:
+    call void @putint(i32 0)
+    br label %b_8
+b_8:
+    call void @skip(i32 6)
+    ret i32 3
+}
+